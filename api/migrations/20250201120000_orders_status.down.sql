@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS check_order_status ON orders;
+DROP FUNCTION IF EXISTS check_order_status_transition;
+ALTER TABLE orders DROP COLUMN IF EXISTS status;
+DROP TYPE IF EXISTS order_status;
\ No newline at end of file
@@ -0,0 +1,35 @@
+CREATE TYPE order_status AS ENUM (
+	'pending',
+	'paid',
+	'shipped',
+	'delivered',
+	'cancelled',
+	'refunded'
+);
+
+ALTER TABLE orders
+	ADD COLUMN status order_status NOT NULL DEFAULT 'pending';
+
+--	function/triggers
+
+	--	--	only allow the order lifecycle transitions
+
+	CREATE OR REPLACE FUNCTION check_order_status_transition()
+	RETURNS TRIGGER AS $$
+	BEGIN
+		IF NOT (
+			(OLD.status = 'pending' AND NEW.status IN ('paid', 'cancelled')) OR
+			(OLD.status = 'paid' AND NEW.status IN ('shipped', 'cancelled', 'refunded')) OR
+			(OLD.status = 'shipped' AND NEW.status IN ('delivered', 'refunded')) OR
+			(OLD.status = 'delivered' AND NEW.status = 'refunded')
+		) THEN
+			RAISE EXCEPTION 'invalid-order-status-transition';
+		END IF;
+		RETURN NEW;
+	END;
+	$$ LANGUAGE plpgsql;
+	--  --
+	CREATE TRIGGER check_order_status
+	BEFORE UPDATE OF status ON orders
+	FOR EACH ROW
+	EXECUTE FUNCTION check_order_status_transition();
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS ledger_entries;
+DROP FUNCTION IF EXISTS forbid_ledger_entries_modification;
+DROP TYPE IF EXISTS ledger_entry_kind;
\ No newline at end of file
@@ -0,0 +1,50 @@
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+CREATE TYPE ledger_entry_kind AS ENUM (
+	'opening_balance',
+	'top_up',
+	'order_debit',
+	'sale_credit',
+	'refund'
+);
+
+CREATE TABLE IF NOT EXISTS ledger_entries (
+	id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+	kind ledger_entry_kind NOT NULL,
+	amount_in_cents BIGINT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS ledger_entries_user_id_created_at_idx
+	ON ledger_entries (user_id, created_at DESC);
+
+--	balances existing before the ledger
+
+INSERT INTO ledger_entries ( user_id, kind, amount_in_cents )
+SELECT id, 'opening_balance', sold_in_cents
+FROM users
+WHERE sold_in_cents <> 0;
+
+--	function/triggers
+
+	--	--	append-only, except for the foreign keys actions (user/order deletion)
+
+	CREATE OR REPLACE FUNCTION forbid_ledger_entries_modification()
+	RETURNS TRIGGER AS $$
+	BEGIN
+		IF pg_trigger_depth() < 2 THEN
+			RAISE EXCEPTION 'append-only-ledger';
+		END IF;
+		IF TG_OP = 'DELETE' THEN
+			RETURN OLD;
+		END IF;
+		RETURN NEW;
+	END;
+	$$ LANGUAGE plpgsql;
+	--  --
+	CREATE TRIGGER append_only_ledger_entries
+	BEFORE UPDATE OR DELETE ON ledger_entries
+	FOR EACH ROW
+	EXECUTE FUNCTION forbid_ledger_entries_modification();
\ No newline at end of file
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS cart_items;
+DROP TABLE IF EXISTS order_items;
+DROP FUNCTION IF EXISTS check_if_order_item_belong_to_buyer;
+
+DELETE FROM orders WHERE product_id IS NULL;
+ALTER TABLE orders
+	ALTER COLUMN product_id SET NOT NULL,
+	ALTER COLUMN products_number SET NOT NULL;
\ No newline at end of file
@@ -0,0 +1,76 @@
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+--	order lines, an order can now contain several products
+
+CREATE TABLE IF NOT EXISTS order_items (
+	id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+	order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	product_id UUID NOT NULL REFERENCES products(id),
+	products_number INTEGER NOT NULL CHECK(
+		products_number >= 1 AND
+		products_number <= 1000
+	),
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	UNIQUE(order_id, product_id)
+);
+
+INSERT INTO order_items ( order_id, product_id, products_number, created_at )
+SELECT id, product_id, products_number, created_at
+FROM orders;
+
+--	only set for single product orders
+ALTER TABLE orders
+	ALTER COLUMN product_id DROP NOT NULL,
+	ALTER COLUMN products_number DROP NOT NULL;
+
+--	shopping cart
+
+CREATE TABLE IF NOT EXISTS cart_items (
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+	products_number INTEGER NOT NULL CHECK(
+		products_number >= 1 AND
+		products_number <= 1000
+	),
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (user_id, product_id)
+);
+
+--	function/triggers
+
+	--	--	check order.user != product.user
+
+	CREATE OR REPLACE FUNCTION check_if_order_item_belong_to_buyer()
+	RETURNS TRIGGER AS $$
+	BEGIN
+		IF EXISTS (
+			SELECT 1
+			FROM products
+			JOIN orders ON orders.id = NEW.order_id
+			WHERE products.id = NEW.product_id AND products.user_id = orders.user_id
+		) THEN
+			RAISE EXCEPTION 'auto-buying';
+		END IF;
+		RETURN NEW;
+	END;
+	$$ LANGUAGE plpgsql;
+	--  --
+	CREATE TRIGGER check_order_item_product
+	BEFORE INSERT OR UPDATE ON order_items
+	FOR EACH ROW
+	EXECUTE FUNCTION check_if_order_item_belong_to_buyer();
+
+	--	--	check user != product.user
+
+	CREATE TRIGGER check_cart_item_product
+	BEFORE INSERT OR UPDATE ON cart_items
+	FOR EACH ROW
+	EXECUTE FUNCTION check_if_product_belong_to_buyer();
+
+	--	--	update timestamp
+
+	CREATE TRIGGER update_cart_items_timestamp
+	BEFORE UPDATE ON cart_items
+	FOR EACH ROW
+	EXECUTE FUNCTION update_updated_at();
\ No newline at end of file
@@ -0,0 +1,21 @@
+DROP TRIGGER IF EXISTS check_order_details_owner ON orders;
+DROP FUNCTION IF EXISTS check_if_order_details_belong_to_buyer;
+
+UPDATE orders SET order_details_id = NULL;
+DELETE FROM order_details;
+
+ALTER TABLE orders
+	DROP CONSTRAINT IF EXISTS orders_order_details_id_fkey,
+	ADD CONSTRAINT orders_order_details_id_fkey
+		FOREIGN KEY (order_details_id) REFERENCES users(id) ON DELETE SET NULL,
+	ADD CONSTRAINT orders_order_details_id_key UNIQUE (order_details_id);
+
+DROP INDEX IF EXISTS order_details_user_id_idx;
+
+ALTER TABLE order_details
+	DROP COLUMN user_id,
+	DROP COLUMN street,
+	DROP COLUMN city,
+	DROP COLUMN postal_code,
+	DROP COLUMN country,
+	ADD COLUMN delivery_address VARCHAR(255) NOT NULL CHECK(delivery_address <> '');
\ No newline at end of file
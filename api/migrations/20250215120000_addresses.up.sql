@@ -0,0 +1,46 @@
+--	order_details becomes the users' address book
+
+--	existing rows had no owner and could not be referenced by an order
+--	(orders.order_details_id was pointing at users), so they are dropped
+UPDATE orders SET order_details_id = NULL;
+DELETE FROM order_details;
+
+ALTER TABLE order_details
+	DROP COLUMN delivery_address,
+	ADD COLUMN user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	ADD COLUMN street VARCHAR(255) NOT NULL CHECK(street <> ''),
+	ADD COLUMN city VARCHAR(100) NOT NULL CHECK(city <> ''),
+	ADD COLUMN postal_code VARCHAR(20) NOT NULL CHECK(postal_code <> ''),
+	ADD COLUMN country VARCHAR(100) NOT NULL CHECK(country <> '');
+
+CREATE INDEX IF NOT EXISTS order_details_user_id_idx ON order_details (user_id);
+
+--	several orders can be delivered to the same address
+ALTER TABLE orders
+	DROP CONSTRAINT IF EXISTS orders_order_details_id_key,
+	DROP CONSTRAINT IF EXISTS orders_order_details_id_fkey,
+	ADD CONSTRAINT orders_order_details_id_fkey
+		FOREIGN KEY (order_details_id) REFERENCES order_details(id) ON DELETE SET NULL;
+
+--	function/triggers
+
+	--	--	check order.user == order_details.user
+
+	CREATE OR REPLACE FUNCTION check_if_order_details_belong_to_buyer()
+	RETURNS TRIGGER AS $$
+	BEGIN
+		IF NEW.order_details_id IS NOT NULL AND NOT EXISTS (
+			SELECT 1
+			FROM order_details
+			WHERE id = NEW.order_details_id AND user_id = NEW.user_id
+		) THEN
+			RAISE EXCEPTION 'foreign-order-details';
+		END IF;
+		RETURN NEW;
+	END;
+	$$ LANGUAGE plpgsql;
+	--  --
+	CREATE TRIGGER check_order_details_owner
+	BEFORE INSERT OR UPDATE OF order_details_id ON orders
+	FOR EACH ROW
+	EXECUTE FUNCTION check_if_order_details_belong_to_buyer();
\ No newline at end of file
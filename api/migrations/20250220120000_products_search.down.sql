@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS products_in_stock_idx;
+DROP INDEX IF EXISTS products_user_id_idx;
+DROP INDEX IF EXISTS products_created_at_idx;
+DROP INDEX IF EXISTS products_price_in_cents_idx;
+DROP INDEX IF EXISTS products_search_vector_idx;
+
+ALTER TABLE products DROP COLUMN IF EXISTS search_vector;
\ No newline at end of file
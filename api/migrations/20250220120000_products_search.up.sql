@@ -0,0 +1,16 @@
+--	full-text search over the products' name and description
+
+ALTER TABLE products
+	ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+		setweight(to_tsvector('english', name), 'A') ||
+		setweight(to_tsvector('english', COALESCE(description, '')), 'B')
+	) STORED;
+
+CREATE INDEX IF NOT EXISTS products_search_vector_idx ON products USING GIN (search_vector);
+
+--	filters and sorts of GET /api/products/search
+
+CREATE INDEX IF NOT EXISTS products_price_in_cents_idx ON products (price_in_cents);
+CREATE INDEX IF NOT EXISTS products_created_at_idx ON products (created_at DESC);
+CREATE INDEX IF NOT EXISTS products_user_id_idx ON products (user_id);
+CREATE INDEX IF NOT EXISTS products_in_stock_idx ON products (created_at DESC) WHERE number_in_stock > 0;
\ No newline at end of file
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS ledger_entries_user_id_created_at_id_idx;
+DROP INDEX IF EXISTS order_details_user_id_created_at_id_idx;
+DROP INDEX IF EXISTS orders_user_id_created_at_id_idx;
+DROP INDEX IF EXISTS orders_created_at_id_idx;
+DROP INDEX IF EXISTS products_user_id_created_at_id_idx;
+DROP INDEX IF EXISTS products_created_at_id_idx;
+DROP INDEX IF EXISTS users_created_at_id_idx;
\ No newline at end of file
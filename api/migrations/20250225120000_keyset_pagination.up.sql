@@ -0,0 +1,9 @@
+--	keyset pagination: lists are read by `(created_at, id) < cursor ORDER BY created_at DESC, id DESC`
+
+CREATE INDEX IF NOT EXISTS users_created_at_id_idx ON users (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS products_created_at_id_idx ON products (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS products_user_id_created_at_id_idx ON products (user_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS orders_created_at_id_idx ON orders (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS orders_user_id_created_at_id_idx ON orders (user_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS order_details_user_id_created_at_id_idx ON order_details (user_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS ledger_entries_user_id_created_at_id_idx ON ledger_entries (user_id, created_at DESC, id DESC);
\ No newline at end of file
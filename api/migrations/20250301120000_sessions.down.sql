@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS last_token_id VARCHAR(255) DEFAULT NULL;
+
+DROP INDEX IF EXISTS sessions_user_id_created_at_id_idx;
+DROP TABLE IF EXISTS sessions;
\ No newline at end of file
@@ -0,0 +1,19 @@
+--	one row per login, so that logging in on a device does not log out the others
+
+CREATE TABLE IF NOT EXISTS sessions (
+	id UUID NOT NULL PRIMARY KEY DEFAULT(uuid_generate_v4()),
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	--	sha-256 (hex) of the jti of the current access token and of the refresh token
+	access_token_hash CHAR(64) NOT NULL UNIQUE,
+	refresh_token_hash CHAR(64) NOT NULL UNIQUE,
+	device_label VARCHAR(100) DEFAULT NULL,
+	user_agent VARCHAR(512) DEFAULT NULL,
+	ip_address VARCHAR(45) DEFAULT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS sessions_user_id_created_at_id_idx ON sessions (user_id, created_at DESC, id DESC);
+
+--	every user has to log in again
+ALTER TABLE users DROP COLUMN IF EXISTS last_token_id;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS rotated_refresh_tokens_session_id_idx;
+DROP TABLE IF EXISTS rotated_refresh_tokens;
\ No newline at end of file
@@ -0,0 +1,11 @@
+--	refresh tokens are rotated on every refresh: a session is a family of refresh tokens,
+--	and the ones it rotated out are kept to detect when one of them is used again
+
+CREATE TABLE IF NOT EXISTS rotated_refresh_tokens (
+	--	sha-256 (hex) of the jti, like sessions.refresh_token_hash
+	token_hash CHAR(64) NOT NULL PRIMARY KEY,
+	session_id UUID NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+	rotated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS rotated_refresh_tokens_session_id_idx ON rotated_refresh_tokens (session_id);
\ No newline at end of file
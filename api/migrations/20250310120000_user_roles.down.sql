@@ -0,0 +1,8 @@
+DROP TRIGGER IF EXISTS promote_to_seller ON products;
+DROP FUNCTION IF EXISTS promote_product_owner_to_seller;
+
+ALTER TABLE users
+	DROP COLUMN IF EXISTS suspended_at,
+	DROP COLUMN IF EXISTS role;
+
+DROP TYPE IF EXISTS user_role;
\ No newline at end of file
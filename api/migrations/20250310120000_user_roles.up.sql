@@ -0,0 +1,31 @@
+CREATE TYPE user_role AS ENUM (
+	'customer',
+	'seller',
+	'admin'
+);
+
+ALTER TABLE users
+	ADD COLUMN role user_role NOT NULL DEFAULT 'customer',
+	--	a suspended user can neither log in nor use its tokens
+	ADD COLUMN suspended_at TIMESTAMPTZ;
+
+UPDATE users SET role = 'seller'
+WHERE EXISTS (SELECT 1 FROM products WHERE products.user_id = users.id);
+
+--	function/triggers
+
+	--	--	a customer becomes a seller with its first product
+
+	CREATE OR REPLACE FUNCTION promote_product_owner_to_seller()
+	RETURNS TRIGGER AS $$
+	BEGIN
+		UPDATE users SET role = 'seller'
+		WHERE id = NEW.user_id AND role = 'customer';
+		RETURN NEW;
+	END;
+	$$ LANGUAGE plpgsql;
+	--  --
+	CREATE TRIGGER promote_to_seller
+	AFTER INSERT ON products
+	FOR EACH ROW
+	EXECUTE FUNCTION promote_product_owner_to_seller();
\ No newline at end of file
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS user_tokens_user_id_kind_idx;
+DROP TABLE IF EXISTS user_tokens;
+DROP TYPE IF EXISTS user_token_kind;
+
+ALTER TABLE users DROP COLUMN IF EXISTS email_verified_at;
\ No newline at end of file
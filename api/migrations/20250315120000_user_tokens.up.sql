@@ -0,0 +1,25 @@
+--	accounts are only usable once their email is verified,
+--	the existing ones are considered verified
+ALTER TABLE users
+	ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+UPDATE users SET email_verified_at = created_at;
+
+CREATE TYPE user_token_kind AS ENUM (
+	'email_verification',
+	'password_reset'
+);
+
+--	single-use tokens sent by email
+CREATE TABLE IF NOT EXISTS user_tokens (
+	id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	kind user_token_kind NOT NULL,
+	--	sha-256 (hex) of the token
+	token_hash CHAR(64) NOT NULL UNIQUE,
+	expires_at TIMESTAMPTZ NOT NULL,
+	used_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS user_tokens_user_id_kind_idx ON user_tokens (user_id, kind);
\ No newline at end of file
@@ -0,0 +1,20 @@
+--	optional second factor, pending until confirmed with a first code
+CREATE TABLE IF NOT EXISTS user_totp (
+	user_id UUID NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+	secret BYTEA NOT NULL,
+	enabled_at TIMESTAMPTZ,
+	--	time step of the last accepted code, which can not be used again
+	last_step BIGINT,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+--	single-use codes for when the authenticator is lost, hashed like passwords
+CREATE TABLE IF NOT EXISTS recovery_codes (
+	id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	code_hash VARCHAR(255) NOT NULL,
+	used_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_user_id_idx ON recovery_codes (user_id);
\ No newline at end of file
@@ -0,0 +1,4 @@
+--	failed logins in a row, the account is locked out for longer and longer past a threshold
+ALTER TABLE users
+	ADD COLUMN failed_login_attempts INT NOT NULL DEFAULT 0,
+	ADD COLUMN locked_until TIMESTAMPTZ;
\ No newline at end of file
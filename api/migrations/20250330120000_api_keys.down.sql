@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS api_keys_user_id_created_at_idx;
+DROP TABLE IF EXISTS api_keys;
+DROP TYPE IF EXISTS api_key_scope;
\ No newline at end of file
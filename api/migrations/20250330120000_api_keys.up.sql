@@ -0,0 +1,25 @@
+CREATE TYPE api_key_scope AS ENUM (
+	'users:read',
+	'products:read',
+	'products:write',
+	'orders:read',
+	'orders:write',
+	'cart:read',
+	'cart:write'
+);
+
+--	keys of the backend jobs, sent instead of an access token
+CREATE TABLE IF NOT EXISTS api_keys (
+	id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	name VARCHAR(100) NOT NULL,
+	--	start of the key, to recognize it once only the hash is left
+	prefix VARCHAR(16) NOT NULL,
+	--	sha-256 (hex) of the key
+	key_hash CHAR(64) NOT NULL UNIQUE,
+	scopes api_key_scope[] NOT NULL,
+	last_used_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_user_id_created_at_idx ON api_keys (user_id, created_at DESC, id DESC);
\ No newline at end of file
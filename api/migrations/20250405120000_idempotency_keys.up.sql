@@ -0,0 +1,16 @@
+--	responses of the requests moving money, replayed when retried with the same key
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	key VARCHAR(255) NOT NULL,
+	--	sha-256 (hex) of the method, path, query and body of the first request
+	fingerprint CHAR(64) NOT NULL,
+	--	null while the first request is running
+	response_status SMALLINT,
+	response_content_type VARCHAR(255),
+	response_body BYTEA,
+	expires_at TIMESTAMPTZ NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
\ No newline at end of file
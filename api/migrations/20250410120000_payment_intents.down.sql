@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS payment_intents_user_id_created_at_idx;
+DROP TABLE IF EXISTS payment_intents;
+DROP TYPE IF EXISTS payment_intent_status;
\ No newline at end of file
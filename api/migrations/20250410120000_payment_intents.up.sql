@@ -0,0 +1,28 @@
+CREATE TYPE payment_intent_status AS ENUM (
+	'pending',
+	--	confirmed, the outcome comes with a webhook of the gateway
+	'processing',
+	'succeeded',
+	'failed'
+);
+
+--	top-ups of the balances, only credited once the gateway confirms them
+CREATE TABLE IF NOT EXISTS payment_intents (
+	id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+	user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	--	id of the intent on the side of the gateway
+	gateway_intent_id VARCHAR(255) NOT NULL UNIQUE,
+	amount_in_cents BIGINT NOT NULL CHECK (amount_in_cents > 0),
+	status payment_intent_status NOT NULL DEFAULT 'pending',
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS payment_intents_user_id_created_at_idx ON payment_intents (user_id, created_at DESC, id DESC);
+
+--	function/triggers
+
+	CREATE TRIGGER update_payment_intents_timestamp
+	BEFORE UPDATE ON payment_intents
+	FOR EACH ROW
+	EXECUTE FUNCTION update_updated_at();
\ No newline at end of file
@@ -0,0 +1,9 @@
+--	why an order was cancelled by its buyer or refunded by its seller
+CREATE TABLE IF NOT EXISTS order_cancellations (
+	order_id UUID NOT NULL PRIMARY KEY REFERENCES orders(id) ON DELETE CASCADE,
+	--	the buyer for a cancellation, the seller for a refund
+	cancelled_by UUID REFERENCES users(id) ON DELETE SET NULL,
+	status order_status NOT NULL CHECK(status IN ('cancelled', 'refunded')),
+	reason VARCHAR(500) NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
\ No newline at end of file
@@ -0,0 +1,11 @@
+--	tracking of the orders shipped by their seller
+CREATE TABLE IF NOT EXISTS order_shipments (
+	order_id UUID NOT NULL PRIMARY KEY REFERENCES orders(id) ON DELETE CASCADE,
+	shipped_by UUID REFERENCES users(id) ON DELETE SET NULL,
+	tracking_number VARCHAR(255) NOT NULL,
+	shipped_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	delivered_at TIMESTAMPTZ
+);
+
+--	sales of a seller, from the products to their orders
+CREATE INDEX IF NOT EXISTS order_items_product_id_idx ON order_items (product_id);
\ No newline at end of file
@@ -0,0 +1,8 @@
+ALTER TABLE orders
+	DROP COLUMN IF EXISTS total_in_cents,
+	DROP COLUMN IF EXISTS product_name,
+	DROP COLUMN IF EXISTS unit_price_in_cents;
+
+ALTER TABLE order_items
+	DROP COLUMN IF EXISTS product_name,
+	DROP COLUMN IF EXISTS unit_price_in_cents;
\ No newline at end of file
@@ -0,0 +1,37 @@
+--	what the buyer agreed to pay, validation charges it instead of the current price
+
+ALTER TABLE order_items
+	ADD COLUMN unit_price_in_cents BIGINT CHECK(unit_price_in_cents >= 0),
+	ADD COLUMN product_name VARCHAR(100);
+
+--	only set for single product orders, like `product_id`
+ALTER TABLE orders
+	ADD COLUMN unit_price_in_cents BIGINT CHECK(unit_price_in_cents >= 0),
+	ADD COLUMN product_name VARCHAR(100),
+	ADD COLUMN total_in_cents BIGINT CHECK(total_in_cents >= 0);
+
+--	existing orders agreed to the current prices
+
+UPDATE order_items
+SET unit_price_in_cents = products.price_in_cents, product_name = products.name
+FROM products
+WHERE products.id = order_items.product_id;
+
+UPDATE orders
+SET unit_price_in_cents = products.price_in_cents, product_name = products.name
+FROM products
+WHERE products.id = orders.product_id;
+
+UPDATE orders
+SET total_in_cents = COALESCE((
+	SELECT SUM(order_items.unit_price_in_cents * order_items.products_number)
+	FROM order_items
+	WHERE order_items.order_id = orders.id
+), 0);
+
+ALTER TABLE order_items
+	ALTER COLUMN unit_price_in_cents SET NOT NULL,
+	ALTER COLUMN product_name SET NOT NULL;
+
+ALTER TABLE orders
+	ALTER COLUMN total_in_cents SET NOT NULL;
\ No newline at end of file
@@ -0,0 +1,2 @@
+--	a value can not be removed from an enum, the commission entries are kept as they are
+SELECT 1;
\ No newline at end of file
@@ -0,0 +1,2 @@
+--	the platform commission taken on each sale, see `platform_ledger`
+ALTER TYPE ledger_entry_kind ADD VALUE IF NOT EXISTS 'commission';
\ No newline at end of file
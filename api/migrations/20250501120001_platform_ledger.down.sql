@@ -0,0 +1,8 @@
+--	the ledger is append-only, except for this rollback
+ALTER TABLE ledger_entries DISABLE TRIGGER append_only_ledger_entries;
+DELETE FROM ledger_entries WHERE user_id IS NULL;
+ALTER TABLE ledger_entries ENABLE TRIGGER append_only_ledger_entries;
+
+ALTER TABLE ledger_entries
+	DROP CONSTRAINT IF EXISTS ledger_entries_platform_check,
+	ALTER COLUMN user_id SET NOT NULL;
\ No newline at end of file
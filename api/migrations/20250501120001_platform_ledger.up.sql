@@ -0,0 +1,15 @@
+--	entries without a user belong to the platform, the commissions it took (or gave back)
+--	so the entries of an order always add up to zero
+
+ALTER TABLE ledger_entries
+	ALTER COLUMN user_id DROP NOT NULL,
+	ADD CONSTRAINT ledger_entries_platform_check CHECK((user_id IS NULL) = (kind = 'commission'));
+
+--	commissions taken before they were recorded
+
+INSERT INTO ledger_entries ( order_id, kind, amount_in_cents, created_at )
+SELECT order_id, 'commission', -SUM(amount_in_cents), MIN(created_at)
+FROM ledger_entries
+WHERE kind IN ('order_debit', 'sale_credit') AND order_id IS NOT NULL
+GROUP BY order_id
+HAVING SUM(amount_in_cents) <> 0;
\ No newline at end of file
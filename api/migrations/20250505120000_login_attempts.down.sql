@@ -0,0 +1,12 @@
+ALTER TABLE users
+	ADD COLUMN failed_login_attempts INT NOT NULL DEFAULT 0,
+	ADD COLUMN locked_until TIMESTAMPTZ;
+
+--	the attempts on unknown emails are lost
+UPDATE users
+SET failed_login_attempts = login_attempts.failed_login_attempts,
+	locked_until = login_attempts.locked_until
+FROM login_attempts
+WHERE login_attempts.email = users.email;
+
+DROP TABLE IF EXISTS login_attempts;
\ No newline at end of file
@@ -0,0 +1,17 @@
+--	failed logins are counted per email, whether an account has it or not,
+--	so that neither a lockout nor its absence tells which emails are registered
+CREATE TABLE IF NOT EXISTS login_attempts (
+	email VARCHAR(255) PRIMARY KEY CHECK(email <> ''),
+	failed_login_attempts INT NOT NULL DEFAULT 0,
+	locked_until TIMESTAMPTZ,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO login_attempts (email, failed_login_attempts, locked_until)
+SELECT email, failed_login_attempts, locked_until
+FROM users
+WHERE failed_login_attempts > 0 OR locked_until IS NOT NULL;
+
+ALTER TABLE users
+	DROP COLUMN failed_login_attempts,
+	DROP COLUMN locked_until;
\ No newline at end of file
@@ -0,0 +1,3 @@
+--	the removed products are listed again
+ALTER TABLE products
+	DROP COLUMN IF EXISTS removed_at;
\ No newline at end of file
@@ -0,0 +1,4 @@
+--	ordered products are referenced by order_items and orders, so they are not deleted
+--	but marked as removed, and no longer listed nor sold
+ALTER TABLE products
+	ADD COLUMN removed_at TIMESTAMPTZ;
\ No newline at end of file
@@ -0,0 +1,8 @@
+DROP TRIGGER IF EXISTS copy_order_delivery_address ON orders;
+DROP FUNCTION IF EXISTS copy_order_delivery_address;
+
+ALTER TABLE orders
+	DROP COLUMN IF EXISTS delivery_street,
+	DROP COLUMN IF EXISTS delivery_city,
+	DROP COLUMN IF EXISTS delivery_postal_code,
+	DROP COLUMN IF EXISTS delivery_country;
\ No newline at end of file
@@ -0,0 +1,46 @@
+--	the delivery address is copied on the order, so that editing or deleting it
+--	in the address book does not change where past orders were sent
+
+--	orders placed before the address book have no address to copy: the
+--	20250215120000_addresses migration dropped the order_details rows and reset the
+--	order_details_id of the orders. No route ever inserted into order_details, and
+--	orders.order_details_id referenced users(id), not that table, so no order could point
+--	at one of its rows. A row added by hand had no owner, nor a street, city and postal
+--	code to split its free text into
+
+ALTER TABLE orders
+	ADD COLUMN delivery_street VARCHAR(255),
+	ADD COLUMN delivery_city VARCHAR(100),
+	ADD COLUMN delivery_postal_code VARCHAR(20),
+	ADD COLUMN delivery_country VARCHAR(100);
+
+UPDATE orders
+SET delivery_street = order_details.street,
+	delivery_city = order_details.city,
+	delivery_postal_code = order_details.postal_code,
+	delivery_country = order_details.country
+FROM order_details
+WHERE order_details.id = orders.order_details_id;
+
+--	function/triggers
+
+	--	--	copy the address chosen for the order
+
+	CREATE OR REPLACE FUNCTION copy_order_delivery_address()
+	RETURNS TRIGGER AS $$
+	BEGIN
+		--	the copy is kept when the address is deleted (order_details_id set to NULL)
+		IF NEW.order_details_id IS NOT NULL THEN
+			SELECT street, city, postal_code, country
+			INTO NEW.delivery_street, NEW.delivery_city, NEW.delivery_postal_code, NEW.delivery_country
+			FROM order_details
+			WHERE id = NEW.order_details_id;
+		END IF;
+		RETURN NEW;
+	END;
+	$$ LANGUAGE plpgsql;
+	--  --
+	CREATE TRIGGER copy_order_delivery_address
+	BEFORE INSERT OR UPDATE OF order_details_id ON orders
+	FOR EACH ROW
+	EXECUTE FUNCTION copy_order_delivery_address();
\ No newline at end of file
@@ -0,0 +1,21 @@
+ALTER TABLE order_items
+	DROP COLUMN IF EXISTS shipment_id,
+	DROP COLUMN IF EXISTS refunded_at,
+	DROP COLUMN IF EXISTS refund_reason;
+
+DROP INDEX IF EXISTS order_shipments_order_id_idx;
+
+--	back to one shipment per order, the first one
+DELETE FROM order_shipments
+WHERE id IN (
+	SELECT id
+	FROM (
+		SELECT id, ROW_NUMBER() OVER (PARTITION BY order_id ORDER BY shipped_at, id) AS rank
+		FROM order_shipments
+	) AS shipments
+	WHERE rank > 1
+);
+
+ALTER TABLE order_shipments
+	DROP COLUMN id,
+	ADD PRIMARY KEY (order_id);
\ No newline at end of file
@@ -0,0 +1,27 @@
+--	an order holds the products of several sellers: each of them ships, delivers and refunds
+--	their own items, and the order follows once every item not refunded did
+
+--	one shipment per seller of the order
+ALTER TABLE order_shipments
+	DROP CONSTRAINT order_shipments_pkey,
+	ADD COLUMN id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4());
+
+CREATE INDEX IF NOT EXISTS order_shipments_order_id_idx ON order_shipments (order_id);
+
+ALTER TABLE order_items
+	ADD COLUMN shipment_id UUID REFERENCES order_shipments(id) ON DELETE SET NULL,
+	ADD COLUMN refunded_at TIMESTAMPTZ,
+	ADD COLUMN refund_reason VARCHAR(500);
+
+--	the orders shipped or refunded so far were as a whole
+
+UPDATE order_items
+SET shipment_id = order_shipments.id
+FROM order_shipments
+WHERE order_shipments.order_id = order_items.order_id;
+
+UPDATE order_items
+SET refunded_at = order_cancellations.created_at, refund_reason = order_cancellations.reason
+FROM order_cancellations
+WHERE order_cancellations.order_id = order_items.order_id
+	AND order_cancellations.status = 'refunded';
\ No newline at end of file
@@ -1,7 +1,16 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use crate::utils::models::{Order, Product, User};
+use crate::utils::{
+    models::{
+        Address, ApiKey, ApiKeyScope, BalanceDrift, CartItem, IdempotencyKey, LedgerEntry,
+        LoginAttempts, Order, OrderCancellation, OrderItem, OrderShipment, PaymentIntent, Product,
+        ProductSearch, RecoveryCode, SaleSearch, Session, SessionDevice, User, UserTokenKind,
+        UserTotp,
+    },
+    pagination::{Page, PageRequest, SearchCursor},
+};
 
 pub mod init;
 pub mod psql;
@@ -21,15 +30,14 @@ pub trait UserExtractor {
         limit: usize,
     ) -> Result<Vec<User>, sqlx::Error>;
 
-    async fn get_all_users(&self, page: u32, limit: usize) -> Result<Vec<User>, sqlx::Error>;
+    async fn get_all_users(&self, page: &PageRequest) -> Result<Page<User>, sqlx::Error>;
 
     #[allow(dead_code)]
     async fn get_all_users_starting_by(
         &self,
         name: String,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<User>, sqlx::Error>;
+        page: &PageRequest,
+    ) -> Result<Page<User>, sqlx::Error>;
 
     async fn save_user<T: Into<String> + Send>(
         &self,
@@ -45,23 +53,14 @@ pub trait UserExtractor {
 pub trait ProductExtractor {
     async fn get_product(&self, product_id: &Uuid) -> Result<Option<Product>, sqlx::Error>;
 
-    #[allow(dead_code)]
-    async fn get_products_by_name(
-        &self,
-        name: String,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Product>, sqlx::Error>;
-
-    async fn get_all_products(&self, page: u32, limit: usize) -> Result<Vec<Product>, sqlx::Error>;
+    async fn get_all_products(&self, page: &PageRequest) -> Result<Page<Product>, sqlx::Error>;
 
-    #[allow(dead_code)]
-    async fn get_all_products_starting_by(
+    /// Keyset paginated on the value of `search.applied_sort()`, then `id`
+    async fn search_products(
         &self,
-        name: String,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Product>, sqlx::Error>;
+        search: &ProductSearch,
+        page: &PageRequest<SearchCursor>,
+    ) -> Result<Page<Product>, sqlx::Error>;
 
     async fn save_product<T: Into<String> + Send>(
         &self,
@@ -72,14 +71,24 @@ pub trait ProductExtractor {
         number_in_stock: i32,
     ) -> Result<Product, sqlx::Error>;
 
+    /// Only marks it as removed, it stays in the orders which reference it
     async fn delete_product(&self, product_id: &Uuid) -> Result<(), sqlx::Error>;
 
+    /// `None` fields are left unchanged
+    async fn modify_product(
+        &self,
+        product_id: &Uuid,
+        name: Option<&str>,
+        description: Option<&str>,
+        price_in_cents: Option<i64>,
+        number_in_stock: Option<i32>,
+    ) -> Result<Product, sqlx::Error>;
+
     async fn get_products_by_user(
         &self,
         user_id: &Uuid,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Product>, sqlx::Error>;
+        page: &PageRequest,
+    ) -> Result<Page<Product>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -92,9 +101,10 @@ pub trait OrderExtractor {
         order_id: &Uuid,
     ) -> Result<Option<Order>, sqlx::Error>;
 
-    #[allow(dead_code)]
-    async fn get_all_orders(&self, page: u32, limit: usize) -> Result<Vec<Order>, sqlx::Error>;
+    async fn get_all_orders(&self, page: &PageRequest) -> Result<Page<Order>, sqlx::Error>;
 
+    /// Snapshots the price and the name of the product, fails with `RowNotFound`
+    /// if it does not exist
     async fn save_order(
         &self,
         user_id: &Uuid,
@@ -108,25 +118,332 @@ pub trait OrderExtractor {
     async fn get_orders_by_user(
         &self,
         user_id: &Uuid,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Order>, sqlx::Error>;
+        page: &PageRequest,
+    ) -> Result<Page<Order>, sqlx::Error>;
+
+    /// Orders with at least one product of the seller, newest first
+    async fn get_sales_by_seller(
+        &self,
+        seller_id: &Uuid,
+        search: &SaleSearch,
+        page: &PageRequest,
+    ) -> Result<Page<Order>, sqlx::Error>;
+
+    async fn get_order_items(&self, order_id: &Uuid) -> Result<Vec<OrderItem>, sqlx::Error>;
+
+    /// Only the items of the order sold by `seller_id`
+    async fn get_order_items_by_seller(
+        &self,
+        order_id: &Uuid,
+        seller_id: &Uuid,
+    ) -> Result<Vec<OrderItem>, sqlx::Error>;
+
+    async fn get_order_cancellation(
+        &self,
+        order_id: &Uuid,
+    ) -> Result<Option<OrderCancellation>, sqlx::Error>;
+
+    /// One per seller who shipped their items of the order
+    async fn get_order_shipments(&self, order_id: &Uuid)
+        -> Result<Vec<OrderShipment>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait AddressExtractor {
+    async fn get_address_if_belong_to_user(
+        &self,
+        user_id: &Uuid,
+        address_id: &Uuid,
+    ) -> Result<Option<Address>, sqlx::Error>;
+
+    async fn get_addresses_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<Address>, sqlx::Error>;
+
+    async fn save_address(
+        &self,
+        user_id: &Uuid,
+        street: &str,
+        city: &str,
+        postal_code: &str,
+        country: &str,
+    ) -> Result<Address, sqlx::Error>;
+
+    /// Fails with `RowNotFound` if the address does not belong to the user
+    async fn modify_address(
+        &self,
+        user_id: &Uuid,
+        address_id: &Uuid,
+        street: &str,
+        city: &str,
+        postal_code: &str,
+        country: &str,
+    ) -> Result<Address, sqlx::Error>;
+
+    /// Fails with `RowNotFound` if the address does not belong to the user
+    async fn delete_address(&self, user_id: &Uuid, address_id: &Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait CartExtractor {
+    async fn get_cart_items(&self, user_id: &Uuid) -> Result<Vec<CartItem>, sqlx::Error>;
+
+    /// Adds `products_number` to the line if the product is already in the cart
+    async fn save_cart_item(
+        &self,
+        user_id: &Uuid,
+        product_id: &Uuid,
+        products_number: i32,
+    ) -> Result<CartItem, sqlx::Error>;
+
+    async fn modify_cart_item(
+        &self,
+        user_id: &Uuid,
+        product_id: &Uuid,
+        products_number: i32,
+    ) -> Result<CartItem, sqlx::Error>;
+
+    async fn delete_cart_item(&self, user_id: &Uuid, product_id: &Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait LedgerExtractor {
+    async fn get_ledger_entries_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<LedgerEntry>, sqlx::Error>;
+
+    /// Every balance movement made for the order, oldest first
+    async fn get_ledger_entries_by_order(
+        &self,
+        order_id: &Uuid,
+    ) -> Result<Vec<LedgerEntry>, sqlx::Error>;
+
+    /// Users whose `sold_in_cents` differs from the sum of their ledger entries
+    async fn get_balance_drifts(&self) -> Result<Vec<BalanceDrift>, sqlx::Error>;
 }
 
 #[async_trait]
 pub trait UserModifier: UserExtractor {
-    async fn modify_user_last_token_id(
+    /// `None` fields are left unchanged, a taken email violates `users_email_key`.
+    /// A new email has to be verified again
+    async fn modify_user(
+        &self,
+        user_id: &Uuid,
+        name: Option<&str>,
+        email: Option<&str>,
+        photo_url: Option<&str>,
+    ) -> Result<User, sqlx::Error>;
+
+    async fn modify_user_password(
         &self,
-        value: Option<&Uuid>,
         user_id: &Uuid,
+        hashed_password: &str,
     ) -> Result<(), sqlx::Error>;
+
+    async fn get_login_attempts(&self, email: &str) -> Result<Option<LoginAttempts>, sqlx::Error>;
+
+    /// Locks the email out once `max_attempts` logins in a row failed, for `lockout_seconds`
+    /// doubled by each further failure up to `max_lockout_seconds`
+    async fn record_failed_login(
+        &self,
+        email: &str,
+        max_attempts: i32,
+        lockout_seconds: i64,
+        max_lockout_seconds: i64,
+    ) -> Result<LoginAttempts, sqlx::Error>;
+
+    async fn reset_failed_logins(&self, email: &str) -> Result<(), sqlx::Error>;
+
+    /// Suspending an already suspended user keeps its first suspension date
+    async fn modify_user_suspension(
+        &self,
+        user_id: &Uuid,
+        suspended: bool,
+    ) -> Result<User, sqlx::Error>;
 }
 
 #[async_trait]
-pub trait UserUtils: UserExtractor {
-    async fn check_is_last_token(
+pub trait SessionExtractor {
+    /// Only the sha-256 of the token ids is stored
+    async fn save_session(
+        &self,
+        user_id: &Uuid,
+        access_token_id: &Uuid,
+        refresh_token_id: &Uuid,
+        device: &SessionDevice,
+    ) -> Result<Session, sqlx::Error>;
+
+    /// Also marks the session as used now
+    async fn get_session_by_access_token(
+        &self,
+        user_id: &Uuid,
+        access_token_id: &str,
+    ) -> Result<Option<Session>, sqlx::Error>;
+
+    async fn get_session_by_refresh_token(
         &self,
-        token_id: &str,
         user_id: &Uuid,
-    ) -> Result<bool, sqlx::Error>;
+        refresh_token_id: &str,
+    ) -> Result<Option<Session>, sqlx::Error>;
+
+    /// The session which has already rotated this refresh token out
+    async fn get_session_by_rotated_refresh_token(
+        &self,
+        user_id: &Uuid,
+        refresh_token_id: &str,
+    ) -> Result<Option<Session>, sqlx::Error>;
+
+    async fn get_sessions_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<Session>, sqlx::Error>;
+
+    /// Fails with `RowNotFound` if the session does not belong to the user
+    async fn delete_session(&self, user_id: &Uuid, session_id: &Uuid) -> Result<(), sqlx::Error>;
+
+    /// Logs the user out of every device
+    async fn delete_sessions_by_user(&self, user_id: &Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait UserTokenExtractor {
+    /// Replaces the unused tokens of this kind, only the sha-256 of the token is stored
+    async fn save_user_token(
+        &self,
+        user_id: &Uuid,
+        kind: UserTokenKind,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Uses the token, `None` if it is unknown, expired or already used
+    async fn verify_user_email(&self, token: &str) -> Result<Option<User>, sqlx::Error>;
+
+    /// Uses the token, `None` if it is unknown, expired or already used.
+    /// Receiving the email also verifies it
+    async fn reset_user_password(
+        &self,
+        token: &str,
+        hashed_password: &str,
+    ) -> Result<Option<User>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait TotpExtractor {
+    async fn get_user_totp(&self, user_id: &Uuid) -> Result<Option<UserTotp>, sqlx::Error>;
+
+    /// Replaces a pending secret, `None` if the TOTP is already enabled
+    async fn save_user_totp_secret(
+        &self,
+        user_id: &Uuid,
+        secret: &[u8],
+    ) -> Result<Option<UserTotp>, sqlx::Error>;
+
+    /// Enables the pending TOTP with the step of its first code, and replaces the
+    /// recovery codes. Fails with `RowNotFound` if there is no pending TOTP
+    async fn enable_user_totp(
+        &self,
+        user_id: &Uuid,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), sqlx::Error>;
+
+    /// `false` if a code of this step, or of a later one, was already accepted
+    async fn use_totp_step(&self, user_id: &Uuid, step: i64) -> Result<bool, sqlx::Error>;
+
+    async fn get_unused_recovery_codes(
+        &self,
+        user_id: &Uuid,
+    ) -> Result<Vec<RecoveryCode>, sqlx::Error>;
+
+    /// `false` if the code was already used
+    async fn use_recovery_code(&self, recovery_code_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Also deletes the recovery codes
+    async fn delete_user_totp(&self, user_id: &Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ApiKeyExtractor {
+    /// Only the sha-256 of the key is stored
+    async fn save_api_key(
+        &self,
+        user_id: &Uuid,
+        name: &str,
+        key: &str,
+        prefix: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<ApiKey, sqlx::Error>;
+
+    /// Also marks the key as used now
+    async fn get_api_key(&self, key: &str) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    async fn get_api_keys_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<ApiKey>, sqlx::Error>;
+
+    /// Fails with `RowNotFound` if the key does not belong to the user
+    async fn delete_api_key(&self, user_id: &Uuid, api_key_id: &Uuid) -> Result<(), sqlx::Error>;
+
+    async fn delete_api_keys_by_user(&self, user_id: &Uuid) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait IdempotencyKeyExtractor {
+    /// `None` if the user already used the key, the expired keys of the user are deleted first
+    async fn save_idempotency_key(
+        &self,
+        user_id: &Uuid,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+
+    /// Expired keys are not returned
+    async fn get_idempotency_key(
+        &self,
+        user_id: &Uuid,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+
+    async fn save_idempotency_response(
+        &self,
+        user_id: &Uuid,
+        key: &str,
+        status: i16,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), sqlx::Error>;
+
+    /// Frees the key, when the request failed before doing anything
+    async fn delete_idempotency_key(&self, user_id: &Uuid, key: &str) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait PaymentExtractor {
+    async fn save_payment_intent(
+        &self,
+        user_id: &Uuid,
+        gateway_intent_id: &str,
+        amount_in_cents: i64,
+    ) -> Result<PaymentIntent, sqlx::Error>;
+
+    async fn get_payment_intent_if_belong_to_user(
+        &self,
+        user_id: &Uuid,
+        payment_intent_id: &Uuid,
+    ) -> Result<Option<PaymentIntent>, sqlx::Error>;
+
+    /// For the webhooks, which only know the id of the gateway
+    async fn get_payment_intent_by_gateway_id(
+        &self,
+        gateway_intent_id: &str,
+    ) -> Result<Option<PaymentIntent>, sqlx::Error>;
 }
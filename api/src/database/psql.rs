@@ -1,10 +1,24 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
-use crate::utils::models::{Order, Product, User};
-
-use super::{OrderExtractor, ProductExtractor, UserExtractor, UserModifier, UserUtils};
+use crate::utils::{
+    models::{
+        Address, ApiKey, ApiKeyScope, BalanceDrift, CartItem, IdempotencyKey, LedgerEntry,
+        LoginAttempts, Order, OrderCancellation, OrderItem, OrderShipment, PaymentIntent, Product,
+        ProductSearch, ProductSort, RecoveryCode, SaleSearch, Session, SessionDevice, User,
+        UserTokenKind, UserTotp,
+    },
+    pagination::{Page, PageRequest, SearchCursor},
+    token,
+};
+
+use super::{
+    AddressExtractor, ApiKeyExtractor, CartExtractor, IdempotencyKeyExtractor, LedgerExtractor,
+    OrderExtractor, PaymentExtractor, ProductExtractor, SessionExtractor, TotpExtractor,
+    UserExtractor, UserModifier, UserTokenExtractor,
+};
 
 #[derive(Debug, Clone)]
 pub struct DBClient {
@@ -22,12 +36,20 @@ impl DBClient {
     }
 }
 
+/// A product found by a search, with the rank its next page starts after
+#[derive(sqlx::FromRow)]
+struct SearchedProduct {
+    #[sqlx(flatten)]
+    product: Product,
+    rank: f32,
+}
+
 #[async_trait]
 impl UserExtractor for DBClient {
     async fn get_user(&self, user_id: &Uuid) -> Result<Option<User>, sqlx::Error> {
         let user: Option<User> = sqlx::query_as::<_, User>(
             r"
-			SELECT id, name, email, password, sold_in_cents, last_token_id, created_at, updated_at
+			SELECT id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, created_at, updated_at
 			FROM users
 			WHERE id = $1
 			",
@@ -42,7 +64,7 @@ impl UserExtractor for DBClient {
     async fn get_user_by_email(&self, email: String) -> Result<Option<User>, sqlx::Error> {
         let user: Option<User> = sqlx::query_as::<_, User>(
             r"
-			SELECT id, name, email, password, sold_in_cents, last_token_id, created_at, updated_at
+			SELECT id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, created_at, updated_at
 			FROM users
 			WHERE email = $1
 			",
@@ -64,7 +86,7 @@ impl UserExtractor for DBClient {
 
         let users: Vec<User> = sqlx::query_as::<_, User>(
             r"
-			SELECT id, name, email, password, sold_in_cents, last_token_id, created_at, updated_at
+			SELECT id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, created_at, updated_at
 			FROM users
 			WHERE name = $1
 			LIMIT $2
@@ -80,49 +102,80 @@ impl UserExtractor for DBClient {
         Ok(users)
     }
 
-    async fn get_all_users(&self, page: u32, limit: usize) -> Result<Vec<User>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
-
-        let users: Vec<User> = sqlx::query_as::<_, User>(
+    async fn get_all_users(&self, page: &PageRequest) -> Result<Page<User>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(
             r"
-			SELECT id, name, email, password, sold_in_cents, last_token_id, created_at, updated_at
-			FROM users
-			LIMIT $1
-			OFFSET $2
-			",
+				SELECT id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, created_at, updated_at
+				FROM users
+				WHERE ($1::TIMESTAMPTZ IS NULL OR (created_at, id) < ($1, $2))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $3
+				",
         )
-        .bind(limit as i64)
-        .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
         .await?;
 
-        Ok(users)
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM users
+					",
+            )
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(users, page, total))
     }
 
     async fn get_all_users_starting_by(
         &self,
         name: String,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<User>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
-
-        let users: Vec<User> = sqlx::query_as::<_, User>(
+        page: &PageRequest,
+    ) -> Result<Page<User>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(
             r"
-			SELECT id, name, email, password, sold_in_cents, last_token_id, created_at, updated_at
-			FROM users
-			WHERE starts_with(name, $1)
-			LIMIT $2
-			OFFSET $3
-			",
+				SELECT id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, created_at, updated_at
+				FROM users
+				WHERE starts_with(name, $1)
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
+				",
         )
-        .bind(name)
-        .bind(limit as i64)
-        .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .bind(&name)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
         .await?;
 
-        Ok(users)
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM users
+					WHERE starts_with(name, $1)
+					",
+            )
+            .bind(name)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(users, page, total))
     }
 
     async fn save_user<T: Into<String> + Send>(
@@ -135,7 +188,7 @@ impl UserExtractor for DBClient {
             r"
 			INSERT INTO users ( name, email, password )
 			VALUES ( $1, $2, $3 )
-			RETURNING id, name, email, password, sold_in_cents, last_token_id, updated_at, created_at
+			RETURNING id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, updated_at, created_at
 			",
         )
         .bind(name.into())
@@ -168,145 +221,339 @@ impl UserExtractor for DBClient {
 
 #[async_trait]
 impl UserModifier for DBClient {
-    async fn modify_user_last_token_id(
+    async fn modify_user(
         &self,
-        value: Option<&Uuid>,
         user_id: &Uuid,
-    ) -> Result<(), sqlx::Error> {
-        let value = if let Some(token_id) = value {
-            Some(bcrypt::hash(token_id.to_string(), 4).map_err(|_| sqlx::Error::WorkerCrashed)?)
-        } else {
-            None
-        };
+        name: Option<&str>,
+        email: Option<&str>,
+        photo_url: Option<&str>,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(
+            r"
+			UPDATE users
+			SET name = COALESCE($1, name),
+				email = COALESCE($2, email),
+				photo_url = COALESCE($3, photo_url),
+				email_verified_at = CASE WHEN $2 IS NULL OR $2 = email THEN email_verified_at END
+			WHERE id = $4
+			RETURNING id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, updated_at, created_at
+			",
+        )
+        .bind(name)
+        .bind(email)
+        .bind(photo_url)
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
 
-        sqlx::query(
+        user.ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn modify_user_password(
+        &self,
+        user_id: &Uuid,
+        hashed_password: &str,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
             r"
 			UPDATE users
-			SET last_token_id = $1
+			SET password = $1
 			WHERE id = $2
 			",
         )
-        .bind(value)
+        .bind(hashed_password)
         .bind(user_id)
         .execute(self.pool())
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
         Ok(())
     }
-}
 
-#[async_trait]
-impl ProductExtractor for DBClient {
-    async fn get_product(&self, product_id: &Uuid) -> Result<Option<Product>, sqlx::Error> {
-        let product: Option<Product> = sqlx::query_as::<_, Product>(
+    async fn get_login_attempts(&self, email: &str) -> Result<Option<LoginAttempts>, sqlx::Error> {
+        let attempts = sqlx::query_as::<_, LoginAttempts>(
             r"
-			SELECT id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at
-			FROM products
-			WHERE id = $1
+			SELECT email, failed_login_attempts, locked_until, updated_at
+			FROM login_attempts
+			WHERE email = $1
 			",
         )
-        .bind(product_id)
-        .fetch_optional(&self.pool)
+        .bind(email)
+        .fetch_optional(self.pool())
         .await?;
 
-        Ok(product)
+        Ok(attempts)
     }
 
-    async fn get_products_by_user(
+    async fn record_failed_login(
+        &self,
+        email: &str,
+        max_attempts: i32,
+        lockout_seconds: i64,
+        max_lockout_seconds: i64,
+    ) -> Result<LoginAttempts, sqlx::Error> {
+        // the exponent is capped before POWER can overflow
+        let attempts = sqlx::query_as::<_, LoginAttempts>(
+            r"
+			INSERT INTO login_attempts AS attempts (email, failed_login_attempts, locked_until)
+			VALUES ($1, 1, CASE WHEN 1 >= $2 THEN NOW() + make_interval(secs => LEAST($3, $4)) END)
+			ON CONFLICT (email) DO UPDATE
+			SET failed_login_attempts = attempts.failed_login_attempts + 1,
+				locked_until = CASE
+					WHEN attempts.failed_login_attempts + 1 >= $2 THEN NOW() + make_interval(secs => LEAST(
+						$3 * POWER(2, LEAST(attempts.failed_login_attempts + 1 - $2, 32)),
+						$4
+					))
+					ELSE attempts.locked_until
+				END,
+				updated_at = NOW()
+			RETURNING email, failed_login_attempts, locked_until, updated_at
+			",
+        )
+        .bind(email)
+        .bind(max_attempts)
+        .bind(lockout_seconds)
+        .bind(max_lockout_seconds)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(attempts)
+    }
+
+    async fn reset_failed_logins(&self, email: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+			DELETE FROM login_attempts
+			WHERE email = $1
+			",
+        )
+        .bind(email)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn modify_user_suspension(
         &self,
         user_id: &Uuid,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Product>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
+        suspended: bool,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(
+            r"
+			UPDATE users
+			SET suspended_at = CASE WHEN $1 THEN COALESCE(suspended_at, NOW()) END
+			WHERE id = $2
+			RETURNING id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, updated_at, created_at
+			",
+        )
+        .bind(suspended)
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
 
-        let products: Vec<Product> = sqlx::query_as::<_, Product>(
+        user.ok_or(sqlx::Error::RowNotFound)
+    }
+}
+
+#[async_trait]
+impl ProductExtractor for DBClient {
+    async fn get_product(&self, product_id: &Uuid) -> Result<Option<Product>, sqlx::Error> {
+        let product: Option<Product> = sqlx::query_as::<_, Product>(
             r"
 			SELECT id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at
 			FROM products
-			WHERE user_id = $1
-			LIMIT $2
-			OFFSET $3
+			WHERE id = $1 AND removed_at IS NULL
 			",
         )
-        .bind(user_id)
-        .bind(limit as i64)
-        .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .bind(product_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(products)
+        Ok(product)
     }
 
-    async fn get_products_by_name(
+    async fn get_products_by_user(
         &self,
-        name: String,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Product>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
-
-        let products: Vec<Product> = sqlx::query_as::<_, Product>(
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<Product>, sqlx::Error> {
+        let products = sqlx::query_as::<_, Product>(
             r"
 				SELECT id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at
 				FROM products
-				WHERE name = $1
-				LIMIT $2
-				OFFSET $3
+				WHERE user_id = $1 AND removed_at IS NULL
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
 				",
         )
-        .bind(name)
-        .bind(limit as i64)
-        .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .bind(user_id)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
         .await?;
 
-        Ok(products)
-    }
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM products
+					WHERE user_id = $1 AND removed_at IS NULL
+					",
+            )
+            .bind(user_id)
+            .fetch_one(self.pool())
+            .await?;
 
-    async fn get_all_products(&self, page: u32, limit: usize) -> Result<Vec<Product>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(products, page, total))
+    }
 
-        let products: Vec<Product> = sqlx::query_as::<_, Product>(
+    async fn get_all_products(&self, page: &PageRequest) -> Result<Page<Product>, sqlx::Error> {
+        let products = sqlx::query_as::<_, Product>(
             r"
 				SELECT id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at
 				FROM products
-				LIMIT $1
-				OFFSET $2
+				WHERE removed_at IS NULL
+					AND ($1::TIMESTAMPTZ IS NULL OR (created_at, id) < ($1, $2))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $3
 				",
         )
-        .bind(limit as i64)
-        .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
         .await?;
 
-        Ok(products)
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM products
+					WHERE removed_at IS NULL
+					",
+            )
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(products, page, total))
     }
 
-    async fn get_all_products_starting_by(
+    async fn search_products(
         &self,
-        name: String,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Product>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
+        search: &ProductSearch,
+        page: &PageRequest<SearchCursor>,
+    ) -> Result<Page<Product>, sqlx::Error> {
+        let sort = search.applied_sort();
+
+        // `id` last and in the same direction, so that a row compares as a whole with the cursor
+        let (sorted_by, after_value, direction) = match sort {
+            ProductSort::Relevance => (
+                "ts_rank(search_vector, websearch_to_tsquery('english', $1))",
+                "$6",
+                "DESC",
+            ),
+            ProductSort::Newest => ("created_at", "$7", "DESC"),
+            ProductSort::PriceAsc => ("price_in_cents", "$8", "ASC"),
+            ProductSort::PriceDesc => ("price_in_cents", "$8", "DESC"),
+        };
+        let comparison = if direction == "ASC" { ">" } else { "<" };
 
-        let products: Vec<Product> = sqlx::query_as::<_, Product>(
+        let query = format!(
             r"
-				SELECT id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at
+				SELECT id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at,
+					COALESCE(ts_rank(search_vector, websearch_to_tsquery('english', $1)), 0) AS rank
 				FROM products
-				WHERE starts_with(name, $1)
-				LIMIT $2
-				OFFSET $3
-				",
-        )
-        .bind(name)
-        .bind(limit as i64)
-        .bind(offset as i64)
-        .fetch_all(&self.pool)
-        .await?;
+				WHERE removed_at IS NULL
+					AND ($1::TEXT IS NULL OR search_vector @@ websearch_to_tsquery('english', $1))
+					AND ($2::BIGINT IS NULL OR price_in_cents >= $2)
+					AND ($3::BIGINT IS NULL OR price_in_cents <= $3)
+					AND (NOT $4 OR number_in_stock > 0)
+					AND ($5::UUID IS NULL OR user_id = $5)
+					AND ($9::UUID IS NULL OR ({sorted_by}, id) {comparison} ({after_value}, $9))
+				ORDER BY {sorted_by} {direction}, id {direction}
+				LIMIT $10
+				"
+        );
+
+        let (after_rank, after_created_at, after_price) = match page.after {
+            Some(SearchCursor::Rank(rank, _)) => (Some(rank), None, None),
+            Some(SearchCursor::CreatedAt(created_at, _)) => (None, Some(created_at), None),
+            Some(SearchCursor::Price(price, _)) => (None, None, Some(price)),
+            None => (None, None, None),
+        };
+
+        let mut rows: Vec<SearchedProduct> = sqlx::query_as::<_, SearchedProduct>(&query)
+            .bind(search.text.as_deref())
+            .bind(search.min_price_in_cents)
+            .bind(search.max_price_in_cents)
+            .bind(search.in_stock)
+            .bind(search.seller_id)
+            .bind(after_rank)
+            .bind(after_created_at)
+            .bind(after_price)
+            .bind(page.after.as_ref().map(SearchCursor::id))
+            .bind(page.fetch_limit())
+            .fetch_all(self.pool())
+            .await?;
+
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM products
+					WHERE removed_at IS NULL
+						AND ($1::TEXT IS NULL OR search_vector @@ websearch_to_tsquery('english', $1))
+						AND ($2::BIGINT IS NULL OR price_in_cents >= $2)
+						AND ($3::BIGINT IS NULL OR price_in_cents <= $3)
+						AND (NOT $4 OR number_in_stock > 0)
+						AND ($5::UUID IS NULL OR user_id = $5)
+					",
+            )
+            .bind(search.text.as_deref())
+            .bind(search.min_price_in_cents)
+            .bind(search.max_price_in_cents)
+            .bind(search.in_stock)
+            .bind(search.seller_id)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        let has_more = rows.len() > page.limit;
+        rows.truncate(page.limit);
+
+        let next_cursor = if has_more {
+            rows.last()
+                .map(|row| SearchCursor::of(sort, &row.product, row.rank).encode())
+        } else {
+            None
+        };
 
-        Ok(products)
+        Ok(Page {
+            items: rows.into_iter().map(|row| row.product).collect(),
+            next_cursor,
+            has_more,
+            total,
+        })
     }
 
     async fn save_product<T: Into<String> + Send>(
@@ -335,22 +582,57 @@ impl ProductExtractor for DBClient {
         Ok(product)
     }
 
-    async fn delete_product(&self, user_id: &Uuid) -> Result<(), sqlx::Error> {
-        let result = sqlx::query(
+    async fn delete_product(&self, product_id: &Uuid) -> Result<(), sqlx::Error> {
+        // the orders keep it, the carts drop it
+        let removed = sqlx::query_scalar::<_, Uuid>(
             r"
-			DELETE FROM products
-			WHERE id = $1
+			WITH removed AS (
+				UPDATE products
+				SET removed_at = NOW()
+				WHERE id = $1 AND removed_at IS NULL
+				RETURNING id
+			), cleared AS (
+				DELETE FROM cart_items
+				WHERE product_id IN (SELECT id FROM removed)
+			)
+			SELECT id FROM removed
 			",
         )
-        .bind(user_id)
-        .execute(&self.pool)
+        .bind(product_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        if result.rows_affected() == 0 {
-            return Err(sqlx::Error::RowNotFound);
-        }
+        removed.map(|_| ()).ok_or(sqlx::Error::RowNotFound)
+    }
 
-        Ok(())
+    async fn modify_product(
+        &self,
+        product_id: &Uuid,
+        name: Option<&str>,
+        description: Option<&str>,
+        price_in_cents: Option<i64>,
+        number_in_stock: Option<i32>,
+    ) -> Result<Product, sqlx::Error> {
+        let product = sqlx::query_as::<_, Product>(
+            r"
+				UPDATE products
+				SET name = COALESCE($1, name),
+					description = COALESCE($2, description),
+					price_in_cents = COALESCE($3, price_in_cents),
+					number_in_stock = COALESCE($4, number_in_stock)
+				WHERE id = $5 AND removed_at IS NULL
+				RETURNING id, name, user_id, description, price_in_cents, number_in_stock, created_at, updated_at
+				",
+        )
+        .bind(name)
+        .bind(description)
+        .bind(price_in_cents)
+        .bind(number_in_stock)
+        .bind(product_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        product.ok_or(sqlx::Error::RowNotFound)
     }
 }
 
@@ -359,7 +641,9 @@ impl OrderExtractor for DBClient {
     async fn get_order(&self, order_id: &Uuid) -> Result<Option<Order>, sqlx::Error> {
         let order = sqlx::query_as::<_, Order>(
             r"
-				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number
+				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number,
+					unit_price_in_cents, product_name, total_in_cents, status,
+					delivery_street, delivery_city, delivery_postal_code, delivery_country
 				FROM orders
 				WHERE id = $1
 				",
@@ -393,23 +677,40 @@ impl OrderExtractor for DBClient {
         }
     }
 
-    async fn get_all_orders(&self, page: u32, limit: usize) -> Result<Vec<Order>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
-
+    async fn get_all_orders(&self, page: &PageRequest) -> Result<Page<Order>, sqlx::Error> {
         let orders = sqlx::query_as::<_, Order>(
             r"
-				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number
+				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number,
+					unit_price_in_cents, product_name, total_in_cents, status,
+					delivery_street, delivery_city, delivery_postal_code, delivery_country
 				FROM orders
-				ORDER BY created_at DESC
-				LIMIT $1 OFFSET $2
+				WHERE ($1::TIMESTAMPTZ IS NULL OR (created_at, id) < ($1, $2))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $3
 				",
         )
-        .bind(limit as i64)
-        .bind(offset as i64)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
         .fetch_all(self.pool())
         .await?;
 
-        Ok(orders)
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM orders
+					",
+            )
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(orders, page, total))
     }
 
     async fn save_order(
@@ -419,20 +720,46 @@ impl OrderExtractor for DBClient {
         order_details_id: Option<&Uuid>,
         products_number: i32,
     ) -> Result<Order, sqlx::Error> {
+        let mut tx = self.pool().begin().await?;
+
+        // the price and the name of the product are snapshotted, no row if it does not exist
         let order = sqlx::query_as::<_, Order>(
             r"
-				INSERT INTO orders( user_id, product_id, order_details_id, products_number )
-				VALUES ( $1, $2, $3, $4 )
-				RETURNING id, user_id, product_id, order_details_id, created_at, updated_at, products_number
+				INSERT INTO orders(
+					user_id, product_id, order_details_id, products_number,
+					unit_price_in_cents, product_name, total_in_cents
+				)
+				SELECT $1, id, $3, $4, price_in_cents, name, price_in_cents * $4
+				FROM products
+				WHERE id = $2 AND removed_at IS NULL
+				RETURNING id, user_id, product_id, order_details_id, created_at, updated_at, products_number,
+					unit_price_in_cents, product_name, total_in_cents, status,
+					delivery_street, delivery_city, delivery_postal_code, delivery_country
 				",
         )
         .bind(user_id)
         .bind(product_id)
         .bind(order_details_id)
         .bind(products_number)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r"
+				INSERT INTO order_items( order_id, product_id, products_number, unit_price_in_cents, product_name )
+				VALUES ( $1, $2, $3, $4, $5 )
+				",
+        )
+        .bind(order.id)
+        .bind(product_id)
+        .bind(products_number)
+        .bind(order.unit_price_in_cents)
+        .bind(&order.product_name)
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(order)
     }
 
@@ -457,741 +784,3181 @@ impl OrderExtractor for DBClient {
     async fn get_orders_by_user(
         &self,
         user_id: &Uuid,
-        page: u32,
-        limit: usize,
-    ) -> Result<Vec<Order>, sqlx::Error> {
-        let offset = (page - 1) * limit as u32;
-
+        page: &PageRequest,
+    ) -> Result<Page<Order>, sqlx::Error> {
         let orders = sqlx::query_as::<_, Order>(
             r"
-				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number
+				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number,
+					unit_price_in_cents, product_name, total_in_cents, status,
+					delivery_street, delivery_city, delivery_postal_code, delivery_country
 				FROM orders
 				WHERE user_id = $1
-				ORDER BY created_at DESC
-				LIMIT $2 OFFSET $3
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
 				",
         )
         .bind(user_id)
-        .bind(limit as i64)
-        .bind(offset as i64)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
         .fetch_all(self.pool())
         .await?;
 
-        Ok(orders)
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM orders
+					WHERE user_id = $1
+					",
+            )
+            .bind(user_id)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(orders, page, total))
     }
-}
 
-#[async_trait]
-impl UserUtils for DBClient {
-    async fn check_is_last_token(
+    async fn get_sales_by_seller(
         &self,
-        token_id: &str,
-        user_id: &Uuid,
-    ) -> Result<bool, sqlx::Error> {
-        let user = self
-            .get_user(user_id)
-            .await?
-            .ok_or_else(|| sqlx::Error::RowNotFound)?;
+        seller_id: &Uuid,
+        search: &SaleSearch,
+        page: &PageRequest,
+    ) -> Result<Page<Order>, sqlx::Error> {
+        let orders = sqlx::query_as::<_, Order>(
+            r"
+				SELECT id, user_id, product_id, order_details_id, created_at, updated_at, products_number,
+					unit_price_in_cents, product_name, total_in_cents, status,
+					delivery_street, delivery_city, delivery_postal_code, delivery_country
+				FROM orders
+				WHERE EXISTS (
+						SELECT 1
+						FROM order_items
+						JOIN products ON products.id = order_items.product_id
+						WHERE order_items.order_id = orders.id AND products.user_id = $1
+					)
+					AND ($2::order_status IS NULL OR status = $2)
+					AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+					AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+					AND ($5::TIMESTAMPTZ IS NULL OR (created_at, id) < ($5, $6))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $7
+				",
+        )
+        .bind(seller_id)
+        .bind(search.status)
+        .bind(search.created_after)
+        .bind(search.created_before)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
+        .await?;
+
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM orders
+					WHERE EXISTS (
+							SELECT 1
+							FROM order_items
+							JOIN products ON products.id = order_items.product_id
+							WHERE order_items.order_id = orders.id AND products.user_id = $1
+						)
+						AND ($2::order_status IS NULL OR status = $2)
+						AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+						AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+					",
+            )
+            .bind(seller_id)
+            .bind(search.status)
+            .bind(search.created_after)
+            .bind(search.created_before)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(orders, page, total))
+    }
+
+    async fn get_order_items(&self, order_id: &Uuid) -> Result<Vec<OrderItem>, sqlx::Error> {
+        let items = sqlx::query_as::<_, OrderItem>(
+            r"
+				SELECT id, order_id, product_id, products_number, unit_price_in_cents, product_name,
+					shipment_id, refunded_at, refund_reason, created_at
+				FROM order_items
+				WHERE order_id = $1
+				ORDER BY created_at, id
+				",
+        )
+        .bind(order_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(items)
+    }
+
+    async fn get_order_items_by_seller(
+        &self,
+        order_id: &Uuid,
+        seller_id: &Uuid,
+    ) -> Result<Vec<OrderItem>, sqlx::Error> {
+        let items = sqlx::query_as::<_, OrderItem>(
+            r"
+				SELECT order_items.id, order_id, product_id, products_number, unit_price_in_cents,
+					product_name, shipment_id, refunded_at, refund_reason, order_items.created_at
+				FROM order_items
+				JOIN products ON products.id = order_items.product_id
+				WHERE order_id = $1 AND products.user_id = $2
+				ORDER BY order_items.created_at, order_items.id
+				",
+        )
+        .bind(order_id)
+        .bind(seller_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(items)
+    }
+
+    async fn get_order_cancellation(
+        &self,
+        order_id: &Uuid,
+    ) -> Result<Option<OrderCancellation>, sqlx::Error> {
+        let cancellation = sqlx::query_as::<_, OrderCancellation>(
+            r"
+				SELECT order_id, cancelled_by, status, reason, created_at
+				FROM order_cancellations
+				WHERE order_id = $1
+				",
+        )
+        .bind(order_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(cancellation)
+    }
+
+    async fn get_order_shipments(
+        &self,
+        order_id: &Uuid,
+    ) -> Result<Vec<OrderShipment>, sqlx::Error> {
+        let shipments = sqlx::query_as::<_, OrderShipment>(
+            r"
+				SELECT id, order_id, shipped_by, tracking_number, shipped_at, delivered_at
+				FROM order_shipments
+				WHERE order_id = $1
+				ORDER BY shipped_at, id
+				",
+        )
+        .bind(order_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(shipments)
+    }
+}
+
+#[async_trait]
+impl AddressExtractor for DBClient {
+    async fn get_address_if_belong_to_user(
+        &self,
+        user_id: &Uuid,
+        address_id: &Uuid,
+    ) -> Result<Option<Address>, sqlx::Error> {
+        let address = sqlx::query_as::<_, Address>(
+            r"
+				SELECT id, user_id, street, city, postal_code, country, created_at, updated_at
+				FROM order_details
+				WHERE id = $1 AND user_id = $2
+				",
+        )
+        .bind(address_id)
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(address)
+    }
+
+    async fn get_addresses_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<Address>, sqlx::Error> {
+        let addresses = sqlx::query_as::<_, Address>(
+            r"
+				SELECT id, user_id, street, city, postal_code, country, created_at, updated_at
+				FROM order_details
+				WHERE user_id = $1
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
+				",
+        )
+        .bind(user_id)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
+        .await?;
+
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM order_details
+					WHERE user_id = $1
+					",
+            )
+            .bind(user_id)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(addresses, page, total))
+    }
+
+    async fn save_address(
+        &self,
+        user_id: &Uuid,
+        street: &str,
+        city: &str,
+        postal_code: &str,
+        country: &str,
+    ) -> Result<Address, sqlx::Error> {
+        let address = sqlx::query_as::<_, Address>(
+            r"
+				INSERT INTO order_details( user_id, street, city, postal_code, country )
+				VALUES ( $1, $2, $3, $4, $5 )
+				RETURNING id, user_id, street, city, postal_code, country, created_at, updated_at
+				",
+        )
+        .bind(user_id)
+        .bind(street)
+        .bind(city)
+        .bind(postal_code)
+        .bind(country)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(address)
+    }
+
+    async fn modify_address(
+        &self,
+        user_id: &Uuid,
+        address_id: &Uuid,
+        street: &str,
+        city: &str,
+        postal_code: &str,
+        country: &str,
+    ) -> Result<Address, sqlx::Error> {
+        let address = sqlx::query_as::<_, Address>(
+            r"
+				UPDATE order_details
+				SET street = $1, city = $2, postal_code = $3, country = $4
+				WHERE id = $5 AND user_id = $6
+				RETURNING id, user_id, street, city, postal_code, country, created_at, updated_at
+				",
+        )
+        .bind(street)
+        .bind(city)
+        .bind(postal_code)
+        .bind(country)
+        .bind(address_id)
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        address.ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn delete_address(&self, user_id: &Uuid, address_id: &Uuid) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            r"
+				DELETE FROM order_details
+				WHERE id = $1 AND user_id = $2
+				",
+        )
+        .bind(address_id)
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CartExtractor for DBClient {
+    async fn get_cart_items(&self, user_id: &Uuid) -> Result<Vec<CartItem>, sqlx::Error> {
+        let items = sqlx::query_as::<_, CartItem>(
+            r"
+				SELECT user_id, product_id, products_number, created_at, updated_at
+				FROM cart_items
+				WHERE user_id = $1
+				ORDER BY created_at, product_id
+				",
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(items)
+    }
+
+    async fn save_cart_item(
+        &self,
+        user_id: &Uuid,
+        product_id: &Uuid,
+        products_number: i32,
+    ) -> Result<CartItem, sqlx::Error> {
+        let item = sqlx::query_as::<_, CartItem>(
+            r"
+				INSERT INTO cart_items( user_id, product_id, products_number )
+				VALUES ( $1, $2, $3 )
+				ON CONFLICT ( user_id, product_id )
+				DO UPDATE SET products_number = cart_items.products_number + EXCLUDED.products_number
+				RETURNING user_id, product_id, products_number, created_at, updated_at
+				",
+        )
+        .bind(user_id)
+        .bind(product_id)
+        .bind(products_number)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(item)
+    }
+
+    async fn modify_cart_item(
+        &self,
+        user_id: &Uuid,
+        product_id: &Uuid,
+        products_number: i32,
+    ) -> Result<CartItem, sqlx::Error> {
+        let item = sqlx::query_as::<_, CartItem>(
+            r"
+				UPDATE cart_items
+				SET products_number = $1
+				WHERE user_id = $2 AND product_id = $3
+				RETURNING user_id, product_id, products_number, created_at, updated_at
+				",
+        )
+        .bind(products_number)
+        .bind(user_id)
+        .bind(product_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        item.ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn delete_cart_item(&self, user_id: &Uuid, product_id: &Uuid) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            r"
+				DELETE FROM cart_items
+				WHERE user_id = $1 AND product_id = $2
+				",
+        )
+        .bind(user_id)
+        .bind(product_id)
+        .execute(self.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LedgerExtractor for DBClient {
+    async fn get_ledger_entries_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<LedgerEntry>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, LedgerEntry>(
+            r"
+				SELECT id, user_id, order_id, kind, amount_in_cents, created_at
+				FROM ledger_entries
+				WHERE user_id = $1
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
+				",
+        )
+        .bind(user_id)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
+        .await?;
+
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM ledger_entries
+					WHERE user_id = $1
+					",
+            )
+            .bind(user_id)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(entries, page, total))
+    }
+
+    async fn get_ledger_entries_by_order(
+        &self,
+        order_id: &Uuid,
+    ) -> Result<Vec<LedgerEntry>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, LedgerEntry>(
+            r"
+				SELECT id, user_id, order_id, kind, amount_in_cents, created_at
+				FROM ledger_entries
+				WHERE order_id = $1
+				ORDER BY created_at, id
+				",
+        )
+        .bind(order_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn get_balance_drifts(&self) -> Result<Vec<BalanceDrift>, sqlx::Error> {
+        let drifts = sqlx::query_as::<_, BalanceDrift>(
+            r"
+				SELECT users.id AS user_id, users.sold_in_cents,
+					COALESCE(SUM(ledger_entries.amount_in_cents), 0)::BIGINT AS ledger_sum_in_cents
+				FROM users
+				LEFT JOIN ledger_entries ON ledger_entries.user_id = users.id
+				GROUP BY users.id
+				HAVING users.sold_in_cents <> COALESCE(SUM(ledger_entries.amount_in_cents), 0)
+				",
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(drifts)
+    }
+}
+
+#[async_trait]
+impl SessionExtractor for DBClient {
+    async fn save_session(
+        &self,
+        user_id: &Uuid,
+        access_token_id: &Uuid,
+        refresh_token_id: &Uuid,
+        device: &SessionDevice,
+    ) -> Result<Session, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r"
+				INSERT INTO sessions ( user_id, access_token_hash, refresh_token_hash, device_label, user_agent, ip_address )
+				VALUES ( $1, $2, $3, $4, $5, $6 )
+				RETURNING id, user_id, access_token_hash, refresh_token_hash, device_label, user_agent, ip_address, created_at, last_used_at
+				",
+        )
+        .bind(user_id)
+        .bind(token::hash_token_id(&access_token_id.to_string()))
+        .bind(token::hash_token_id(&refresh_token_id.to_string()))
+        .bind(device.label.as_deref())
+        .bind(device.user_agent.as_deref())
+        .bind(device.ip_address.as_deref())
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn get_session_by_access_token(
+        &self,
+        user_id: &Uuid,
+        access_token_id: &str,
+    ) -> Result<Option<Session>, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r"
+				UPDATE sessions
+				SET last_used_at = NOW()
+				WHERE user_id = $1 AND access_token_hash = $2
+				RETURNING id, user_id, access_token_hash, refresh_token_hash, device_label, user_agent, ip_address, created_at, last_used_at
+				",
+        )
+        .bind(user_id)
+        .bind(token::hash_token_id(access_token_id))
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn get_session_by_refresh_token(
+        &self,
+        user_id: &Uuid,
+        refresh_token_id: &str,
+    ) -> Result<Option<Session>, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r"
+				SELECT id, user_id, access_token_hash, refresh_token_hash, device_label, user_agent, ip_address, created_at, last_used_at
+				FROM sessions
+				WHERE user_id = $1 AND refresh_token_hash = $2
+				",
+        )
+        .bind(user_id)
+        .bind(token::hash_token_id(refresh_token_id))
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn get_session_by_rotated_refresh_token(
+        &self,
+        user_id: &Uuid,
+        refresh_token_id: &str,
+    ) -> Result<Option<Session>, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r"
+				SELECT sessions.id, sessions.user_id, sessions.access_token_hash, sessions.refresh_token_hash,
+					sessions.device_label, sessions.user_agent, sessions.ip_address, sessions.created_at, sessions.last_used_at
+				FROM rotated_refresh_tokens
+				JOIN sessions ON sessions.id = rotated_refresh_tokens.session_id
+				WHERE sessions.user_id = $1 AND rotated_refresh_tokens.token_hash = $2
+				",
+        )
+        .bind(user_id)
+        .bind(token::hash_token_id(refresh_token_id))
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(session)
+    }
+
+    async fn get_sessions_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<Session>, sqlx::Error> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r"
+				SELECT id, user_id, access_token_hash, refresh_token_hash, device_label, user_agent, ip_address, created_at, last_used_at
+				FROM sessions
+				WHERE user_id = $1
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
+				",
+        )
+        .bind(user_id)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
+        .await?;
+
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM sessions
+					WHERE user_id = $1
+					",
+            )
+            .bind(user_id)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(sessions, page, total))
+    }
+
+    async fn delete_session(&self, user_id: &Uuid, session_id: &Uuid) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            r"
+				DELETE FROM sessions
+				WHERE id = $1 AND user_id = $2
+				",
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_sessions_by_user(&self, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+				DELETE FROM sessions
+				WHERE user_id = $1
+				",
+        )
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserTokenExtractor for DBClient {
+    async fn save_user_token(
+        &self,
+        user_id: &Uuid,
+        kind: UserTokenKind,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+				WITH replaced AS (
+					DELETE FROM user_tokens
+					WHERE user_id = $1 AND kind = $2 AND used_at IS NULL
+				)
+				INSERT INTO user_tokens ( user_id, kind, token_hash, expires_at )
+				VALUES ( $1, $2, $3, $4 )
+				",
+        )
+        .bind(user_id)
+        .bind(kind)
+        .bind(token::hash_token_id(token))
+        .bind(expires_at)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn verify_user_email(&self, token: &str) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(
+            r"
+				WITH token AS (
+					UPDATE user_tokens
+					SET used_at = NOW()
+					WHERE token_hash = $1 AND kind = $2 AND used_at IS NULL AND expires_at > NOW()
+					RETURNING user_id
+				)
+				UPDATE users
+				SET email_verified_at = COALESCE(email_verified_at, NOW())
+				FROM token
+				WHERE users.id = token.user_id
+				RETURNING id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, updated_at, created_at
+				",
+        )
+        .bind(token::hash_token_id(token))
+        .bind(UserTokenKind::EmailVerification)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn reset_user_password(
+        &self,
+        token: &str,
+        hashed_password: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(
+            r"
+				WITH token AS (
+					UPDATE user_tokens
+					SET used_at = NOW()
+					WHERE token_hash = $1 AND kind = $2 AND used_at IS NULL AND expires_at > NOW()
+					RETURNING user_id
+				)
+				UPDATE users
+				SET password = $3,
+					email_verified_at = COALESCE(email_verified_at, NOW())
+				FROM token
+				WHERE users.id = token.user_id
+				RETURNING id, name, email, password, sold_in_cents, role, suspended_at, email_verified_at, photo_url, updated_at, created_at
+				",
+        )
+        .bind(token::hash_token_id(token))
+        .bind(UserTokenKind::PasswordReset)
+        .bind(hashed_password)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl TotpExtractor for DBClient {
+    async fn get_user_totp(&self, user_id: &Uuid) -> Result<Option<UserTotp>, sqlx::Error> {
+        let totp = sqlx::query_as::<_, UserTotp>(
+            r"
+				SELECT user_id, secret, enabled_at, last_step, created_at
+				FROM user_totp
+				WHERE user_id = $1
+				",
+        )
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(totp)
+    }
+
+    async fn save_user_totp_secret(
+        &self,
+        user_id: &Uuid,
+        secret: &[u8],
+    ) -> Result<Option<UserTotp>, sqlx::Error> {
+        let totp = sqlx::query_as::<_, UserTotp>(
+            r"
+				INSERT INTO user_totp ( user_id, secret )
+				VALUES ( $1, $2 )
+				ON CONFLICT (user_id) DO UPDATE
+				SET secret = EXCLUDED.secret, last_step = NULL, created_at = NOW()
+				WHERE user_totp.enabled_at IS NULL
+				RETURNING user_id, secret, enabled_at, last_step, created_at
+				",
+        )
+        .bind(user_id)
+        .bind(secret)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(totp)
+    }
+
+    async fn enable_user_totp(
+        &self,
+        user_id: &Uuid,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let enabled = sqlx::query_scalar::<_, bool>(
+            r"
+				WITH enabled AS (
+					UPDATE user_totp
+					SET enabled_at = NOW(), last_step = $2
+					WHERE user_id = $1 AND enabled_at IS NULL
+					RETURNING user_id
+				), replaced AS (
+					DELETE FROM recovery_codes
+					WHERE user_id IN (SELECT user_id FROM enabled)
+				), inserted AS (
+					INSERT INTO recovery_codes ( user_id, code_hash )
+					SELECT enabled.user_id, code_hash
+					FROM enabled, UNNEST($3::VARCHAR[]) AS code_hash
+				)
+				SELECT EXISTS (SELECT 1 FROM enabled)
+				",
+        )
+        .bind(user_id)
+        .bind(step)
+        .bind(recovery_code_hashes)
+        .fetch_one(self.pool())
+        .await?;
+
+        if !enabled {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn use_totp_step(&self, user_id: &Uuid, step: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE user_totp
+				SET last_step = $2
+				WHERE user_id = $1
+					AND enabled_at IS NOT NULL
+					AND (last_step IS NULL OR last_step < $2)
+				",
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn get_unused_recovery_codes(
+        &self,
+        user_id: &Uuid,
+    ) -> Result<Vec<RecoveryCode>, sqlx::Error> {
+        let codes = sqlx::query_as::<_, RecoveryCode>(
+            r"
+				SELECT id, user_id, code_hash, used_at, created_at
+				FROM recovery_codes
+				WHERE user_id = $1 AND used_at IS NULL
+				",
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(codes)
+    }
+
+    async fn use_recovery_code(&self, recovery_code_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE recovery_codes
+				SET used_at = NOW()
+				WHERE id = $1 AND used_at IS NULL
+				",
+        )
+        .bind(recovery_code_id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete_user_totp(&self, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+				WITH codes AS (
+					DELETE FROM recovery_codes
+					WHERE user_id = $1
+				)
+				DELETE FROM user_totp
+				WHERE user_id = $1
+				",
+        )
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ApiKeyExtractor for DBClient {
+    async fn save_api_key(
+        &self,
+        user_id: &Uuid,
+        name: &str,
+        key: &str,
+        prefix: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<ApiKey, sqlx::Error> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r"
+				INSERT INTO api_keys ( user_id, name, prefix, key_hash, scopes )
+				VALUES ( $1, $2, $3, $4, $5 )
+				RETURNING id, user_id, name, prefix, key_hash, scopes, last_used_at, created_at
+				",
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(prefix)
+        .bind(token::hash_token_id(key))
+        .bind(scopes)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(api_key)
+    }
+
+    async fn get_api_key(&self, key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r"
+				UPDATE api_keys
+				SET last_used_at = NOW()
+				WHERE key_hash = $1
+				RETURNING id, user_id, name, prefix, key_hash, scopes, last_used_at, created_at
+				",
+        )
+        .bind(token::hash_token_id(key))
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(api_key)
+    }
+
+    async fn get_api_keys_by_user(
+        &self,
+        user_id: &Uuid,
+        page: &PageRequest,
+    ) -> Result<Page<ApiKey>, sqlx::Error> {
+        let api_keys = sqlx::query_as::<_, ApiKey>(
+            r"
+				SELECT id, user_id, name, prefix, key_hash, scopes, last_used_at, created_at
+				FROM api_keys
+				WHERE user_id = $1
+					AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+				ORDER BY created_at DESC, id DESC
+				LIMIT $4
+				",
+        )
+        .bind(user_id)
+        .bind(page.after_created_at())
+        .bind(page.after_id())
+        .bind(page.fetch_limit())
+        .fetch_all(self.pool())
+        .await?;
+
+        let total = if page.with_total {
+            let count = sqlx::query_scalar::<_, i64>(
+                r"
+					SELECT COUNT(*)
+					FROM api_keys
+					WHERE user_id = $1
+					",
+            )
+            .bind(user_id)
+            .fetch_one(self.pool())
+            .await?;
+
+            Some(count)
+        } else {
+            None
+        };
+
+        Ok(Page::new(api_keys, page, total))
+    }
+
+    async fn delete_api_key(&self, user_id: &Uuid, api_key_id: &Uuid) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            r"
+				DELETE FROM api_keys
+				WHERE id = $1 AND user_id = $2
+				",
+        )
+        .bind(api_key_id)
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_api_keys_by_user(&self, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+				DELETE FROM api_keys
+				WHERE user_id = $1
+				",
+        )
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IdempotencyKeyExtractor for DBClient {
+    async fn save_idempotency_key(
+        &self,
+        user_id: &Uuid,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        sqlx::query(
+            r"
+				DELETE FROM idempotency_keys
+				WHERE user_id = $1 AND expires_at <= NOW()
+				",
+        )
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        let idempotency_key = sqlx::query_as::<_, IdempotencyKey>(
+            r"
+				INSERT INTO idempotency_keys ( user_id, key, fingerprint, expires_at )
+				VALUES ( $1, $2, $3, $4 )
+				ON CONFLICT ( user_id, key ) DO NOTHING
+				RETURNING user_id, key, fingerprint, response_status, response_content_type,
+					response_body, expires_at, created_at
+				",
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(fingerprint)
+        .bind(expires_at)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(idempotency_key)
+    }
+
+    async fn get_idempotency_key(
+        &self,
+        user_id: &Uuid,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        let idempotency_key = sqlx::query_as::<_, IdempotencyKey>(
+            r"
+				SELECT user_id, key, fingerprint, response_status, response_content_type,
+					response_body, expires_at, created_at
+				FROM idempotency_keys
+				WHERE user_id = $1 AND key = $2 AND expires_at > NOW()
+				",
+        )
+        .bind(user_id)
+        .bind(key)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(idempotency_key)
+    }
+
+    async fn save_idempotency_response(
+        &self,
+        user_id: &Uuid,
+        key: &str,
+        status: i16,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+				UPDATE idempotency_keys
+				SET response_status = $3, response_content_type = $4, response_body = $5
+				WHERE user_id = $1 AND key = $2
+				",
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(status)
+        .bind(content_type)
+        .bind(body)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_idempotency_key(&self, user_id: &Uuid, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r"
+				DELETE FROM idempotency_keys
+				WHERE user_id = $1 AND key = $2
+				",
+        )
+        .bind(user_id)
+        .bind(key)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PaymentExtractor for DBClient {
+    async fn save_payment_intent(
+        &self,
+        user_id: &Uuid,
+        gateway_intent_id: &str,
+        amount_in_cents: i64,
+    ) -> Result<PaymentIntent, sqlx::Error> {
+        let payment_intent = sqlx::query_as::<_, PaymentIntent>(
+            r"
+				INSERT INTO payment_intents ( user_id, gateway_intent_id, amount_in_cents )
+				VALUES ( $1, $2, $3 )
+				RETURNING id, user_id, gateway_intent_id, amount_in_cents, status, updated_at, created_at
+				",
+        )
+        .bind(user_id)
+        .bind(gateway_intent_id)
+        .bind(amount_in_cents)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(payment_intent)
+    }
+
+    async fn get_payment_intent_if_belong_to_user(
+        &self,
+        user_id: &Uuid,
+        payment_intent_id: &Uuid,
+    ) -> Result<Option<PaymentIntent>, sqlx::Error> {
+        let payment_intent = sqlx::query_as::<_, PaymentIntent>(
+            r"
+				SELECT id, user_id, gateway_intent_id, amount_in_cents, status, updated_at, created_at
+				FROM payment_intents
+				WHERE id = $1 AND user_id = $2
+				",
+        )
+        .bind(payment_intent_id)
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(payment_intent)
+    }
+
+    async fn get_payment_intent_by_gateway_id(
+        &self,
+        gateway_intent_id: &str,
+    ) -> Result<Option<PaymentIntent>, sqlx::Error> {
+        let payment_intent = sqlx::query_as::<_, PaymentIntent>(
+            r"
+				SELECT id, user_id, gateway_intent_id, amount_in_cents, status, updated_at, created_at
+				FROM payment_intents
+				WHERE gateway_intent_id = $1
+				",
+        )
+        .bind(gateway_intent_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(payment_intent)
+    }
+}
+
+#[cfg(test)]
+mod user_tests {
+    use super::*;
+    use crate::utils::{models::UserRole, test_utils::init_test_users};
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_user_by_id(pool: Pool<Postgres>) {
+        let (id_1, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let user = db_client
+            .get_user(&id_1)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get user by id: {err}"))
+            .expect("User not found");
+
+        assert_eq!(user.id, id_1);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_user_by_nonexistent_id(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let nonexistant_id = Uuid::new_v4();
+
+        let result = db_client
+            .get_user(&nonexistant_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get user by id: {err}"));
+
+        assert!(result.is_none(), "Expected user to be None");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_user_by_email(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let email_to_find = "madamou@gmail.com";
+
+        let user = db_client
+            .get_user_by_email(email_to_find.to_string())
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get user by email: {err}"))
+            .expect("User not found");
+
+        assert_eq!(user.email, email_to_find);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_user_by_noneistent_email(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let email_to_find = "nonexistant@gmail.com";
+
+        let result = db_client
+            .get_user_by_email(email_to_find.to_string())
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get user by email: {err}"));
+
+        assert!(result.is_none(), "Expected user to be None");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_users_by_name(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let name_to_find = "Idrissa Baby";
+
+        let users = db_client
+            .get_users_by_name(name_to_find.to_string(), 1, 10)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get users by name: {err}"));
+
+        assert!(users.len() == 2, "Expected to found 2 users");
+
+        for user in users {
+            assert_eq!(user.name, name_to_find);
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_users_by_nonexistent_name(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let name_to_find = "Whoever Iam";
+
+        let users = db_client
+            .get_users_by_name(name_to_find.to_string(), 1, 10)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get users by name: {err}"));
+
+        assert_eq!(users.len(), 0);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_users(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let users = db_client
+            .get_all_users(&PageRequest::first(10))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get all users: {err}"))
+            .items;
+
+        assert_eq!(users.len(), 4);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_user(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let name = "Mohammed Dembele";
+        let email = "mdembele@gmail.com";
+        let password = "somestrongpassword";
+
+        db_client.save_user(name, email, password).await.unwrap();
+
+        let user = db_client
+            .get_user_by_email(email.to_string())
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get users by name: {err}"))
+            .expect("User not found");
+
+        assert_eq!(name, user.name);
+        assert_eq!(email, user.email);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_user_but_email_is_taken(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let name = "Imhad Thari";
+        let email = "ithari@gmail.com";
+        let password = "mostsecurepass";
+
+        let result = db_client.save_user(name, email, password).await;
+
+        match result {
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                // Ok !
+            }
+            _ => {
+                panic!("Expected unique constraint violation error");
+            }
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_user_but_name_too_long(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let too_long_name = "a".repeat(200);
+        let email = "exemple@gamil.com";
+        let password = "password";
+
+        let result = db_client
+            .save_user(too_long_name.as_str(), email, password)
+            .await;
+
+        assert!(result.is_err(), "Expected save to fail");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_user(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        db_client
+            .delete_user(&user_id)
+            .await
+            .expect("Failed to delete user");
+
+        let result = db_client.get_user(&user_id).await.unwrap();
+
+        assert!(result.is_none(), "User found, but no one expected");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_invalid_user(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let result = db_client.delete_user(&Uuid::new_v4()).await.err();
+
+        match result {
+            None => panic!("No error returned, but one was expected"),
+            Some(sqlx::Error::RowNotFound) => (), // ok
+            Some(_) => panic!("Failed to delete user"),
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn suspend_and_unsuspend_user(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let suspended = db_client
+            .modify_user_suspension(&user_id, true)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to suspend user: {err}"));
+
+        assert!(suspended.is_suspended());
+
+        // suspending twice keeps the first date
+        let again = db_client
+            .modify_user_suspension(&user_id, true)
+            .await
+            .unwrap();
+
+        assert_eq!(again.suspended_at, suspended.suspended_at);
+
+        let unsuspended = db_client
+            .modify_user_suspension(&user_id, false)
+            .await
+            .unwrap();
+
+        assert!(!unsuspended.is_suspended());
+
+        let result = db_client
+            .modify_user_suspension(&Uuid::new_v4(), true)
+            .await;
+
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn first_product_makes_a_seller(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+        assert_eq!(user.role, UserRole::Customer);
+
+        db_client
+            .save_product("hat", &user_id, None, 15, 3)
+            .await
+            .unwrap();
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+        assert_eq!(user.role, UserRole::Seller);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn progressive_login_lockout(pool: Pool<Postgres>) {
+        init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let email = "madamou@gmail.com";
+
+        let attempts = db_client
+            .record_failed_login(email, 2, 60, 200)
+            .await
+            .unwrap();
+        assert_eq!(attempts.failed_login_attempts, 1);
+        assert!(attempts.lockout_seconds_left().is_none());
+
+        // doubled with each attempt past the limit, up to the maximum
+        for expected_seconds in [60, 120, 200, 200] {
+            let attempts = db_client
+                .record_failed_login(email, 2, 60, 200)
+                .await
+                .unwrap();
+            let seconds = attempts.lockout_seconds_left().expect("Email not locked");
+
+            assert!((expected_seconds - 1..=expected_seconds).contains(&seconds));
+        }
+
+        db_client.reset_failed_logins(email).await.unwrap();
+
+        assert!(db_client.get_login_attempts(email).await.unwrap().is_none());
+
+        // counted all the same without an account
+        let attempts = db_client
+            .record_failed_login("nonexistent@gmail.com", 1, 60, 200)
+            .await
+            .unwrap();
+        assert!(attempts.lockout_seconds_left().is_some());
+    }
+}
+
+#[cfg(test)]
+mod user_tokens_tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn verify_user_email(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+
+        let user = db_client
+            .save_user("Ayoub Arab", "ayarab@gmail.com", "password")
+            .await
+            .unwrap();
+        assert!(!user.is_email_verified());
+
+        let expires_at = Utc::now() + Duration::hours(1);
+
+        db_client
+            .save_user_token(
+                &user.id,
+                UserTokenKind::EmailVerification,
+                "first",
+                expires_at,
+            )
+            .await
+            .unwrap();
+        db_client
+            .save_user_token(
+                &user.id,
+                UserTokenKind::EmailVerification,
+                "second",
+                expires_at,
+            )
+            .await
+            .unwrap();
+
+        // replaced by the second one
+        assert!(db_client
+            .verify_user_email("first")
+            .await
+            .unwrap()
+            .is_none());
+        // not a verification token
+        assert!(db_client
+            .reset_user_password("second", "hashed")
+            .await
+            .unwrap()
+            .is_none());
+
+        let verified = db_client
+            .verify_user_email("second")
+            .await
+            .unwrap()
+            .expect("Token not found");
+
+        assert_eq!(verified.id, user.id);
+        assert!(verified.is_email_verified());
+
+        assert!(db_client
+            .verify_user_email("second")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn reset_user_password_with_expired_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+
+        let user = db_client
+            .save_user("Ayoub Arab", "ayarab@gmail.com", "password")
+            .await
+            .unwrap();
+
+        db_client
+            .save_user_token(
+                &user.id,
+                UserTokenKind::PasswordReset,
+                "expired",
+                Utc::now() - Duration::minutes(1),
+            )
+            .await
+            .unwrap();
+
+        assert!(db_client
+            .reset_user_password("expired", "hashed")
+            .await
+            .unwrap()
+            .is_none());
+
+        db_client
+            .save_user_token(
+                &user.id,
+                UserTokenKind::PasswordReset,
+                "valid",
+                Utc::now() + Duration::minutes(1),
+            )
+            .await
+            .unwrap();
+
+        let user = db_client
+            .reset_user_password("valid", "hashed")
+            .await
+            .unwrap()
+            .expect("Token not found");
+
+        assert_eq!(user.password, "hashed");
+        // the reset link was received at this email
+        assert!(user.is_email_verified());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn modify_user_email_unverifies_it(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+
+        let user = db_client
+            .save_user("Ayoub Arab", "ayarab@gmail.com", "password")
+            .await
+            .unwrap();
+        crate::utils::test_utils::verify_email(&pool, &user.id).await;
+
+        // same email
+        let user = db_client
+            .modify_user(&user.id, None, Some("ayarab@gmail.com"), None)
+            .await
+            .unwrap();
+        assert!(user.is_email_verified());
+
+        let user = db_client
+            .modify_user(&user.id, None, Some("ayoub@gmail.com"), None)
+            .await
+            .unwrap();
+        assert!(!user.is_email_verified());
+    }
+}
+
+#[cfg(test)]
+mod totp_tests {
+    use super::*;
+    use crate::utils::test_utils::init_test_users;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn enable_user_totp(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let pending = db_client
+            .save_user_totp_secret(&user_id, b"first")
+            .await
+            .unwrap()
+            .expect("TOTP not saved");
+        assert!(!pending.is_enabled());
+
+        // not enabled yet, a new setup replaces the secret
+        assert!(!db_client.use_totp_step(&user_id, 10).await.unwrap());
+        db_client
+            .save_user_totp_secret(&user_id, b"second")
+            .await
+            .unwrap()
+            .expect("TOTP not replaced");
+
+        db_client
+            .enable_user_totp(&user_id, 10, &["hash1".to_string(), "hash2".to_string()])
+            .await
+            .unwrap();
+
+        let totp = db_client.get_user_totp(&user_id).await.unwrap().unwrap();
+        assert_eq!(totp.secret, b"second");
+        assert!(totp.is_enabled());
+
+        assert!(db_client
+            .save_user_totp_secret(&user_id, b"third")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            db_client.enable_user_totp(&user_id, 11, &[]).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+
+        // each step once, and never an earlier one
+        assert!(!db_client.use_totp_step(&user_id, 10).await.unwrap());
+        assert!(db_client.use_totp_step(&user_id, 12).await.unwrap());
+        assert!(!db_client.use_totp_step(&user_id, 11).await.unwrap());
+
+        let codes = db_client.get_unused_recovery_codes(&user_id).await.unwrap();
+        assert_eq!(codes.len(), 2);
+
+        assert!(db_client.use_recovery_code(&codes[0].id).await.unwrap());
+        assert!(!db_client.use_recovery_code(&codes[0].id).await.unwrap());
+        assert_eq!(
+            db_client
+                .get_unused_recovery_codes(&user_id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        db_client.delete_user_totp(&user_id).await.unwrap();
+
+        assert!(db_client.get_user_totp(&user_id).await.unwrap().is_none());
+        assert!(db_client
+            .get_unused_recovery_codes(&user_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[cfg(test)]
+mod products_tests {
+    use super::*;
+    use crate::utils::{pagination::Cursor, test_utils::init_test_products};
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_product_by_id(pool: Pool<Postgres>) {
+        let (product_data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let product = db_client
+            .get_product(&product_data.product_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get product by id: {err}"))
+            .expect("product not found");
+
+        assert_eq!(product.id, product_data.product_id);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_product_by_nonexistent_id(pool: Pool<Postgres>) {
+        init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let nonexistant_id = Uuid::new_v4();
+
+        let result = db_client
+            .get_product(&nonexistant_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get product by id: {err}"));
+
+        assert!(result.is_none(), "Expected product to be None");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_products_by_user(pool: Pool<Postgres>) {
+        let (_, _, data) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let products = db_client
+            .get_products_by_user(&data.user_id, &PageRequest::first(5))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get products by user: {err}"))
+            .items;
+
+        assert_eq!(products.len(), 1);
+
+        let product = products.first().unwrap();
+        assert_eq!(product.user_id, data.user_id);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_products_by_nonexistent_user_id(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let nonexistent_user_id = Uuid::new_v4();
+
+        let result = db_client
+            .get_products_by_user(&nonexistent_user_id, &PageRequest::first(5))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get products by user id: {err}"))
+            .items;
+
+        assert_eq!(result.len(), 0);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_products(pool: Pool<Postgres>) {
+        init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let products = db_client
+            .get_all_products(&PageRequest::first(10))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get all products: {err}"))
+            .items;
+
+        assert_eq!(products.len(), 3);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_products_after_cursor(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let first_page = db_client
+            .get_all_products(&PageRequest {
+                after: None,
+                limit: 2,
+                with_total: true,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(first_page.items.len(), 2);
+        assert!(first_page.has_more);
+        assert_eq!(first_page.total, Some(3));
+        assert!(first_page.items[0].created_at >= first_page.items[1].created_at);
+
+        let cursor = first_page.next_cursor.expect("a second page");
+
+        // a row inserted meanwhile is newer, so it doesn't shift the next page
+        let _ = db_client
+            .save_product("newcomer", &data.user_id, None, 10, 1)
+            .await
+            .unwrap();
+
+        let second_page = db_client
+            .get_all_products(&PageRequest {
+                after: Cursor::decode(&cursor),
+                limit: 2,
+                with_total: false,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(second_page.items.len(), 1);
+        assert!(!second_page.has_more);
+        assert_eq!(second_page.next_cursor, None);
+        assert_eq!(second_page.total, None);
+        assert!(!first_page.items.contains(&second_page.items[0]));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_product(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let name = "Car";
+        let user_id = data.user_id;
+        let description = Some("A beautiful car".to_string());
+        let price_in_cents = 1200;
+
+        let saved = db_client
+            .save_product(name, &user_id, description.as_ref(), price_in_cents, 1)
+            .await
+            .unwrap();
+
+        let product = db_client
+            .get_product(&saved.id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get product: {err}"))
+            .expect("the saved product");
+
+        assert_eq!(product.name, name);
+        assert_eq!(product.user_id, user_id);
+        assert_eq!(product.description, description);
+        assert_eq!(product.price_in_cents, price_in_cents);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_product_with_invalid_user_id(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let name = "Car";
+        let user_id = Uuid::new_v4();
+        let description = Some("A beautiful car".to_string());
+        let price_in_cents = 1200;
+
+        let result = db_client
+            .save_product(name, &user_id, description.as_ref(), price_in_cents, 1)
+            .await;
+
+        match result {
+            Err(sqlx::Error::Database(db_err)) => {
+                if db_err.is_foreign_key_violation() {
+                    // Ok
+                    return;
+                }
+
+                panic!(
+                    "Foreign key violation expected, found: {}",
+                    db_err.message()
+                )
+            }
+            Err(err) => panic!("Database error expected, found: {err}"),
+            Ok(_) => panic!("Call succeded, but a Database error was expected"),
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_product_but_name_too_long(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let too_long_name = "a".repeat(200);
+        let user_id = data.user_id;
+        let description = None;
+        let price_in_cents = 12;
+
+        let result = db_client
+            .save_product(
+                too_long_name.as_str(),
+                &user_id,
+                description,
+                price_in_cents,
+                1,
+            )
+            .await;
+
+        assert!(result.is_err(), "Expected save to fail");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_product(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        db_client
+            .delete_product(&data.product_id)
+            .await
+            .expect("Failed to delete product");
+
+        let result = db_client.get_product(&data.product_id).await.unwrap();
+
+        assert!(result.is_none(), "Product found, but no one expected");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn modify_product(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let product = db_client
+            .modify_product(&data.product_id, None, Some("Red shoes"), Some(40), None)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to modify product: {err}"));
+
+        assert_eq!(product.name, "shoes");
+        assert_eq!(product.description, Some("Red shoes".to_string()));
+        assert_eq!(product.price_in_cents, 40);
+        assert_eq!(product.number_in_stock, 1);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn modify_invalid_product(pool: Pool<Postgres>) {
+        init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let result = db_client
+            .modify_product(&Uuid::new_v4(), Some("hat"), None, None, None)
+            .await
+            .err();
+
+        assert!(matches!(result, Some(sqlx::Error::RowNotFound)));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_invalid_product(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let result = db_client.delete_product(&Uuid::new_v4()).await.err();
+
+        match result {
+            None => panic!("No error returned, but one was expected"),
+            Some(sqlx::Error::RowNotFound) => (), // Ok
+            _ => panic!("Failed to delete product"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod orders_test {
+    use super::*;
+    use crate::utils::test_utils::init_test_orders;
 
-        if let Some(hashed_last_token_id) = user.last_token_id {
-            let result = bcrypt::verify(token_id, &hashed_last_token_id);
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_order_by_id(pool: Pool<Postgres>) {
+        let (order_data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let order = db_client
+            .get_order(&order_data.order_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get order by id: {err}"))
+            .expect("order not found");
+
+        assert_eq!(order.id, order_data.order_id);
+        assert_eq!(order.product_id, Some(order_data.product_id));
+        assert_eq!(order.user_id, order_data.user_id);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_order_by_nonexistent_id(pool: Pool<Postgres>) {
+        init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool);
 
-            if let Ok(is_valid) = result {
-                return Ok(is_valid);
-            }
+        let nonexistant_id = Uuid::new_v4();
 
-            return Ok(false);
-        }
+        let result = db_client
+            .get_order(&nonexistant_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get order by id: {err}"));
 
-        // no last token (first time loging)
-        return Ok(false);
+        assert!(result.is_none(), "Expected order to be None");
     }
-}
-
-#[cfg(test)]
-mod user_tests {
-    use super::*;
-    use crate::utils::test_utils::init_test_users;
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_user_by_id(pool: Pool<Postgres>) {
-        let (id_1, _, _) = init_test_users(&pool).await;
+    async fn get_orders_by_user(pool: Pool<Postgres>) {
+        let (_, _, data) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let user = db_client
-            .get_user(&id_1)
+        let orders = db_client
+            .get_orders_by_user(&data.user_id, &PageRequest::first(5))
             .await
-            .unwrap_or_else(|err| panic!("Failed to get user by id: {err}"))
-            .expect("User not found");
+            .unwrap_or_else(|err| panic!("Failed to get orders by user: {err}"))
+            .items;
 
-        assert_eq!(user.id, id_1);
+        assert_eq!(orders.len(), 1);
+
+        let order = orders.first().unwrap();
+        assert_eq!(order.user_id, data.user_id);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_user_by_nonexistent_id(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn get_orders_by_nonexistent_user_id(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let nonexistant_id = Uuid::new_v4();
+        let nonexistent_user_id = Uuid::new_v4();
 
         let result = db_client
-            .get_user(&nonexistant_id)
+            .get_orders_by_user(&nonexistent_user_id, &PageRequest::first(5))
             .await
-            .unwrap_or_else(|err| panic!("Failed to get user by id: {err}"));
+            .unwrap_or_else(|err| panic!("Failed to get orders by user id: {err}"))
+            .items;
 
-        assert!(result.is_none(), "Expected user to be None");
+        assert_eq!(result.len(), 0);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_user_by_email(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn get_orders(pool: Pool<Postgres>) {
+        init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let email_to_find = "madamou@gmail.com";
-
-        let user = db_client
-            .get_user_by_email(email_to_find.to_string())
+        let orders = db_client
+            .get_all_orders(&PageRequest::first(10))
             .await
-            .unwrap_or_else(|err| panic!("Failed to get user by email: {err}"))
-            .expect("User not found");
+            .unwrap_or_else(|err| panic!("Failed to get all orders: {err}"))
+            .items;
 
-        assert_eq!(user.email, email_to_find);
+        assert_eq!(orders.len(), 3);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_user_by_noneistent_email(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn save_order(pool: Pool<Postgres>) {
+        let (_, _, data3) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let email_to_find = "nonexistant@gmail.com";
+        let test_user = db_client
+            .save_user("test_user", "test_user@gmail.com", "test")
+            .await
+            .unwrap();
 
-        let result = db_client
-            .get_user_by_email(email_to_find.to_string())
+        let user_id = &test_user.id;
+        let product_id = &data3.product_id;
+        let order_details_id = None;
+
+        db_client
+            .save_order(user_id, product_id, order_details_id, 2)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get user by email: {err}"));
+            .unwrap();
 
-        assert!(result.is_none(), "Expected user to be None");
+        let orders = db_client
+            .get_orders_by_user(user_id, &PageRequest::first(5))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get orders by user_id: {err}"))
+            .items;
+
+        assert_eq!(orders.len(), 1);
+
+        let order = orders.first().unwrap();
+
+        assert_eq!(order.user_id, user_id.clone());
+        assert_eq!(order.product_id, Some(*product_id));
+        assert_eq!(order.order_details_id, order_details_id.copied());
+        assert_eq!(order.products_number, Some(2));
+
+        let product = db_client.get_product(product_id).await.unwrap().unwrap();
+
+        assert_eq!(order.unit_price_in_cents, Some(product.price_in_cents));
+        assert_eq!(order.product_name, Some(product.name.clone()));
+        assert_eq!(order.total_in_cents, 2 * product.price_in_cents);
+
+        let items = db_client.get_order_items(&order.id).await.unwrap();
+
+        assert_eq!(items[0].unit_price_in_cents, product.price_in_cents);
+        assert_eq!(items[0].product_name, product.name);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_users_by_name(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn get_order_items(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let name_to_find = "Idrissa Baby";
+        let items = db_client
+            .get_order_items(&data.order_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get order items: {err}"));
 
-        let users = db_client
-            .get_users_by_name(name_to_find.to_string(), 1, 10)
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].product_id, data.product_id);
+        assert_eq!(items[0].products_number, 1);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_order_items_by_seller(pool: Pool<Postgres>) {
+        let (data, data2, data3) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let items = db_client
+            .get_order_items_by_seller(&data.order_id, &data2.user_id)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get users by name: {err}"));
+            .unwrap_or_else(|err| panic!("Failed to get order items: {err}"));
 
-        assert!(users.len() == 2, "Expected to found 2 users");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].product_id, data.product_id);
 
-        for user in users {
-            assert_eq!(user.name, name_to_find);
+        let items = db_client
+            .get_order_items_by_seller(&data.order_id, &data3.user_id)
+            .await
+            .unwrap();
+
+        assert!(items.is_empty());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn save_order_with_invalid_user_id(pool: Pool<Postgres>) {
+        let (_, _, data) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let user_id = Uuid::new_v4();
+        let product_id = &data.product_id;
+        let order_details_id = None;
+
+        let result = db_client
+            .save_order(&user_id, product_id, order_details_id, 1)
+            .await;
+
+        match result {
+            Err(sqlx::Error::Database(db_err)) => {
+                if db_err.is_foreign_key_violation() {
+                    // Ok
+                    return;
+                }
+
+                panic!(
+                    "Foreign key violation expected, found: {}",
+                    db_err.message()
+                )
+            }
+            Err(err) => panic!("Database error expected, found: {err}"),
+            Ok(_) => panic!("Call succeded, but a Database error was expected"),
         }
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_users_by_nonexistent_name(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn save_order_with_invalid_product_id(pool: Pool<Postgres>) {
+        let (_, _, data) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let name_to_find = "Whoever Iam";
+        let user_id = &data.user_id;
+        let product_id = Uuid::new_v4();
+        let order_details_id = None;
 
-        let users = db_client
-            .get_users_by_name(name_to_find.to_string(), 1, 10)
-            .await
-            .unwrap_or_else(|err| panic!("Failed to get users by name: {err}"));
+        let result = db_client
+            .save_order(user_id, &product_id, order_details_id, 1)
+            .await;
 
-        assert_eq!(users.len(), 0);
+        match result {
+            // nothing to snapshot the price from
+            Err(sqlx::Error::RowNotFound) => (), // ok
+            Err(err) => panic!("RowNotFound expected, found: {err}"),
+            Ok(_) => panic!("Call succeded, but an error was expected"),
+        }
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_users(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn save_order_with_invalid_order_details_id(pool: Pool<Postgres>) {
+        let (_, _, data) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let users = db_client
-            .get_all_users(1, 10)
+        let test_user = db_client
+            .save_user("test_user", "test_user@gmail.com", "test")
             .await
-            .unwrap_or_else(|err| panic!("Failed to get all users: {err}"));
+            .unwrap();
 
-        assert_eq!(users.len(), 4);
+        let user_id = &test_user.id;
+        let product_id = &data.product_id;
+        let order_details_id = Some(Uuid::new_v4());
+
+        let result = db_client
+            .save_order(user_id, product_id, order_details_id.as_ref(), 1)
+            .await;
+
+        match result {
+            Err(sqlx::Error::Database(db_err)) => {
+                assert_eq!(db_err.message(), "foreign-order-details");
+            }
+            Err(err) => panic!("Database error expected, found: {err}"),
+            Ok(_) => panic!("Call succeded, but a Database error was expected"),
+        }
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_user(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn save_order_but_products_number_too_low(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let name = "Mohammed Dembele";
-        let email = "mdembele@gmail.com";
-        let password = "somestrongpassword";
+        let result = db_client
+            .save_order(&data.user_id, &data2.product_id, None, 0)
+            .await
+            .err();
 
-        db_client.save_user(name, email, password).await.unwrap();
+        match result {
+            None => panic!("Call succeded, but an error was expected"),
+            Some(sqlx::Error::Database(db_err)) => {
+                if db_err.is_check_violation() {
+                    // Ok
+                } else {
+                    // other error
+                    panic!("Failed to save order: {}", db_err.message())
+                }
+            }
 
-        let user = db_client
-            .get_user_by_email(email.to_string())
+            // other error
+            Some(err) => panic!("Failed to save order: {err}"),
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_order(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        db_client
+            .delete_order(&data.order_id)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get users by name: {err}"))
-            .expect("User not found");
+            .expect("Failed to delete order");
 
-        assert_eq!(name, user.name);
-        assert_eq!(email, user.email);
+        let result = db_client.get_order(&data.order_id).await.unwrap();
+
+        assert!(result.is_none(), "Order found, but no one expected");
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_user_but_email_is_taken(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn delete_invalid_order(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let name = "Imhad Thari";
-        let email = "ithari@gmail.com";
-        let password = "mostsecurepass";
-
-        let result = db_client.save_user(name, email, password).await;
+        let result = db_client.delete_order(&Uuid::new_v4()).await.err();
 
         match result {
-            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
-                // Ok !
-            }
-            _ => {
-                panic!("Expected unique constraint violation error");
-            }
+            None => panic!("No error returned, but one was expected"),
+            Some(sqlx::Error::RowNotFound) => (), // ok
+            Some(_) => panic!("Failed to delete order"),
         }
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_user_but_name_too_long(pool: Pool<Postgres>) {
-        init_test_users(&pool).await;
+    async fn get_sales_by_seller(pool: Pool<Postgres>) {
+        use crate::{
+            database::transaction::{DBTransaction, ITransaction},
+            utils::models::OrderStatus,
+        };
+
+        let (data, data2, data3) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+
+        // the seller of the first order's product, sold a second time
+        let seller_id = data2.user_id;
+        let paid_order = db_client
+            .save_order(&data3.user_id, &data.product_id, None, 1)
+            .await
+            .unwrap();
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&paid_order.id, OrderStatus::Paid)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let sales = db_client
+            .get_sales_by_seller(&seller_id, &SaleSearch::default(), &PageRequest::first(10))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get sales: {err}"))
+            .items;
+
+        assert_eq!(sales.len(), 2);
+        assert_eq!(sales[0].id, paid_order.id);
+        assert_eq!(sales[1].id, data.order_id);
+
+        let search = SaleSearch {
+            status: Some(OrderStatus::Paid),
+            ..Default::default()
+        };
+        let sales = db_client
+            .get_sales_by_seller(&seller_id, &search, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items;
+
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].id, paid_order.id);
+
+        let search = SaleSearch {
+            created_before: Some(paid_order.created_at),
+            ..Default::default()
+        };
+        let sales = db_client
+            .get_sales_by_seller(&seller_id, &search, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items;
+
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].id, data.order_id);
+    }
+}
+
+#[cfg(test)]
+mod ledger_tests {
+    use super::*;
+    use crate::{
+        database::transaction::{DBTransaction, ITransaction},
+        utils::{models::LedgerEntryKind, test_utils::init_test_orders},
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_ledger_entries_by_user(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let too_long_name = "a".repeat(200);
-        let email = "exemple@gamil.com";
-        let password = "password";
-
-        let result = db_client
-            .save_user(too_long_name.as_str(), email, password)
-            .await;
+        DBTransaction::begin(db_client.pool())
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 100, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .decrease_user_sold(
+                &data.user_id,
+                50,
+                LedgerEntryKind::OrderDebit,
+                Some(&data.order_id),
+            )
+            .await
+            .unwrap()
+            .increase_user_sold(
+                &data2.user_id,
+                50,
+                LedgerEntryKind::SaleCredit,
+                Some(&data.order_id),
+            )
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
 
-        assert!(result.is_err(), "Expected save to fail");
+        let entries = db_client
+            .get_ledger_entries_by_user(&data.user_id, &PageRequest::first(10))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get ledger entries by user: {err}"))
+            .items;
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.user_id == Some(data.user_id)));
+        assert!(entries
+            .iter()
+            .any(|entry| entry.kind == LedgerEntryKind::TopUp && entry.amount_in_cents == 100));
+        assert!(entries
+            .iter()
+            .any(|entry| entry.kind == LedgerEntryKind::OrderDebit
+                && entry.amount_in_cents == -50
+                && entry.order_id == Some(data.order_id)));
+
+        let drifts = db_client.get_balance_drifts().await.unwrap();
+
+        assert!(drifts.is_empty(), "Expected balances to match the ledger");
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_user(pool: Pool<Postgres>) {
-        let (user_id, _, _) = init_test_users(&pool).await;
+    async fn get_balance_drifts(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        db_client
-            .delete_user(&user_id)
+        // balance modified without going through the ledger
+        sqlx::query("UPDATE users SET sold_in_cents = 42 WHERE id = $1")
+            .bind(data.user_id)
+            .execute(db_client.pool())
             .await
-            .expect("Failed to delete user");
-
-        let result = db_client.get_user(&user_id).await.unwrap();
+            .unwrap();
 
-        assert!(result.is_none(), "User found, but no one expected");
+        let drifts = db_client
+            .get_balance_drifts()
+            .await
+            .unwrap_or_else(|err| panic!("Failed to get balance drifts: {err}"));
+
+        assert_eq!(
+            drifts,
+            vec![BalanceDrift {
+                user_id: data.user_id,
+                sold_in_cents: 42,
+                ledger_sum_in_cents: 0,
+            }]
+        );
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_invalid_user(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_users(&pool).await;
+    async fn modify_ledger_entry(pool: Pool<Postgres>) {
+        init_test_orders(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let result = db_client.delete_user(&Uuid::new_v4()).await.err();
+        let user = db_client
+            .save_user("test_user", "test_user@gmail.com", "test")
+            .await
+            .unwrap();
+
+        DBTransaction::begin(db_client.pool())
+            .await
+            .unwrap()
+            .increase_user_sold(&user.id, 100, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let result = sqlx::query("UPDATE ledger_entries SET amount_in_cents = 1000000")
+            .execute(db_client.pool())
+            .await
+            .err();
 
         match result {
-            None => panic!("No error returned, but one was expected"),
-            Some(sqlx::Error::RowNotFound) => (), // ok
-            Some(_) => panic!("Failed to delete user"),
+            Some(sqlx::Error::Database(db_err)) => {
+                assert_eq!(db_err.message(), "append-only-ledger");
+            }
+            Some(err) => panic!("Database error expected, found: {err}"),
+            None => panic!("Call succeded, but a Database error was expected"),
         }
+
+        // deleting the user still deletes its entries
+        db_client
+            .delete_user(&user.id)
+            .await
+            .expect("Failed to delete user");
     }
 }
 
 #[cfg(test)]
-mod products_tests {
+mod cart_tests {
     use super::*;
     use crate::utils::test_utils::init_test_products;
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_product_by_id(pool: Pool<Postgres>) {
-        let (product_data, _, _) = init_test_products(&pool).await;
+    async fn save_cart_item_twice(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let product = db_client
-            .get_product(&product_data.product_id)
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 1)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get product by id: {err}"))
-            .expect("product not found");
+            .unwrap();
+        let item = db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 2)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to save cart item: {err}"));
 
-        assert_eq!(product.id, product_data.product_id);
+        assert_eq!(item.products_number, 3);
+
+        let items = db_client.get_cart_items(&data.user_id).await.unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].products_number, 3);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_product_by_nonexistent_id(pool: Pool<Postgres>) {
-        init_test_products(&pool).await;
+    async fn save_own_product_in_cart(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let nonexistant_id = Uuid::new_v4();
-
         let result = db_client
-            .get_product(&nonexistant_id)
+            .save_cart_item(&data.user_id, &data.product_id, 1)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get product by id: {err}"));
+            .err();
 
-        assert!(result.is_none(), "Expected product to be None");
+        match result {
+            Some(sqlx::Error::Database(db_err)) => {
+                assert_eq!(db_err.message(), "auto-buying");
+            }
+            Some(err) => panic!("Database error expected, found: {err}"),
+            None => panic!("Call succeded, but a Database error was expected"),
+        }
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_products_by_user(pool: Pool<Postgres>) {
-        let (_, _, data) = init_test_products(&pool).await;
+    async fn modify_and_delete_cart_item(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let products = db_client
-            .get_products_by_user(&data.user_id, 1, 5)
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 1)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get products by user: {err}"));
+            .unwrap();
 
-        assert_eq!(products.len(), 1);
+        let item = db_client
+            .modify_cart_item(&data.user_id, &data2.product_id, 5)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to modify cart item: {err}"));
 
-        let product = products.first().unwrap();
-        assert_eq!(product.user_id, data.user_id);
-    }
+        assert_eq!(item.products_number, 5);
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_products_by_nonexistent_user_id(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_products(&pool).await;
-        let db_client = DBClient::new(pool);
+        db_client
+            .delete_cart_item(&data.user_id, &data2.product_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to delete cart item: {err}"));
 
-        let nonexistent_user_id = Uuid::new_v4();
+        assert!(db_client
+            .get_cart_items(&data.user_id)
+            .await
+            .unwrap()
+            .is_empty());
 
         let result = db_client
-            .get_products_by_user(&nonexistent_user_id, 1, 5)
+            .delete_cart_item(&data.user_id, &data2.product_id)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get products by user id: {err}"));
+            .err();
 
-        assert_eq!(result.len(), 0);
+        assert!(matches!(result, Some(sqlx::Error::RowNotFound)));
     }
+}
+
+#[cfg(test)]
+mod addresses_tests {
+    use super::*;
+    use crate::utils::test_utils::init_test_products;
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_products_by_name(pool: Pool<Postgres>) {
-        init_test_products(&pool).await;
+    async fn save_and_get_address(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let name_to_find = "shoes";
-
-        let products = db_client
-            .get_products_by_name(name_to_find.to_string(), 1, 10)
+        let address = db_client
+            .save_address(&data.user_id, "1 main street", "Paris", "75001", "France")
             .await
-            .unwrap_or_else(|err| panic!("Failed to get products by name: {err}"));
-
-        assert_eq!(products.len(), 1);
-        assert_eq!(products.first().unwrap().name, name_to_find);
-    }
+            .unwrap_or_else(|err| panic!("Failed to save address: {err}"));
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_products_by_nonexistent_name(pool: Pool<Postgres>) {
-        init_test_products(&pool).await;
-        let db_client = DBClient::new(pool);
+        let found = db_client
+            .get_address_if_belong_to_user(&data.user_id, &address.id)
+            .await
+            .unwrap();
 
-        let name_to_find = "Unkwown product";
+        assert_eq!(found, Some(address.clone()));
 
-        let products = db_client
-            .get_products_by_name(name_to_find.to_string(), 1, 5)
+        // not visible by someone else
+        let found = db_client
+            .get_address_if_belong_to_user(&data2.user_id, &address.id)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get products by name: {err}"));
+            .unwrap();
 
-        assert_eq!(products.len(), 0);
+        assert!(found.is_none());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_products(pool: Pool<Postgres>) {
-        init_test_products(&pool).await;
+    async fn save_order_with_own_address(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let products = db_client
-            .get_all_products(1, 10)
+        let address = db_client
+            .save_address(&data.user_id, "1 main street", "Paris", "75001", "France")
             .await
-            .unwrap_or_else(|err| panic!("Failed to get all products: {err}"));
-
-        assert_eq!(products.len(), 3);
-    }
+            .unwrap();
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_product(pool: Pool<Postgres>) {
-        let (data, _, _) = init_test_products(&pool).await;
-        let db_client = DBClient::new(pool);
+        let order = db_client
+            .save_order(&data.user_id, &data2.product_id, Some(&address.id), 1)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to save order: {err}"));
 
-        let name = "Car";
-        let user_id = data.user_id;
-        let description = Some("A beautiful car".to_string());
-        let price_in_cents = 1200;
+        assert_eq!(order.order_details_id, Some(address.id));
+        assert_eq!(order.delivery_street.as_deref(), Some("1 main street"));
+        assert_eq!(order.delivery_country.as_deref(), Some("France"));
 
+        // the same address can be used again
         db_client
-            .save_product(name, &user_id, description.as_ref(), price_in_cents, 1)
+            .save_order(&data.user_id, &data2.product_id, Some(&address.id), 1)
             .await
-            .unwrap();
+            .unwrap_or_else(|err| panic!("Failed to save order: {err}"));
 
-        let products = db_client
-            .get_products_by_name(name.to_string(), 1, 5)
+        // the order keeps the address it was sent to
+        db_client
+            .modify_address(
+                &data.user_id,
+                &address.id,
+                "2 rue de Rivoli",
+                "Paris",
+                "75004",
+                "France",
+            )
             .await
-            .unwrap_or_else(|err| panic!("Failed to get products by name: {err}"));
+            .unwrap();
 
-        assert_eq!(products.len(), 1);
+        let order = db_client.get_order(&order.id).await.unwrap().unwrap();
+        assert_eq!(order.delivery_street.as_deref(), Some("1 main street"));
+        assert_eq!(order.delivery_postal_code.as_deref(), Some("75001"));
 
-        let product = products.first().unwrap();
+        db_client
+            .delete_address(&data.user_id, &address.id)
+            .await
+            .unwrap();
 
-        assert_eq!(product.name, name);
-        assert_eq!(product.user_id, user_id);
-        assert_eq!(product.description, description);
-        assert_eq!(product.price_in_cents, price_in_cents);
+        let order = db_client.get_order(&order.id).await.unwrap().unwrap();
+        assert_eq!(order.order_details_id, None);
+        assert_eq!(order.delivery_street.as_deref(), Some("1 main street"));
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_product_with_invalid_user_id(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_products(&pool).await;
+    async fn save_order_with_someone_else_address(pool: Pool<Postgres>) {
+        let (data, data2, data3) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let name = "Car";
-        let user_id = Uuid::new_v4();
-        let description = Some("A beautiful car".to_string());
-        let price_in_cents = 1200;
+        let address = db_client
+            .save_address(&data3.user_id, "1 main street", "Paris", "75001", "France")
+            .await
+            .unwrap();
 
         let result = db_client
-            .save_product(name, &user_id, description.as_ref(), price_in_cents, 1)
-            .await;
+            .save_order(&data.user_id, &data2.product_id, Some(&address.id), 1)
+            .await
+            .err();
 
         match result {
-            Err(sqlx::Error::Database(db_err)) => {
-                if db_err.is_foreign_key_violation() {
-                    // Ok
-                    return;
-                }
-
-                panic!(
-                    "Foreign key violation expected, found: {}",
-                    db_err.message()
-                )
+            Some(sqlx::Error::Database(db_err)) => {
+                assert_eq!(db_err.message(), "foreign-order-details");
             }
-            Err(err) => panic!("Database error expected, found: {err}"),
-            Ok(_) => panic!("Call succeded, but a Database error was expected"),
+            Some(err) => panic!("Database error expected, found: {err}"),
+            None => panic!("Call succeded, but a Database error was expected"),
         }
     }
+}
+
+#[cfg(test)]
+mod products_search_tests {
+    use super::*;
+    use crate::utils::test_utils::init_test_products;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn search_products_by_text(pool: Pool<Postgres>) {
+        let (_, data2, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        // matches the description, with stemming
+        let search = ProductSearch {
+            text: Some("jackets".to_string()),
+            ..Default::default()
+        };
+
+        let products = db_client
+            .search_products(&search, &PageRequest::first(10))
+            .await
+            .unwrap_or_else(|err| panic!("Failed to search products: {err}"))
+            .items;
+
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0].id, data2.product_id);
+    }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_product_but_name_too_long(pool: Pool<Postgres>) {
+    async fn search_products_by_rank_pages(pool: Pool<Postgres>) {
+        use crate::utils::pagination::SearchCursor;
+
         let (data, _, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let too_long_name = "a".repeat(200);
-        let user_id = data.user_id;
-        let description = None;
-        let price_in_cents = 12;
+        // equal ranks, told apart by their id
+        for _ in 0..3 {
+            db_client
+                .save_product("wool jacket", &data.user_id, None, 80, 1)
+                .await
+                .unwrap();
+        }
 
-        let result = db_client
-            .save_product(
-                too_long_name.as_str(),
-                &user_id,
-                description,
-                price_in_cents,
-                1,
-            )
-            .await;
+        let search = ProductSearch {
+            text: Some("jacket".to_string()),
+            ..Default::default()
+        };
+        let mut page = PageRequest {
+            with_total: true,
+            ..PageRequest::first(2)
+        };
+        let mut seen = Vec::new();
 
-        assert!(result.is_err(), "Expected save to fail");
+        loop {
+            let products = db_client
+                .search_products(&search, &page)
+                .await
+                .unwrap_or_else(|err| panic!("Failed to search products: {err}"));
+
+            assert_eq!(products.total, Some(4));
+            seen.extend(products.items.iter().map(|product| product.id));
+
+            match products.next_cursor {
+                Some(cursor) => {
+                    page.after = SearchCursor::decode(&cursor, ProductSort::Relevance);
+                    assert!(page.after.is_some());
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(seen.len(), 4);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 4);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_product(pool: Pool<Postgres>) {
-        let (data, _, _) = init_test_products(&pool).await;
+    async fn search_products_by_price_sorted(pool: Pool<Postgres>) {
+        init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        db_client
-            .delete_product(&data.product_id)
+        // shoes (35) and jacket (50), not the hat (15)
+        let search = ProductSearch {
+            min_price_in_cents: Some(20),
+            max_price_in_cents: Some(50),
+            sort: ProductSort::PriceDesc,
+            ..Default::default()
+        };
+
+        let products = db_client
+            .search_products(&search, &PageRequest::first(10))
             .await
-            .expect("Failed to delete product");
+            .unwrap()
+            .items;
 
-        let result = db_client.get_product(&data.product_id).await.unwrap();
+        let names: Vec<&str> = products.iter().map(|p| p.name.as_str()).collect();
 
-        assert!(result.is_none(), "Product found, but no one expected");
+        assert_eq!(names, vec!["jacket", "shoes"]);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_invalid_product(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_products(&pool).await;
+    async fn search_products_in_stock_by_seller(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let result = db_client.delete_product(&Uuid::new_v4()).await.err();
+        let search = ProductSearch {
+            seller_id: Some(data.user_id),
+            in_stock: true,
+            ..Default::default()
+        };
 
-        match result {
-            None => panic!("No error returned, but one was expected"),
-            Some(sqlx::Error::RowNotFound) => (), // Ok
-            _ => panic!("Failed to delete product"),
-        }
+        assert_eq!(
+            db_client
+                .search_products(&search, &PageRequest::first(10))
+                .await
+                .unwrap()
+                .items
+                .len(),
+            1
+        );
+
+        db_client
+            .modify_product(&data.product_id, None, None, None, Some(0))
+            .await
+            .unwrap();
+
+        assert!(db_client
+            .search_products(&search, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .is_empty());
     }
 }
 
 #[cfg(test)]
-mod orders_test {
+mod sessions_tests {
     use super::*;
-    use crate::utils::test_utils::init_test_orders;
+    use crate::{
+        database::transaction::{DBTransaction, ITransaction},
+        utils::test_utils::init_test_users,
+    };
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_order_by_id(pool: Pool<Postgres>) {
-        let (order_data, _, _) = init_test_orders(&pool).await;
+    async fn save_and_get_session(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let order = db_client
-            .get_order(&order_data.order_id)
-            .await
-            .unwrap_or_else(|err| panic!("Failed to get order by id: {err}"))
-            .expect("order not found");
-
-        assert_eq!(order.id, order_data.order_id);
-        assert_eq!(order.product_id, order_data.product_id);
-        assert_eq!(order.user_id, order_data.user_id);
-    }
+        let access_token_id = Uuid::new_v4();
+        let refresh_token_id = Uuid::new_v4();
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_order_by_nonexistent_id(pool: Pool<Postgres>) {
-        init_test_orders(&pool).await;
-        let db_client = DBClient::new(pool);
+        let session = db_client
+            .save_session(
+                &user_id,
+                &access_token_id,
+                &refresh_token_id,
+                &SessionDevice {
+                    label: Some("laptop".to_string()),
+                    user_agent: None,
+                    ip_address: Some("127.0.0.1".to_string()),
+                },
+            )
+            .await
+            .unwrap_or_else(|err| panic!("Failed to save session: {err}"));
 
-        let nonexistant_id = Uuid::new_v4();
+        // the ids themselves are never stored
+        assert_ne!(session.access_token_hash, access_token_id.to_string());
+        assert_eq!(session.device_label.as_deref(), Some("laptop"));
 
-        let result = db_client
-            .get_order(&nonexistant_id)
+        let used = db_client
+            .get_session_by_access_token(&user_id, &access_token_id.to_string())
             .await
-            .unwrap_or_else(|err| panic!("Failed to get order by id: {err}"));
-
-        assert!(result.is_none(), "Expected order to be None");
-    }
+            .unwrap()
+            .expect("Session not found by access token");
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_orders_by_user(pool: Pool<Postgres>) {
-        let (_, _, data) = init_test_orders(&pool).await;
-        let db_client = DBClient::new(pool);
+        assert_eq!(used.id, session.id);
+        assert!(used.last_used_at >= session.last_used_at);
 
-        let orders = db_client
-            .get_orders_by_user(&data.user_id, 1, 5)
+        let by_refresh = db_client
+            .get_session_by_refresh_token(&user_id, &refresh_token_id.to_string())
             .await
-            .unwrap_or_else(|err| panic!("Failed to get orders by user: {err}"));
+            .unwrap()
+            .expect("Session not found by refresh token");
 
-        assert_eq!(orders.len(), 1);
+        assert_eq!(by_refresh.id, session.id);
 
-        let order = orders.first().unwrap();
-        assert_eq!(order.user_id, data.user_id);
+        // the access token is not a refresh token
+        assert!(db_client
+            .get_session_by_refresh_token(&user_id, &access_token_id.to_string())
+            .await
+            .unwrap()
+            .is_none());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_orders_by_nonexistent_user_id(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_orders(&pool).await;
+    async fn delete_sessions(pool: Pool<Postgres>) {
+        let (user_id, other_user_id, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let nonexistent_user_id = Uuid::new_v4();
+        let mut sessions = vec![];
+        for _ in 0..3 {
+            sessions.push(
+                db_client
+                    .save_session(
+                        &user_id,
+                        &Uuid::new_v4(),
+                        &Uuid::new_v4(),
+                        &SessionDevice::default(),
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
 
+        // not the session of this user
         let result = db_client
-            .get_orders_by_user(&nonexistent_user_id, 1, 5)
-            .await
-            .unwrap_or_else(|err| panic!("Failed to get orders by user id: {err}"));
+            .delete_session(&other_user_id, &sessions[0].id)
+            .await;
 
-        assert_eq!(result.len(), 0);
-    }
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_orders(pool: Pool<Postgres>) {
-        init_test_orders(&pool).await;
-        let db_client = DBClient::new(pool);
+        db_client
+            .delete_session(&user_id, &sessions[0].id)
+            .await
+            .unwrap();
 
-        let orders = db_client
-            .get_all_orders(1, 10)
+        let page = db_client
+            .get_sessions_by_user(
+                &user_id,
+                &PageRequest {
+                    after: None,
+                    limit: 10,
+                    with_total: true,
+                },
+            )
             .await
-            .unwrap_or_else(|err| panic!("Failed to get all orders: {err}"));
+            .unwrap();
 
-        assert_eq!(orders.len(), 3);
+        assert_eq!(page.total, Some(2));
+
+        db_client.delete_sessions_by_user(&user_id).await.unwrap();
+
+        assert!(db_client
+            .get_sessions_by_user(&user_id, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .is_empty());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_order(pool: Pool<Postgres>) {
-        let (_, _, data3) = init_test_orders(&pool).await;
+    async fn rotate_session_tokens(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let test_user = db_client
-            .save_user("test_user", "test_user@gmail.com", "test")
+        let refresh_token_id = Uuid::new_v4().to_string();
+        let new_refresh_token_id = Uuid::new_v4();
+
+        let session = db_client
+            .save_session(
+                &user_id,
+                &Uuid::new_v4(),
+                &Uuid::parse_str(&refresh_token_id).unwrap(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let user_id = &test_user.id;
-        let product_id = &data3.product_id;
-        let order_details_id = None;
-
-        db_client
-            .save_order(user_id, product_id, order_details_id, 2)
+        DBTransaction::begin(db_client.pool())
+            .await
+            .unwrap()
+            .rotate_session_tokens(
+                &session.id,
+                &refresh_token_id,
+                &Uuid::new_v4(),
+                &new_refresh_token_id,
+            )
+            .await
+            .unwrap_or_else(|err| panic!("Failed to rotate session tokens: {err}"))
+            .commit()
             .await
             .unwrap();
 
-        let orders = db_client
-            .get_orders_by_user(user_id, 1, 5)
+        assert!(db_client
+            .get_session_by_refresh_token(&user_id, &refresh_token_id)
             .await
-            .unwrap_or_else(|err| panic!("Failed to get orders by user_id: {err}"));
+            .unwrap()
+            .is_none());
 
-        assert_eq!(orders.len(), 1);
+        let rotated = db_client
+            .get_session_by_rotated_refresh_token(&user_id, &refresh_token_id)
+            .await
+            .unwrap()
+            .expect("Session not found by rotated refresh token");
 
-        let order = orders.first().unwrap();
+        assert_eq!(rotated.id, session.id);
 
-        assert_eq!(order.user_id, user_id.clone());
-        assert_eq!(order.product_id, product_id.clone());
-        assert_eq!(order.order_details_id, order_details_id.copied());
-        assert_eq!(order.products_number, 2);
+        // the rotated out token can't be rotated a second time
+        let result = DBTransaction::begin(db_client.pool())
+            .await
+            .unwrap()
+            .rotate_session_tokens(
+                &session.id,
+                &refresh_token_id,
+                &Uuid::new_v4(),
+                &Uuid::new_v4(),
+            )
+            .await;
+
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+
+        assert!(db_client
+            .get_session_by_refresh_token(&user_id, &new_refresh_token_id.to_string())
+            .await
+            .unwrap()
+            .is_some());
     }
+}
+
+#[cfg(test)]
+mod api_keys_tests {
+    use super::*;
+    use crate::utils::test_utils::init_test_users;
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_order_with_invalid_user_id(pool: Pool<Postgres>) {
-        let (_, _, data) = init_test_orders(&pool).await;
+    async fn save_and_get_api_key(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let user_id = Uuid::new_v4();
-        let product_id = &data.product_id;
-        let order_details_id = None;
+        let (key, prefix) = token::generate_api_key();
 
-        let result = db_client
-            .save_order(&user_id, product_id, order_details_id, 1)
-            .await;
+        let api_key = db_client
+            .save_api_key(
+                &user_id,
+                "ci",
+                &key,
+                &prefix,
+                &[ApiKeyScope::ProductsRead, ApiKeyScope::OrdersRead],
+            )
+            .await
+            .unwrap_or_else(|err| panic!("Failed to save API key: {err}"));
 
-        match result {
-            Err(sqlx::Error::Database(db_err)) => {
-                if db_err.is_foreign_key_violation() {
-                    // Ok
-                    return;
-                }
+        // the key itself is never stored
+        assert_ne!(api_key.key_hash, key);
+        assert!(key.starts_with(&api_key.prefix));
+        assert!(api_key.last_used_at.is_none());
+        assert!(api_key.has_scope(ApiKeyScope::ProductsRead));
+        assert!(!api_key.has_scope(ApiKeyScope::ProductsWrite));
 
-                panic!(
-                    "Foreign key violation expected, found: {}",
-                    db_err.message()
-                )
-            }
-            Err(err) => panic!("Database error expected, found: {err}"),
-            Ok(_) => panic!("Call succeded, but a Database error was expected"),
-        }
+        let used = db_client
+            .get_api_key(&key)
+            .await
+            .unwrap()
+            .expect("API key not found");
+
+        assert_eq!(used.id, api_key.id);
+        assert!(used.last_used_at.is_some());
+
+        assert!(db_client
+            .get_api_key(&token::generate_api_key().0)
+            .await
+            .unwrap()
+            .is_none());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_order_with_invalid_product_id(pool: Pool<Postgres>) {
-        let (_, _, data) = init_test_orders(&pool).await;
+    async fn delete_api_key(pool: Pool<Postgres>) {
+        let (user_id, other_user_id, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let user_id = &data.user_id;
-        let product_id = Uuid::new_v4();
-        let order_details_id = None;
+        let (key, prefix) = token::generate_api_key();
+        let api_key = db_client
+            .save_api_key(&user_id, "ci", &key, &prefix, &[ApiKeyScope::CartRead])
+            .await
+            .unwrap();
 
-        let result = db_client
-            .save_order(user_id, &product_id, order_details_id, 1)
-            .await;
+        // not the key of this user
+        let result = db_client.delete_api_key(&other_user_id, &api_key.id).await;
 
-        match result {
-            Err(sqlx::Error::Database(db_err)) => {
-                if db_err.is_foreign_key_violation() {
-                    // Ok
-                    return;
-                }
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
 
-                panic!(
-                    "Foreign key violation expected (found: {})",
-                    db_err.message()
-                )
-            }
-            Err(err) => panic!("Database error expected, found: {err}"),
-            Ok(_) => panic!("Call succeded, but a Database error was expected"),
-        }
+        let page = db_client
+            .get_api_keys_by_user(
+                &user_id,
+                &PageRequest {
+                    after: None,
+                    limit: 10,
+                    with_total: true,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(page.total, Some(1));
+
+        db_client
+            .delete_api_key(&user_id, &api_key.id)
+            .await
+            .unwrap();
+
+        assert!(db_client.get_api_key(&key).await.unwrap().is_none());
+        assert!(db_client
+            .get_api_keys_by_user(&user_id, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .is_empty());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_order_with_invalid_order_details_id(pool: Pool<Postgres>) {
-        let (_, _, data) = init_test_orders(&pool).await;
+    async fn delete_api_keys_by_user(pool: Pool<Postgres>) {
+        let (user_id, other_user_id, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let test_user = db_client
-            .save_user("test_user", "test_user@gmail.com", "test")
+        let (key, prefix) = token::generate_api_key();
+        db_client
+            .save_api_key(&user_id, "ci", &key, &prefix, &[ApiKeyScope::CartRead])
+            .await
+            .unwrap();
+        let (other_key, other_prefix) = token::generate_api_key();
+        db_client
+            .save_api_key(
+                &other_user_id,
+                "ci",
+                &other_key,
+                &other_prefix,
+                &[ApiKeyScope::CartRead],
+            )
             .await
             .unwrap();
 
-        let user_id = &test_user.id;
-        let product_id = &data.product_id;
-        let order_details_id = Some(Uuid::new_v4());
+        db_client
+            .delete_api_keys_by_user(&user_id)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to delete api keys: {err}"));
 
-        let result = db_client
-            .save_order(user_id, product_id, order_details_id.as_ref(), 1)
-            .await;
+        assert!(db_client.get_api_key(&key).await.unwrap().is_none());
+        assert!(db_client.get_api_key(&other_key).await.unwrap().is_some());
+    }
+}
 
-        match result {
-            Err(sqlx::Error::Database(db_err)) => {
-                if db_err.is_foreign_key_violation() {
-                    // Ok
-                    return;
-                }
+#[cfg(test)]
+mod idempotency_keys_tests {
+    use chrono::Duration;
 
-                panic!(
-                    "Foreign key violation expected (found: {})",
-                    db_err.message()
-                )
-            }
-            Err(err) => panic!("Database error expected, found: {err}"),
-            Ok(_) => panic!("Call succeded, but a Database error was expected"),
-        }
+    use super::*;
+    use crate::utils::test_utils::init_test_users;
+
+    /// Like the ones of the requests, `CHAR(64)` pads the shorter ones
+    fn fingerprint(request: &str) -> String {
+        token::hash_token_id(request)
+    }
+
+    fn in_a_minute() -> DateTime<Utc> {
+        Utc::now() + Duration::minutes(1)
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn save_order_but_products_number_too_low(pool: Pool<Postgres>) {
-        let (data, data2, _) = init_test_orders(&pool).await;
+    async fn save_idempotency_key_once(pool: Pool<Postgres>) {
+        let (user_id, other_user_id, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let result = db_client
-            .save_order(&data.user_id, &data2.product_id, None, 0)
+        let saved = db_client
+            .save_idempotency_key(&user_id, "retry-1", &fingerprint("request"), in_a_minute())
             .await
-            .err();
-
-        match result {
-            None => panic!("Call succeded, but an error was expected"),
-            Some(sqlx::Error::Database(db_err)) => {
-                if db_err.is_check_violation() {
-                    // Ok
-                } else {
-                    // other error
-                    panic!("Failed to save order: {}", db_err.message())
-                }
-            }
+            .unwrap()
+            .expect("Idempotency key not saved");
 
-            // other error
-            Some(err) => panic!("Failed to save order: {err}"),
-        }
-    }
+        assert!(saved.response_status.is_none());
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_order(pool: Pool<Postgres>) {
-        let (data, _, _) = init_test_orders(&pool).await;
-        let db_client = DBClient::new(pool);
+        // the key is taken while it is not expired
+        assert!(db_client
+            .save_idempotency_key(
+                &user_id,
+                "retry-1",
+                &fingerprint("other request"),
+                in_a_minute()
+            )
+            .await
+            .unwrap()
+            .is_none());
+
+        // the keys of each user are apart
+        assert!(db_client
+            .save_idempotency_key(
+                &other_user_id,
+                "retry-1",
+                &fingerprint("request"),
+                in_a_minute()
+            )
+            .await
+            .unwrap()
+            .is_some());
 
         db_client
-            .delete_order(&data.order_id)
+            .save_idempotency_response(&user_id, "retry-1", 201, Some("application/json"), b"{}")
             .await
-            .expect("Failed to delete order");
+            .unwrap();
 
-        let result = db_client.get_order(&data.order_id).await.unwrap();
+        let stored = db_client
+            .get_idempotency_key(&user_id, "retry-1")
+            .await
+            .unwrap()
+            .expect("Idempotency key not found");
 
-        assert!(result.is_none(), "Order found, but no one expected");
+        assert_eq!(stored.fingerprint, fingerprint("request"));
+        assert_eq!(stored.response_status, Some(201));
+        assert_eq!(stored.response_body.as_deref(), Some(&b"{}"[..]));
+
+        db_client
+            .delete_idempotency_key(&user_id, "retry-1")
+            .await
+            .unwrap();
+
+        assert!(db_client
+            .get_idempotency_key(&user_id, "retry-1")
+            .await
+            .unwrap()
+            .is_none());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_invalid_order(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_orders(&pool).await;
+    async fn reuse_expired_idempotency_key(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool);
 
-        let result = db_client.delete_order(&Uuid::new_v4()).await.err();
+        db_client
+            .save_idempotency_key(
+                &user_id,
+                "retry-1",
+                &fingerprint("request"),
+                Utc::now() - Duration::minutes(1),
+            )
+            .await
+            .unwrap()
+            .expect("Idempotency key not saved");
 
-        match result {
-            None => panic!("No error returned, but one was expected"),
-            Some(sqlx::Error::RowNotFound) => (), // ok
-            Some(_) => panic!("Failed to delete order"),
-        }
+        assert!(db_client
+            .get_idempotency_key(&user_id, "retry-1")
+            .await
+            .unwrap()
+            .is_none());
+
+        let saved = db_client
+            .save_idempotency_key(
+                &user_id,
+                "retry-1",
+                &fingerprint("other request"),
+                in_a_minute(),
+            )
+            .await
+            .unwrap()
+            .expect("Expired idempotency key not replaced");
+
+        assert_eq!(saved.fingerprint, fingerprint("other request"));
     }
 }
@@ -1,31 +1,52 @@
 use sqlx::{PgConnection, Pool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::utils::{
+    models::{LedgerEntryKind, OrderStatus, PaymentIntentStatus, Product},
+    token,
+};
+
 pub trait ITransaction: Sized {
     type Error;
 
-    #[allow(dead_code)]
     async fn lock_user(self, user_id: &Uuid) -> Result<Self, Self::Error>;
 
+    /// Also appends the matching (negative) entry to the user's ledger
     async fn decrease_user_sold(
         self,
         user_id: &Uuid,
         to_decrease: i64,
+        kind: LedgerEntryKind,
+        order_id: Option<&Uuid>,
     ) -> Result<Self, Self::Error>;
 
+    /// Also appends the matching entry to the user's ledger
     async fn increase_user_sold(
         self,
         user_id: &Uuid,
         to_increase: i64,
+        kind: LedgerEntryKind,
+        order_id: Option<&Uuid>,
     ) -> Result<Self, Self::Error>;
 
-    async fn save_user_token_id(
+    /// Replaces both tokens of the session and keeps the previous refresh token
+    /// as rotated out. Fails with `RowNotFound` if `refresh_token_id` is not the
+    /// current refresh token of the session anymore
+    async fn rotate_session_tokens(
         self,
-        user_id: &Uuid,
-        new_token: &Uuid,
+        session_id: &Uuid,
+        refresh_token_id: &str,
+        new_access_token_id: &Uuid,
+        new_refresh_token_id: &Uuid,
+    ) -> Result<Self, Self::Error>;
+
+    /// Appends a `commission` entry to the platform's ledger, negative to give it back
+    async fn record_platform_commission(
+        self,
+        amount_in_cents: i64,
+        order_id: &Uuid,
     ) -> Result<Self, Self::Error>;
 
-    #[allow(dead_code)]
     async fn lock_product(self, product_id: &Uuid) -> Result<Self, Self::Error>;
 
     async fn decrease_product_stock(
@@ -34,12 +55,91 @@ pub trait ITransaction: Sized {
         to_decrease: i32,
     ) -> Result<Self, Self::Error>;
 
-    #[allow(dead_code)]
+    /// Capped at 999, the most a product can have in stock, so that giving back
+    /// the items of an order never fails after the seller restocked it
     async fn increase_product_stock(
         self,
         product_id: &Uuid,
         to_increase: i32,
     ) -> Result<Self, Self::Error>;
+
+    /// Fails with `invalid-order-status-transition` if the order can not go from
+    /// its current status to `new_status`. It also locks the order until the end of the transaction
+    async fn update_order_status(
+        self,
+        order_id: &Uuid,
+        new_status: OrderStatus,
+    ) -> Result<Self, Self::Error>;
+
+    /// Moves the order to `cancelled` or `refunded` and records why. Fails with `RowNotFound`
+    /// if the order is not in one of the `from` statuses anymore, it also locks the order
+    /// until the end of the transaction
+    async fn cancel_order(
+        self,
+        order_id: &Uuid,
+        from: &[OrderStatus],
+        new_status: OrderStatus,
+        cancelled_by: &Uuid,
+        reason: &str,
+    ) -> Result<Self, Self::Error>;
+
+    /// Fails with `RowNotFound` if the order is not in one of the `from` statuses, it also
+    /// locks the order until the end of the transaction
+    async fn lock_order(self, order_id: &Uuid, from: &[OrderStatus]) -> Result<Self, Self::Error>;
+
+    /// Ships the items of the seller not shipped nor refunded yet, the order is `shipped` once
+    /// all its items are. Fails with `RowNotFound` if the seller has nothing left to ship
+    async fn save_order_shipment(
+        self,
+        order_id: &Uuid,
+        shipped_by: &Uuid,
+        tracking_number: &str,
+    ) -> Result<Self, Self::Error>;
+
+    /// The order is `delivered` once the shipments of all its items are. Fails with
+    /// `RowNotFound` if the seller has no shipment left to deliver
+    async fn mark_order_shipment_delivered(
+        self,
+        order_id: &Uuid,
+        shipped_by: &Uuid,
+    ) -> Result<Self, Self::Error>;
+
+    /// Marks the items of the seller not refunded yet as refunded, the money and the stocks are
+    /// moved by the caller. Fails with `RowNotFound` if the seller has nothing left to refund
+    async fn refund_order_items(
+        self,
+        order_id: &Uuid,
+        seller_id: &Uuid,
+        reason: &str,
+    ) -> Result<Self, Self::Error>;
+
+    /// Creates a pending order without lines, see `save_order_item`
+    async fn save_order(
+        self,
+        order_id: &Uuid,
+        user_id: &Uuid,
+        order_details_id: Option<&Uuid>,
+        total_in_cents: i64,
+    ) -> Result<Self, Self::Error>;
+
+    /// Snapshots the price and the name of the product, as charged for the order
+    async fn save_order_item(
+        self,
+        order_id: &Uuid,
+        product: &Product,
+        products_number: i32,
+    ) -> Result<Self, Self::Error>;
+
+    async fn clear_cart(self, user_id: &Uuid) -> Result<Self, Self::Error>;
+
+    /// Fails with `RowNotFound` if the intent is not in one of the `from` statuses anymore,
+    /// it also locks the intent until the end of the transaction
+    async fn update_payment_intent_status(
+        self,
+        payment_intent_id: &Uuid,
+        from: &[PaymentIntentStatus],
+        new_status: PaymentIntentStatus,
+    ) -> Result<Self, Self::Error>;
 }
 
 #[derive(Debug)]
@@ -57,9 +157,76 @@ impl DBTransaction<'_> {
     pub async fn commit(self) -> Result<(), sqlx::Error> {
         self.tx.commit().await
     }
+
+    async fn append_ledger_entry(
+        mut self,
+        user_id: Option<&Uuid>,
+        amount_in_cents: i64,
+        kind: LedgerEntryKind,
+        order_id: Option<&Uuid>,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query(
+            r"
+				INSERT INTO ledger_entries ( user_id, amount_in_cents, kind, order_id )
+				VALUES ( $1, $2, $3, $4 )
+				",
+        )
+        .bind(user_id)
+        .bind(amount_in_cents)
+        .bind(kind)
+        .bind(order_id)
+        .execute(&mut *self)
+        .await?;
+
+        Ok(self)
+    }
+
+    /// Moves a paid order to `shipped` once all the items not refunded are shipped, then to
+    /// `delivered` once their shipments are all delivered
+    async fn advance_order_status(mut self, order_id: &Uuid) -> Result<Self, sqlx::Error> {
+        sqlx::query(
+            r"
+				UPDATE orders
+				SET status = 'shipped'
+				WHERE id = $1 AND status = 'paid'
+					AND EXISTS (
+						SELECT 1
+						FROM order_items
+						WHERE order_id = $1 AND refunded_at IS NULL
+					)
+					AND NOT EXISTS (
+						SELECT 1
+						FROM order_items
+						WHERE order_id = $1 AND refunded_at IS NULL AND shipment_id IS NULL
+					)
+				",
+        )
+        .bind(order_id)
+        .execute(&mut *self)
+        .await?;
+
+        sqlx::query(
+            r"
+				UPDATE orders
+				SET status = 'delivered'
+				WHERE id = $1 AND status = 'shipped'
+					AND NOT EXISTS (
+						SELECT 1
+						FROM order_items
+						LEFT JOIN order_shipments ON order_shipments.id = order_items.shipment_id
+						WHERE order_items.order_id = $1 AND order_items.refunded_at IS NULL
+							AND order_shipments.delivered_at IS NULL
+					)
+				",
+        )
+        .bind(order_id)
+        .execute(&mut *self)
+        .await?;
+
+        Ok(self)
+    }
 }
 
-// TODO!: write tests for those functions
 impl ITransaction for DBTransaction<'_> {
     type Error = sqlx::Error;
 
@@ -83,6 +250,8 @@ impl ITransaction for DBTransaction<'_> {
         mut self,
         user_id: &Uuid,
         to_decrease: i64,
+        kind: LedgerEntryKind,
+        order_id: Option<&Uuid>,
     ) -> Result<Self, Self::Error> {
         let _ = sqlx::query(
             r"
@@ -96,13 +265,16 @@ impl ITransaction for DBTransaction<'_> {
         .execute(&mut *self)
         .await?;
 
-        Ok(self)
+        self.append_ledger_entry(Some(user_id), -to_decrease, kind, order_id)
+            .await
     }
 
     async fn increase_user_sold(
         mut self,
         user_id: &Uuid,
         to_increase: i64,
+        kind: LedgerEntryKind,
+        order_id: Option<&Uuid>,
     ) -> Result<Self, Self::Error> {
         let _ = sqlx::query(
             r"
@@ -116,7 +288,22 @@ impl ITransaction for DBTransaction<'_> {
         .execute(&mut *self)
         .await?;
 
-        Ok(self)
+        self.append_ledger_entry(Some(user_id), to_increase, kind, order_id)
+            .await
+    }
+
+    async fn record_platform_commission(
+        self,
+        amount_in_cents: i64,
+        order_id: &Uuid,
+    ) -> Result<Self, Self::Error> {
+        self.append_ledger_entry(
+            None,
+            amount_in_cents,
+            LedgerEntryKind::Commission,
+            Some(order_id),
+        )
+        .await
     }
 
     async fn lock_product(mut self, product_id: &Uuid) -> Result<Self, Self::Error> {
@@ -163,7 +350,7 @@ impl ITransaction for DBTransaction<'_> {
         sqlx::query(
             r"
 				UPDATE products
-				SET number_in_stock = number_in_stock + $1
+				SET number_in_stock = LEAST(number_in_stock + $1, 999)
 				WHERE id = $2
 				",
         )
@@ -175,28 +362,307 @@ impl ITransaction for DBTransaction<'_> {
         Ok(self)
     }
 
-    async fn save_user_token_id(
+    async fn update_order_status(
         mut self,
-        new_token_id: &Uuid,
+        order_id: &Uuid,
+        new_status: OrderStatus,
+    ) -> Result<Self, Self::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE orders
+				SET status = $1
+				WHERE id = $2
+				",
+        )
+        .bind(new_status)
+        .bind(order_id)
+        .execute(&mut *self)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(self)
+    }
+
+    async fn cancel_order(
+        mut self,
+        order_id: &Uuid,
+        from: &[OrderStatus],
+        new_status: OrderStatus,
+        cancelled_by: &Uuid,
+        reason: &str,
+    ) -> Result<Self, Self::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE orders
+				SET status = $1
+				WHERE id = $2 AND status = ANY($3)
+				",
+        )
+        .bind(new_status)
+        .bind(order_id)
+        .bind(from)
+        .execute(&mut *self)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        sqlx::query(
+            r"
+				INSERT INTO order_cancellations ( order_id, cancelled_by, status, reason )
+				VALUES ( $1, $2, $3, $4 )
+				",
+        )
+        .bind(order_id)
+        .bind(cancelled_by)
+        .bind(new_status)
+        .bind(reason)
+        .execute(&mut *self)
+        .await?;
+
+        Ok(self)
+    }
+
+    async fn lock_order(
+        mut self,
+        order_id: &Uuid,
+        from: &[OrderStatus],
+    ) -> Result<Self, Self::Error> {
+        sqlx::query(
+            r"
+				SELECT id
+				FROM orders
+				WHERE id = $1 AND status = ANY($2)
+				FOR UPDATE
+				",
+        )
+        .bind(order_id)
+        .bind(from)
+        .fetch_one(&mut *self)
+        .await?;
+
+        Ok(self)
+    }
+
+    async fn save_order_shipment(
+        mut self,
+        order_id: &Uuid,
+        shipped_by: &Uuid,
+        tracking_number: &str,
+    ) -> Result<Self, Self::Error> {
+        let result = sqlx::query(
+            r"
+				WITH shipment AS (
+					INSERT INTO order_shipments ( order_id, shipped_by, tracking_number )
+					VALUES ( $1, $2, $3 )
+					RETURNING id
+				)
+				UPDATE order_items
+				SET shipment_id = shipment.id
+				FROM shipment, products
+				WHERE order_items.order_id = $1 AND products.id = order_items.product_id
+					AND products.user_id = $2 AND order_items.shipment_id IS NULL
+					AND order_items.refunded_at IS NULL
+				",
+        )
+        .bind(order_id)
+        .bind(shipped_by)
+        .bind(tracking_number)
+        .execute(&mut *self)
+        .await?;
+
+        // the shipment without items is dropped with the transaction
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        self.advance_order_status(order_id).await
+    }
+
+    async fn mark_order_shipment_delivered(
+        mut self,
+        order_id: &Uuid,
+        shipped_by: &Uuid,
+    ) -> Result<Self, Self::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE order_shipments
+				SET delivered_at = NOW()
+				WHERE order_id = $1 AND shipped_by = $2 AND delivered_at IS NULL
+				",
+        )
+        .bind(order_id)
+        .bind(shipped_by)
+        .execute(&mut *self)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        self.advance_order_status(order_id).await
+    }
+
+    async fn refund_order_items(
+        mut self,
+        order_id: &Uuid,
+        seller_id: &Uuid,
+        reason: &str,
+    ) -> Result<Self, Self::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE order_items
+				SET refunded_at = NOW(), refund_reason = $3
+				FROM products
+				WHERE order_items.order_id = $1 AND products.id = order_items.product_id
+					AND products.user_id = $2 AND order_items.refunded_at IS NULL
+				",
+        )
+        .bind(order_id)
+        .bind(seller_id)
+        .bind(reason)
+        .execute(&mut *self)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        // the others may only wait for these items
+        self.advance_order_status(order_id).await
+    }
+
+    async fn save_order(
+        mut self,
+        order_id: &Uuid,
         user_id: &Uuid,
+        order_details_id: Option<&Uuid>,
+        total_in_cents: i64,
     ) -> Result<Self, Self::Error> {
-        let hashed_id =
-            bcrypt::hash(new_token_id.to_string(), 4).map_err(|_| sqlx::Error::WorkerCrashed)?; // no the real error
+        sqlx::query(
+            r"
+				INSERT INTO orders ( id, user_id, order_details_id, total_in_cents )
+				VALUES ( $1, $2, $3, $4 )
+				",
+        )
+        .bind(order_id)
+        .bind(user_id)
+        .bind(order_details_id)
+        .bind(total_in_cents)
+        .execute(&mut *self)
+        .await?;
 
+        Ok(self)
+    }
+
+    async fn save_order_item(
+        mut self,
+        order_id: &Uuid,
+        product: &Product,
+        products_number: i32,
+    ) -> Result<Self, Self::Error> {
         sqlx::query(
             r"
-			UPDATE users
-			SET last_token_id = $1
-			WHERE id = $2
-			",
+				INSERT INTO order_items ( order_id, product_id, products_number, unit_price_in_cents, product_name )
+				VALUES ( $1, $2, $3, $4, $5 )
+				",
+        )
+        .bind(order_id)
+        .bind(product.id)
+        .bind(products_number)
+        .bind(product.price_in_cents)
+        .bind(&product.name)
+        .execute(&mut *self)
+        .await?;
+
+        Ok(self)
+    }
+
+    async fn clear_cart(mut self, user_id: &Uuid) -> Result<Self, Self::Error> {
+        sqlx::query(
+            r"
+				DELETE FROM cart_items
+				WHERE user_id = $1
+				",
         )
-        .bind(hashed_id)
         .bind(user_id)
         .execute(&mut *self)
         .await?;
 
         Ok(self)
     }
+
+    async fn rotate_session_tokens(
+        mut self,
+        session_id: &Uuid,
+        refresh_token_id: &str,
+        new_access_token_id: &Uuid,
+        new_refresh_token_id: &Uuid,
+    ) -> Result<Self, Self::Error> {
+        let refresh_token_hash = token::hash_token_id(refresh_token_id);
+
+        // the row lock makes a concurrent rotation with the same token wait, then fail
+        let result = sqlx::query(
+            r"
+			UPDATE sessions
+			SET access_token_hash = $1, refresh_token_hash = $2, last_used_at = NOW()
+			WHERE id = $3 AND refresh_token_hash = $4
+			",
+        )
+        .bind(token::hash_token_id(&new_access_token_id.to_string()))
+        .bind(token::hash_token_id(&new_refresh_token_id.to_string()))
+        .bind(session_id)
+        .bind(&refresh_token_hash)
+        .execute(&mut *self)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        sqlx::query(
+            r"
+			INSERT INTO rotated_refresh_tokens ( token_hash, session_id )
+			VALUES ( $1, $2 )
+			",
+        )
+        .bind(refresh_token_hash)
+        .bind(session_id)
+        .execute(&mut *self)
+        .await?;
+
+        Ok(self)
+    }
+
+    async fn update_payment_intent_status(
+        mut self,
+        payment_intent_id: &Uuid,
+        from: &[PaymentIntentStatus],
+        new_status: PaymentIntentStatus,
+    ) -> Result<Self, Self::Error> {
+        let result = sqlx::query(
+            r"
+				UPDATE payment_intents
+				SET status = $1
+				WHERE id = $2 AND status = ANY($3)
+				",
+        )
+        .bind(new_status)
+        .bind(payment_intent_id)
+        .bind(from)
+        .execute(&mut *self)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(self)
+    }
 }
 
 impl std::ops::DerefMut for DBTransaction<'_> {
@@ -215,5 +681,181 @@ impl std::ops::Deref for DBTransaction<'_> {
 
 #[cfg(test)]
 mod tests {
-    // TODO!: make tests !
+    use super::*;
+    use crate::{
+        database::{psql::DBClient, OrderExtractor, PaymentExtractor},
+        utils::test_utils::init_test_orders,
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn update_order_status(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Paid)
+            .await
+            .expect("Failed to update order status")
+            .commit()
+            .await
+            .unwrap();
+
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+
+        assert_eq!(order.status, OrderStatus::Paid);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn update_order_status_with_invalid_transition(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+
+        // pending -> delivered is not allowed
+        let result = DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Delivered)
+            .await
+            .err();
+
+        match result {
+            Some(sqlx::Error::Database(db_err)) => {
+                assert_eq!(db_err.message(), "invalid-order-status-transition");
+            }
+            Some(err) => panic!("Database error expected, found: {err}"),
+            None => panic!("Call succeded, but a Database error was expected"),
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn update_nonexistent_order_status(pool: Pool<Postgres>) {
+        init_test_orders(&pool).await;
+
+        let result = DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&Uuid::new_v4(), OrderStatus::Paid)
+            .await
+            .err();
+
+        match result {
+            None => panic!("No error returned, but one was expected"),
+            Some(sqlx::Error::RowNotFound) => (), // ok
+            Some(err) => panic!("RowNotFound expected, found: {err}"),
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn cancel_order_only_from_expected_status(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+
+        // paid in the meantime
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Paid)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let result = DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .cancel_order(
+                &data.order_id,
+                &[OrderStatus::Pending],
+                OrderStatus::Cancelled,
+                &data.user_id,
+                "Changed my mind",
+            )
+            .await
+            .err();
+
+        match result {
+            None => panic!("No error returned, but one was expected"),
+            Some(sqlx::Error::RowNotFound) => (), // ok
+            Some(err) => panic!("RowNotFound expected, found: {err}"),
+        }
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .cancel_order(
+                &data.order_id,
+                &[OrderStatus::Paid],
+                OrderStatus::Cancelled,
+                &data.user_id,
+                "Changed my mind",
+            )
+            .await
+            .expect("Failed to cancel order")
+            .commit()
+            .await
+            .unwrap();
+
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let cancellation = db_client
+            .get_order_cancellation(&data.order_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert_eq!(cancellation.reason, "Changed my mind");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn update_payment_intent_status(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+
+        let intent = db_client
+            .save_payment_intent(&data.user_id, "gateway-intent", 500)
+            .await
+            .unwrap();
+
+        assert_eq!(intent.status, PaymentIntentStatus::Pending);
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_payment_intent_status(
+                &intent.id,
+                &[PaymentIntentStatus::Pending],
+                PaymentIntentStatus::Succeeded,
+            )
+            .await
+            .expect("Failed to update payment intent status")
+            .commit()
+            .await
+            .unwrap();
+
+        let intent = db_client
+            .get_payment_intent_by_gateway_id("gateway-intent")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(intent.status, PaymentIntentStatus::Succeeded);
+
+        // not pending anymore
+        let result = DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_payment_intent_status(
+                &intent.id,
+                &[
+                    PaymentIntentStatus::Pending,
+                    PaymentIntentStatus::Processing,
+                ],
+                PaymentIntentStatus::Failed,
+            )
+            .await;
+
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+    }
 }
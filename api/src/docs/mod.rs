@@ -1,19 +1,27 @@
 #![allow(clippy::needless_for_each)]
 
 use utoipa::{
-    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
     Modify, OpenApi,
 };
 
 #[allow(clippy::wildcard_imports)]
 use crate::{
-    dtos::{orders::*, products::*, users::*, *},
+    dtos::{
+        addresses::*, api_keys::*, cart::*, ledger::*, mfa::*, orders::*, payments::*, products::*,
+        sessions::*, users::*, *,
+    },
     error::*,
-    routes::{auth, orders, products, user},
-    utils::status::Status,
+    routes::{admin, api_keys, auth, cart, orders, payments, products, user, well_known},
+    utils::{
+        models::{
+            ApiKeyScope, LedgerEntryKind, OrderStatus, PaymentIntentStatus, ProductSort, UserRole,
+        },
+        status::Status,
+    },
 };
 
-/// Security scheme modifier for JWT Bearer authentication
+/// Security schemes for JWT Bearer authentication, and the API keys of the routes with a scope
 struct SecurityAddon;
 
 impl Modify for SecurityAddon {
@@ -23,6 +31,10 @@ impl Modify for SecurityAddon {
                 "bearer_auth",
                 SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
             );
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+            );
         }
     }
 }
@@ -35,31 +47,83 @@ impl Modify for SecurityAddon {
         auth::login,
         auth::register,
         auth::logout,
+        auth::logout_all,
         auth::refresh,
+        auth::get_sessions,
+        auth::delete_session,
+        auth::verify_email,
+        auth::forgot_password,
+        auth::reset_password,
+        auth::login_mfa,
+        auth::setup_totp,
+        auth::confirm_totp,
+        auth::disable_totp,
+        well_known::jwks,
 
         // Product routes
         products::get_all,
         products::get_by_id,
         products::create,
         products::delete,
+        products::modify,
+        products::search,
 
         // User routes
         user::get_me,
+        user::modify_me,
+        user::change_password,
         user::get_by_id,
         user::get_all,
         user::delete,
-        user::add_sold,
 
         // User sub-routes
         user::products::get_my_products,
         user::products::get_user_products,
         user::orders::get_my_orders,
+        user::orders::get_my_sales,
+        user::transactions::get_my_transactions,
+        user::cart::get_my_cart,
+        user::cart::add_to_my_cart,
+        user::cart::modify_my_cart_item,
+        user::cart::delete_my_cart_item,
+        user::addresses::get_my_addresses,
+        user::addresses::get_my_address,
+        user::addresses::create_my_address,
+        user::addresses::modify_my_address,
+        user::addresses::delete_my_address,
 
         // Order routes
         orders::create,
         orders::get_by_id,
+        orders::get_cancellation,
         orders::delete,
         orders::validate,
+        orders::cancel,
+        orders::refund,
+        orders::get_shipments,
+        orders::ship,
+        orders::deliver,
+
+        // Cart routes
+        cart::checkout,
+
+        // Payment routes
+        payments::create_intent,
+        payments::get_intent,
+        payments::confirm_intent,
+        payments::webhook,
+
+        // API key routes
+        api_keys::create,
+        api_keys::get_all,
+        api_keys::delete,
+
+        // Admin routes
+        admin::get_all_orders,
+        admin::delete_product,
+        admin::suspend_user,
+        admin::unsuspend_user,
+        admin::get_balance_drifts,
     ),
     components(
         schemas(
@@ -72,9 +136,33 @@ impl Modify for SecurityAddon {
             ForeignUserResponseDto,
             UserListResponseDto,
             LoginResponseDto,
-            AddSoldDto,
+            ModifyUserDto,
+            ChangePasswordDto,
+            VerifyEmailDto,
+            ForgotPasswordDto,
+            ResetPasswordDto,
+            UserRole,
+            // MFA DTOs
+            MfaChallengeResponseDto,
+            MfaLoginDto,
+            TotpCodeDto,
+            TotpSetupDto,
+            TotpSetupResponseDto,
+            RecoveryCodesResponseDto,
+            // Session DTOs
+            SessionDto,
+            SessionListResponseDto,
+            // API key DTOs
+            CreateApiKeyDto,
+            ApiKeyDto,
+            ApiKeyCreatedResponseDto,
+            ApiKeyListResponseDto,
+            ApiKeyScope,
             // Product DTOs
             CreateProductDto,
+            ModifyProductDto,
+            SearchProductsQueryDto,
+            ProductSort,
             ProductDto,
             FilterProductDto,
             ProductResponseDto,
@@ -83,12 +171,50 @@ impl Modify for SecurityAddon {
             FilterProductListResponseDto,
             // Order DTOs
             CreateOrderDto,
+            CancelOrderDto,
+            OrderCancellationDto,
+            OrderCancellationResponseDto,
+            ShipOrderDto,
+            SalesQueryDto,
+            OrderShipmentDto,
+            OrderShipmentListResponseDto,
             OrderDto,
             FilterOrderDto,
             OrderResponseDto,
             FilterOrderResponseDto,
             OrderListResponseDto,
             FilterOrderListResponseDto,
+            DeliveryAddressDto,
+            SaleDto,
+            SaleListResponseDto,
+            OrderStatus,
+            OrderItemDto,
+            OrderWithItemsDto,
+            OrderWithItemsResponseDto,
+            // Address DTOs
+            SaveAddressDto,
+            AddressDto,
+            AddressResponseDto,
+            AddressListResponseDto,
+            // Cart DTOs
+            CheckoutDto,
+            AddCartItemDto,
+            ModifyCartItemDto,
+            CartItemDto,
+            CartItemResponseDto,
+            CartItemListResponseDto,
+            // Payment DTOs
+            CreatePaymentIntentDto,
+            ConfirmPaymentIntentDto,
+            PaymentIntentDto,
+            PaymentIntentResponseDto,
+            PaymentIntentStatus,
+            // Ledger DTOs
+            LedgerEntryDto,
+            LedgerEntryListResponseDto,
+            BalanceDriftDto,
+            BalanceDriftListResponseDto,
+            LedgerEntryKind,
             // Common DTOs
             RequestQueryDto,
             // Error responses
@@ -104,6 +230,11 @@ impl Modify for SecurityAddon {
         (name = "Users", description = "User management endpoints"),
         (name = "Products", description = "Product management endpoints"),
         (name = "Orders", description = "Order management endpoints"),
+        (name = "Cart", description = "Shopping cart endpoints"),
+        (name = "Addresses", description = "Delivery address book endpoints"),
+        (name = "Payments", description = "Balance top-ups through the payment gateway"),
+        (name = "API Keys", description = "Keys for backend jobs, limited to the routes of their scopes"),
+        (name = "Admin", description = "Moderation endpoints, for admins only"),
     ),
     info(
         title = "eAPI",
@@ -0,0 +1,70 @@
+use crate::{utils::models::Address, utils::status::Status};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveAddressDto {
+    #[validate(length(min = 1, max = 255, message = "Street is required"))]
+    #[schema(example = "10 Downing Street")]
+    pub street: String,
+
+    #[validate(length(min = 1, max = 100, message = "City is required"))]
+    #[schema(example = "London")]
+    pub city: String,
+
+    #[validate(length(min = 1, max = 20, message = "Postal code is required"))]
+    #[schema(example = "SW1A 2AA")]
+    pub postal_code: String,
+
+    #[validate(length(min = 1, max = 100, message = "Country is required"))]
+    #[schema(example = "United Kingdom")]
+    pub country: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressDto {
+    pub id: Uuid,
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AddressDto {
+    pub fn from(address: &Address) -> Self {
+        AddressDto {
+            id: address.id,
+            street: address.street.clone(),
+            city: address.city.clone(),
+            postal_code: address.postal_code.clone(),
+            country: address.country.clone(),
+
+            created_at: address.created_at,
+            updated_at: address.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AddressResponseDto {
+    pub status: Status,
+    pub data: AddressDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AddressListResponseDto {
+    pub status: Status,
+    pub data: Vec<AddressDto>,
+    pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::utils::{
+    models::{ApiKey, ApiKeyScope},
+    status::Status,
+};
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyDto {
+    #[validate(length(min = 1, max = 100, message = "Name must be 1 to 100 characters long"))]
+    #[schema(example = "Stock sync job")]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    #[schema(example = json!(["products:read", "products:write"]))]
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyDto {
+    pub id: Uuid,
+    pub name: String,
+    /// Start of the key, the rest is not stored
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub last_used_at: Option<DateTime<Utc>>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKeyDto {
+    pub fn from(api_key: &ApiKey) -> Self {
+        ApiKeyDto {
+            id: api_key.id,
+            name: api_key.name.clone(),
+            prefix: api_key.prefix.clone(),
+            scopes: api_key.scopes.clone(),
+            last_used_at: api_key.last_used_at,
+
+            created_at: api_key.created_at,
+        }
+    }
+}
+
+/// The key is only shown here, only its hash is stored
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyCreatedResponseDto {
+    pub status: Status,
+    pub data: ApiKeyDto,
+    #[schema(example = "eapi_0aGQ3X...")]
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyListResponseDto {
+    pub status: Status,
+    pub data: Vec<ApiKeyDto>,
+    pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
+}
@@ -0,0 +1,75 @@
+use crate::{utils::models::CartItem, utils::status::Status};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddCartItemDto {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub product_id: Uuid,
+
+    #[validate(range(
+        min = 1,
+        max = 1000,
+        message = "Product number can only be between 1 and 1000"
+    ))]
+    #[schema(example = 2)]
+    pub products_number: i32,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifyCartItemDto {
+    #[validate(range(
+        min = 1,
+        max = 1000,
+        message = "Product number can only be between 1 and 1000"
+    ))]
+    #[schema(example = 2)]
+    pub products_number: i32,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckoutDto {
+    /// one of the user's addresses
+    pub order_details_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CartItemDto {
+    pub product_id: Uuid,
+    pub products_number: i32,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CartItemDto {
+    pub fn from(item: &CartItem) -> Self {
+        CartItemDto {
+            product_id: item.product_id,
+            products_number: item.products_number,
+
+            created_at: item.created_at,
+            updated_at: item.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CartItemResponseDto {
+    pub status: Status,
+    pub data: CartItemDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CartItemListResponseDto {
+    pub status: Status,
+    pub data: Vec<CartItemDto>,
+    pub results: usize,
+}
@@ -0,0 +1,67 @@
+use crate::{
+    utils::models::{BalanceDrift, LedgerEntry, LedgerEntryKind},
+    utils::status::Status,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntryDto {
+    pub id: Uuid,
+    pub order_id: Option<Uuid>,
+    pub kind: LedgerEntryKind,
+    pub amount_in_cents: i64,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl LedgerEntryDto {
+    pub fn from(entry: &LedgerEntry) -> Self {
+        LedgerEntryDto {
+            id: entry.id,
+            order_id: entry.order_id,
+            kind: entry.kind,
+            amount_in_cents: entry.amount_in_cents,
+
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LedgerEntryListResponseDto {
+    pub status: Status,
+    pub data: Vec<LedgerEntryDto>,
+    pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceDriftDto {
+    pub user_id: Uuid,
+    pub sold_in_cents: i64,
+    pub ledger_sum_in_cents: i64,
+}
+
+impl BalanceDriftDto {
+    pub fn from(drift: &BalanceDrift) -> Self {
+        BalanceDriftDto {
+            user_id: drift.user_id,
+            sold_in_cents: drift.sold_in_cents,
+            ledger_sum_in_cents: drift.ledger_sum_in_cents,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BalanceDriftListResponseDto {
+    pub status: Status,
+    pub data: Vec<BalanceDriftDto>,
+    pub results: usize,
+}
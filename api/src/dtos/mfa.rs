@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::utils::status::Status;
+
+/// Answer of a login with the right password when the TOTP is enabled
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaChallengeResponseDto {
+    pub status: Status,
+    pub mfa_token: String,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaLoginDto {
+    #[validate(length(min = 1, message = "MFA token is required"))]
+    pub mfa_token: String,
+
+    /// A code of the authenticator app, or an unused recovery code
+    #[validate(length(min = 1, max = 20, message = "Code is invalid"))]
+    #[schema(example = "123456")]
+    pub code: String,
+
+    /// Name shown in the list of sessions
+    #[validate(length(
+        max = 100,
+        message = "Device label can not be longer than 100 characters"
+    ))]
+    #[schema(example = "Work laptop")]
+    pub device_label: Option<String>,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpCodeDto {
+    #[validate(length(min = 1, max = 20, message = "Code is invalid"))]
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpSetupDto {
+    /// Base32, for the apps where it is typed in
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TotpSetupResponseDto {
+    pub status: Status,
+    pub data: TotpSetupDto,
+}
+
+/// Shown once, only their hashes are stored
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponseDto {
+    pub status: Status,
+    pub recovery_codes: Vec<String>,
+}
@@ -1,19 +1,49 @@
+pub mod addresses;
+pub mod api_keys;
+pub mod cart;
+pub mod ledger;
+pub mod mfa;
 pub mod orders;
+pub mod payments;
 pub mod products;
+pub mod sessions;
 pub mod users;
 
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::Validate;
 
+use crate::{
+    error::ErrorMessage,
+    utils::pagination::{Cursor, PageRequest},
+};
+
+/// Unknown parameters are refused, e.g. `page` which was replaced by `cursor`
 #[derive(Serialize, Deserialize, Validate, ToSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
 pub struct RequestQueryDto {
-    #[validate(range(min = 1, message = "Page can only be 1 or more"))]
-    #[schema(example = 1)]
-    pub page: Option<usize>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
 
     #[validate(range(min = 1, max = 50, message = "limit can only be between 1 and 50"))]
     #[schema(example = 10)]
     pub limit: Option<usize>,
+
+    /// Also return the total number of items
+    pub with_total: Option<bool>,
+}
+
+impl RequestQueryDto {
+    pub fn page_request(&self) -> Result<PageRequest, ErrorMessage> {
+        let after = match &self.cursor {
+            Some(cursor) => Some(Cursor::decode(cursor).ok_or(ErrorMessage::InvalidCursor)?),
+            None => None,
+        };
+
+        Ok(PageRequest {
+            after,
+            limit: self.limit.unwrap_or(10),
+            with_total: self.with_total.unwrap_or(false),
+        })
+    }
 }
@@ -1,4 +1,10 @@
-use crate::{utils::models::Order, utils::status::Status};
+use crate::{
+    dtos::RequestQueryDto,
+    error::ErrorMessage,
+    utils::models::{Order, OrderCancellation, OrderItem, OrderShipment, OrderStatus, SaleSearch},
+    utils::pagination::PageRequest,
+    utils::status::Status,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -18,14 +24,92 @@ pub struct CreateOrderDto {
     pub products_number: i32,
 }
 
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelOrderDto {
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Reason must be between 1 and 500 characters"
+    ))]
+    #[schema(example = "Ordered the wrong size")]
+    pub reason: String,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShipOrderDto {
+    #[validate(length(
+        min = 1,
+        max = 255,
+        message = "Tracking number must be between 1 and 255 characters"
+    ))]
+    #[schema(example = "1Z999AA10123456784")]
+    pub tracking_number: String,
+}
+
+/// Filters of `/users/me/sales`, then the pagination of `RequestQueryDto`: a single
+/// struct, as unknown parameters are refused
+#[derive(Validate, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SalesQueryDto {
+    pub status: Option<OrderStatus>,
+
+    /// orders created at or after this date
+    #[schema(example = "2025-01-01T00:00:00Z")]
+    pub from: Option<DateTime<Utc>>,
+
+    /// orders created before this date
+    #[schema(example = "2025-02-01T00:00:00Z")]
+    pub to: Option<DateTime<Utc>>,
+
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+
+    #[validate(range(min = 1, max = 50, message = "limit can only be between 1 and 50"))]
+    #[schema(example = 10)]
+    pub limit: Option<usize>,
+
+    /// Also return the total number of items
+    pub with_total: Option<bool>,
+}
+
+impl SalesQueryDto {
+    pub fn to_search(&self) -> SaleSearch {
+        SaleSearch {
+            status: self.status,
+            created_after: self.from,
+            created_before: self.to,
+        }
+    }
+
+    pub fn page_request(&self) -> Result<PageRequest, ErrorMessage> {
+        RequestQueryDto {
+            cursor: self.cursor.clone(),
+            limit: self.limit,
+            with_total: self.with_total,
+        }
+        .page_request()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderDto {
     pub id: uuid::Uuid,
     pub user_id: uuid::Uuid,
     pub order_details_id: Option<Uuid>,
-    pub products_number: i32,
-    pub product_id: uuid::Uuid,
+    /// as it was when ordered, even if the address was edited or deleted since
+    pub delivery_address: Option<DeliveryAddressDto>,
+    /// only set for single product orders, see `OrderItemDto`
+    pub products_number: Option<i32>,
+    pub product_id: Option<Uuid>,
+    /// price and name of the product when ordered, only set for single product orders
+    pub unit_price_in_cents: Option<i64>,
+    pub product_name: Option<String>,
+    /// what validating the order charges
+    pub total_in_cents: i64,
+    pub status: OrderStatus,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -39,6 +123,164 @@ impl OrderDto {
             product_id: order.product_id,
             products_number: order.products_number,
             order_details_id: order.order_details_id,
+            delivery_address: DeliveryAddressDto::from(order),
+            unit_price_in_cents: order.unit_price_in_cents,
+            product_name: order.product_name.clone(),
+            total_in_cents: order.total_in_cents,
+            status: order.status,
+
+            created_at: order.created_at,
+            updated_at: order.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderCancellationDto {
+    pub order_id: Uuid,
+    pub cancelled_by: Option<Uuid>,
+    /// `cancelled` by the buyer or `refunded` by the seller
+    pub status: OrderStatus,
+    pub reason: String,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderCancellationDto {
+    pub fn from(cancellation: &OrderCancellation) -> Self {
+        OrderCancellationDto {
+            order_id: cancellation.order_id,
+            cancelled_by: cancellation.cancelled_by,
+            status: cancellation.status,
+            reason: cancellation.reason.clone(),
+
+            created_at: cancellation.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderShipmentDto {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub shipped_by: Option<Uuid>,
+    pub tracking_number: String,
+    pub shipped_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl OrderShipmentDto {
+    pub fn from(shipment: &OrderShipment) -> Self {
+        OrderShipmentDto {
+            id: shipment.id,
+            order_id: shipment.order_id,
+            shipped_by: shipment.shipped_by,
+            tracking_number: shipment.tracking_number.clone(),
+            shipped_at: shipment.shipped_at,
+            delivered_at: shipment.delivered_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderItemDto {
+    pub id: uuid::Uuid,
+    pub product_id: uuid::Uuid,
+    pub products_number: i32,
+    /// price and name of the product when ordered
+    pub unit_price_in_cents: i64,
+    pub product_name: String,
+    /// see the shipments of the order, `None` until its seller ships it
+    pub shipment_id: Option<Uuid>,
+    pub refunded_at: Option<DateTime<Utc>>,
+    pub refund_reason: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderItemDto {
+    pub fn from(item: &OrderItem) -> Self {
+        OrderItemDto {
+            id: item.id,
+            product_id: item.product_id,
+            products_number: item.products_number,
+            unit_price_in_cents: item.unit_price_in_cents,
+            product_name: item.product_name.clone(),
+            shipment_id: item.shipment_id,
+            refunded_at: item.refunded_at,
+            refund_reason: item.refund_reason.clone(),
+
+            created_at: item.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderWithItemsDto {
+    pub order: OrderDto,
+    pub items: Vec<OrderItemDto>,
+}
+
+impl OrderWithItemsDto {
+    pub fn from(order: &Order, items: &[OrderItem]) -> Self {
+        OrderWithItemsDto {
+            order: OrderDto::from(order),
+            items: items.iter().map(OrderItemDto::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryAddressDto {
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+}
+
+impl DeliveryAddressDto {
+    /// `None` for the orders without an address
+    pub fn from(order: &Order) -> Option<Self> {
+        Some(DeliveryAddressDto {
+            street: order.delivery_street.clone()?,
+            city: order.delivery_city.clone()?,
+            postal_code: order.delivery_postal_code.clone()?,
+            country: order.delivery_country.clone()?,
+        })
+    }
+}
+
+/// An order seen by one of its sellers: only their items, and where to ship them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SaleDto {
+    pub order_id: uuid::Uuid,
+    pub items: Vec<OrderItemDto>,
+    /// of the seller's items only, before the platform commission
+    pub total_in_cents: i64,
+    pub delivery_address: Option<DeliveryAddressDto>,
+    pub status: OrderStatus,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SaleDto {
+    pub fn from(order: &Order, items: &[OrderItem]) -> Self {
+        SaleDto {
+            order_id: order.id,
+            items: items.iter().map(OrderItemDto::from).collect(),
+            total_in_cents: items
+                .iter()
+                .map(|item| item.unit_price_in_cents * i64::from(item.products_number))
+                .sum(),
+            delivery_address: DeliveryAddressDto::from(order),
+            status: order.status,
 
             created_at: order.created_at,
             updated_at: order.updated_at,
@@ -52,7 +294,7 @@ impl OrderDto {
 pub struct FilterOrderDto {
     pub id: uuid::Uuid,
     pub user_id: uuid::Uuid,
-    pub product_id: uuid::Uuid,
+    pub product_id: Option<Uuid>,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -85,11 +327,43 @@ pub struct FilterOrderResponseDto {
     pub data: FilterOrderDto,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrderCancellationResponseDto {
+    pub status: Status,
+    pub data: OrderCancellationDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrderShipmentListResponseDto {
+    pub status: Status,
+    pub data: Vec<OrderShipmentDto>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrderWithItemsResponseDto {
+    pub status: Status,
+    pub data: OrderWithItemsDto,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct OrderListResponseDto {
     pub status: Status,
     pub data: Vec<OrderDto>,
     pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SaleListResponseDto {
+    pub status: Status,
+    pub data: Vec<SaleDto>,
+    pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
 }
 
 #[allow(dead_code)]
@@ -98,4 +372,7 @@ pub struct FilterOrderListResponseDto {
     pub status: Status,
     pub data: Vec<FilterOrderDto>,
     pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
 }
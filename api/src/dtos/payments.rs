@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::utils::{
+    models::{PaymentIntent, PaymentIntentStatus},
+    status::Status,
+};
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePaymentIntentDto {
+    // 1m cents -> 10k dollars
+    #[validate(range(
+        min = 1,
+        max = 1_000_000,
+        message = "amountInCents must be between 1 and 1000000"
+    ))]
+    #[schema(example = 10000)]
+    pub amount_in_cents: i64,
+}
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmPaymentIntentDto {
+    /// Payment method of the gateway, with the mock gateway `mock_card_declined` is always declined
+    #[validate(length(
+        min = 1,
+        max = 255,
+        message = "paymentMethod must be 1 to 255 characters long"
+    ))]
+    #[schema(example = "mock_card_visa")]
+    pub payment_method: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentIntentDto {
+    pub id: Uuid,
+    pub amount_in_cents: i64,
+    pub status: PaymentIntentStatus,
+
+    pub updated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PaymentIntentDto {
+    pub fn from(intent: &PaymentIntent) -> Self {
+        PaymentIntentDto {
+            id: intent.id,
+            amount_in_cents: intent.amount_in_cents,
+            status: intent.status,
+
+            updated_at: intent.updated_at,
+            created_at: intent.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PaymentIntentResponseDto {
+    pub status: Status,
+    pub data: PaymentIntentDto,
+}
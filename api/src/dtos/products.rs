@@ -1,7 +1,13 @@
-use crate::{utils::models::Product, utils::status::Status};
+use crate::{
+    error::ErrorMessage,
+    utils::models::{Product, ProductSearch, ProductSort},
+    utils::pagination::{PageRequest, SearchCursor},
+    utils::status::Status,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use uuid::Uuid;
 use validator::Validate;
 
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
@@ -23,6 +29,94 @@ pub struct CreateProductDto {
     pub price_in_cents: i64,
 }
 
+/// Only the given fields are modified
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifyProductDto {
+    #[validate(length(min = 1, max = 100, message = "Name is required"))]
+    #[schema(example = "Smartphone")]
+    pub name: Option<String>,
+
+    #[validate(length(max = 1000, message = "Description is too long"))]
+    #[schema(example = "A high-quality smartphone")]
+    pub description: Option<String>,
+
+    #[validate(range(min = 0, max = 999, message = "Invalid number in stock"))]
+    #[schema(example = 10)]
+    pub number_in_stock: Option<i32>,
+
+    #[validate(range(min = 0, message = "Prices can not be negative"))]
+    #[schema(example = 25000)]
+    pub price_in_cents: Option<i64>,
+}
+
+#[derive(Validate, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SearchProductsQueryDto {
+    /// full-text query over the name and the description
+    #[validate(length(
+        min = 1,
+        max = 200,
+        message = "q can only be between 1 and 200 characters"
+    ))]
+    #[schema(example = "black jacket")]
+    pub q: Option<String>,
+
+    #[validate(range(min = 0, message = "Prices can not be negative"))]
+    pub min_price: Option<i64>,
+
+    #[validate(range(min = 0, message = "Prices can not be negative"))]
+    pub max_price: Option<i64>,
+
+    pub in_stock: Option<bool>,
+
+    pub seller_id: Option<Uuid>,
+
+    pub sort: Option<ProductSort>,
+
+    /// `next_cursor` of the previous page, with the same sort
+    pub cursor: Option<String>,
+
+    #[validate(range(min = 1, max = 50, message = "limit can only be between 1 and 50"))]
+    #[schema(example = 10)]
+    pub limit: Option<usize>,
+
+    /// Also return the total number of items
+    pub with_total: Option<bool>,
+}
+
+impl SearchProductsQueryDto {
+    pub fn to_search(&self) -> ProductSearch {
+        ProductSearch {
+            text: self.q.clone(),
+            min_price_in_cents: self.min_price,
+            max_price_in_cents: self.max_price,
+            in_stock: self.in_stock.unwrap_or(false),
+            seller_id: self.seller_id,
+            sort: self.sort.unwrap_or_default(),
+        }
+    }
+
+    pub fn page_request(
+        &self,
+        search: &ProductSearch,
+    ) -> Result<PageRequest<SearchCursor>, ErrorMessage> {
+        let after = match &self.cursor {
+            Some(cursor) => Some(
+                SearchCursor::decode(cursor, search.applied_sort())
+                    .ok_or(ErrorMessage::InvalidCursor)?,
+            ),
+            None => None,
+        };
+
+        Ok(PageRequest {
+            after,
+            limit: self.limit.unwrap_or(10),
+            with_total: self.with_total.unwrap_or(false),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ProductDto {
@@ -104,6 +198,9 @@ pub struct ProductListResponseDto {
     pub status: Status,
     pub data: Vec<ProductDto>,
     pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -111,4 +208,7 @@ pub struct FilterProductListResponseDto {
     pub status: Status,
     pub data: Vec<FilterProductDto>,
     pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
 }
@@ -0,0 +1,44 @@
+use crate::{utils::models::Session, utils::status::Status};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDto {
+    pub id: Uuid,
+    pub device_label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// The session of the token used for this request
+    pub current: bool,
+
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+}
+
+impl SessionDto {
+    pub fn from(session: &Session, current_session_id: &Uuid) -> Self {
+        SessionDto {
+            id: session.id,
+            device_label: session.device_label.clone(),
+            user_agent: session.user_agent.clone(),
+            ip_address: session.ip_address.clone(),
+            current: &session.id == current_session_id,
+
+            created_at: session.created_at,
+            last_used_at: session.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionListResponseDto {
+    pub status: Status,
+    pub data: Vec<SessionDto>,
+    pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
+}
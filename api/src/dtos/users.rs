@@ -1,5 +1,5 @@
 use crate::{
-    utils::models::User,
+    utils::models::{User, UserRole},
     utils::status::{validate_password, Status},
 };
 use chrono::{DateTime, Utc};
@@ -29,6 +29,86 @@ pub struct RegisterUserDto {
     pub password_confirm: String,
 }
 
+/// Only the given fields are modified
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifyUserDto {
+    #[validate(length(min = 1, max = 100, message = "Name is required"))]
+    pub name: Option<String>,
+
+    #[validate(
+        length(min = 1, max = 255, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    #[schema(example = "user@example.com")]
+    pub email: Option<String>,
+
+    #[validate(url(message = "Photo URL is invalid"), length(max = 255))]
+    #[schema(example = "https://example.com/me.png")]
+    pub photo_url: Option<String>,
+
+    /// Required to change the email
+    #[schema(example = "password123")]
+    pub current_password: Option<String>,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordDto {
+    #[validate(custom(function = "validate_password"))]
+    #[schema(example = "password123")]
+    pub old_password: String,
+
+    #[validate(custom(function = "validate_password"))]
+    #[schema(example = "password456")]
+    pub new_password: String,
+
+    #[validate(
+        custom(function = "validate_password"),
+        must_match(other = "new_password", message = "Passwords do not match")
+    )]
+    #[schema(example = "password456")]
+    pub new_password_confirm: String,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyEmailDto {
+    /// From the link of the verification mail
+    #[validate(length(min = 1, message = "Token is required"))]
+    pub token: String,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgotPasswordDto {
+    #[validate(
+        length(min = 1, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    #[schema(example = "user@example.com")]
+    pub email: String,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordDto {
+    /// From the link of the password reset mail
+    #[validate(length(min = 1, message = "Token is required"))]
+    pub token: String,
+
+    #[validate(custom(function = "validate_password"))]
+    #[schema(example = "password456")]
+    pub new_password: String,
+
+    #[validate(
+        custom(function = "validate_password"),
+        must_match(other = "new_password", message = "Passwords do not match")
+    )]
+    #[schema(example = "password456")]
+    pub new_password_confirm: String,
+}
+
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginUserDto {
@@ -42,6 +122,14 @@ pub struct LoginUserDto {
     #[validate(custom(function = "validate_password"))]
     #[schema(example = "password123")]
     pub password: String,
+
+    /// Name shown in the list of sessions
+    #[validate(length(
+        max = 100,
+        message = "Device label can not be longer than 100 characters"
+    ))]
+    #[schema(example = "Work laptop")]
+    pub device_label: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
@@ -49,6 +137,7 @@ pub struct LoginUserDto {
 pub struct FilterForeignUserDto {
     pub name: String,
     pub email: String,
+    pub photo_url: Option<String>,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -59,6 +148,7 @@ impl FilterForeignUserDto {
         FilterForeignUserDto {
             email: user.email.clone(),
             name: user.name.clone(),
+            photo_url: user.photo_url.clone(),
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -79,7 +169,11 @@ pub struct FilterUserDto {
     pub id: String,
     pub name: String,
     pub email: String,
+    pub photo_url: Option<String>,
     pub sold_in_cents: i64,
+    pub role: UserRole,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub email_verified_at: Option<DateTime<Utc>>,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -91,7 +185,11 @@ impl FilterUserDto {
             id: user.id.to_string(),
             email: user.email.clone(),
             name: user.name.clone(),
+            photo_url: user.photo_url.clone(),
             sold_in_cents: user.sold_in_cents,
+            role: user.role,
+            suspended_at: user.suspended_at,
+            email_verified_at: user.email_verified_at,
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -115,6 +213,9 @@ pub struct UserListResponseDto {
     pub status: Status,
     pub data: Vec<FilterForeignUserDto>,
     pub results: usize,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -123,12 +224,3 @@ pub struct LoginResponseDto {
     pub data: FilterUserDto,
     pub token: String,
 }
-
-#[derive(Validate, Debug, Serialize, Deserialize, ToSchema)]
-// #[serde(rename_all = "camelCase")]
-pub struct AddSoldDto {
-    // 1m cents -> 10k dollars
-    #[validate(range(min = 1, max = 1_000_000, message = "Invalid field soldToAdd"))]
-    #[schema(example = 10000)]
-    pub sold_to_add: i64,
-}
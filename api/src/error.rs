@@ -1,6 +1,6 @@
 use std::fmt::{self, Display};
 
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{http::header, HttpResponse, ResponseError};
 use bcrypt::BcryptError;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -29,26 +29,56 @@ pub struct Response {
 #[derive(Debug)]
 pub enum ErrorMessage {
     EmptyPassword,
+    CurrentPasswordRequired,
     PasswordTooLong(usize),
     PasswordTooShort(usize),
+    PasswordMissingCharacter(&'static str),
+    PasswordTooCommon,
     HashingError,
     InvalidHashFormat,
     InvalidToken,
     ServerError,
     WrongCredentials,
+    WrongPassword,
     EmailExist,
     UserNoLongerExist,
     UserNotFound,
     ProductNoLongerExist,
     ProductOutOfStock,
+    ProductChanged,
     ProductNotFound,
     NotEnoughProducts(i32),
     OrderNoLongerExist,
     OrderNotFound,
+    InvalidOrderStatus,
+    EmptyCart,
+    CartItemNotFound,
+    AddressNotFound,
+    InvalidCursor,
+    SessionNotFound,
+    InvalidApiKey,
+    ApiKeyNotAllowed,
+    MissingApiKeyScope(&'static str),
+    ApiKeyNotFound,
+    InvalidIdempotencyKey,
+    IdempotencyKeyReused,
+    IdempotencyKeyInProgress,
+    PaymentIntentNotFound,
+    InvalidPaymentIntentStatus,
+    PaymentRejected,
+    InvalidWebhook,
     TokenNotProvided,
     SoldTooLow,
     RefreshTokenNotProvided,
     PermissionDenied,
+    AccountSuspended,
+    EmailNotVerified,
+    InvalidUserToken,
+    InvalidMfaCode,
+    TotpAlreadyEnabled,
+    TotpNotSetUp,
+    TooManyRequests,
+    AccountLocked,
     AutoBuying,
 }
 
@@ -63,10 +93,14 @@ impl ErrorMessage {
         match self {
             ErrorMessage::ServerError => "Server Error. Please try again later".to_string(),
             ErrorMessage::WrongCredentials => "Email or password is wrong".to_string(),
+            ErrorMessage::WrongPassword => "Current password is wrong".to_string(),
             ErrorMessage::EmailExist => "An User with this email already exists".to_string(),
             ErrorMessage::UserNoLongerExist => "User no longer exists".to_string(),
             ErrorMessage::ProductNoLongerExist => "Product no longer exists".to_string(),
             ErrorMessage::EmptyPassword => "Password cannot be empty".to_string(),
+            ErrorMessage::CurrentPasswordRequired => {
+                "Current password is required to change the email".to_string()
+            }
             ErrorMessage::HashingError => "Error encountered while hashing password".to_string(),
             ErrorMessage::InvalidHashFormat => "Invalid password hash format".to_string(),
             ErrorMessage::PasswordTooLong(max_length) => {
@@ -75,6 +109,12 @@ impl ErrorMessage {
             ErrorMessage::PasswordTooShort(max_length) => {
                 format!("Password must not be less than {max_length} characters")
             }
+            ErrorMessage::PasswordMissingCharacter(class) => {
+                format!("Password must contain at least one {class}")
+            }
+            ErrorMessage::PasswordTooCommon => {
+                "Password is too common, please choose another one".to_string()
+            }
             ErrorMessage::InvalidToken => "Authentication token is invalid or expired".to_string(),
             ErrorMessage::TokenNotProvided => {
                 "You are not logged in, please provide token".to_string()
@@ -85,10 +125,56 @@ impl ErrorMessage {
             ErrorMessage::PermissionDenied => {
                 "You are not allowed to perform this action".to_string()
             }
+            ErrorMessage::AccountSuspended => "This account is suspended".to_string(),
+            ErrorMessage::EmailNotVerified => "Verify your email before logging in".to_string(),
+            ErrorMessage::InvalidUserToken => "This link is invalid or has expired".to_string(),
+            ErrorMessage::InvalidMfaCode => "The code is invalid".to_string(),
+            ErrorMessage::TotpAlreadyEnabled => {
+                "Two-factor authentication is already enabled".to_string()
+            }
+            ErrorMessage::TotpNotSetUp => "Two-factor authentication is not set up".to_string(),
+            ErrorMessage::TooManyRequests => "Too many requests, try again later".to_string(),
+            ErrorMessage::AccountLocked => {
+                "Too many failed login attempts, try again later".to_string()
+            }
             ErrorMessage::ProductNotFound => "Product not found".to_string(),
+            ErrorMessage::ProductChanged => {
+                "A product changed since the order was placed, please order it again".to_string()
+            }
             ErrorMessage::UserNotFound => "User not found".to_string(),
             ErrorMessage::OrderNotFound => "Order not found".to_string(),
             ErrorMessage::OrderNoLongerExist => "Order no longer exists".to_string(),
+            ErrorMessage::InvalidOrderStatus => {
+                "This action is not allowed in the current order status".to_string()
+            }
+            ErrorMessage::AddressNotFound => "Address not found".to_string(),
+            ErrorMessage::EmptyCart => "Your cart is empty".to_string(),
+            ErrorMessage::InvalidCursor => "Pagination cursor is invalid".to_string(),
+            ErrorMessage::SessionNotFound => "Session not found".to_string(),
+            ErrorMessage::InvalidApiKey => "API key is invalid or revoked".to_string(),
+            ErrorMessage::ApiKeyNotAllowed => {
+                "API keys can not be used on this route, please log in".to_string()
+            }
+            ErrorMessage::MissingApiKeyScope(scope) => {
+                format!("API key does not have the {scope} scope")
+            }
+            ErrorMessage::ApiKeyNotFound => "API key not found".to_string(),
+            ErrorMessage::InvalidIdempotencyKey => {
+                "Idempotency-Key must be 1 to 255 characters long".to_string()
+            }
+            ErrorMessage::IdempotencyKeyReused => {
+                "This Idempotency-Key was already used for another request".to_string()
+            }
+            ErrorMessage::IdempotencyKeyInProgress => {
+                "A request with this Idempotency-Key is still in progress".to_string()
+            }
+            ErrorMessage::PaymentIntentNotFound => "Payment not found".to_string(),
+            ErrorMessage::InvalidPaymentIntentStatus => {
+                "This payment is already confirmed".to_string()
+            }
+            ErrorMessage::PaymentRejected => "The payment was rejected".to_string(),
+            ErrorMessage::InvalidWebhook => "Invalid webhook signature or event".to_string(),
+            ErrorMessage::CartItemNotFound => "This product is not in your cart".to_string(),
             ErrorMessage::NotEnoughProducts(stock) if stock > &0 => {
                 format!("Only {stock} products remaining")
             }
@@ -116,6 +202,8 @@ impl From<BcryptError> for ErrorMessage {
 pub struct HttpError {
     pub message: String,
     pub status: u16,
+    /// Seconds to wait before retrying, sent as `Retry-After`
+    pub retry_after: Option<u64>,
 }
 
 impl<T> From<HttpError> for Result<T, HttpError> {
@@ -132,6 +220,22 @@ impl From<sqlx::Error> for HttpError {
 
                 if message == "auto-buying" {
                     HttpError::bad_request(ErrorMessage::AutoBuying)
+                } else if db_err.is_unique_violation()
+                    && db_err.constraint() == Some("users_email_key")
+                {
+                    HttpError::conflict(ErrorMessage::EmailExist)
+                } else if message == "foreign-order-details" {
+                    HttpError::not_found(ErrorMessage::AddressNotFound)
+                } else if message == "invalid-order-status-transition" {
+                    HttpError::conflict(ErrorMessage::InvalidOrderStatus)
+                } else if db_err.is_check_violation()
+                    && db_err.constraint() == Some("users_sold_in_cents_check")
+                {
+                    HttpError::payment_required(ErrorMessage::SoldTooLow)
+                } else if db_err.is_check_violation()
+                    && db_err.constraint() == Some("products_number_in_stock_check")
+                {
+                    HttpError::conflict(ErrorMessage::ProductOutOfStock)
                 } else {
                     eprintln!(
                         "Warning: unknown database error: {message} -> convert it to server error"
@@ -150,6 +254,7 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status,
+            retry_after: None,
         }
     }
 
@@ -157,6 +262,7 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status: 500,
+            retry_after: None,
         }
     }
 
@@ -164,6 +270,7 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status: 400,
+            retry_after: None,
         }
     }
 
@@ -171,6 +278,7 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status: 409,
+            retry_after: None,
         }
     }
 
@@ -178,6 +286,15 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status: 401,
+            retry_after: None,
+        }
+    }
+
+    pub fn forbidden(message: impl Display) -> Self {
+        HttpError {
+            message: message.to_string(),
+            status: 403,
+            retry_after: None,
         }
     }
 
@@ -185,6 +302,7 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status: 404,
+            retry_after: None,
         }
     }
 
@@ -192,6 +310,15 @@ impl HttpError {
         HttpError {
             message: message.to_string(),
             status: 402,
+            retry_after: None,
+        }
+    }
+
+    pub fn too_many_requests(message: impl Display, retry_after_seconds: u64) -> Self {
+        HttpError {
+            message: message.to_string(),
+            status: 429,
+            retry_after: Some(retry_after_seconds),
         }
     }
 
@@ -212,6 +339,11 @@ impl HttpError {
                 message: self.message,
             }),
 
+            403 => HttpResponse::Forbidden().json(Response {
+                status: Status::Failure,
+                message: self.message,
+            }),
+
             404 => HttpResponse::NotFound().json(Response {
                 status: Status::Failure,
                 message: self.message,
@@ -222,6 +354,16 @@ impl HttpError {
                 message: self.message,
             }),
 
+            429 => HttpResponse::TooManyRequests()
+                .insert_header((
+                    header::RETRY_AFTER,
+                    self.retry_after.unwrap_or(1).to_string(),
+                ))
+                .json(Response {
+                    status: Status::Failure,
+                    message: self.message,
+                }),
+
             500 => HttpResponse::InternalServerError().json(Response {
                 status: Status::Error,
                 message: self.message,
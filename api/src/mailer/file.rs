@@ -0,0 +1,37 @@
+use std::{fs, path::PathBuf};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::{Mail, MailError, Mailer};
+
+/// Writes the mails to a directory instead of sending them, for local development
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str) -> Result<Self, MailError> {
+        fs::create_dir_all(dir).map_err(|err| MailError(format!("{dir}: {err}")))?;
+
+        Ok(Self { dir: dir.into() })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let path = self.dir.join(format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4()
+        ));
+        let content = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            mail.to, mail.subject, mail.body
+        );
+
+        fs::write(&path, content).map_err(|err| MailError(format!("{}: {err}", path.display())))
+    }
+}
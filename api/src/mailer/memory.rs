@@ -0,0 +1,37 @@
+#![cfg(test)]
+
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{Mail, MailError, Mailer};
+
+/// Keeps the sent mails so that the tests can read the links
+#[derive(Default)]
+pub struct MemoryMailer {
+    mails: Mutex<Vec<Mail>>,
+}
+
+impl MemoryMailer {
+    pub fn mails(&self) -> Vec<Mail> {
+        self.mails.lock().unwrap().clone()
+    }
+
+    /// The token of the link of the last mail sent to `to`
+    pub fn last_token(&self, to: &str) -> Option<String> {
+        let mails = self.mails.lock().unwrap();
+        let mail = mails.iter().rev().find(|mail| mail.to == to)?;
+        let (_, token) = mail.body.split_once("token=")?;
+
+        token.split_whitespace().next().map(str::to_string)
+    }
+}
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        self.mails.lock().unwrap().push(mail);
+
+        Ok(())
+    }
+}
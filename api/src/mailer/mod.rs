@@ -0,0 +1,67 @@
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+
+use crate::utils::config::{Config, MailerConfig};
+
+pub mod file;
+pub mod memory;
+pub mod smtp;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mail {
+    pub fn email_verification(to: &str, app_url: &str, token: &str) -> Self {
+        Self {
+            to: to.to_string(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Welcome!\n\nOpen this link to verify your email:\n{app_url}/verify-email?token={token}\n"
+            ),
+        }
+    }
+
+    pub fn password_reset(to: &str, app_url: &str, token: &str) -> Self {
+        Self {
+            to: to.to_string(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Open this link to choose a new password:\n{app_url}/reset-password?token={token}\n\nIgnore this mail if you did not ask for it.\n"
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+impl Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to send the mail: {}", self.0)
+    }
+}
+
+impl std::error::Error for MailError {}
+
+/// Shared by the handlers as `web::Data<dyn Mailer>`
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+pub fn from_config(config: &Config) -> Result<Arc<dyn Mailer>, MailError> {
+    let mailer: Arc<dyn Mailer> = match &config.mailer {
+        MailerConfig::Smtp { url } => Arc::new(smtp::SmtpMailer::new(url, &config.mail_from)?),
+        MailerConfig::File { dir } => Arc::new(file::FileMailer::new(dir)?),
+    };
+
+    Ok(mailer)
+}
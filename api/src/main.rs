@@ -7,16 +7,26 @@ mod database;
 mod docs;
 mod dtos;
 mod error;
+mod mailer;
 mod middleware;
+mod payment;
+mod rate_limit;
 mod routes;
 mod utils;
 
+use std::sync::Arc;
+
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpResponse, HttpServer, Result};
 use database::{init::init_database, psql::DBClient};
 use docs::ApiDoc;
+use payment::{mock::MockGateway, PaymentGateway};
+use rate_limit::{memory::MemoryStore, RateLimitStore};
 use sqlx::postgres::PgPoolOptions;
-use utils::{config::Config, AppState};
+use utils::{
+    config::{Config, PaymentGatewayConfig},
+    AppState,
+};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -32,6 +42,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
     let config = Config::init();
+    let mailer = web::Data::from(mailer::from_config(&config)?);
+    // shared by the workers, the limits are per process
+    let rate_limit_store =
+        web::Data::from(Arc::new(MemoryStore::default()) as Arc<dyn RateLimitStore>);
 
     init_database(&config.database_url).await?;
 
@@ -43,6 +57,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .await?,
     );
 
+    let payment_gateway = match &config.payment_gateway {
+        PaymentGatewayConfig::Mock { webhook_secret } => {
+            eprintln!(
+                "Warning: the mock payment gateway accepts any card, do not use it in production"
+            );
+
+            let gateway = Arc::new(MockGateway::new(webhook_secret));
+            routes::payments::handle_mock_webhooks(
+                gateway.clone(),
+                web::Data::new(AppState {
+                    db_client: db_client.clone(),
+                    env: config.clone(),
+                }),
+            );
+
+            web::Data::from(gateway as Arc<dyn PaymentGateway>)
+        }
+    };
+
     // // creating redis connection pool
     // let redis_pool = deadpool_redis::Config::from_url(&config.redis_url)
     //     .create_pool(Some(Runtime::Tokio1))?;
@@ -69,6 +102,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         App::new()
             .app_data(app_data)
+            .app_data(mailer.clone())
+            .app_data(rate_limit_store.clone())
+            .app_data(payment_gateway.clone())
             .configure(routes::config)
             .service(
                 SwaggerUi::new("/docs/{_:.*}")
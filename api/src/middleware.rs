@@ -5,24 +5,59 @@ use std::{
 };
 
 use actix_web::{
-    dev::{Service, ServiceRequest, ServiceResponse, Transform},
-    error::{ErrorInternalServerError, ErrorUnauthorized},
-    web, FromRequest, HttpMessage,
+    body::{self, BoxBody},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::{
+        ErrorBadRequest, ErrorConflict, ErrorForbidden, ErrorInternalServerError,
+        ErrorUnauthorized, ErrorUnprocessableEntity,
+    },
+    http::{header, StatusCode},
+    web, FromRequest, HttpMessage, HttpResponse,
 };
+use chrono::{Duration, Utc};
 use futures_util::{
     future::{ready, LocalBoxFuture, Ready},
     FutureExt,
 };
+use ring::digest;
+use uuid::Uuid;
 
 use crate::{
-    database::UserExtractor,
+    database::{ApiKeyExtractor, IdempotencyKeyExtractor, SessionExtractor, UserExtractor},
     error::{ErrorMessage, ErrorResponse, HttpError},
-    utils::{self, models::User, token::extract_token_from, AppState},
+    rate_limit::{RateLimit, RateLimitStore},
+    utils::{
+        self,
+        models::{ApiKey, ApiKeyScope, IdempotencyKey, Session, User, UserRole},
+        token::{extract_api_key_from, extract_token_from},
+        AppState,
+    },
 };
 
 // LocalBoxFuture<'static, Result<ServiceResponse<actix_web::body::BoxBody>, actix_web::Error>>
 
-pub struct Authenticated(User);
+/// How the request was authenticated
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Session(Session),
+    ApiKey(ApiKey),
+}
+
+/// Set by `RequireScope` once the API key is checked, the others routes refuse API keys
+#[derive(Clone, Copy)]
+struct ScopeChecked;
+
+pub struct Authenticated(User, Credential);
+
+impl Authenticated {
+    /// Session of the token used for the request, `None` for an API key
+    pub fn session(&self) -> Option<&Session> {
+        match &self.1 {
+            Credential::Session(session) => Some(session),
+            Credential::ApiKey(_) => None,
+        }
+    }
+}
 
 impl FromRequest for Authenticated {
     type Error = actix_web::Error;
@@ -32,11 +67,19 @@ impl FromRequest for Authenticated {
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        let value = req.extensions().get::<User>().cloned();
+        let user = req.extensions().get::<User>().cloned();
+        let credential = req.extensions().get::<Credential>().cloned();
+        let scope_checked = req.extensions().get::<ScopeChecked>().is_some();
 
-        let result = match value {
-            Some(user) => Ok(Authenticated(user)),
-            None => Err(ErrorInternalServerError(ErrorResponse {
+        let result = match (user, credential) {
+            (Some(_), Some(Credential::ApiKey(_))) if !scope_checked => {
+                Err(ErrorForbidden(ErrorResponse {
+                    status: "fail".to_string(),
+                    message: ErrorMessage::ApiKeyNotAllowed.to_string(),
+                }))
+            }
+            (Some(user), Some(credential)) => Ok(Authenticated(user, credential)),
+            _ => Err(ErrorInternalServerError(ErrorResponse {
                 status: "fail".to_string(),
                 message: ErrorMessage::InvalidToken.to_string(),
             })),
@@ -75,6 +118,10 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(api_key) = extract_api_key_from(req.request()) {
+            return self.call_with_api_key(req, api_key);
+        }
+
         let token = match extract_token_from(req.request()) {
             Ok(token) => token,
             Err(err) => return Box::pin(ready(Err(ErrorUnauthorized(err)))),
@@ -82,16 +129,15 @@ where
 
         let app_state = req.app_data::<web::Data<AppState>>().unwrap();
 
-        let jwt_claims =
-            match utils::token::decode_token(&token, app_state.env.secret_key.as_bytes()) {
-                Ok(claims) => claims,
-                Err(e) => {
-                    return Box::pin(ready(Err(ErrorUnauthorized(ErrorResponse {
-                        status: "fail".to_string(),
-                        message: e.message,
-                    }))))
-                }
-            };
+        let jwt_claims = match utils::token::decode_token(&token, &app_state.env.jwt_keys) {
+            Ok(claims) => claims,
+            Err(e) => {
+                return Box::pin(ready(Err(ErrorUnauthorized(ErrorResponse {
+                    status: "fail".to_string(),
+                    message: e.message,
+                }))))
+            }
+        };
 
         let user_id = jwt_claims.sub;
         let jwt_id = jwt_claims.jti;
@@ -100,9 +146,12 @@ where
         let cloned_service = Rc::clone(&self.service);
 
         async move {
-            let user = cloned_app_state
+            let user = get_active_user(&cloned_app_state, &user_id).await?;
+
+            // the token must be the current access token of one of the user's sessions
+            let session = cloned_app_state
                 .db_client
-                .get_user(&user_id)
+                .get_session_by_access_token(&user.id, &jwt_id)
                 .await
                 .map_err(|e| ErrorInternalServerError(HttpError::server_error(e.to_string())))?
                 .ok_or_else(|| {
@@ -112,40 +161,84 @@ where
                     })
                 })?;
 
-            // check if it was the last active token
-            if user.last_token_id.is_none() {
-                return Err(ErrorUnauthorized(ErrorResponse {
-                    status: "fail".to_string(),
-                    message: ErrorMessage::InvalidToken.to_string(),
-                }));
-            }
+            // store user information for next middlewares/endpoint handlers
+            req.extensions_mut().insert::<User>(user);
+            req.extensions_mut()
+                .insert::<Credential>(Credential::Session(session));
 
-            let last_token_id = user.last_token_id.as_ref().unwrap();
+            let res = cloned_service.call(req).await?;
+            Ok(res)
+        }
+        .boxed_local()
+    }
+}
 
-            let is_last_token = bcrypt::verify(jwt_id, last_token_id).map_err(|_| {
-                ErrorInternalServerError(ErrorResponse {
-                    status: "fail".to_string(),
-                    message: ErrorMessage::ServerError.to_string(),
-                })
-            })?;
+impl<S> AuthMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    fn call_with_api_key(
+        &self,
+        req: ServiceRequest,
+        key: String,
+    ) -> LocalBoxFuture<'static, Result<ServiceResponse<actix_web::body::BoxBody>, actix_web::Error>>
+    {
+        let app_state = req.app_data::<web::Data<AppState>>().unwrap().clone();
+        let cloned_service = Rc::clone(&self.service);
 
-            if !is_last_token {
-                return Err(ErrorUnauthorized(ErrorResponse {
-                    status: "fail".to_string(),
-                    message: ErrorMessage::InvalidToken.to_string(),
-                }));
-            }
+        async move {
+            let api_key = app_state
+                .db_client
+                .get_api_key(&key)
+                .await
+                .map_err(|e| ErrorInternalServerError(HttpError::server_error(e.to_string())))?
+                .ok_or_else(|| {
+                    ErrorUnauthorized(ErrorResponse {
+                        status: "fail".to_string(),
+                        message: ErrorMessage::InvalidApiKey.to_string(),
+                    })
+                })?;
+
+            let user = get_active_user(&app_state, &api_key.user_id).await?;
 
-            // store user information for next middlewares/endpoint handlers
             req.extensions_mut().insert::<User>(user);
+            req.extensions_mut()
+                .insert::<Credential>(Credential::ApiKey(api_key));
 
-            let res = cloned_service.call(req).await?;
-            Ok(res)
+            cloned_service.call(req).await
         }
         .boxed_local()
     }
 }
 
+/// The user of a token or of an API key, refused once suspended
+async fn get_active_user(app_state: &AppState, user_id: &Uuid) -> Result<User, actix_web::Error> {
+    let user = app_state
+        .db_client
+        .get_user(user_id)
+        .await
+        .map_err(|e| ErrorInternalServerError(HttpError::server_error(e.to_string())))?
+        .ok_or_else(|| {
+            ErrorUnauthorized(ErrorResponse {
+                status: "fail".to_string(),
+                message: ErrorMessage::InvalidToken.to_string(),
+            })
+        })?;
+
+    if user.is_suspended() {
+        return Err(ErrorForbidden(ErrorResponse {
+            status: "fail".to_string(),
+            message: ErrorMessage::AccountSuspended.to_string(),
+        }));
+    }
+
+    Ok(user)
+}
+
 pub struct RequireAuth;
 
 impl<S> Transform<S, ServiceRequest> for RequireAuth
@@ -169,6 +262,428 @@ where
     }
 }
 
+pub struct RoleMiddleware<S> {
+    service: Rc<S>,
+    roles: &'static [UserRole],
+}
+
+impl<S> Service<ServiceRequest> for RoleMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, actix_web::Error>>;
+
+    fn poll_ready(&self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // the role of the database, not the one of the token which can be outdated
+        let role = req.extensions().get::<User>().map(|user| user.role);
+        let with_api_key = matches!(
+            req.extensions().get::<Credential>(),
+            Some(Credential::ApiKey(_))
+        );
+
+        match role {
+            // the routes of a role are for people, not for the jobs of their API keys
+            Some(_) if with_api_key => Box::pin(ready(Err(ErrorForbidden(ErrorResponse {
+                status: "fail".to_string(),
+                message: ErrorMessage::ApiKeyNotAllowed.to_string(),
+            })))),
+            Some(role) if self.roles.contains(&role) => self.service.call(req).boxed_local(),
+            Some(_) => Box::pin(ready(Err(ErrorForbidden(ErrorResponse {
+                status: "fail".to_string(),
+                message: ErrorMessage::PermissionDenied.to_string(),
+            })))),
+            // `RequireAuth` did not run before
+            None => Box::pin(ready(Err(ErrorInternalServerError(
+                HttpError::server_error(ErrorMessage::ServerError),
+            )))),
+        }
+    }
+}
+
+/// Only lets through the users with one of these roles, must be wrapped by `RequireAuth`:
+/// `.wrap(RequireRole(&[UserRole::Admin])).wrap(RequireAuth)`
+pub struct RequireRole(pub &'static [UserRole]);
+
+impl<S> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RoleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RoleMiddleware {
+            service: Rc::new(service),
+            roles: self.0,
+        }))
+    }
+}
+
+pub struct ScopeMiddleware<S> {
+    service: Rc<S>,
+    scope: ApiKeyScope,
+}
+
+impl<S> Service<ServiceRequest> for ScopeMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, actix_web::Error>>;
+
+    fn poll_ready(&self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let allowed = match req.extensions().get::<Credential>() {
+            Some(Credential::ApiKey(api_key)) => Some(api_key.has_scope(self.scope)),
+            Some(Credential::Session(_)) => Some(true),
+            None => None,
+        };
+
+        match allowed {
+            Some(true) => {
+                req.extensions_mut().insert(ScopeChecked);
+                self.service.call(req).boxed_local()
+            }
+            Some(false) => Box::pin(ready(Err(ErrorForbidden(ErrorResponse {
+                status: "fail".to_string(),
+                message: ErrorMessage::MissingApiKeyScope(self.scope.as_str()).to_string(),
+            })))),
+            // `RequireAuth` did not run before
+            None => Box::pin(ready(Err(ErrorInternalServerError(
+                HttpError::server_error(ErrorMessage::ServerError),
+            )))),
+        }
+    }
+}
+
+/// Lets the API keys with this scope use the route, the sessions always can.
+/// Must be wrapped by `RequireAuth`:
+/// `#[get("", wrap = "RequireScope(ApiKeyScope::ProductsRead)", wrap = "RequireAuth")]`
+pub struct RequireScope(pub ApiKeyScope);
+
+impl<S> Transform<S, ServiceRequest> for RequireScope
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = ScopeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ScopeMiddleware {
+            service: Rc::new(service),
+            scope: self.0,
+        }))
+    }
+}
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const IDEMPOTENCY_KEY_MAX_LENGTH: usize = 255;
+/// Set on the responses replayed from a previous request
+const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, actix_web::Error>>;
+
+    fn poll_ready(&self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+            return self.service.call(req).boxed_local();
+        };
+
+        let key = match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= IDEMPOTENCY_KEY_MAX_LENGTH => {
+                key.to_string()
+            }
+            _ => {
+                return Box::pin(ready(Err(ErrorBadRequest(ErrorResponse {
+                    status: "fail".to_string(),
+                    message: ErrorMessage::InvalidIdempotencyKey.to_string(),
+                }))))
+            }
+        };
+
+        let user_id = req.extensions().get::<User>().map(|user| user.id);
+        let app_state = req.app_data::<web::Data<AppState>>().cloned();
+
+        let (Some(user_id), Some(app_state)) = (user_id, app_state) else {
+            // `RequireAuth` did not run before
+            return Box::pin(ready(Err(ErrorInternalServerError(
+                HttpError::server_error(ErrorMessage::ServerError),
+            ))));
+        };
+
+        let cloned_service = Rc::clone(&self.service);
+
+        async move {
+            let db_client = &app_state.db_client;
+            let server_error = |err: sqlx::Error| {
+                ErrorInternalServerError(HttpError::server_error(err.to_string()))
+            };
+
+            let body = req.extract::<web::Bytes>().await?;
+            let fingerprint = request_fingerprint(&req, &body);
+            req.set_payload(Payload::from(body));
+
+            let expires_at =
+                Utc::now() + Duration::seconds(app_state.env.idempotency_key_max_seconds);
+            let saved = db_client
+                .save_idempotency_key(&user_id, &key, &fingerprint, expires_at)
+                .await
+                .map_err(server_error)?;
+
+            if saved.is_none() {
+                let stored = db_client
+                    .get_idempotency_key(&user_id, &key)
+                    .await
+                    .map_err(server_error)?;
+
+                return match stored {
+                    Some(stored) if stored.fingerprint != fingerprint => {
+                        Err(ErrorUnprocessableEntity(ErrorResponse {
+                            status: "fail".to_string(),
+                            message: ErrorMessage::IdempotencyKeyReused.to_string(),
+                        }))
+                    }
+                    Some(IdempotencyKey {
+                        response_status: Some(status),
+                        response_content_type,
+                        response_body,
+                        ..
+                    }) => Ok(req.into_response(replayed_response(
+                        status,
+                        response_content_type,
+                        response_body.unwrap_or_default(),
+                    ))),
+                    // the first request is still running, or its key expired in the meantime
+                    _ => Err(ErrorConflict(ErrorResponse {
+                        status: "fail".to_string(),
+                        message: ErrorMessage::IdempotencyKeyInProgress.to_string(),
+                    })),
+                };
+            }
+
+            let response = match cloned_service.call(req).await {
+                Ok(response) if !response.status().is_server_error() => response,
+                // nothing to replay, a retry with the same key runs again
+                result => {
+                    db_client
+                        .delete_idempotency_key(&user_id, &key)
+                        .await
+                        .map_err(server_error)?;
+
+                    return result;
+                }
+            };
+
+            let (req, response) = response.into_parts();
+            let (response, response_body) = response.into_parts();
+            let response_body = body::to_bytes(response_body).await.map_err(|_| {
+                ErrorInternalServerError(HttpError::server_error(ErrorMessage::ServerError))
+            })?;
+
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok());
+
+            // the key stays in progress until it expires, retrying can not run the request twice
+            if let Err(err) = db_client
+                .save_idempotency_response(
+                    &user_id,
+                    &key,
+                    response.status().as_u16() as i16,
+                    content_type,
+                    &response_body,
+                )
+                .await
+            {
+                eprintln!("Warning: failed to save the response of an idempotency key: {err}");
+            }
+
+            Ok(ServiceResponse::new(
+                req,
+                response.set_body(BoxBody::new(response_body)),
+            ))
+        }
+        .boxed_local()
+    }
+}
+
+/// Tells apart the requests reusing a key: same route, same query and same body
+fn request_fingerprint(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(req.method().as_str().as_bytes());
+    context.update(b" ");
+    context.update(
+        req.uri()
+            .path_and_query()
+            .map_or("", |path_and_query| path_and_query.as_str())
+            .as_bytes(),
+    );
+    context.update(b"\n");
+    context.update(body);
+
+    context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn replayed_response(status: i16, content_type: Option<String>, body: Vec<u8>) -> HttpResponse {
+    let mut response = HttpResponse::build(
+        StatusCode::from_u16(status as u16).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+    );
+
+    if let Some(content_type) = content_type {
+        response.insert_header((header::CONTENT_TYPE, content_type));
+    }
+
+    response
+        .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+        .body(body)
+}
+
+/// Makes the retries of a request with the same `Idempotency-Key` header get the response of the
+/// first one instead of running it again. Optional for the clients, must be wrapped by
+/// `RequireAuth`: `#[post("/intents", wrap = "Idempotent", wrap = "RequireAuth")]`
+pub struct Idempotent;
+
+impl<S> Transform<S, ServiceRequest> for Idempotent
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = IdempotencyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limit: RateLimit,
+}
+
+impl<S> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, actix_web::Error>>;
+
+    fn poll_ready(&self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let store = req.app_data::<web::Data<dyn RateLimitStore>>().cloned();
+        let ip = req.peer_addr().map(|addr| addr.ip());
+
+        let (Some(store), Some(ip)) = (store, ip) else {
+            return self.service.call(req).boxed_local();
+        };
+
+        let limit = self.limit;
+        let cloned_service = Rc::clone(&self.service);
+
+        async move {
+            limit.check(store.get_ref(), &format!("ip:{ip}")).await?;
+
+            cloned_service.call(req).await
+        }
+        .boxed_local()
+    }
+}
+
+/// Limits the requests of each IP address on a route, with the `web::Data<dyn RateLimitStore>`
+/// of the app: `#[post("/login", wrap = "LOGIN_RATE_LIMIT")]`
+impl<S> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limit: *self,
+        }))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use actix_web::{
@@ -180,8 +695,9 @@ mod tests {
     use uuid::Uuid;
 
     use crate::{
-        database::{psql::DBClient, UserModifier},
+        database::{psql::DBClient, ApiKeyExtractor, SessionExtractor},
         utils::{
+            models::SessionDevice,
             password,
             test_utils::{self, init_test_users},
             token,
@@ -195,6 +711,31 @@ mod tests {
         HttpResponse::Ok().finish()
     }
 
+    #[get(
+        "/scoped",
+        wrap = "RequireScope(ApiKeyScope::ProductsRead)",
+        wrap = "RequireAuth"
+    )]
+    async fn handler_with_requirescope(user: Authenticated) -> HttpResponse {
+        HttpResponse::Ok().body(user.id.to_string())
+    }
+
+    #[get("/unscoped", wrap = "RequireAuth")]
+    async fn handler_without_requirescope(user: Authenticated) -> HttpResponse {
+        HttpResponse::Ok().body(user.id.to_string())
+    }
+
+    const TEST_RATE_LIMIT: RateLimit = RateLimit {
+        name: "test",
+        max_requests: 2,
+        window_seconds: 60,
+    };
+
+    #[get("/limited", wrap = "TEST_RATE_LIMIT")]
+    async fn handler_with_ratelimit() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
     #[sqlx::test(migrator = "crate::MIGRATOR")]
     async fn auth_middelware_valid_token(pool: Pool<Postgres>) {
         init_test_users(&pool).await;
@@ -213,7 +754,7 @@ mod tests {
         )
         .await;
 
-        let hashed_password = password::hash("password123").unwrap();
+        let hashed_password = password::hash("password123", &config.password_hash).unwrap();
         let user = db_client
             .save_user("John", "john@example.com", &hashed_password)
             .await
@@ -221,12 +762,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &user.id)
+            .save_session(
+                &user.id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token =
-            token::create_token(&user.id, config.secret_key.as_bytes(), 60, &token_id).unwrap();
+        let token = token::create_token(
+            &user.id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let request = test::TestRequest::default()
             .insert_header((
@@ -326,4 +878,123 @@ mod tests {
             }
         }
     }
+
+    #[actix_web::test]
+    async fn rate_limit_middleware_per_ip() {
+        let app = test::init_service(
+            App::new()
+                .app_data(test_utils::test_rate_limit_store())
+                .service(handler_with_ratelimit),
+        )
+        .await;
+
+        let request = |ip: &str| {
+            test::TestRequest::get()
+                .uri("/limited")
+                .peer_addr(format!("{ip}:1234").parse().unwrap())
+                .to_request()
+        };
+
+        for _ in 0..TEST_RATE_LIMIT.max_requests {
+            let response = test::call_service(&app, request("10.0.0.1")).await;
+            assert_eq!(response.status(), http::StatusCode::OK);
+        }
+
+        let Err(err) = test::try_call_service(&app, request("10.0.0.1")).await else {
+            panic!("Service call succeeded, but an error was expected");
+        };
+        let response = err.error_response();
+
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(http::header::RETRY_AFTER).unwrap(),
+            "60"
+        );
+
+        let response = test::call_service(&app, request("10.0.0.2")).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn scope_middleware_with_api_key(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    db_client: db_client.clone(),
+                    env: test_utils::test_config(),
+                }))
+                .service(handler_with_requirescope)
+                .service(handler_without_requirescope),
+        )
+        .await;
+
+        let (key, prefix) = token::generate_api_key();
+        db_client
+            .save_api_key(&user_id, "ci", &key, &prefix, &[ApiKeyScope::ProductsRead])
+            .await
+            .unwrap();
+
+        let (other_key, other_prefix) = token::generate_api_key();
+        db_client
+            .save_api_key(
+                &user_id,
+                "ci",
+                &other_key,
+                &other_prefix,
+                &[ApiKeyScope::OrdersRead],
+            )
+            .await
+            .unwrap();
+
+        for header in [
+            (token::API_KEY_HEADER, key.clone()),
+            (
+                http::header::AUTHORIZATION.as_str(),
+                format!("ApiKey {key}"),
+            ),
+        ] {
+            let request = test::TestRequest::get()
+                .uri("/scoped")
+                .insert_header(header)
+                .to_request();
+            let body = test::call_and_read_body(&app, request).await;
+
+            assert_eq!(body, user_id.to_string());
+        }
+
+        let status_of = |uri: &'static str, key: String| {
+            let app = &app;
+            async move {
+                let request = test::TestRequest::get()
+                    .uri(uri)
+                    .insert_header((token::API_KEY_HEADER, key))
+                    .to_request();
+
+                match test::try_call_service(app, request).await {
+                    Ok(response) => response.status(),
+                    Err(err) => err.error_response().status(),
+                }
+            }
+        };
+
+        // without the scope of the route
+        assert_eq!(
+            status_of("/scoped", other_key).await,
+            http::StatusCode::FORBIDDEN
+        );
+
+        // routes without a scope are only for sessions
+        assert_eq!(
+            status_of("/unscoped", key).await,
+            http::StatusCode::FORBIDDEN
+        );
+
+        assert_eq!(
+            status_of("/scoped", token::generate_api_key().0).await,
+            http::StatusCode::UNAUTHORIZED
+        );
+    }
 }
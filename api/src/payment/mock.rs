@@ -0,0 +1,289 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+use chrono::Utc;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::token;
+
+use super::{GatewayIntent, PaymentError, PaymentEvent, PaymentGateway, PaymentOutcome};
+
+/// `t=<unix timestamp>,v1=<hex HMAC-SHA256 of "<timestamp>.<body>">`
+pub const SIGNATURE_HEADER: &str = "Mock-Signature";
+/// Always declined, the other payment methods always go through
+pub const DECLINED_PAYMENT_METHOD: &str = "mock_card_declined";
+/// Older webhooks are refused, a captured one can not be replayed later
+const SIGNATURE_TOLERANCE_SECONDS: i64 = 5 * 60;
+
+const SUCCEEDED_EVENT: &str = "payment_intent.succeeded";
+const FAILED_EVENT: &str = "payment_intent.failed";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MockEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    intent_id: String,
+}
+
+/// A webhook the gateway would send to `POST /api/payments/webhook`
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub signature: String,
+    pub body: Vec<u8>,
+}
+
+/// Gateway without any provider behind: the intents live in memory and the webhooks are kept
+/// until they are taken with `take_deliveries`
+pub struct MockGateway {
+    key: hmac::Key,
+    /// Created and not confirmed yet
+    pending_intents: Mutex<HashSet<String>>,
+    deliveries: Mutex<Vec<WebhookDelivery>>,
+}
+
+impl MockGateway {
+    pub fn new(webhook_secret: &str) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, webhook_secret.as_bytes()),
+            pending_intents: Mutex::default(),
+            deliveries: Mutex::default(),
+        }
+    }
+
+    /// Oldest first, each webhook is only taken once
+    pub fn take_deliveries(&self) -> Vec<WebhookDelivery> {
+        std::mem::take(&mut *self.deliveries.lock().unwrap())
+    }
+
+    pub fn sign(&self, timestamp: i64, body: &[u8]) -> String {
+        let tag = hmac::sign(&self.key, &signed_payload(timestamp, body));
+
+        format!("t={timestamp},v1={}", to_hex(tag.as_ref()))
+    }
+}
+
+#[async_trait]
+impl PaymentGateway for MockGateway {
+    async fn create_intent(&self, _amount_in_cents: i64) -> Result<GatewayIntent, PaymentError> {
+        let id = format!("mock_pi_{}", token::random_token());
+        self.pending_intents.lock().unwrap().insert(id.clone());
+
+        Ok(GatewayIntent { id })
+    }
+
+    async fn confirm_intent(
+        &self,
+        gateway_intent_id: &str,
+        payment_method: &str,
+    ) -> Result<(), PaymentError> {
+        if !self
+            .pending_intents
+            .lock()
+            .unwrap()
+            .remove(gateway_intent_id)
+        {
+            return Err(PaymentError::Rejected(format!(
+                "no pending intent {gateway_intent_id}"
+            )));
+        }
+
+        let kind = if payment_method == DECLINED_PAYMENT_METHOD {
+            FAILED_EVENT
+        } else {
+            SUCCEEDED_EVENT
+        };
+
+        let body = serde_json::to_vec(&MockEvent {
+            kind: kind.to_string(),
+            intent_id: gateway_intent_id.to_string(),
+        })
+        .map_err(|err| PaymentError::Rejected(err.to_string()))?;
+
+        let signature = self.sign(Utc::now().timestamp(), &body);
+        self.deliveries
+            .lock()
+            .unwrap()
+            .push(WebhookDelivery { signature, body });
+
+        Ok(())
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<PaymentEvent, PaymentError> {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(PaymentError::InvalidWebhook)?;
+
+        let (timestamp, tag) = parse_signature(signature).ok_or(PaymentError::InvalidWebhook)?;
+
+        if (Utc::now().timestamp() - timestamp).abs() > SIGNATURE_TOLERANCE_SECONDS {
+            return Err(PaymentError::InvalidWebhook);
+        }
+
+        // in constant time
+        hmac::verify(&self.key, &signed_payload(timestamp, body), &tag)
+            .map_err(|_| PaymentError::InvalidWebhook)?;
+
+        let event: MockEvent =
+            serde_json::from_slice(body).map_err(|_| PaymentError::InvalidWebhook)?;
+
+        let outcome = match event.kind.as_str() {
+            SUCCEEDED_EVENT => PaymentOutcome::Succeeded,
+            FAILED_EVENT => PaymentOutcome::Failed,
+            _ => return Err(PaymentError::InvalidWebhook),
+        };
+
+        Ok(PaymentEvent {
+            gateway_intent_id: event.intent_id,
+            outcome,
+        })
+    }
+}
+
+fn signed_payload(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{timestamp}.").into_bytes();
+    payload.extend_from_slice(body);
+
+    payload
+}
+
+fn parse_signature(signature: &str) -> Option<(i64, Vec<u8>)> {
+    let mut timestamp = None;
+    let mut tag = None;
+
+    for part in signature.split(',') {
+        match part.trim().split_once('=')? {
+            ("t", value) => timestamp = value.parse().ok(),
+            ("v1", value) => tag = from_hex(value),
+            _ => {}
+        }
+    }
+
+    Some((timestamp?, tag?))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    use super::*;
+
+    fn headers(signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("mock-signature"),
+            HeaderValue::from_str(signature).unwrap(),
+        );
+
+        headers
+    }
+
+    #[actix_web::test]
+    async fn confirm_sends_signed_webhook() {
+        let gateway = MockGateway::new("webhook-secret");
+
+        let succeeded = gateway.create_intent(500).await.unwrap();
+        let declined = gateway.create_intent(500).await.unwrap();
+
+        gateway
+            .confirm_intent(&succeeded.id, "mock_card_visa")
+            .await
+            .unwrap();
+        gateway
+            .confirm_intent(&declined.id, DECLINED_PAYMENT_METHOD)
+            .await
+            .unwrap();
+
+        // confirmed once
+        assert!(gateway
+            .confirm_intent(&succeeded.id, "mock_card_visa")
+            .await
+            .is_err());
+
+        let events = gateway
+            .take_deliveries()
+            .into_iter()
+            .map(|delivery| {
+                gateway
+                    .verify_webhook(&headers(&delivery.signature), &delivery.body)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            events,
+            vec![
+                PaymentEvent {
+                    gateway_intent_id: succeeded.id,
+                    outcome: PaymentOutcome::Succeeded,
+                },
+                PaymentEvent {
+                    gateway_intent_id: declined.id,
+                    outcome: PaymentOutcome::Failed,
+                },
+            ]
+        );
+        assert!(gateway.take_deliveries().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn refuse_invalid_signatures() {
+        let gateway = MockGateway::new("webhook-secret");
+        let intent = gateway.create_intent(500).await.unwrap();
+        gateway
+            .confirm_intent(&intent.id, "mock_card_visa")
+            .await
+            .unwrap();
+
+        let delivery = gateway.take_deliveries().remove(0);
+        let now = Utc::now().timestamp();
+
+        let mut tampered = delivery.body.clone();
+        tampered.extend_from_slice(b" ");
+
+        for (signature, body) in [
+            (delivery.signature.clone(), tampered),
+            (
+                MockGateway::new("other-secret").sign(now, &delivery.body),
+                delivery.body.clone(),
+            ),
+            (
+                gateway.sign(now - SIGNATURE_TOLERANCE_SECONDS - 1, &delivery.body),
+                delivery.body.clone(),
+            ),
+            ("t=1,v1=zz".to_string(), delivery.body.clone()),
+        ] {
+            assert!(matches!(
+                gateway.verify_webhook(&headers(&signature), &body),
+                Err(PaymentError::InvalidWebhook)
+            ));
+        }
+
+        assert!(matches!(
+            gateway.verify_webhook(&HeaderMap::new(), &delivery.body),
+            Err(PaymentError::InvalidWebhook)
+        ));
+    }
+}
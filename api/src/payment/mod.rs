@@ -0,0 +1,67 @@
+use std::fmt::{self, Display};
+
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+
+pub mod mock;
+
+/// The intent on the side of the gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayIntent {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    Succeeded,
+    Failed,
+}
+
+/// What a webhook of the gateway tells once its signature is checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentEvent {
+    pub gateway_intent_id: String,
+    pub outcome: PaymentOutcome,
+}
+
+#[derive(Debug)]
+pub enum PaymentError {
+    /// Refused by the gateway, like an unknown intent
+    Rejected(String),
+    /// Missing or wrong signature, or a body which is not an event
+    InvalidWebhook,
+}
+
+impl Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Rejected(reason) => {
+                write!(f, "payment rejected by the gateway: {reason}")
+            }
+            PaymentError::InvalidWebhook => write!(f, "invalid payment webhook"),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+/// Shared by the handlers as `web::Data<dyn PaymentGateway>`. Nothing is credited from the
+/// responses of the gateway, only from its signed webhooks
+#[async_trait]
+pub trait PaymentGateway: Send + Sync {
+    async fn create_intent(&self, amount_in_cents: i64) -> Result<GatewayIntent, PaymentError>;
+
+    /// Starts charging `payment_method`, the outcome comes later with a webhook
+    async fn confirm_intent(
+        &self,
+        gateway_intent_id: &str,
+        payment_method: &str,
+    ) -> Result<(), PaymentError>;
+
+    /// Checks the signature of a webhook before reading its event
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<PaymentEvent, PaymentError>;
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use super::RateLimitStore;
+
+/// Past this number of keys, the ones of ended windows are dropped
+const MAX_KEYS_BEFORE_CLEANUP: usize = 10_000;
+
+struct Window {
+    started_at: Instant,
+    length: Duration,
+    requests: u32,
+}
+
+/// Fixed windows in the memory of the process, each instance of the API counts on its own
+#[derive(Default)]
+pub struct MemoryStore {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn hit(&self, key: &str, max_requests: u32, window: Duration) -> Option<u64> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() > MAX_KEYS_BEFORE_CLEANUP {
+            windows.retain(|_, window| now.duration_since(window.started_at) < window.length);
+        }
+
+        let current = windows.entry(key.to_string()).or_insert(Window {
+            started_at: now,
+            length: window,
+            requests: 0,
+        });
+
+        if now.duration_since(current.started_at) >= current.length {
+            current.started_at = now;
+            current.length = window;
+            current.requests = 0;
+        }
+
+        current.requests += 1;
+
+        if current.requests <= max_requests {
+            return None;
+        }
+
+        let left = current.length - now.duration_since(current.started_at);
+
+        // rounded up, a client waiting that long is sure to get through
+        Some(left.as_secs() + u64::from(left.subsec_nanos() > 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn limit_each_key() {
+        let store = MemoryStore::default();
+        let window = Duration::from_secs(60);
+
+        for _ in 0..3 {
+            assert_eq!(store.hit("login:ip:1", 3, window).await, None);
+        }
+
+        let retry_after = store.hit("login:ip:1", 3, window).await;
+        assert!(matches!(retry_after, Some(1..=60)));
+
+        assert_eq!(store.hit("login:ip:2", 3, window).await, None);
+    }
+
+    #[actix_web::test]
+    async fn new_window() {
+        let store = MemoryStore::default();
+        let window = Duration::from_millis(50);
+
+        assert_eq!(store.hit("key", 1, window).await, None);
+        assert_eq!(store.hit("key", 1, window).await, Some(1));
+
+        std::thread::sleep(window);
+
+        assert_eq!(store.hit("key", 1, window).await, None);
+    }
+}
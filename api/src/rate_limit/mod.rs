@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::{ErrorMessage, HttpError};
+
+pub mod memory;
+
+/// Counts the requests of each key, shared by the handlers as `web::Data<dyn RateLimitStore>`.
+/// Nothing is limited when the app has none, like in most tests
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a request for `key`, `Some(seconds to wait)` once there were more than
+    /// `max_requests` in the current window
+    async fn hit(&self, key: &str, max_requests: u32, window: Duration) -> Option<u64>;
+}
+
+/// At most `max_requests` per `window_seconds`, for each IP address when wrapping a route
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub name: &'static str,
+    pub max_requests: u32,
+    pub window_seconds: u64,
+}
+
+impl RateLimit {
+    /// 429 with the time to wait, once `subject` went over the limit
+    pub async fn check(&self, store: &dyn RateLimitStore, subject: &str) -> Result<(), HttpError> {
+        let key = format!("{}:{subject}", self.name);
+        let window = Duration::from_secs(self.window_seconds);
+
+        match store.hit(&key, self.max_requests, window).await {
+            Some(retry_after) => Err(HttpError::too_many_requests(
+                ErrorMessage::TooManyRequests,
+                retry_after,
+            )),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,593 @@
+use actix_web::{
+    delete, get, post,
+    web::{self, Path, Query},
+    HttpResponse,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    database::{
+        LedgerExtractor, OrderExtractor, ProductExtractor, SessionExtractor, UserExtractor,
+        UserModifier,
+    },
+    dtos::{
+        ledger::{BalanceDriftDto, BalanceDriftListResponseDto},
+        orders::{OrderDto, OrderListResponseDto},
+        users::{FilterUserDto, UserResponseDto},
+        RequestQueryDto,
+    },
+    error::{ErrorMessage, HttpError},
+    middleware::{RequireAuth, RequireRole},
+    utils::{models::UserRole, status::Status, AppState},
+};
+
+pub(super) fn config(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope("/admin")
+            .wrap(RequireRole(&[UserRole::Admin]))
+            .wrap(RequireAuth)
+            .service(get_all_orders)
+            .service(delete_product)
+            .service(suspend_user)
+            .service(unsuspend_user)
+            .service(get_balance_drifts),
+    );
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/orders",
+    params(
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Number of items per page"),
+        ("with_total" = Option<bool>, Query, description = "Also count all the items")
+    ),
+    responses(
+        (status = 200, description = "Orders of every user retrieved successfully", body = OrderListResponseDto),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "User is not an admin")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+#[get("/orders")]
+async fn get_all_orders(
+    query: Query<RequestQueryDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    query
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let page = query.page_request().map_err(HttpError::bad_request)?;
+
+    let orders = data
+        .db_client
+        .get_all_orders(&page)
+        .await
+        .map_err(HttpError::from)?
+        .map(OrderDto::from);
+
+    Ok(HttpResponse::Ok().json(OrderListResponseDto {
+        status: Status::Success,
+        results: orders.items.len(),
+        data: orders.items,
+        next_cursor: orders.next_cursor,
+        has_more: orders.has_more,
+        total: orders.total,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/products/{product_id}",
+    params(
+        ("product_id" = Uuid, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 204, description = "Product removed successfully"),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "User is not an admin"),
+        (status = 404, description = "Product not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+#[delete("/products/{product_id}")]
+async fn delete_product(
+    product_id: Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    data.db_client
+        .delete_product(&product_id.into_inner())
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::ProductNotFound),
+            err => HttpError::from(err),
+        })?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/suspend",
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User suspended and logged out of every device", body = UserResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "User is not an admin, or tries to suspend an admin"),
+        (status = 404, description = "User not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+#[post("/users/{user_id}/suspend")]
+async fn suspend_user(
+    user_id: Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let user_id = user_id.into_inner();
+
+    let user = data
+        .db_client
+        .get_user(&user_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::UserNotFound))?;
+
+    if user.role == UserRole::Admin {
+        return HttpError::forbidden(ErrorMessage::PermissionDenied).into();
+    }
+
+    let user = data
+        .db_client
+        .modify_user_suspension(&user.id, true)
+        .await
+        .map_err(HttpError::from)?;
+
+    data.db_client
+        .delete_sessions_by_user(&user.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(UserResponseDto {
+        status: Status::Success,
+        data: FilterUserDto::filter_user(&user),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{user_id}/unsuspend",
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User can log in again", body = UserResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "User is not an admin"),
+        (status = 404, description = "User not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+#[post("/users/{user_id}/unsuspend")]
+async fn unsuspend_user(
+    user_id: Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let user = data
+        .db_client
+        .modify_user_suspension(&user_id.into_inner(), false)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::UserNotFound),
+            err => HttpError::from(err),
+        })?;
+
+    Ok(HttpResponse::Ok().json(UserResponseDto {
+        status: Status::Success,
+        data: FilterUserDto::filter_user(&user),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/ledger/drifts",
+    responses(
+        (status = 200, description = "Users whose balance is not the sum of their ledger entries", body = BalanceDriftListResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "User is not an admin")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Admin"
+)]
+#[get("/ledger/drifts")]
+async fn get_balance_drifts(data: web::Data<AppState>) -> Result<HttpResponse, HttpError> {
+    let drifts = data
+        .db_client
+        .get_balance_drifts()
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(BalanceDriftListResponseDto {
+        status: Status::Success,
+        results: drifts.len(),
+        data: drifts.iter().map(BalanceDriftDto::from).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http, test, App};
+    use sqlx::{Pool, Postgres};
+
+    use super::*;
+    use crate::{
+        database::psql::DBClient,
+        utils::{
+            models::SessionDevice,
+            password,
+            test_utils::{
+                init_test_orders, init_test_products, make_admin, test_config, verify_email,
+            },
+            token,
+        },
+    };
+
+    /// Logs in a new user, made admin if asked
+    async fn user_token(pool: &Pool<Postgres>, email: &str, admin: bool) -> (Uuid, String) {
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Admin",
+                email,
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(pool, &user.id).await;
+
+        let role = if admin {
+            make_admin(pool, &user.id).await;
+            UserRole::Admin
+        } else {
+            UserRole::Customer
+        };
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user.id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(&user.id, role, &config.jwt_keys, 60, &token_id).unwrap();
+
+        (user.id, token)
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_all_orders_as_admin(pool: Pool<Postgres>) {
+        init_test_orders(&pool).await;
+        let (_, token) = user_token(&pool, "admin@eapi.com", true).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: DBClient::new(pool),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/admin/orders?with_total=true")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body: OrderListResponseDto = test::read_body_json(response).await;
+
+        assert_eq!(body.results, 3);
+        assert_eq!(body.total, Some(3));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn admin_routes_are_forbidden_to_other_users(pool: Pool<Postgres>) {
+        let (_, token) = user_token(&pool, "customer@eapi.com", false).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: DBClient::new(pool),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/admin/orders")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let err = test::try_call_service(&app, request)
+            .await
+            .expect_err("Customer reached an admin route");
+
+        assert_eq!(
+            err.as_response_error().status_code(),
+            http::StatusCode::FORBIDDEN
+        );
+
+        // and a token is still needed
+        let request = test::TestRequest::get().uri("/admin/orders").to_request();
+
+        let err = test::try_call_service(&app, request)
+            .await
+            .expect_err("Anonymous user reached an admin route");
+
+        assert_eq!(
+            err.as_response_error().status_code(),
+            http::StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_product_as_admin(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let (_, token) = user_token(&pool, "admin@eapi.com", true).await;
+        let db_client = DBClient::new(pool);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/admin/products/{}", data.product_id))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+
+        assert!(db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // already removed
+        let request = test::TestRequest::delete()
+            .uri(&format!("/admin/products/{}", data.product_id))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_ordered_product(pool: Pool<Postgres>) {
+        use crate::utils::pagination::PageRequest;
+
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let (_, token) = user_token(&pool, "admin@eapi.com", true).await;
+        let db_client = DBClient::new(pool);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/admin/products/{}", data.product_id))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+
+        // no longer listed
+        assert!(db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_client
+            .get_all_products(&PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .iter()
+            .all(|product| product.id != data.product_id));
+
+        // but still in the order, for its seller too
+        let items = db_client.get_order_items(&data.order_id).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].product_id, data.product_id);
+
+        let items = db_client
+            .get_order_items_by_seller(&data.order_id, &data2.user_id)
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn suspend_and_unsuspend_user(pool: Pool<Postgres>) {
+        let (_, admin_token) = user_token(&pool, "admin@eapi.com", true).await;
+        let (user_id, user_token) = user_token(&pool, "customer@eapi.com", false).await;
+        let db_client = DBClient::new(pool);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config)
+                .configure(crate::routes::auth::config),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/admin/users/{user_id}/suspend"))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {admin_token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body: UserResponseDto = test::read_body_json(response).await;
+        assert!(body.data.suspended_at.is_some());
+
+        // logged out of every device
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {user_token}")))
+            .to_request();
+
+        assert!(test::try_call_service(&app, request).await.is_err());
+
+        // and can't log in again
+        let request = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(serde_json::json!({
+                "email": "customer@eapi.com",
+                "password": "password",
+            }))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/admin/users/{user_id}/unsuspend"))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {admin_token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let request = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(serde_json::json!({
+                "email": "customer@eapi.com",
+                "password": "password",
+            }))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn suspend_an_admin(pool: Pool<Postgres>) {
+        let (admin_id, admin_token) = user_token(&pool, "admin@eapi.com", true).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: DBClient::new(pool),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/admin/users/{admin_id}/suspend"))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {admin_token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/admin/users/{}/suspend", Uuid::new_v4()))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {admin_token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_balance_drifts_as_admin(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let (_, token) = user_token(&pool, "admin@eapi.com", true).await;
+
+        // balance modified without going through the ledger
+        sqlx::query("UPDATE users SET sold_in_cents = 42 WHERE id = $1")
+            .bind(data.user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: DBClient::new(pool),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/admin/ledger/drifts")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body: BalanceDriftListResponseDto = test::read_body_json(response).await;
+
+        assert_eq!(
+            body.data,
+            vec![BalanceDriftDto {
+                user_id: data.user_id,
+                sold_in_cents: 42,
+                ledger_sum_in_cents: 0,
+            }]
+        );
+    }
+}
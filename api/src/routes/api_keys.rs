@@ -0,0 +1,291 @@
+use actix_web::{
+    delete, get, post,
+    web::{self, Json, Path, Query},
+    HttpResponse,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    database::ApiKeyExtractor,
+    dtos::{
+        api_keys::{ApiKeyCreatedResponseDto, ApiKeyDto, ApiKeyListResponseDto, CreateApiKeyDto},
+        RequestQueryDto,
+    },
+    error::{ErrorMessage, HttpError},
+    middleware::{Authenticated, RequireAuth},
+    utils::{status::Status, token, AppState},
+};
+
+/// Only with a session, an API key can not manage the keys
+pub fn config(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope("/api-keys")
+            .service(create)
+            .service(get_all)
+            .service(delete),
+    );
+}
+
+/* ------------------ */
+/* --- [ ROUTES ] --- */
+/* ------------------ */
+
+#[utoipa::path(
+    post,
+    path = "/api/api-keys",
+    request_body = CreateApiKeyDto,
+    responses(
+        (status = 201, description = "API key created, the key is only shown in this response", body = ApiKeyCreatedResponseDto),
+        (status = 400, description = "Invalid request data"),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "Sent with an API key")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "API Keys"
+)]
+#[post("", wrap = "RequireAuth")]
+async fn create(
+    user: Authenticated,
+    infos: Json<CreateApiKeyDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let mut scopes = infos.scopes.clone();
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    let (key, prefix) = token::generate_api_key();
+
+    let api_key = data
+        .db_client
+        .save_api_key(&user.id, &infos.name, &key, &prefix, &scopes)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Created().json(ApiKeyCreatedResponseDto {
+        status: Status::Success,
+        data: ApiKeyDto::from(&api_key),
+        key,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/api-keys",
+    params(
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Number of items per page"),
+        ("with_total" = Option<bool>, Query, description = "Also count all the items")
+    ),
+    responses(
+        (status = 200, description = "User's API keys retrieved successfully", body = ApiKeyListResponseDto),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "Sent with an API key")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "API Keys"
+)]
+#[get("", wrap = "RequireAuth")]
+async fn get_all(
+    user: Authenticated,
+    query: Query<RequestQueryDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    query
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let page = query.page_request().map_err(HttpError::bad_request)?;
+
+    let api_keys = data
+        .db_client
+        .get_api_keys_by_user(&user.id, &page)
+        .await
+        .map_err(HttpError::from)?
+        .map(ApiKeyDto::from);
+
+    Ok(HttpResponse::Ok().json(ApiKeyListResponseDto {
+        status: Status::Success,
+        results: api_keys.items.len(),
+        data: api_keys.items,
+        next_cursor: api_keys.next_cursor,
+        has_more: api_keys.has_more,
+        total: api_keys.total,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/api-keys/{api_key_id}",
+    params(
+        ("api_key_id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "Sent with an API key"),
+        (status = 404, description = "API key not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "API Keys"
+)]
+#[delete("/{api_key_id}", wrap = "RequireAuth")]
+async fn delete(
+    user: Authenticated,
+    api_key_id: Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    data.db_client
+        .delete_api_key(&user.id, &api_key_id)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::ApiKeyNotFound),
+            err => HttpError::from(err),
+        })?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http, test, App};
+    use serde_json::json;
+    use sqlx::{Pool, Postgres};
+
+    use crate::{
+        database::{psql::DBClient, SessionExtractor},
+        routes::products,
+        utils::{
+            models::{ApiKeyScope, SessionDevice, UserRole},
+            test_utils::{init_test_users, test_config},
+        },
+    };
+
+    use super::*;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn create_use_and_revoke_api_key(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config)
+                .configure(products::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+        let bearer = (http::header::AUTHORIZATION, format!("Bearer {token}"));
+
+        let request = test::TestRequest::post()
+            .uri("/api-keys")
+            .insert_header(bearer.clone())
+            .set_json(json!({
+                "name": "stock sync",
+                "scopes": ["products:read", "products:read"]
+            }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+
+        let created: ApiKeyCreatedResponseDto = test::read_body_json(response).await;
+
+        assert!(created.key.starts_with(&created.data.prefix));
+        assert_eq!(created.data.scopes, vec![ApiKeyScope::ProductsRead]);
+
+        let request = test::TestRequest::get()
+            .uri("/api-keys")
+            .insert_header(bearer.clone())
+            .to_request();
+        let body = test::call_and_read_body(&app, request).await;
+
+        // the key is not shown again
+        assert!(!String::from_utf8_lossy(&body).contains(&created.key));
+
+        let list: ApiKeyListResponseDto = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(list.data, vec![created.data.clone()]);
+
+        let request = test::TestRequest::get()
+            .uri("/products")
+            .insert_header((token::API_KEY_HEADER, created.key.clone()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        // a key can not create other keys
+        let request = test::TestRequest::get()
+            .uri("/api-keys")
+            .insert_header((token::API_KEY_HEADER, created.key.clone()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/api-keys/{}", created.data.id))
+            .insert_header(bearer.clone())
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+
+        let request = test::TestRequest::get()
+            .uri("/products")
+            .insert_header((token::API_KEY_HEADER, created.key))
+            .to_request();
+        let Err(err) = test::try_call_service(&app, request).await else {
+            panic!("Service call succeeded, but an error was expected");
+        };
+
+        assert_eq!(
+            err.error_response().status(),
+            http::StatusCode::UNAUTHORIZED
+        );
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/api-keys/{}", created.data.id))
+            .insert_header(bearer)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    }
+}
@@ -1,7 +1,7 @@
 use actix_web::{
-    cookie::{time::Duration, CookieBuilder, SameSite},
-    post,
-    web::{self, Json},
+    cookie::{time::Duration, Cookie, CookieBuilder, SameSite},
+    delete, get, http, post,
+    web::{self, Json, Path, Query},
     HttpRequest, HttpResponse,
 };
 use chrono::Utc;
@@ -11,40 +11,132 @@ use validator::Validate;
 
 use crate::{
     database::{
-        UserExtractor, UserModifier, UserUtils, transaction::{DBTransaction, ITransaction}
-    }, dtos::users::{
-        FilterUserDto, LoginResponseDto, LoginUserDto, RegisterUserDto, UserResponseDto,
-    }, error::{ErrorMessage, HttpError}, middleware::{Authenticated, RequireAuth}, utils::{
-        AppState, constants, password, status::Status, token::{self, extract_token_from}
-    }
+        transaction::{DBTransaction, ITransaction},
+        ApiKeyExtractor, SessionExtractor, TotpExtractor, UserExtractor, UserModifier,
+        UserTokenExtractor,
+    },
+    dtos::{
+        mfa::{
+            MfaChallengeResponseDto, MfaLoginDto, RecoveryCodesResponseDto, TotpCodeDto,
+            TotpSetupDto, TotpSetupResponseDto,
+        },
+        sessions::{SessionDto, SessionListResponseDto},
+        users::{
+            FilterUserDto, ForgotPasswordDto, LoginResponseDto, LoginUserDto, RegisterUserDto,
+            ResetPasswordDto, UserResponseDto, VerifyEmailDto,
+        },
+        RequestQueryDto,
+    },
+    error::{ErrorMessage, HttpError},
+    mailer::{Mail, Mailer},
+    middleware::{Authenticated, RequireAuth},
+    rate_limit::{RateLimit, RateLimitStore},
+    utils::{
+        constants,
+        models::{SessionDevice, User, UserTokenKind, UserTotp},
+        password,
+        status::Status,
+        token::{self, extract_token_from},
+        totp, AppState,
+    },
 };
 
 pub(super) fn config(config: &mut web::ServiceConfig) {
     config.service(
         web::scope("/auth")
             .service(login)
+            .service(login_mfa)
             .service(register)
             .service(logout)
-            .service(refresh),
+            .service(logout_all)
+            .service(refresh)
+            .service(get_sessions)
+            .service(delete_session)
+            .service(verify_email)
+            .service(forgot_password)
+            .service(reset_password)
+            .service(setup_totp)
+            .service(confirm_totp)
+            .service(disable_totp),
     );
 }
 
+/// Per IP address, on top of the lockout of the accounts
+const LOGIN_RATE_LIMIT: RateLimit = RateLimit {
+    name: "login",
+    max_requests: 20,
+    window_seconds: 60,
+};
+
+/// Per email, for the attempts spread over many IP addresses
+const LOGIN_EMAIL_RATE_LIMIT: RateLimit = RateLimit {
+    name: "login",
+    max_requests: 10,
+    window_seconds: 5 * 60,
+};
+
+/// Per IP address, on the routes which send mails or check their tokens
+const MAIL_RATE_LIMIT: RateLimit = RateLimit {
+    name: "mail",
+    max_requests: 5,
+    window_seconds: 15 * 60,
+};
+
+/// The lockout doubles with each failed login, up to this
+const MAX_LOCKOUT_SECONDS: i64 = 24 * 60 * 60;
+
+/// User agents are cut to the size of `sessions.user_agent`
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+fn session_device(request: &HttpRequest, label: Option<String>) -> SessionDevice {
+    SessionDevice {
+        label,
+        user_agent: request
+            .headers()
+            .get(http::header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect()),
+        ip_address: request.peer_addr().map(|address| address.ip().to_string()),
+    }
+}
+
+fn refresh_token_cookie<'c>(refresh_token: String, max_age_seconds: i64) -> Cookie<'c> {
+    CookieBuilder::new(constants::REFRESH_TOKEN.to_string(), refresh_token)
+        .path("/")
+        .max_age(Duration::seconds(max_age_seconds))
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .finish()
+}
+
+fn removal_cookie<'c>() -> Cookie<'c> {
+    CookieBuilder::new(constants::REFRESH_TOKEN.clone(), "")
+        .path("/")
+        .max_age(Duration::seconds(0))
+        .http_only(true)
+        .finish()
+}
+
 #[utoipa::path(
     post,
     path = "/api/auth/login",
     request_body = LoginUserDto,
     responses(
         (status = 200, description = "Login successful", body = LoginResponseDto),
+        (status = 202, description = "Password checked, the TOTP code is to be sent to /api/auth/login/mfa", body = MfaChallengeResponseDto),
         (status = 400, description = "Invalid request data"),
-        (status = 401, description = "Invalid credentials"),
-        (status = 404, description = "User not found")
+        (status = 401, description = "Invalid credentials, unknown emails included"),
+        (status = 403, description = "Account suspended or email not verified"),
+        (status = 429, description = "Too many attempts, or account locked after failed logins")
     ),
     tag = "Authentication"
 )]
-#[post("/login")]
+#[post("/login", wrap = "LOGIN_RATE_LIMIT")]
 async fn login(
+    request: HttpRequest,
     infos: Json<LoginUserDto>,
     data: web::Data<AppState>,
+    rate_limit_store: Option<web::Data<dyn RateLimitStore>>,
 ) -> Result<HttpResponse, HttpError> {
     infos
         .validate()
@@ -53,15 +145,32 @@ async fn login(
     // searching user
     let infos = infos.into_inner();
 
+    if let Some(store) = rate_limit_store {
+        let email = format!("email:{}", infos.email.to_lowercase());
+        LOGIN_EMAIL_RATE_LIMIT
+            .check(store.get_ref(), &email)
+            .await?;
+    }
+
+    // before the password, so that a locked account can not be guessed. Counted per email,
+    // registered or not, so that a lockout does not tell if an account exists
+    check_lockout(&data, &infos.email).await?;
+
     let user = data
         .db_client
-        .get_user_by_email(infos.email)
+        .get_user_by_email(infos.email.clone())
         .await
-        .map_err(|err| HttpError::server_error(err.to_string()))?
-        .ok_or_else(|| HttpError::not_found(ErrorMessage::UserNotFound))?;
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+
+    // unknown emails are checked against a dummy hash, to answer in the same time and the same way
+    let hashed_password = user
+        .as_ref()
+        .map_or(password::dummy_hash(&data.env.password_hash), |user| {
+            user.password.as_str()
+        });
 
     // check passwords
-    let password_matches = match password::compare(&infos.password, &user.password) {
+    let password_matches = match password::compare(&infos.password, hashed_password) {
         Ok(result) => result,
         Err(ErrorMessage::HashingError) => {
             return HttpError::server_error(ErrorMessage::HashingError).into()
@@ -69,42 +178,243 @@ async fn login(
         Err(err) => return HttpError::unauthorized(err).into(),
     };
 
-    if !password_matches {
-        return HttpError::unauthorized(ErrorMessage::WrongCredentials).into();
+    let user = match user {
+        Some(user) if password_matches => user,
+        _ => {
+            record_failed_login(&data, &infos.email).await?;
+            return HttpError::unauthorized(ErrorMessage::WrongCredentials).into();
+        }
+    };
+
+    if password::needs_rehash(&user.password, &data.env.password_hash) {
+        rehash_password(&data, &user, &infos.password).await;
+    }
+
+    if user.is_suspended() {
+        return HttpError::forbidden(ErrorMessage::AccountSuspended).into();
+    }
+
+    if !user.is_email_verified() {
+        return HttpError::forbidden(ErrorMessage::EmailNotVerified).into();
+    }
+
+    let totp = data
+        .db_client
+        .get_user_totp(&user.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    // the attempts are only cleared with the code, when there is one
+    if totp.is_some_and(|totp| totp.is_enabled()) {
+        let mfa_token =
+            token::create_mfa_token(&user.id, &data.env.jwt_keys, data.env.mfa_token_max_seconds)
+                .map_err(|_| HttpError::server_error(ErrorMessage::HashingError))?;
+
+        return Ok(HttpResponse::Accepted().json(MfaChallengeResponseDto {
+            status: Status::Success,
+            mfa_token,
+        }));
+    }
+
+    data.db_client
+        .reset_failed_logins(&user.email)
+        .await
+        .map_err(HttpError::from)?;
+
+    start_session(&request, &data, &user, infos.device_label).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/login/mfa",
+    request_body = MfaLoginDto,
+    responses(
+        (status = 200, description = "Login successful", body = LoginResponseDto),
+        (status = 400, description = "Invalid request data"),
+        (status = 401, description = "Invalid or expired MFA token, or invalid code"),
+        (status = 403, description = "Account suspended"),
+        (status = 429, description = "Too many attempts, or account locked after failed logins")
+    ),
+    tag = "Authentication"
+)]
+#[post("/login/mfa", wrap = "LOGIN_RATE_LIMIT")]
+async fn login_mfa(
+    request: HttpRequest,
+    infos: Json<MfaLoginDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let infos = infos.into_inner();
+    let claims = token::decode_mfa_token(&infos.mfa_token, &data.env.jwt_keys)?;
+
+    let user = data
+        .db_client
+        .get_user(&claims.sub)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+
+    if user.is_suspended() {
+        return HttpError::forbidden(ErrorMessage::AccountSuspended).into();
+    }
+
+    check_lockout(&data, &user.email).await?;
+
+    let totp = data
+        .db_client
+        .get_user_totp(&user.id)
+        .await
+        .map_err(HttpError::from)?
+        .filter(UserTotp::is_enabled)
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
+
+    if let Err(err) = check_mfa_code(&data, &totp, &infos.code).await {
+        if err.status == 401 {
+            record_failed_login(&data, &user.email).await?;
+        }
+        return Err(err);
+    }
+
+    data.db_client
+        .reset_failed_logins(&user.email)
+        .await
+        .map_err(HttpError::from)?;
+
+    start_session(&request, &data, &user, infos.device_label).await
+}
+
+/// Replaces an outdated hash while the password is known, the login goes on if it fails
+async fn rehash_password(data: &AppState, user: &User, password: &str) {
+    let result = match password::hash(password, &data.env.password_hash) {
+        Ok(hashed_password) => data
+            .db_client
+            .modify_user_password(&user.id, &hashed_password)
+            .await
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("Warning: password rehash of user {}: {err}", user.id);
+    }
+}
+
+async fn check_lockout(data: &AppState, email: &str) -> Result<(), HttpError> {
+    let attempts = data
+        .db_client
+        .get_login_attempts(email)
+        .await
+        .map_err(HttpError::from)?;
+
+    match attempts.and_then(|attempts| attempts.lockout_seconds_left()) {
+        Some(seconds) => HttpError::too_many_requests(ErrorMessage::AccountLocked, seconds).into(),
+        None => Ok(()),
+    }
+}
+
+async fn record_failed_login(data: &AppState, email: &str) -> Result<(), HttpError> {
+    data.db_client
+        .record_failed_login(
+            email,
+            data.env.login_max_failed_attempts,
+            data.env.login_lockout_seconds,
+            MAX_LOCKOUT_SECONDS,
+        )
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(())
+}
+
+/// A TOTP code, which is refused once a code of its step was accepted,
+/// or an unused recovery code, which is then used up
+async fn check_mfa_code(data: &AppState, totp: &UserTotp, code: &str) -> Result<(), HttpError> {
+    if let Some(step) = totp::verify(&totp.secret, code, Utc::now().timestamp()) {
+        let accepted = data
+            .db_client
+            .use_totp_step(&totp.user_id, step)
+            .await
+            .map_err(HttpError::from)?;
+
+        return if accepted {
+            Ok(())
+        } else {
+            HttpError::unauthorized(ErrorMessage::InvalidMfaCode).into()
+        };
+    }
+
+    let recovery_codes = data
+        .db_client
+        .get_unused_recovery_codes(&totp.user_id)
+        .await
+        .map_err(HttpError::from)?;
+
+    let code = code.trim().to_lowercase();
+
+    for recovery_code in recovery_codes {
+        if !password::compare(&code, &recovery_code.code_hash).unwrap_or(false) {
+            continue;
+        }
+
+        let used = data
+            .db_client
+            .use_recovery_code(&recovery_code.id)
+            .await
+            .map_err(HttpError::from)?;
+
+        if used {
+            return Ok(());
+        }
     }
 
-    // building response
-    let token_id = Uuid::new_v4();
+    HttpError::unauthorized(ErrorMessage::InvalidMfaCode).into()
+}
+
+/// Logs the user in on a new device: access token in the body, refresh token in a cookie
+async fn start_session(
+    request: &HttpRequest,
+    data: &AppState,
+    user: &User,
+    device_label: Option<String>,
+) -> Result<HttpResponse, HttpError> {
+    let access_token_id = Uuid::new_v4();
+    let refresh_token_id = Uuid::new_v4();
 
     let access_token = token::create_token(
         &user.id,
-        data.env.secret_key.as_bytes(),
+        user.role,
+        &data.env.jwt_keys,
         data.env.access_token_max_seconds,
-        &token_id,
+        &access_token_id,
     )
     .map_err(|_| HttpError::server_error(ErrorMessage::HashingError))?;
 
     let refresh_token = token::create_token(
         &user.id,
-        data.env.secret_key.as_bytes(),
+        user.role,
+        &data.env.jwt_keys,
         data.env.refresh_token_max_seconds,
-        &Uuid::nil(),
+        &refresh_token_id,
     )
     .map_err(|_| HttpError::server_error(ErrorMessage::HashingError))?;
 
+    // a new session, the ones of the other devices are kept
     data.db_client
-        .modify_user_last_token_id(Some(&token_id), &user.id)
+        .save_session(
+            &user.id,
+            &access_token_id,
+            &refresh_token_id,
+            &session_device(request, device_label),
+        )
         .await
         .map_err(HttpError::from)?;
 
-    let filtered_user = FilterUserDto::filter_user(&user);
+    let filtered_user = FilterUserDto::filter_user(user);
 
-    let cookie = CookieBuilder::new(constants::REFRESH_TOKEN.to_string(), refresh_token)
-        .path("/")
-        .max_age(Duration::seconds(data.env.refresh_token_max_seconds))
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .finish();
+    let cookie = refresh_token_cookie(refresh_token, data.env.refresh_token_max_seconds);
 
     Ok(HttpResponse::Ok().cookie(cookie).json(LoginResponseDto {
         status: Status::Success,
@@ -118,7 +428,7 @@ async fn login(
     path = "/api/auth/register",
     request_body = RegisterUserDto,
     responses(
-        (status = 201, description = "User registered successfully", body = UserResponseDto),
+        (status = 201, description = "User registered successfully, a verification link is mailed", body = UserResponseDto),
         (status = 400, description = "Invalid request data"),
         (status = 409, description = "Email already exists")
     ),
@@ -128,6 +438,7 @@ async fn login(
 async fn register(
     infos: Json<RegisterUserDto>,
     data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
 ) -> Result<HttpResponse, HttpError> {
     let infos = infos.into_inner();
 
@@ -135,13 +446,13 @@ async fn register(
         .validate()
         .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-    let hashed_password = match password::hash(infos.password) {
-        Ok(hash) => hash,
-        Err(ErrorMessage::HashingError) => {
-            return HttpError::server_error(ErrorMessage::HashingError).into()
-        }
-        Err(err) => return HttpError::server_error(err).into(),
-    };
+    data.env
+        .password_policy
+        .check(&infos.password)
+        .map_err(HttpError::bad_request)?;
+
+    let hashed_password = password::hash(&infos.password, &data.env.password_hash)
+        .map_err(HttpError::server_error)?;
 
     let result = data
         .db_client
@@ -149,10 +460,14 @@ async fn register(
         .await;
 
     match result {
-        Ok(user) => Ok(HttpResponse::Created().json(UserResponseDto {
-            status: Status::Success,
-            data: FilterUserDto::filter_user(&user),
-        })),
+        Ok(user) => {
+            send_email_verification(&data, mailer.get_ref(), &user).await;
+
+            Ok(HttpResponse::Created().json(UserResponseDto {
+                status: Status::Success,
+                data: FilterUserDto::filter_user(&user),
+            }))
+        }
 
         Err(sqlx::Error::Database(db_err)) => {
             if db_err.is_unique_violation() {
@@ -168,196 +483,1163 @@ async fn register(
 
 #[utoipa::path(
     post,
-    path = "/api/auth/logout",
+    path = "/api/auth/totp/setup",
     responses(
-        (status = 200, description = "Logout successful", body = Response),
-        (status = 401, description = "Already logged out")
+        (status = 200, description = "New pending secret, to be confirmed with a first code", body = TotpSetupResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 409, description = "Two-factor authentication already enabled")
     ),
     security(
         ("bearer_auth" = [])
     ),
     tag = "Authentication"
 )]
-#[post("/logout", wrap = "RequireAuth")]
-async fn logout(
+#[post("/totp/setup", wrap = "RequireAuth")]
+async fn setup_totp(
     user: Authenticated,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
-    let cookie = CookieBuilder::new(constants::REFRESH_TOKEN.clone(), "")
-        .path("/")
-        .max_age(Duration::seconds(0))
-        .http_only(true)
-        .finish();
+    let secret = totp::generate_secret();
 
     data.db_client
-        .modify_user_last_token_id(None, &user.id)
+        .save_user_totp_secret(&user.id, &secret)
         .await
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?;
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::conflict(ErrorMessage::TotpAlreadyEnabled))?;
 
-    Ok(
-        HttpResponse::Ok()
-            .cookie(cookie)
-            .json(json!({"status": Status::Success}))
-    )
+    Ok(HttpResponse::Ok().json(TotpSetupResponseDto {
+        status: Status::Success,
+        data: TotpSetupDto {
+            secret: totp::encode_secret(&secret),
+            otpauth_uri: totp::otpauth_uri(&secret, &data.env.totp_issuer, &user.email),
+        },
+    }))
 }
 
 #[utoipa::path(
     post,
-    path = "/api/auth/refresh",
+    path = "/api/auth/totp/confirm",
+    request_body = TotpCodeDto,
     responses(
-        (status = 200, description = "Token refreshed successfully", body = Response),
-        (status = 401, description = "Invalid or expired refresh token")
+        (status = 200, description = "Two-factor authentication enabled, the recovery codes are only shown now", body = RecoveryCodesResponseDto),
+        (status = 400, description = "Invalid request data, or not set up"),
+        (status = 401, description = "User not logged in, or invalid code"),
+        (status = 409, description = "Two-factor authentication already enabled")
     ),
     security(
         ("bearer_auth" = [])
     ),
     tag = "Authentication"
 )]
-#[post("/refresh")]
-async fn refresh(
-    request: HttpRequest,
+#[post("/totp/confirm", wrap = "RequireAuth")]
+async fn confirm_totp(
+    user: Authenticated,
+    infos: Json<TotpCodeDto>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
-    // find refresh-token
-    let refresh_token = request
-        .cookie(&constants::REFRESH_TOKEN)
-        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::RefreshTokenNotProvided))?;
-
-    let refresh_token_claims =
-        token::decode_token(refresh_token.value(), data.env.secret_key.as_bytes())?;
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-    // check if the refresh token is still valid
-    // + 30 is to not be too strict
-    let now = Utc::now().timestamp() as usize;
+    let totp = data
+        .db_client
+        .get_user_totp(&user.id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::TotpNotSetUp))?;
 
-    if refresh_token_claims.exp < now + 30 {
-        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
+    if totp.is_enabled() {
+        return HttpError::conflict(ErrorMessage::TotpAlreadyEnabled).into();
     }
 
-    let refresh_user_id = refresh_token_claims.sub;
+    let step = totp::verify(&totp.secret, &infos.code, Utc::now().timestamp())
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidMfaCode))?;
 
-    // verify deprecated token
+    let recovery_codes = totp::generate_recovery_codes();
+    let recovery_code_hashes = recovery_codes
+        .iter()
+        .map(|code| password::hash(code, &data.env.password_hash))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(HttpError::server_error)?;
 
-    let deprecated_token = match extract_token_from(&request) {
-        Ok(token) => token,
-        Err(err) => return HttpError::unauthorized(err.message).into(),
-    };
+    data.db_client
+        .enable_user_totp(&user.id, step, &recovery_code_hashes)
+        .await
+        .map_err(|err| match err {
+            // enabled by a concurrent request
+            sqlx::Error::RowNotFound => HttpError::conflict(ErrorMessage::TotpAlreadyEnabled),
+            err => HttpError::from(err),
+        })?;
 
-    let deprecated_claims = token::decode_token(deprecated_token, data.env.secret_key.as_bytes())?;
-    let deprecated_user_id = deprecated_claims.sub;
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponseDto {
+        status: Status::Success,
+        recovery_codes,
+    }))
+}
 
-    if deprecated_user_id != refresh_user_id {
-        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
-    }
+#[utoipa::path(
+    post,
+    path = "/api/auth/totp/disable",
+    request_body = TotpCodeDto,
+    responses(
+        (status = 204, description = "Two-factor authentication disabled"),
+        (status = 400, description = "Invalid request data, or not enabled"),
+        (status = 401, description = "User not logged in, or invalid code")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+#[post("/totp/disable", wrap = "RequireAuth")]
+async fn disable_totp(
+    user: Authenticated,
+    infos: Json<TotpCodeDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-    // check if it was the last active token
-    let is_last_token = data
+    let totp = data
         .db_client
-        .check_is_last_token(&deprecated_claims.jti, &refresh_user_id)
+        .get_user_totp(&user.id)
         .await
-        .map_err(HttpError::from)?;
-
-    if !is_last_token {
-        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
-    }
-
-    // create the new token
-    let new_token_id = Uuid::new_v4();
+        .map_err(HttpError::from)?
+        .filter(UserTotp::is_enabled)
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::TotpNotSetUp))?;
 
-    let new_token = token::create_token(
-        &refresh_user_id,
-        data.env.secret_key.as_bytes(),
-        data.env.access_token_max_seconds,
-        &new_token_id,
-    )
-    .map_err(|_| HttpError::server_error(ErrorMessage::HashingError))?;
+    // a stolen access token is not enough
+    check_mfa_code(&data, &totp, &infos.code).await?;
 
-    // set the new token id as the user's last active token
-    DBTransaction::begin(data.db_client.pool())
-        .await
-        .map_err(HttpError::from)?
-        // .lock_user(&new_token_id).await
-        // 	.map_err(HttpError::from)?
-        .save_user_token_id(&new_token_id, &refresh_user_id)
-        .await
-        .map_err(HttpError::from)?
-        .commit()
+    data.db_client
+        .delete_user_totp(&user.id)
         .await
         .map_err(HttpError::from)?;
 
-    Ok(HttpResponse::Ok().json(json!({
-        "status": Status::Success.to_string(),
-        "token": new_token,
-    })))
+    Ok(HttpResponse::NoContent().finish())
 }
 
-#[cfg(test)]
-mod tests {
-    use actix_web::{
-        http::{self},
-        test, App,
-    };
-    use sqlx::{Pool, Postgres};
-
-    use super::*;
-    use crate::{
-        database::psql::DBClient,
-        utils::{constants::REFRESH_TOKEN, test_utils::test_config},
-    };
-
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn register_valid_user(pool: Pool<Postgres>) {
-        let db_client = DBClient::new(pool);
-        let config = test_config();
+/// Mails a new verification link. Failures are only logged: the account exists anyway,
+/// and resetting the password also verifies the email
+pub(super) async fn send_email_verification(data: &AppState, mailer: &dyn Mailer, user: &User) {
+    let token = token::random_token();
+    let expires_at =
+        Utc::now() + chrono::Duration::seconds(data.env.email_verification_max_seconds);
 
-        let app = test::init_service(
-            App::new()
-                .app_data(web::Data::new(AppState {
-                    env: config,
-                    db_client,
-                }))
-                .service(super::register),
+    let saved = data
+        .db_client
+        .save_user_token(
+            &user.id,
+            UserTokenKind::EmailVerification,
+            &token,
+            expires_at,
         )
         .await;
 
-        let name = "Ayoub Arab".to_string();
-        let email = "ayarab@gmail.com".to_string();
-        let password = "password".to_string();
+    if let Err(err) = saved {
+        eprintln!(
+            "Warning: email verification token of user {}: {err}",
+            user.id
+        );
+        return;
+    }
 
-        let request = test::TestRequest::post()
-            .uri("/register")
-            .set_json(RegisterUserDto {
-                name: name.clone(),
-                email: email.clone(),
-                password: password.clone(),
-                password_confirm: password.clone(),
+    let mail = Mail::email_verification(&user.email, &data.env.app_url, &token);
+
+    if let Err(err) = mailer.send(mail).await {
+        eprintln!("Warning: email verification of user {}: {err}", user.id);
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify-email",
+    request_body = VerifyEmailDto,
+    responses(
+        (status = 204, description = "Email verified"),
+        (status = 400, description = "Invalid, expired or already used token")
+    ),
+    tag = "Authentication"
+)]
+#[post("/verify-email")]
+async fn verify_email(
+    infos: Json<VerifyEmailDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    data.db_client
+        .verify_user_email(&infos.token)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidUserToken))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/forgot-password",
+    request_body = ForgotPasswordDto,
+    responses(
+        (status = 202, description = "A reset link is mailed if an account uses this email", body = Response),
+        (status = 400, description = "Invalid request data"),
+        (status = 429, description = "Too many requests")
+    ),
+    tag = "Authentication"
+)]
+#[post("/forgot-password", wrap = "MAIL_RATE_LIMIT")]
+async fn forgot_password(
+    infos: Json<ForgotPasswordDto>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let user = data
+        .db_client
+        .get_user_by_email(infos.into_inner().email)
+        .await
+        .map_err(HttpError::from)?;
+
+    // same response either way, so that it does not tell which emails have an account
+    if let Some(user) = user {
+        let token = token::random_token();
+        let expires_at =
+            Utc::now() + chrono::Duration::seconds(data.env.password_reset_max_seconds);
+
+        data.db_client
+            .save_user_token(&user.id, UserTokenKind::PasswordReset, &token, expires_at)
+            .await
+            .map_err(HttpError::from)?;
+
+        let mail = Mail::password_reset(&user.email, &data.env.app_url, &token);
+
+        if let Err(err) = mailer.send(mail).await {
+            eprintln!("Warning: password reset of user {}: {err}", user.id);
+        }
+    }
+
+    Ok(HttpResponse::Accepted().json(json!({"status": Status::Success})))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/reset-password",
+    request_body = ResetPasswordDto,
+    responses(
+        (status = 204, description = "Password changed, every session and API key is revoked"),
+        (status = 400, description = "Invalid request data, or invalid, expired or already used token"),
+        (status = 429, description = "Too many requests")
+    ),
+    tag = "Authentication"
+)]
+#[post("/reset-password", wrap = "MAIL_RATE_LIMIT")]
+async fn reset_password(
+    infos: Json<ResetPasswordDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    data.env
+        .password_policy
+        .check(&infos.new_password)
+        .map_err(HttpError::bad_request)?;
+
+    let hashed_password = password::hash(&infos.new_password, &data.env.password_hash)
+        .map_err(HttpError::server_error)?;
+
+    let user = data
+        .db_client
+        .reset_user_password(&infos.token, &hashed_password)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::bad_request(ErrorMessage::InvalidUserToken))?;
+
+    // whoever knew the old password is logged out, and loses the keys they could have made
+    data.db_client
+        .delete_sessions_by_user(&user.id)
+        .await
+        .map_err(HttpError::from)?;
+    data.db_client
+        .delete_api_keys_by_user(&user.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    responses(
+        (status = 200, description = "Logout successful", body = Response),
+        (status = 401, description = "Already logged out")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+#[post("/logout", wrap = "RequireAuth")]
+async fn logout(user: Authenticated, data: web::Data<AppState>) -> Result<HttpResponse, HttpError> {
+    let Some(session) = user.session() else {
+        return HttpError::forbidden(ErrorMessage::ApiKeyNotAllowed).into();
+    };
+
+    // only this device
+    data.db_client
+        .delete_session(&user.id, &session.id)
+        .await
+        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(removal_cookie())
+        .json(json!({"status": Status::Success})))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout-all",
+    responses(
+        (status = 200, description = "Logged out of every device", body = Response),
+        (status = 401, description = "Already logged out")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+#[post("/logout-all", wrap = "RequireAuth")]
+async fn logout_all(
+    user: Authenticated,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    data.db_client
+        .delete_sessions_by_user(&user.id)
+        .await
+        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(removal_cookie())
+        .json(json!({"status": Status::Success})))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    responses(
+        (status = 200, description = "Token refreshed successfully, the refresh-token cookie is rotated", body = Response),
+        (status = 401, description = "Invalid, expired or reused refresh token"),
+        (status = 403, description = "Account suspended")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+#[post("/refresh")]
+async fn refresh(
+    request: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    // find refresh-token
+    let refresh_token = request
+        .cookie(&constants::REFRESH_TOKEN)
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::RefreshTokenNotProvided))?;
+
+    let refresh_token_claims = token::decode_token(refresh_token.value(), &data.env.jwt_keys)?;
+
+    // check if the refresh token is still valid
+    // + 30 is to not be too strict
+    let now = Utc::now().timestamp() as usize;
+
+    if refresh_token_claims.exp < now + 30 {
+        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
+    }
+
+    let refresh_user_id = refresh_token_claims.sub;
+
+    // verify deprecated token
+
+    let deprecated_token = match extract_token_from(&request) {
+        Ok(token) => token,
+        Err(err) => return HttpError::unauthorized(err.message).into(),
+    };
+
+    let deprecated_claims = token::decode_token(deprecated_token, &data.env.jwt_keys)?;
+    let deprecated_user_id = deprecated_claims.sub;
+
+    if deprecated_user_id != refresh_user_id {
+        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
+    }
+
+    // the session of the refresh token, which must still be on its last access token
+    let session = data
+        .db_client
+        .get_session_by_refresh_token(&refresh_user_id, &refresh_token_claims.jti)
+        .await
+        .map_err(HttpError::from)?;
+
+    let Some(session) = session else {
+        revoke_if_reused(&data, &refresh_user_id, &refresh_token_claims.jti).await?;
+        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
+    };
+
+    if session.access_token_hash != token::hash_token_id(&deprecated_claims.jti) {
+        return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
+    }
+
+    // the new tokens carry the current role
+    let user = data
+        .db_client
+        .get_user(&refresh_user_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist))?;
+
+    if user.is_suspended() {
+        return HttpError::forbidden(ErrorMessage::AccountSuspended).into();
+    }
+
+    // create the new tokens
+    let new_token_id = Uuid::new_v4();
+    let new_refresh_token_id = Uuid::new_v4();
+
+    let new_token = token::create_token(
+        &refresh_user_id,
+        user.role,
+        &data.env.jwt_keys,
+        data.env.access_token_max_seconds,
+        &new_token_id,
+    )
+    .map_err(|_| HttpError::server_error(ErrorMessage::HashingError))?;
+
+    let new_refresh_token = token::create_token(
+        &refresh_user_id,
+        user.role,
+        &data.env.jwt_keys,
+        data.env.refresh_token_max_seconds,
+        &new_refresh_token_id,
+    )
+    .map_err(|_| HttpError::server_error(ErrorMessage::HashingError))?;
+
+    // set the new token ids as the session's ones
+    let rotation = DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .rotate_session_tokens(
+            &session.id,
+            &refresh_token_claims.jti,
+            &new_token_id,
+            &new_refresh_token_id,
+        )
+        .await;
+
+    match rotation {
+        Ok(tx) => tx.commit().await.map_err(HttpError::from)?,
+        // rotated by a concurrent request with the same cookie
+        Err(sqlx::Error::RowNotFound) => {
+            revoke_if_reused(&data, &refresh_user_id, &refresh_token_claims.jti).await?;
+            return HttpError::unauthorized(ErrorMessage::InvalidToken).into();
+        }
+        Err(err) => return Err(HttpError::from(err)),
+    }
+
+    let cookie = refresh_token_cookie(new_refresh_token, data.env.refresh_token_max_seconds);
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(json!({
+        "status": Status::Success.to_string(),
+        "token": new_token,
+    })))
+}
+
+/// A refresh token which was already rotated out is presented again: either the
+/// legitimate client or an attacker has a stolen copy, so the whole session is revoked
+async fn revoke_if_reused(
+    data: &web::Data<AppState>,
+    user_id: &Uuid,
+    refresh_token_id: &str,
+) -> Result<(), HttpError> {
+    let session = data
+        .db_client
+        .get_session_by_rotated_refresh_token(user_id, refresh_token_id)
+        .await
+        .map_err(HttpError::from)?;
+
+    if let Some(session) = session {
+        data.db_client
+            .delete_session(user_id, &session.id)
+            .await
+            .map_err(HttpError::from)?;
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/sessions",
+    params(
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Number of items per page"),
+        ("with_total" = Option<bool>, Query, description = "Also count all the items")
+    ),
+    responses(
+        (status = 200, description = "User's sessions retrieved successfully", body = SessionListResponseDto),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "User not logged in")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+#[get("/sessions", wrap = "RequireAuth")]
+async fn get_sessions(
+    user: Authenticated,
+    query: Query<RequestQueryDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    query
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let page = query.page_request().map_err(HttpError::bad_request)?;
+    let Some(current_session) = user.session() else {
+        return HttpError::forbidden(ErrorMessage::ApiKeyNotAllowed).into();
+    };
+    let current_session_id = current_session.id;
+
+    let sessions = data
+        .db_client
+        .get_sessions_by_user(&user.id, &page)
+        .await
+        .map_err(HttpError::from)?
+        .map(|session| SessionDto::from(session, &current_session_id));
+
+    Ok(HttpResponse::Ok().json(SessionListResponseDto {
+        status: Status::Success,
+        results: sessions.items.len(),
+        data: sessions.items,
+        next_cursor: sessions.next_cursor,
+        has_more: sessions.has_more,
+        total: sessions.total,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/auth/sessions/{session_id}",
+    params(
+        ("session_id" = Uuid, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "Session not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Authentication"
+)]
+#[delete("/sessions/{session_id}", wrap = "RequireAuth")]
+async fn delete_session(
+    user: Authenticated,
+    session_id: Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    data.db_client
+        .delete_session(&user.id, &session_id)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::SessionNotFound),
+            err => HttpError::from(err),
+        })?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        dev::ServiceResponse,
+        http::{self},
+        test, App,
+    };
+    use sqlx::{Pool, Postgres};
+
+    use super::*;
+    use crate::{
+        database::psql::DBClient,
+        utils::{
+            config::Config,
+            constants::REFRESH_TOKEN,
+            models::ApiKeyScope,
+            pagination::PageRequest,
+            test_utils::{test_config, test_mailer, test_rate_limit_store, verify_email},
+        },
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn register_valid_user(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+        let (mailer_handle, mailer) = test_mailer();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .app_data(mailer)
+                .service(super::register),
+        )
+        .await;
+
+        let name = "Ayoub Arab".to_string();
+        let email = "ayarab@gmail.com".to_string();
+        let password = "password".to_string();
+
+        let request = test::TestRequest::post()
+            .uri("/register")
+            .set_json(RegisterUserDto {
+                name: name.clone(),
+                email: email.clone(),
+                password: password.clone(),
+                password_confirm: password.clone(),
+            })
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+
+        let body = test::read_body(response).await;
+        let response_data = serde_json::from_slice::<UserResponseDto>(&body)
+            .expect("Failed to deserialize user response from JSON");
+
+        let user = &response_data.data;
+
+        assert_eq!(user.name, name);
+        assert_eq!(user.email, email);
+        assert!(user.email_verified_at.is_none());
+        assert!(mailer_handle.last_token(&email).is_some());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn register_duplicate_email(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+        let (mailer_handle, mailer) = test_mailer();
+
+        let name = "Idrissa 1".to_string();
+        let email = "ibaby@gmail.com".to_string();
+        let password = "password".to_string();
+
+        db_client.save_user(&name, &email, &password).await.unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .app_data(mailer)
+                .service(super::register),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/register")
+            .set_json(RegisterUserDto {
+                name: name.clone(),
+                email: email.clone(),
+                password: password.clone(),
+                password_confirm: password.clone(),
+            })
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+
+        let body = test::read_body(response).await;
+        let expected_message = "An User with this email already exists".to_string();
+
+        let body_json =
+            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize Json");
+
+        let actual_message = body_json["message"].as_str().unwrap();
+
+        assert_eq!(expected_message, actual_message);
+        assert!(mailer_handle.mails().is_empty());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_valid_credentials(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let name = "Ayoub Arab".to_string();
+        let email = "ayarab@gmail.com".to_string();
+        let password = "pawword".to_string();
+
+        let hashed_password = password::hash(&password, &test_config().password_hash).unwrap();
+
+        let user = db_client
+            .save_user(&name, &email, &hashed_password)
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/login")
+            .set_json(LoginUserDto {
+                email: email.clone(),
+                password: password.clone(),
+                device_label: None,
+            })
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body = test::read_body(response).await;
+        let response_data =
+            serde_json::from_slice::<LoginResponseDto>(&body).expect("Failed to deserialize Json");
+
+        let user = response_data.data;
+
+        assert_eq!(email, user.email);
+        assert_eq!(name, user.name);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_valid_credentials_receive_cookie_and_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let name = "Ayoub Arab".to_string();
+        let email = "ayarab@gmail.com".to_string();
+        let password = "pawword".to_string();
+
+        let hashed_password = password::hash(&password, &test_config().password_hash).unwrap();
+
+        let user = db_client
+            .save_user(&name, &email, &hashed_password)
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/login")
+            .set_json(LoginUserDto {
+                email: email.clone(),
+                password: password.clone(),
+                device_label: None,
+            })
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        // find the refresh token and get the subject (user id)
+        let refresh_token = response
+            .response()
+            .cookies()
+            .find(|header| header.name() == REFRESH_TOKEN.to_string())
+            .expect("refresh-token cookie not found");
+
+        let refresh_token_subject = token::decode_token(refresh_token.value(), &config.jwt_keys)
+            .unwrap()
+            .sub;
+
+        // deserialize response and get the authentication token
+        let authentication_token =
+            serde_json::from_slice::<LoginResponseDto>(&test::read_body(response).await)
+                .unwrap()
+                .token;
+
+        // get the user id from auth token
+        let authentication_token_subject =
+            token::decode_token(authentication_token, &config.jwt_keys)
+                .unwrap()
+                .sub;
+
+        assert_eq!(refresh_token_subject, authentication_token_subject);
+
+        let token_subject = db_client
+            .get_user(&authentication_token_subject)
+            .await
+            .expect("Failed to get user")
+            .expect("User not found");
+
+        assert_eq!(token_subject.name, name);
+        assert_eq!(token_subject.email, email);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_with_nonexistent_user(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(&pool, &user.id).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let attempt = |email: &str, password: &str| {
+            test::TestRequest::post()
+                .uri("/login")
+                .set_json(LoginUserDto {
+                    email: email.to_string(),
+                    password: password.to_string(),
+                    device_label: None,
+                })
+                .to_request()
+        };
+
+        let response = test::call_service(&app, attempt("nonexistent@gmail.com", "password")).await;
+
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+
+        let body = test::read_body(response).await;
+        let body =
+            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize json");
+
+        let expected_message = "Email or password is wrong";
+        let actual_mesage = body["message"].as_str().unwrap();
+
+        assert_eq!(actual_mesage, expected_message);
+
+        // nothing tells an unknown email from a wrong password
+        let response =
+            test::call_service(&app, attempt("ayarab@gmail.com", "wrong-password")).await;
+
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&test::read_body(response).await).unwrap(),
+            body
+        );
+
+        // and the password is checked all the same
+        let started_at = std::time::Instant::now();
+        test::call_service(&app, attempt("nonexistent@gmail.com", "password")).await;
+        let unknown_email_duration = started_at.elapsed();
+
+        let started_at = std::time::Instant::now();
+        test::call_service(&app, attempt("ayarab@gmail.com", "wrong-password")).await;
+        let wrong_password_duration = started_at.elapsed();
+
+        assert!(unknown_email_duration * 4 > wrong_password_duration);
+
+        // locked out alike, the lockout is counted per email
+        for email in ["nonexistent@gmail.com", "ayarab@gmail.com"] {
+            let response = test::call_service(&app, attempt(email, "wrong-password")).await;
+            assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+        }
+
+        let response = test::call_service(&app, attempt("nonexistent@gmail.com", "password")).await;
+
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+
+        let body = test::read_body(response).await;
+
+        let response = test::call_service(&app, attempt("ayarab@gmail.com", "password")).await;
+
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(test::read_body(response).await, body);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_with_wrong_email(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let _ = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/login")
+            .set_json(LoginUserDto {
+                email: "nonexistent@gmail.com".to_string(),
+                password: "password".to_string(),
+                device_label: None,
+            })
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+
+        let body = test::read_body(response).await;
+        let body =
+            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize json");
+
+        let expected_message = "Email or password is wrong";
+        let actual_mesage = body["message"].as_str().unwrap();
+
+        assert_eq!(actual_mesage, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_with_wrong_password(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let _ = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/login")
+            .set_json(LoginUserDto {
+                email: "ayarab@gmail.com".to_string(),
+                password: "wrongpassword".to_string(),
+                device_label: None,
             })
             .to_request();
 
         let response = test::call_service(&app, request).await;
 
-        assert_eq!(response.status(), http::StatusCode::CREATED);
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+
+        let body = test::read_body(response).await;
+        let body =
+            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize json");
+
+        let expected_message = "Email or password is wrong";
+        let actual_mesage = body["message"].as_str().unwrap();
+
+        assert_eq!(actual_mesage, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_with_no_data(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let request = test::TestRequest::post().uri("/login").to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+
+        let body = test::read_body(response).await;
+        let actual_message = String::from_utf8_lossy(&body);
+
+        let expected_message = "Content type error";
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_with_empty_json(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .service(super::login),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/login")
+            .set_json(json!({}))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
 
         let body = test::read_body(response).await;
-        let response_data = serde_json::from_slice::<UserResponseDto>(&body)
-            .expect("Failed to deserialize user response from JSON");
+        let actual_message = String::from_utf8_lossy(&body);
 
-        let user = &response_data.data;
+        let expected_message = "Json deserialize error: missing field";
 
-        assert_eq!(user.name, name);
-        assert_eq!(user.email, email);
+        assert!(actual_message.contains(expected_message));
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn register_duplicate_email(pool: Pool<Postgres>) {
-        let db_client = DBClient::new(pool);
+    async fn logout_clear_refresh_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
-        let name = "Idrissa 1".to_string();
-        let email = "ibaby@gmail.com".to_string();
-        let password = "password".to_string();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .service(super::logout),
+        )
+        .await;
 
-        db_client.save_user(&name, &email, &password).await.unwrap();
+        let req = test::TestRequest::post().uri("/logout").to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let new_refresh_token = resp
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == REFRESH_TOKEN.to_string())
+            .expect("Refresh cookie not cleared");
+
+        assert!(new_refresh_token.value().is_empty());
+        assert_eq!(new_refresh_token.max_age().unwrap(), Duration::seconds(0));
+    }
+
+    fn login_request(email: &str, password: &str, device_label: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/auth/login")
+            .insert_header((http::header::USER_AGENT, "test-agent"))
+            .set_json(LoginUserDto {
+                email: email.to_string(),
+                password: password.to_string(),
+                device_label: Some(device_label.to_string()),
+            })
+    }
+
+    /// The access token and the refresh-token cookie of a successful login
+    async fn read_login(response: ServiceResponse) -> (String, Cookie<'static>) {
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let cookie = response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == REFRESH_TOKEN.to_string())
+            .expect("refresh-token cookie not found")
+            .into_owned();
+
+        let token = serde_json::from_slice::<LoginResponseDto>(&test::read_body(response).await)
+            .unwrap()
+            .token;
+
+        (token, cookie)
+    }
+
+    /// Same as `read_login` for the `/auth/refresh` response
+    async fn read_refresh(response: ServiceResponse) -> (String, Cookie<'static>) {
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let cookie = response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == REFRESH_TOKEN.to_string())
+            .expect("refresh-token cookie not found")
+            .into_owned();
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&test::read_body(response).await).unwrap();
+
+        (body["token"].as_str().unwrap().to_string(), cookie)
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn login_on_two_devices_keeps_both_sessions(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
 
         let app = test::init_service(
             App::new()
@@ -365,50 +1647,298 @@ mod tests {
                     env: config,
                     db_client,
                 }))
-                .service(super::register),
+                .configure(super::config),
         )
         .await;
 
-        let request = test::TestRequest::post()
-            .uri("/register")
-            .set_json(RegisterUserDto {
-                name: name.clone(),
-                email: email.clone(),
-                password: password.clone(),
-                password_confirm: password.clone(),
-            })
+        let (laptop_token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
+        let (phone_token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "phone").to_request(),
+            )
+            .await,
+        )
+        .await;
+
+        // the first token still works after the second login
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((
+                http::header::AUTHORIZATION,
+                format!("Bearer {laptop_token}"),
+            ))
             .to_request();
 
         let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
 
-        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+        let sessions: SessionListResponseDto =
+            serde_json::from_slice(&test::read_body(response).await).unwrap();
 
-        let body = test::read_body(response).await;
-        let expected_message = "An User with this email already exists".to_string();
+        assert_eq!(sessions.results, 2);
 
-        let body_json =
-            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize Json");
+        let current: Vec<&SessionDto> = sessions.data.iter().filter(|s| s.current).collect();
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0].device_label.as_deref(), Some("laptop"));
+        assert_eq!(current[0].user_agent.as_deref(), Some("test-agent"));
 
-        let actual_message = body_json["message"].as_str().unwrap();
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {phone_token}")))
+            .to_request();
 
-        assert_eq!(expected_message, actual_message);
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            http::StatusCode::OK
+        );
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_valid_credentials(pool: Pool<Postgres>) {
+    async fn delete_session_revokes_its_token(pool: Pool<Postgres>) {
         let db_client = DBClient::new(pool);
         let config = test_config();
 
-        let name = "Ayoub Arab".to_string();
-        let email = "ayarab@gmail.com".to_string();
-        let password = "pawword".to_string();
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
 
-        let hashed_password = password::hash(&password).unwrap();
+        let (laptop_token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
+        let (phone_token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "phone").to_request(),
+            )
+            .await,
+        )
+        .await;
 
-        db_client
-            .save_user(&name, &email, &hashed_password)
+        let phone_session = db_client
+            .get_sessions_by_user(&user.id, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .find(|session| session.device_label.as_deref() == Some("phone"))
+            .unwrap();
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/auth/sessions/{}", phone_session.id))
+            .insert_header((
+                http::header::AUTHORIZATION,
+                format!("Bearer {laptop_token}"),
+            ))
+            .to_request();
+
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            http::StatusCode::NO_CONTENT
+        );
+
+        // the phone is logged out, the laptop is not
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {phone_token}")))
+            .to_request();
+
+        assert!(test::try_call_service(&app, request).await.is_err());
+
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((
+                http::header::AUTHORIZATION,
+                format!("Bearer {laptop_token}"),
+            ))
+            .to_request();
+
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            http::StatusCode::OK
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_someone_else_session(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let other = db_client
+            .save_user(
+                "Other",
+                "other@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let other_session = db_client
+            .save_session(
+                &other.id,
+                &Uuid::new_v4(),
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let (token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/auth/sessions/{}", other_session.id))
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            http::StatusCode::NOT_FOUND
+        );
+
+        assert_eq!(
+            db_client
+                .get_sessions_by_user(&other.id, &PageRequest::first(10))
+                .await
+                .unwrap()
+                .items
+                .len(),
+            1
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn logout_all_revokes_every_session(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let (laptop_token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
+        let _ = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "phone").to_request(),
+            )
+            .await,
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/auth/logout-all")
+            .insert_header((
+                http::header::AUTHORIZATION,
+                format!("Bearer {laptop_token}"),
+            ))
+            .to_request();
+
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            http::StatusCode::OK
+        );
+
+        assert!(db_client
+            .get_sessions_by_user(&user.id, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn refresh_replaces_the_session_access_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool);
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
             .await
             .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
 
         let app = test::init_service(
             App::new()
@@ -416,312 +1946,639 @@ mod tests {
                     env: config,
                     db_client,
                 }))
-                .service(super::login),
+                .configure(super::config),
+        )
+        .await;
+
+        let (old_token, refresh_cookie) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
         )
         .await;
 
         let request = test::TestRequest::post()
-            .uri("/login")
-            .set_json(LoginUserDto {
-                email: email.clone(),
-                password: password.clone(),
-            })
+            .uri("/auth/refresh")
+            .cookie(refresh_cookie.clone())
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {old_token}")))
+            .to_request();
+
+        let (new_token, new_refresh_cookie) =
+            read_refresh(test::call_service(&app, request).await).await;
+
+        assert_ne!(new_refresh_cookie.value(), refresh_cookie.value());
+
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {new_token}")))
+            .to_request();
+
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            http::StatusCode::OK
+        );
+
+        // the previous access token of the session is revoked
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {old_token}")))
+            .to_request();
+
+        assert!(test::try_call_service(&app, request).await.is_err());
+
+        // the rotated cookie is the one to use for the next refresh
+        let request = test::TestRequest::post()
+            .uri("/auth/refresh")
+            .cookie(new_refresh_cookie)
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {new_token}")))
+            .to_request();
+
+        read_refresh(test::call_service(&app, request).await).await;
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn reusing_rotated_refresh_token_revokes_the_session(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let (token, refresh_cookie) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/auth/refresh")
+            .cookie(refresh_cookie.clone())
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let (new_token, new_refresh_cookie) =
+            read_refresh(test::call_service(&app, request).await).await;
+
+        // the old cookie is replayed, e.g. by whoever stole it
+        let request = test::TestRequest::post()
+            .uri("/auth/refresh")
+            .cookie(refresh_cookie)
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {new_token}")))
             .to_request();
 
         let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
 
-        assert_eq!(response.status(), http::StatusCode::OK);
+        let sessions = DBClient::new(pool)
+            .get_sessions_by_user(&user.id, &PageRequest::first(10))
+            .await
+            .unwrap();
+        assert!(sessions.items.is_empty());
 
-        let body = test::read_body(response).await;
-        let response_data =
-            serde_json::from_slice::<LoginResponseDto>(&body).expect("Failed to deserialize Json");
+        // the tokens issued by the last rotation are revoked too
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {new_token}")))
+            .to_request();
 
-        let user = response_data.data;
+        assert!(test::try_call_service(&app, request).await.is_err());
 
-        assert_eq!(email, user.email);
-        assert_eq!(name, user.name);
+        let request = test::TestRequest::post()
+            .uri("/auth/refresh")
+            .cookie(new_refresh_cookie)
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {new_token}")))
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_valid_credentials_receive_cookie_and_token(pool: Pool<Postgres>) {
+    async fn login_after_email_verification(pool: Pool<Postgres>) {
         let db_client = DBClient::new(pool);
-        let config = test_config();
-
-        let name = "Ayoub Arab".to_string();
-        let email = "ayarab@gmail.com".to_string();
-        let password = "pawword".to_string();
-
-        let hashed_password = password::hash(&password).unwrap();
-
-        db_client
-            .save_user(&name, &email, &hashed_password)
-            .await
-            .unwrap();
+        let (mailer_handle, mailer) = test_mailer();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
-                    env: config.clone(),
-                    db_client: db_client.clone(),
+                    env: test_config(),
+                    db_client,
                 }))
-                .service(super::login),
+                .app_data(mailer)
+                .configure(super::config),
         )
         .await;
 
         let request = test::TestRequest::post()
-            .uri("/login")
-            .set_json(LoginUserDto {
-                email: email.clone(),
-                password: password.clone(),
+            .uri("/auth/register")
+            .set_json(RegisterUserDto {
+                name: "Ayoub Arab".to_string(),
+                email: "ayarab@gmail.com".to_string(),
+                password: "password".to_string(),
+                password_confirm: "password".to_string(),
             })
             .to_request();
 
         let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::CREATED);
 
-        // find the refresh token and get the subject (user id)
-        let refresh_token = response
-            .response()
-            .cookies()
-            .find(|header| header.name() == REFRESH_TOKEN.to_string())
-            .expect("refresh-token cookie not found");
-
-        let refresh_token_subject =
-            token::decode_token(refresh_token.value(), config.secret_key.as_bytes())
-                .unwrap()
-                .sub;
-
-        // deserialize response and get the authentication token
-        let authentication_token =
-            serde_json::from_slice::<LoginResponseDto>(&test::read_body(response).await)
-                .unwrap()
-                .token;
-
-        // get the user id from auth token
-        let authentication_token_subject =
-            token::decode_token(authentication_token, config.secret_key.as_bytes())
-                .unwrap()
-                .sub;
-
-        assert_eq!(refresh_token_subject, authentication_token_subject);
-
-        let token_subject = db_client
-            .get_user(&authentication_token_subject)
-            .await
-            .expect("Failed to get user")
-            .expect("User not found");
+        let response = test::call_service(
+            &app,
+            login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        let verification_token = mailer_handle
+            .last_token("ayarab@gmail.com")
+            .expect("verification mail not sent");
+
+        for expected_status in [http::StatusCode::NO_CONTENT, http::StatusCode::BAD_REQUEST] {
+            let request = test::TestRequest::post()
+                .uri("/auth/verify-email")
+                .set_json(VerifyEmailDto {
+                    token: verification_token.clone(),
+                })
+                .to_request();
+
+            // single use
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), expected_status);
+        }
 
-        assert_eq!(token_subject.name, name);
-        assert_eq!(token_subject.email, email);
+        read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_with_nonexistent_user(pool: Pool<Postgres>) {
+    async fn forgot_password_of_unknown_email(pool: Pool<Postgres>) {
         let db_client = DBClient::new(pool);
-        let config = test_config();
+        let (mailer_handle, mailer) = test_mailer();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
-                    env: config,
+                    env: test_config(),
                     db_client,
                 }))
-                .service(super::login),
+                .app_data(mailer)
+                .configure(super::config),
         )
         .await;
 
         let request = test::TestRequest::post()
-            .uri("/login")
-            .set_json(LoginUserDto {
+            .uri("/auth/forgot-password")
+            .set_json(ForgotPasswordDto {
                 email: "nonexistent@gmail.com".to_string(),
-                password: "password".to_string(),
             })
             .to_request();
 
         let response = test::call_service(&app, request).await;
 
-        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
-
-        let body = test::read_body(response).await;
-        let body =
-            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize json");
-
-        let expected_message = "Email or password is wrong";
-        let actual_mesage = body["message"].as_str().unwrap();
-
-        assert_eq!(actual_mesage, expected_message);
+        // same as for a known email
+        assert_eq!(response.status(), http::StatusCode::ACCEPTED);
+        assert!(mailer_handle.mails().is_empty());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_with_wrong_email(pool: Pool<Postgres>) {
-        let db_client = DBClient::new(pool);
-        let config = test_config();
+    async fn reset_password_revokes_sessions_and_api_keys(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+        let (mailer_handle, mailer) = test_mailer();
+
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
 
-        let _ = db_client
-            .save_user("Ayoub Arab", "ayarab@gmail.com", "password")
-            .await;
+        let (key, prefix) = token::generate_api_key();
+        db_client
+            .save_api_key(&user.id, "ci", &key, &prefix, &[ApiKeyScope::CartRead])
+            .await
+            .unwrap();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
-                    env: config,
+                    env: test_config(),
                     db_client,
                 }))
-                .service(super::login),
+                .app_data(mailer)
+                .configure(super::config),
+        )
+        .await;
+
+        read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
         )
         .await;
 
         let request = test::TestRequest::post()
-            .uri("/login")
-            .set_json(LoginUserDto {
-                email: "nonexistent@gmail.com".to_string(),
-                password: "password".to_string(),
+            .uri("/auth/forgot-password")
+            .set_json(ForgotPasswordDto {
+                email: "ayarab@gmail.com".to_string(),
             })
             .to_request();
 
         let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::ACCEPTED);
+
+        let reset_token = mailer_handle
+            .last_token("ayarab@gmail.com")
+            .expect("reset mail not sent");
+
+        for expected_status in [http::StatusCode::NO_CONTENT, http::StatusCode::BAD_REQUEST] {
+            let request = test::TestRequest::post()
+                .uri("/auth/reset-password")
+                .set_json(ResetPasswordDto {
+                    token: reset_token.clone(),
+                    new_password: "new-password".to_string(),
+                    new_password_confirm: "new-password".to_string(),
+                })
+                .to_request();
+
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), expected_status);
+        }
 
-        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
-
-        let body = test::read_body(response).await;
-        let body =
-            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize json");
+        let db_client = DBClient::new(pool);
+        let sessions = db_client
+            .get_sessions_by_user(&user.id, &PageRequest::first(10))
+            .await
+            .unwrap();
+        assert!(sessions.items.is_empty());
+        assert!(db_client.get_api_key(&key).await.unwrap().is_none());
 
-        let expected_message = "Email or password is wrong";
-        let actual_mesage = body["message"].as_str().unwrap();
+        let response = test::call_service(
+            &app,
+            login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
 
-        assert_eq!(actual_mesage, expected_message);
+        read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "new-password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_with_wrong_password(pool: Pool<Postgres>) {
-        let db_client = DBClient::new(pool);
-        let config = test_config();
+    async fn login_with_totp(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
 
-        let _ = db_client
-            .save_user("Ayoub Arab", "ayarab@gmail.com", "password")
-            .await;
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(db_client.pool(), &user.id).await;
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
-                    env: config,
-                    db_client,
+                    env: test_config(),
+                    db_client: db_client.clone(),
                 }))
-                .service(super::login),
+                .configure(super::config),
+        )
+        .await;
+
+        let (token, _) = read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
         )
         .await;
 
         let request = test::TestRequest::post()
-            .uri("/login")
-            .set_json(LoginUserDto {
-                email: "ayarab@gmail.com".to_string(),
-                password: "wrongpassword".to_string(),
+            .uri("/auth/totp/setup")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+
+        let setup: TotpSetupResponseDto =
+            test::read_body_json(test::call_service(&app, request).await).await;
+        assert!(setup.data.otpauth_uri.starts_with("otpauth://totp/eapi:"));
+
+        let secret = db_client
+            .get_user_totp(&user.id)
+            .await
+            .unwrap()
+            .expect("TOTP not saved")
+            .secret;
+        assert_eq!(totp::encode_secret(&secret), setup.data.secret);
+
+        let now = Utc::now().timestamp();
+
+        let request = test::TestRequest::post()
+            .uri("/auth/totp/confirm")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .set_json(TotpCodeDto {
+                code: totp::code_at(&secret, totp::step_at(now)),
             })
             .to_request();
 
         let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let recovery_codes = test::read_body_json::<RecoveryCodesResponseDto, _>(response)
+            .await
+            .recovery_codes;
+        assert_eq!(recovery_codes.len(), totp::RECOVERY_CODES_NUMBER);
+
+        // the password is not enough anymore
+        let mfa_login = |code: String, mfa_token: &str| {
+            test::TestRequest::post()
+                .uri("/auth/login/mfa")
+                .set_json(MfaLoginDto {
+                    mfa_token: mfa_token.to_string(),
+                    code,
+                    device_label: Some("phone".to_string()),
+                })
+                .to_request()
+        };
+
+        let response = test::call_service(
+            &app,
+            login_request("ayarab@gmail.com", "password", "phone").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), http::StatusCode::ACCEPTED);
+
+        let mfa_token = test::read_body_json::<MfaChallengeResponseDto, _>(response)
+            .await
+            .mfa_token;
+
+        // not an access token
+        let request = test::TestRequest::get()
+            .uri("/auth/sessions")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {mfa_token}")))
+            .to_request();
+        assert!(test::try_call_service(&app, request).await.is_err());
 
+        // the code of the confirmation can not be replayed
+        let response = test::call_service(
+            &app,
+            mfa_login(totp::code_at(&secret, totp::step_at(now)), &mfa_token),
+        )
+        .await;
         assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
 
-        let body = test::read_body(response).await;
-        let body =
-            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize json");
+        read_login(
+            test::call_service(
+                &app,
+                mfa_login(totp::code_at(&secret, totp::step_at(now) + 1), &mfa_token),
+            )
+            .await,
+        )
+        .await;
 
-        let expected_message = "Email or password is wrong";
-        let actual_mesage = body["message"].as_str().unwrap();
+        // a recovery code works once
+        for expected_status in [http::StatusCode::OK, http::StatusCode::UNAUTHORIZED] {
+            let response =
+                test::call_service(&app, mfa_login(recovery_codes[0].clone(), &mfa_token)).await;
+            assert_eq!(response.status(), expected_status);
+        }
 
-        assert_eq!(actual_mesage, expected_message);
+        let request = test::TestRequest::post()
+            .uri("/auth/totp/disable")
+            .insert_header((http::header::AUTHORIZATION, format!("Bearer {token}")))
+            .set_json(TotpCodeDto {
+                code: recovery_codes[1].clone(),
+            })
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+
+        read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_with_no_data(pool: Pool<Postgres>) {
-        let db_client = DBClient::new(pool);
+    async fn login_lockout_after_failed_attempts(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &password::hash("password", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(&pool, &user.id).await;
+
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
-                    env: config,
-                    db_client,
+                    env: config.clone(),
+                    db_client: db_client.clone(),
                 }))
-                .service(super::login),
+                .configure(super::config),
         )
         .await;
 
-        let request = test::TestRequest::post().uri("/login").to_request();
-
-        let response = test::call_service(&app, request).await;
+        for _ in 0..config.login_max_failed_attempts {
+            let response = test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "wrong-password", "laptop").to_request(),
+            )
+            .await;
+            assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+        }
 
-        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+        // locked, even with the right password
+        let response = test::call_service(
+            &app,
+            login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+
+        let retry_after = response
+            .headers()
+            .get(http::header::RETRY_AFTER)
+            .expect("Retry-After header not found")
+            .to_str()
+            .unwrap()
+            .parse::<i64>()
+            .unwrap();
+        assert!((1..=config.login_lockout_seconds).contains(&retry_after));
 
-        let body = test::read_body(response).await;
-        let actual_message = String::from_utf8_lossy(&body);
+        sqlx::query("UPDATE login_attempts SET locked_until = NOW() WHERE email = $1")
+            .bind(&user.email)
+            .execute(&pool)
+            .await
+            .unwrap();
 
-        let expected_message = "Content type error";
+        read_login(
+            test::call_service(
+                &app,
+                login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+            )
+            .await,
+        )
+        .await;
 
-        assert_eq!(actual_message, expected_message);
+        assert!(db_client
+            .get_login_attempts(&user.email)
+            .await
+            .unwrap()
+            .is_none());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn login_with_empty_json(pool: Pool<Postgres>) {
-        let db_client = DBClient::new(pool);
-        let config = test_config();
+    async fn login_rate_limited(pool: Pool<Postgres>) {
+        // unknown emails are locked out too, only the rate limits are tested here
+        let config = Config {
+            login_max_failed_attempts: 100,
+            ..test_config()
+        };
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config,
-                    db_client,
+                    db_client: DBClient::new(pool),
                 }))
-                .service(super::login),
+                .app_data(test_rate_limit_store())
+                .configure(super::config),
         )
         .await;
 
-        let request = test::TestRequest::post()
-            .uri("/login")
-            .set_json(json!({}))
-            .to_request();
-
-        let response = test::call_service(&app, request).await;
-
-        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+        let attempt = |email: &str, ip: &str| {
+            login_request(email, "password", "laptop")
+                .peer_addr(format!("{ip}:1234").parse().unwrap())
+                .to_request()
+        };
+
+        // per email, whatever the IP address
+        for index in 0..LOGIN_EMAIL_RATE_LIMIT.max_requests {
+            let response = test::call_service(
+                &app,
+                attempt("nonexistent@gmail.com", &format!("10.0.0.{index}")),
+            )
+            .await;
+            assert_ne!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        }
 
-        let body = test::read_body(response).await;
-        let actual_message = String::from_utf8_lossy(&body);
+        let response = test::call_service(&app, attempt("Nonexistent@gmail.com", "10.0.1.1")).await;
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(http::header::RETRY_AFTER));
 
-        let expected_message = "Json deserialize error: missing field";
+        // per IP address, whatever the email
+        for index in 0..LOGIN_RATE_LIMIT.max_requests {
+            let response =
+                test::call_service(&app, attempt(&format!("user{index}@gmail.com"), "10.0.2.1"))
+                    .await;
+            assert_ne!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        }
 
-        assert!(actual_message.contains(expected_message));
+        // refused by the middleware, before the handler
+        let Err(err) = test::try_call_service(&app, attempt("other@gmail.com", "10.0.2.1")).await
+        else {
+            panic!("Service call succeeded, but an error was expected");
+        };
+        assert_eq!(
+            err.error_response().status(),
+            http::StatusCode::TOO_MANY_REQUESTS
+        );
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn logout_clear_refresh_token(pool: Pool<Postgres>) {
+    async fn login_rehashes_bcrypt_password(pool: Pool<Postgres>) {
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        // as hashed before Argon2id
+        let user = db_client
+            .save_user(
+                "Ayoub Arab",
+                "ayarab@gmail.com",
+                &bcrypt::hash("password", 4).unwrap(),
+            )
+            .await
+            .unwrap();
+        verify_email(&pool, &user.id).await;
+
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
-                .service(super::logout),
+                .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::post().uri("/logout").to_request();
-
-        let resp = test::call_service(&app, req).await;
-
-        assert_eq!(resp.status(), http::StatusCode::OK);
-
-        let new_refresh_token = resp
-            .response()
-            .cookies()
-            .find(|cookie| cookie.name() == REFRESH_TOKEN.to_string())
-            .expect("Refresh cookie not cleared");
+        for _ in 0..2 {
+            read_login(
+                test::call_service(
+                    &app,
+                    login_request("ayarab@gmail.com", "password", "laptop").to_request(),
+                )
+                .await,
+            )
+            .await;
 
-        assert!(new_refresh_token.value().is_empty());
-        assert_eq!(new_refresh_token.max_age().unwrap(), Duration::seconds(0));
+            let user = db_client.get_user(&user.id).await.unwrap().unwrap();
+            assert!(user.password.starts_with("$argon2id$"));
+            assert!(!password::needs_rehash(
+                &user.password,
+                &config.password_hash
+            ));
+        }
     }
 }
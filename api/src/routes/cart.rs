@@ -0,0 +1,662 @@
+use actix_web::{post, web, HttpResponse};
+use uuid::Uuid;
+
+use super::orders::{check_lines, pay_order, total_cost, OrderLine};
+use crate::{
+    database::{
+        transaction::{DBTransaction, ITransaction},
+        CartExtractor, OrderExtractor,
+    },
+    dtos::{
+        cart::CheckoutDto,
+        orders::{OrderWithItemsDto, OrderWithItemsResponseDto},
+    },
+    error::{ErrorMessage, HttpError},
+    middleware::{Authenticated, Idempotent, RequireAuth, RequireScope},
+    utils::{
+        models::{ApiKeyScope, OrderStatus},
+        status::Status,
+        AppState,
+    },
+};
+
+pub fn config(config: &mut web::ServiceConfig) {
+    config.service(web::scope("/cart").service(checkout));
+}
+
+/* ------------------ */
+/* --- [ ROUTES ] --- */
+/* ------------------ */
+
+#[utoipa::path(
+    post,
+    path = "/api/cart/checkout",
+    request_body(content = CheckoutDto, description = "Delivery address, `{}` for none"),
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first order instead of paying the cart again")
+    ),
+    responses(
+        (status = 200, description = "Cart turned into a paid order", body = OrderWithItemsResponseDto),
+        (status = 400, description = "Invalid body, empty cart, or own product in the cart"),
+        (status = 401, description = "User not logged in"),
+        (status = 402, description = "Payment required (insufficient balance)"),
+        (status = 404, description = "A product no longer exists, or address not found"),
+        (status = 409, description = "A product is out of stock, or a request with the same Idempotency-Key still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
+    ),
+    tag = "Cart"
+)]
+#[post(
+    "/checkout",
+    wrap = "Idempotent",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn checkout(
+    user: Authenticated,
+    infos: web::Json<CheckoutDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let order_details_id = infos.order_details_id;
+
+    let cart = data
+        .db_client
+        .get_cart_items(&user.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    if cart.is_empty() {
+        return HttpError::bad_request(ErrorMessage::EmptyCart).into();
+    }
+
+    let mut lines = Vec::with_capacity(cart.len());
+
+    for item in &cart {
+        lines.push(OrderLine::fetch(&data, &item.product_id, item.products_number).await?);
+    }
+
+    check_lines(&user, &lines)?;
+
+    let order_id = Uuid::new_v4();
+
+    // the order, its payment and the cleared cart are committed together, or not at all
+    let mut tx = DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .save_order(
+            &order_id,
+            &user.id,
+            order_details_id.as_ref(),
+            total_cost(&lines),
+        )
+        .await
+        .map_err(HttpError::from)?;
+
+    for line in &lines {
+        tx = tx
+            .save_order_item(&order_id, &line.product, line.products_number)
+            .await
+            .map_err(HttpError::from)?;
+    }
+
+    let tx = tx
+        .update_order_status(&order_id, OrderStatus::Paid)
+        .await
+        .map_err(HttpError::from)?;
+
+    pay_order(tx, &data.env, &user.id, &order_id, &lines)
+        .await
+        .map_err(HttpError::from)?
+        .clear_cart(&user.id)
+        .await
+        .map_err(HttpError::from)?
+        .commit()
+        .await
+        .map_err(HttpError::from)?;
+
+    let order = data
+        .db_client
+        .get_order(&order_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::server_error(ErrorMessage::ServerError))?;
+
+    let items = data
+        .db_client
+        .get_order_items(&order_id)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(OrderWithItemsResponseDto {
+        status: Status::Success,
+        data: OrderWithItemsDto::from(&order, &items),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::{http, test, App};
+    use sqlx::{Pool, Postgres};
+
+    use crate::{
+        database::{
+            psql::DBClient, AddressExtractor, LedgerExtractor, ProductExtractor, SessionExtractor,
+            UserExtractor,
+        },
+        middleware::IDEMPOTENCY_KEY_HEADER,
+        utils::{
+            models::{LedgerEntryKind, SessionDevice, UserRole},
+            pagination::PageRequest,
+            test_utils::{init_test_products, test_config},
+            token,
+        },
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn checkout(pool: Pool<Postgres>) {
+        let (data, data2, data3) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 2)
+            .await
+            .unwrap();
+        db_client
+            .save_cart_item(&data.user_id, &data3.product_id, 1)
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/cart/checkout")
+            .set_json(CheckoutDto::default())
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+
+        let response: OrderWithItemsResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize order response from JSON");
+
+        assert_eq!(response.data.order.status, OrderStatus::Paid);
+        assert_eq!(response.data.order.product_id, None);
+        assert_eq!(response.data.items.len(), 2);
+
+        let jacket = db_client
+            .get_product(&data2.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let hat = db_client
+            .get_product(&data3.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(jacket.number_in_stock, 0);
+        assert_eq!(hat.number_in_stock, 2);
+
+        let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+        let seller2 = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
+        let seller3 = db_client.get_user(&data3.user_id).await.unwrap().unwrap();
+
+        assert_eq!(buyer.sold_in_cents, 1000 - 2 * 50 - 15);
+        assert_eq!(seller2.sold_in_cents, 2 * 50);
+        assert_eq!(seller3.sold_in_cents, 15);
+
+        assert!(db_client
+            .get_cart_items(&data.user_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let entries = db_client
+            .get_ledger_entries_by_user(&data.user_id, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items;
+
+        assert!(entries
+            .iter()
+            .any(|entry| entry.kind == LedgerEntryKind::OrderDebit
+                && entry.order_id == Some(response.data.order.id)));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn checkout_retried_with_idempotency_key(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 1)
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let post_checkout = |order_details_id: Option<Uuid>| {
+            test::TestRequest::post()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .insert_header((IDEMPOTENCY_KEY_HEADER, "checkout-1"))
+                .uri("/cart/checkout")
+                .set_json(CheckoutDto { order_details_id })
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, post_checkout(None)).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert!(resp.headers().get("Idempotent-Replayed").is_none());
+
+        let first = test::read_body(resp).await;
+
+        // retried once the cart is empty, the first order is sent back
+        let resp = test::call_service(&app, post_checkout(None)).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(resp.headers().get("Idempotent-Replayed").unwrap(), "true");
+        assert_eq!(test::read_body(resp).await, first);
+
+        // same key for another checkout
+        let Err(err) = test::try_call_service(&app, post_checkout(Some(Uuid::new_v4()))).await
+        else {
+            panic!("Service call succeeded, but an error was expected");
+        };
+
+        assert_eq!(
+            err.error_response().status(),
+            http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        // paid only once
+        let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        assert_eq!(buyer.sold_in_cents, 1000 - 50);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn checkout_with_empty_cart(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/cart/checkout")
+            .set_json(CheckoutDto::default())
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn checkout_with_product_out_of_stock(pool: Pool<Postgres>) {
+        let (data, data2, data3) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        // only 2 jackets in stock
+        db_client
+            .save_cart_item(&data.user_id, &data3.product_id, 1)
+            .await
+            .unwrap();
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 3)
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/cart/checkout")
+            .set_json(CheckoutDto::default())
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+        // nothing was bought
+        let hat = db_client
+            .get_product(&data3.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        assert_eq!(hat.number_in_stock, 3);
+        assert_eq!(buyer.sold_in_cents, 1000);
+        assert!(db_client
+            .get_orders_by_user(&data.user_id, &PageRequest::first(10))
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        assert_eq!(
+            db_client.get_cart_items(&data.user_id).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn checkout_with_someone_else_address(pool: Pool<Postgres>) {
+        let (data, data2, data3) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 1)
+            .await
+            .unwrap();
+
+        let address = db_client
+            .save_address(&data3.user_id, "1 main street", "Paris", "75001", "France")
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/cart/checkout")
+            .set_json(CheckoutDto {
+                order_details_id: Some(address.id),
+            })
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        assert_eq!(buyer.sold_in_cents, 1000);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn checkout_with_malformed_body(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        db_client
+            .save_cart_item(&data.user_id, &data2.product_id, 1)
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        // a misspelt address must not be taken for no address
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/cart/checkout")
+            .set_json(serde_json::json!({ "orderDetailsId": "not-an-id" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        assert_eq!(buyer.sold_in_cents, 1000);
+        assert_eq!(
+            db_client.get_cart_items(&data.user_id).await.unwrap().len(),
+            1
+        );
+    }
+}
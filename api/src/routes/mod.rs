@@ -1,16 +1,25 @@
+pub mod admin;
+pub mod api_keys;
 pub mod auth;
+pub mod cart;
 pub mod orders;
+pub mod payments;
 pub mod products;
 pub mod user;
+pub mod well_known;
 
 use actix_web::web;
 
 pub fn config(config: &mut web::ServiceConfig) {
-    config.service(
+    config.configure(well_known::config).service(
         web::scope("/api")
             .configure(user::config)
             .configure(auth::config)
             .configure(products::config)
-            .configure(orders::config),
+            .configure(orders::config)
+            .configure(cart::config)
+            .configure(admin::config)
+            .configure(api_keys::config)
+            .configure(payments::config),
     );
 }
@@ -1,3 +1,8 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+};
+
 use actix_web::{
     delete, get, post,
     web::{self},
@@ -9,13 +14,18 @@ use validator::Validate;
 use crate::{
     database::{
         transaction::{DBTransaction, ITransaction},
-        OrderExtractor, ProductExtractor,
+        LedgerExtractor, OrderExtractor, ProductExtractor,
+    },
+    dtos::orders::{
+        CancelOrderDto, CreateOrderDto, OrderCancellationDto, OrderCancellationResponseDto,
+        OrderDto, OrderResponseDto, OrderShipmentDto, OrderShipmentListResponseDto, ShipOrderDto,
     },
-    dtos::orders::{CreateOrderDto, OrderDto, OrderResponseDto},
     error::{ErrorMessage, HttpError},
-    middleware::{Authenticated, RequireAuth},
-    utils::models::{Order, Product, User},
-    utils::{status::Status, AppState},
+    middleware::{Authenticated, Idempotent, RequireAuth, RequireScope},
+    utils::models::{
+        ApiKeyScope, LedgerEntry, LedgerEntryKind, Order, OrderItem, OrderStatus, Product, User,
+    },
+    utils::{config::Config, status::Status, AppState},
 };
 
 pub fn config(config: &mut web::ServiceConfig) {
@@ -23,8 +33,14 @@ pub fn config(config: &mut web::ServiceConfig) {
         web::scope("/orders")
             .service(create)
             .service(get_by_id)
+            .service(get_cancellation)
             .service(delete)
-            .service(validate),
+            .service(validate)
+            .service(cancel)
+            .service(refund)
+            .service(get_shipments)
+            .service(ship)
+            .service(deliver),
     );
 }
 
@@ -44,11 +60,16 @@ pub fn config(config: &mut web::ServiceConfig) {
         (status = 404, description = "Order not found")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:read"])
     ),
     tag = "Orders"
 )]
-#[get("/{order_id}", wrap = "RequireAuth")]
+#[get(
+    "/{order_id}",
+    wrap = "RequireScope(ApiKeyScope::OrdersRead)",
+    wrap = "RequireAuth"
+)]
 async fn get_by_id(
     user: Authenticated,
     order_id: web::Path<Uuid>,
@@ -72,43 +93,230 @@ async fn get_by_id(
     }))
 }
 
-fn check_order(user: &User, product: &Product, order: &Order) -> Result<(), HttpError> {
-    if product.user_id == user.id {
-        // if user want to buy his own product
-        return HttpError::bad_request(ErrorMessage::AutoBuying).into();
-    } else if product.number_in_stock < order.products_number {
-        return HttpError::conflict(ErrorMessage::ProductOutOfStock).into();
+#[utoipa::path(
+    get,
+    path = "/api/orders/{order_id}/cancellation",
+    params(
+        ("order_id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Why the order was cancelled or refunded", body = OrderCancellationResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "Order not found, or neither cancelled nor refunded")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:read"])
+    ),
+    tag = "Orders"
+)]
+#[get(
+    "/{order_id}/cancellation",
+    wrap = "RequireScope(ApiKeyScope::OrdersRead)",
+    wrap = "RequireAuth"
+)]
+async fn get_cancellation(
+    user: Authenticated,
+    order_id: web::Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let order = data
+        .db_client
+        .get_order_if_belong_to_user(&user.id, &order_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNoLongerExist))?;
+
+    let cancellation = data
+        .db_client
+        .get_order_cancellation(&order.id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNotFound))?;
+
+    Ok(HttpResponse::Ok().json(OrderCancellationResponseDto {
+        status: Status::Success,
+        data: OrderCancellationDto::from(&cancellation),
+    }))
+}
+
+/// A product bought in an order, with its number of units and the price charged for each
+pub(super) struct OrderLine {
+    pub product: Product,
+    pub products_number: i32,
+    pub unit_price_in_cents: i64,
+}
+
+impl OrderLine {
+    /// At the current price of the product
+    pub(super) async fn fetch(
+        data: &AppState,
+        product_id: &Uuid,
+        products_number: i32,
+    ) -> Result<Self, HttpError> {
+        let product = data
+            .db_client
+            .get_product(product_id)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::not_found(ErrorMessage::ProductNoLongerExist))?;
+
+        Ok(OrderLine {
+            unit_price_in_cents: product.price_in_cents,
+            product,
+            products_number,
+        })
+    }
+
+    /// At the price snapshotted in the order, the buyer has to order again if the
+    /// product changed since
+    async fn fetch_item(data: &AppState, item: &OrderItem) -> Result<Self, HttpError> {
+        let line = Self::fetch(data, &item.product_id, item.products_number).await?;
+
+        if line.product.price_in_cents != item.unit_price_in_cents
+            || line.product.name != item.product_name
+        {
+            return HttpError::conflict(ErrorMessage::ProductChanged).into();
+        }
+
+        Ok(OrderLine {
+            unit_price_in_cents: item.unit_price_in_cents,
+            ..line
+        })
+    }
+
+    fn cost(&self) -> i64 {
+        self.unit_price_in_cents * i64::from(self.products_number)
     }
+}
+
+pub(super) fn total_cost(lines: &[OrderLine]) -> i64 {
+    lines.iter().map(OrderLine::cost).sum()
+}
 
-    let total_cost = product.price_in_cents * i64::from(order.products_number);
+/// Fail fast checks, the balance and the stocks are checked again once locked
+pub(super) fn check_lines(user: &User, lines: &[OrderLine]) -> Result<(), HttpError> {
+    for line in lines {
+        if line.product.user_id == user.id {
+            // if user want to buy his own product
+            return HttpError::bad_request(ErrorMessage::AutoBuying).into();
+        } else if line.product.number_in_stock < line.products_number {
+            return HttpError::conflict(ErrorMessage::ProductOutOfStock).into();
+        }
+    }
 
-    if user.sold_in_cents < total_cost {
+    if user.sold_in_cents < total_cost(lines) {
         return HttpError::payment_required(ErrorMessage::SoldTooLow).into();
     }
 
     Ok(())
 }
 
+fn check_order(user: &User, order: &Order, lines: &[OrderLine]) -> Result<(), HttpError> {
+    if order.status != OrderStatus::Pending {
+        // already paid, or not payable anymore
+        return HttpError::conflict(ErrorMessage::InvalidOrderStatus).into();
+    }
+
+    check_lines(user, lines)
+}
+
+/// Charges the buyer, credits each seller (minus the platform commission, recorded in the
+/// platform's ledger) and takes the products out of stock. A balance or a stock going
+/// negative violates a CHECK constraint (-> 402 or 409), so the whole transaction is dropped
+pub(super) async fn pay_order<'c>(
+    mut tx: DBTransaction<'c>,
+    env: &Config,
+    buyer_id: &Uuid,
+    order_id: &Uuid,
+    lines: &[OrderLine],
+) -> Result<DBTransaction<'c>, sqlx::Error> {
+    let mut seller_shares: BTreeMap<Uuid, i64> = BTreeMap::new();
+
+    for line in lines {
+        *seller_shares.entry(line.product.user_id).or_default() += line.cost();
+    }
+
+    // always lock rows in the same order, so two crossed purchases can not deadlock
+    let product_ids: BTreeSet<Uuid> = lines.iter().map(|line| line.product.id).collect();
+    let mut user_ids: BTreeSet<Uuid> = seller_shares.keys().copied().collect();
+    user_ids.insert(*buyer_id);
+
+    for product_id in &product_ids {
+        tx = tx.lock_product(product_id).await?;
+    }
+
+    for user_id in &user_ids {
+        tx = tx.lock_user(user_id).await?;
+    }
+
+    tx = tx
+        .decrease_user_sold(
+            buyer_id,
+            total_cost(lines),
+            LedgerEntryKind::OrderDebit,
+            Some(order_id),
+        )
+        .await?;
+
+    let mut commission = 0;
+
+    for (seller_id, sold) in seller_shares {
+        let seller_commission = env.platform_commission(sold);
+        commission += seller_commission;
+
+        tx = tx
+            .increase_user_sold(
+                &seller_id,
+                sold - seller_commission,
+                LedgerEntryKind::SaleCredit,
+                Some(order_id),
+            )
+            .await?;
+    }
+
+    // the rest of the debit, so the entries of the order add up to zero
+    if commission > 0 {
+        tx = tx.record_platform_commission(commission, order_id).await?;
+    }
+
+    for line in lines {
+        tx = tx
+            .decrease_product_stock(&line.product.id, line.products_number)
+            .await?;
+    }
+
+    Ok(tx)
+}
+
 #[utoipa::path(
     post,
     path = "/api/orders/{order_id}/validate",
     params(
-        ("order_id" = Uuid, Path, description = "Order ID")
+        ("order_id" = Uuid, Path, description = "Order ID"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response instead of being charged again")
     ),
     responses(
-        (status = 204, description = "Order validated and processed successfully"),
+        (status = 204, description = "Order validated, buyer charged and sellers credited"),
         (status = 400, description = "Invalid order (auto-buying, insufficient funds, etc.)"),
         (status = 401, description = "User not logged in"),
         (status = 402, description = "Payment required (insufficient balance)"),
         (status = 404, description = "Order not found"),
-        (status = 409, description = "Product out of stock")
+        (status = 409, description = "Product out of stock or changed since the order, order not pending, or a request with the same Idempotency-Key still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
     ),
     tag = "Orders"
 )]
-#[post("/{order_id}/validate", wrap = "RequireAuth")]
+#[post(
+    "/{order_id}/validate",
+    wrap = "Idempotent",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
 async fn validate(
     user: Authenticated,
     order_id: web::Path<Uuid>,
@@ -121,29 +329,30 @@ async fn validate(
         .map_err(HttpError::from)?
         .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNoLongerExist))?;
 
-    let product: Product = data
+    let items = data
         .db_client
-        .get_product(&order.product_id)
+        .get_order_items(&order.id)
         .await
-        .map_err(HttpError::from)?
-        .ok_or_else(|| HttpError::not_found(ErrorMessage::ProductNoLongerExist))?;
+        .map_err(HttpError::from)?;
 
-    check_order(&user, &product, &order)?;
+    let mut lines = Vec::with_capacity(items.len());
 
-    let total_cost = product.price_in_cents * i64::from(order.products_number);
+    for item in &items {
+        lines.push(OrderLine::fetch_item(&data, item).await?);
+    }
+
+    check_order(&user, &order, &lines)?;
 
     // building a transaction to thread-safely modify values in database
-    DBTransaction::begin(data.db_client.pool())
+    let tx = DBTransaction::begin(data.db_client.pool())
         .await
         .map_err(HttpError::from)?
-        // .lock_user(&user.id).await
-        //     .map_err(HttpError::from)?
-        .decrease_user_sold(&user.id, total_cost)
+        // first, to lock the order and fail if it was validated in the meantime
+        .update_order_status(&order.id, OrderStatus::Paid)
         .await
-        .map_err(HttpError::from)?
-        // .lock_product(&product.id).await
-        //     .map_err(HttpError::from)?
-        .decrease_product_stock(&product.id, order.products_number)
+        .map_err(HttpError::from)?;
+
+    pay_order(tx, &data.env, &user.id, &order.id, &lines)
         .await
         .map_err(HttpError::from)?
         .commit()
@@ -153,24 +362,185 @@ async fn validate(
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// The items of the order sold by `seller_id`, removed products included
+async fn seller_items(
+    data: &AppState,
+    order_id: &Uuid,
+    seller_id: &Uuid,
+) -> Result<Vec<OrderItem>, HttpError> {
+    let items = data
+        .db_client
+        .get_order_items_by_seller(order_id, seller_id)
+        .await
+        .map_err(HttpError::from)?;
+
+    if items.is_empty() {
+        //	not found, to not indicate if the order exists for other sellers
+        return HttpError::not_found(ErrorMessage::OrderNoLongerExist).into();
+    }
+
+    Ok(items)
+}
+
+/// What the order moved for each user, the buyer negative, refunds deducted
+fn moved_by_user(entries: &[LedgerEntry]) -> BTreeMap<Uuid, i64> {
+    let mut moved: BTreeMap<Uuid, i64> = BTreeMap::new();
+
+    for entry in entries {
+        if let (
+            Some(user_id),
+            LedgerEntryKind::OrderDebit | LedgerEntryKind::SaleCredit | LedgerEntryKind::Refund,
+        ) = (entry.user_id, entry.kind)
+        {
+            *moved.entry(user_id).or_default() += entry.amount_in_cents;
+        }
+    }
+
+    moved
+}
+
+/// Gives back what paying the order moved and was not refunded yet, from the sellers and
+/// the platform to the buyer, and puts the products not refunded back in stock. A pending
+/// order was not paid, only its status changes
+async fn revert_order(
+    data: &AppState,
+    order: &Order,
+    new_status: OrderStatus,
+    cancelled_by: &Uuid,
+    reason: &str,
+) -> Result<(), HttpError> {
+    let mut tx = DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        // only from the status read above, and first: the items and the ledger entries read
+        // below can not change until the end of the transaction
+        .cancel_order(&order.id, &[order.status], new_status, cancelled_by, reason)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::conflict(ErrorMessage::InvalidOrderStatus),
+            err => HttpError::from(err),
+        })?;
+
+    let items = data
+        .db_client
+        .get_order_items(&order.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    if items.iter().any(|item| item.shipment_id.is_some()) {
+        // a seller already shipped their items, only refunds are left
+        return HttpError::conflict(ErrorMessage::InvalidOrderStatus).into();
+    }
+
+    if order.status != OrderStatus::Pending {
+        let entries = data
+            .db_client
+            .get_ledger_entries_by_order(&order.id)
+            .await
+            .map_err(HttpError::from)?;
+
+        let moved = moved_by_user(&entries);
+        let commission: i64 = entries
+            .iter()
+            .filter(|entry| entry.user_id.is_none() && entry.kind == LedgerEntryKind::Commission)
+            .map(|entry| entry.amount_in_cents)
+            .sum();
+        let items: Vec<&OrderItem> = items
+            .iter()
+            .filter(|item| item.refunded_at.is_none())
+            .collect();
+
+        // same locking order as `pay_order`
+        let product_ids: BTreeSet<Uuid> = items.iter().map(|item| item.product_id).collect();
+
+        for product_id in &product_ids {
+            tx = tx.lock_product(product_id).await.map_err(HttpError::from)?;
+        }
+
+        for user_id in moved.keys() {
+            tx = tx.lock_user(user_id).await.map_err(HttpError::from)?;
+        }
+
+        for (user_id, amount) in moved {
+            tx = match amount.cmp(&0) {
+                Ordering::Less => {
+                    tx.increase_user_sold(
+                        &user_id,
+                        -amount,
+                        LedgerEntryKind::Refund,
+                        Some(&order.id),
+                    )
+                    .await
+                }
+                // a seller who already spent the money can not refund (-> 402)
+                Ordering::Greater => {
+                    tx.decrease_user_sold(
+                        &user_id,
+                        amount,
+                        LedgerEntryKind::Refund,
+                        Some(&order.id),
+                    )
+                    .await
+                }
+                // a seller who already refunded their items
+                Ordering::Equal => Ok(tx),
+            }
+            .map_err(HttpError::from)?;
+        }
+
+        if commission != 0 {
+            tx = tx
+                .record_platform_commission(-commission, &order.id)
+                .await
+                .map_err(HttpError::from)?;
+        }
+
+        for item in items {
+            tx = tx
+                .increase_product_stock(&item.product_id, item.products_number)
+                .await
+                .map_err(HttpError::from)?;
+        }
+    }
+
+    tx.commit().await.map_err(HttpError::from)?;
+
+    Ok(())
+}
+
 #[utoipa::path(
     post,
-    path = "/api/orders",
-    request_body = CreateOrderDto,
+    path = "/api/orders/{order_id}/cancel",
+    request_body = CancelOrderDto,
+    params(
+        ("order_id" = Uuid, Path, description = "Order ID"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response")
+    ),
     responses(
-        (status = 200, description = "Order created successfully", body = OrderResponseDto),
-        (status = 400, description = "Invalid request data"),
-        (status = 401, description = "User not logged in")
+        (status = 204, description = "Order cancelled, buyer refunded and products restocked"),
+        (status = 400, description = "Invalid reason"),
+        (status = 401, description = "User not logged in"),
+        (status = 402, description = "A seller's balance is too low to give the money back"),
+        (status = 404, description = "Order not found"),
+        (status = 409, description = "Order already shipped or cancelled, or a request with the same Idempotency-Key still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
     ),
     tag = "Orders"
 )]
-#[post("", wrap = "RequireAuth")]
-async fn create(
+#[post(
+    "/{order_id}/cancel",
+    wrap = "Idempotent",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn cancel(
     user: Authenticated,
-    infos: web::Json<CreateOrderDto>,
+    order_id: web::Path<Uuid>,
+    infos: web::Json<CancelOrderDto>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
     infos
@@ -179,130 +549,1702 @@ async fn create(
 
     let order = data
         .db_client
-        .save_order(
-            &user.id,
-            &infos.product_id,
-            infos.order_details_id.as_ref(),
-            infos.products_number,
-        )
+        .get_order_if_belong_to_user(&user.id, &order_id)
         .await
-        .map_err(HttpError::from)?;
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNoLongerExist))?;
 
-    Ok(HttpResponse::Ok().json(OrderResponseDto {
-        status: Status::Success,
-        data: OrderDto::from(&order),
-    }))
+    if !matches!(order.status, OrderStatus::Pending | OrderStatus::Paid) {
+        // shipped orders can only be refunded by the seller
+        return HttpError::conflict(ErrorMessage::InvalidOrderStatus).into();
+    }
+
+    revert_order(
+        &data,
+        &order,
+        OrderStatus::Cancelled,
+        &user.id,
+        &infos.reason,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
 #[utoipa::path(
-    delete,
-    path = "/api/orders/{order_id}",
+    post,
+    path = "/api/orders/{order_id}/refund",
+    request_body = CancelOrderDto,
     params(
-        ("order_id" = Uuid, Path, description = "Order ID")
+        ("order_id" = Uuid, Path, description = "Order ID"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response")
     ),
     responses(
-        (status = 204, description = "Order deleted successfully"),
+        (status = 204, description = "Seller's items refunded and restocked, the order is refunded once all its items are"),
+        (status = 400, description = "Invalid reason"),
         (status = 401, description = "User not logged in"),
-        (status = 404, description = "Order not found")
+        (status = 402, description = "Seller's balance too low to give the money back"),
+        (status = 404, description = "No order with products of the seller"),
+        (status = 409, description = "Order not paid, seller's items already refunded, or a request with the same Idempotency-Key still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
     ),
     tag = "Orders"
 )]
-#[delete("/{order_id}", wrap = "RequireAuth")]
-async fn delete(
+#[post(
+    "/{order_id}/refund",
+    wrap = "Idempotent",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn refund(
     user: Authenticated,
     order_id: web::Path<Uuid>,
+    infos: web::Json<CancelOrderDto>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    seller_items(&data, &order_id, &user.id).await?;
+
+    let paid = [
+        OrderStatus::Paid,
+        OrderStatus::Shipped,
+        OrderStatus::Delivered,
+    ];
+
+    // first: the items and the ledger entries read below can not change until the end of
+    // the transaction
+    let mut tx = DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .lock_order(&order_id, &paid)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::conflict(ErrorMessage::InvalidOrderStatus),
+            err => HttpError::from(err),
+        })?;
+
     let order = data
         .db_client
         .get_order(&order_id)
         .await
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
+        .map_err(HttpError::from)?
         .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNoLongerExist))?;
 
-    if order.user_id != user.id {
-        return HttpError::not_found(ErrorMessage::OrderNoLongerExist).into();
+    let items: Vec<OrderItem> = seller_items(&data, &order.id, &user.id)
+        .await?
+        .into_iter()
+        .filter(|item| item.refunded_at.is_none())
+        .collect();
+
+    if items.is_empty() {
+        // already refunded
+        return HttpError::conflict(ErrorMessage::InvalidOrderStatus).into();
     }
 
-    data.db_client
-        .delete_order(&order_id)
+    let entries = data
+        .db_client
+        .get_ledger_entries_by_order(&order.id)
         .await
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?;
+        .map_err(HttpError::from)?;
 
-    Ok(HttpResponse::NoContent().finish())
-}
+    // the seller refunds all their items at once: what they were credited for them, and the
+    // platform the rest of the price, its commission
+    let refunded: i64 = items
+        .iter()
+        .map(|item| item.unit_price_in_cents * i64::from(item.products_number))
+        .sum();
+    let credited = moved_by_user(&entries)
+        .get(&user.id)
+        .copied()
+        .unwrap_or_default();
+    let commission = refunded - credited;
+
+    // same locking order as `pay_order`
+    let product_ids: BTreeSet<Uuid> = items.iter().map(|item| item.product_id).collect();
+    let user_ids = BTreeSet::from([order.user_id, user.id]);
+
+    for product_id in &product_ids {
+        tx = tx.lock_product(product_id).await.map_err(HttpError::from)?;
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    for user_id in &user_ids {
+        tx = tx.lock_user(user_id).await.map_err(HttpError::from)?;
+    }
 
-    use actix_web::{http, test, web, App};
-    use sqlx::{Pool, Postgres};
+    tx = tx
+        .increase_user_sold(
+            &order.user_id,
+            refunded,
+            LedgerEntryKind::Refund,
+            Some(&order.id),
+        )
+        .await
+        .map_err(HttpError::from)?;
 
-    use crate::{
-        database::{psql::DBClient, UserModifier},
-        utils::{
-            test_utils::{init_test_orders, test_config},
-            token,
-        },
-    };
+    if credited > 0 {
+        // a seller who already spent the money can not refund (-> 402)
+        tx = tx
+            .decrease_user_sold(&user.id, credited, LedgerEntryKind::Refund, Some(&order.id))
+            .await
+            .map_err(HttpError::from)?;
+    }
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_order_with_valid_id(pool: Pool<Postgres>) {
-        let (data, _, _) = init_test_orders(&pool).await;
-        let db_client = DBClient::new(pool.clone());
-        let config = test_config();
+    if commission != 0 {
+        tx = tx
+            .record_platform_commission(-commission, &order.id)
+            .await
+            .map_err(HttpError::from)?;
+    }
 
-        let app = test::init_service(
-            App::new()
-                .app_data(web::Data::new(AppState {
-                    env: config.clone(),
-                    db_client: db_client.clone(),
-                }))
-                .configure(super::config),
-        )
-        .await;
+    for item in &items {
+        tx = tx
+            .increase_product_stock(&item.product_id, item.products_number)
+            .await
+            .map_err(HttpError::from)?;
+    }
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+    tx = tx
+        .refund_order_items(&order.id, &user.id, &infos.reason)
+        .await
+        .map_err(HttpError::from)?;
+
+    let others_left = data
+        .db_client
+        .get_order_items(&order.id)
+        .await
+        .map_err(HttpError::from)?
+        .iter()
+        .any(|item| {
+            item.refunded_at.is_none() && !items.iter().any(|refunded| refunded.id == item.id)
+        });
+
+    if !others_left {
+        // the last items of the order
+        tx = tx
+            .cancel_order(
+                &order.id,
+                &paid,
+                OrderStatus::Refunded,
+                &user.id,
+                &infos.reason,
+            )
             .await
-            .unwrap();
+            .map_err(HttpError::from)?;
+    }
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+    tx.commit().await.map_err(HttpError::from)?;
 
-        let req = test::TestRequest::get()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", data.order_id))
-            .to_request();
+    Ok(HttpResponse::NoContent().finish())
+}
 
-        let resp = test::call_service(&app, req).await;
+#[utoipa::path(
+    post,
+    path = "/api/orders/{order_id}/ship",
+    request_body = ShipOrderDto,
+    params(
+        ("order_id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 204, description = "Seller's items shipped, the order is shipped once all its items are"),
+        (status = 400, description = "Invalid tracking number"),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "No order with products of the seller"),
+        (status = 409, description = "Order not paid, or seller's items already shipped or refunded")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
+    ),
+    tag = "Orders"
+)]
+#[post(
+    "/{order_id}/ship",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn ship(
+    user: Authenticated,
+    order_id: web::Path<Uuid>,
+    infos: web::Json<ShipOrderDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-        assert_eq!(resp.status(), http::StatusCode::OK);
+    seller_items(&data, &order_id, &user.id).await?;
 
-        let body = test::read_body(resp).await;
+    // not paid, or nothing left to ship
+    let conflict = |err| match err {
+        sqlx::Error::RowNotFound => HttpError::conflict(ErrorMessage::InvalidOrderStatus),
+        err => HttpError::from(err),
+    };
 
-        let order_response: OrderResponseDto =
-            serde_json::from_slice(&body).expect("Failed to deserialize orders response from JSON");
+    // the order stays paid until every seller shipped
+    DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .lock_order(&order_id, &[OrderStatus::Paid])
+        .await
+        .map_err(conflict)?
+        .save_order_shipment(&order_id, &user.id, &infos.tracking_number)
+        .await
+        .map_err(conflict)?
+        .commit()
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders/{order_id}/deliver",
+    params(
+        ("order_id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 204, description = "Seller's shipment delivered, the order is delivered once all its shipments are"),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "No order with products of the seller"),
+        (status = 409, description = "Seller's items not shipped, or already delivered")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
+    ),
+    tag = "Orders"
+)]
+#[post(
+    "/{order_id}/deliver",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn deliver(
+    user: Authenticated,
+    order_id: web::Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    seller_items(&data, &order_id, &user.id).await?;
+
+    // not shipped, already delivered or refunded in the meantime
+    let conflict = |err| match err {
+        sqlx::Error::RowNotFound => HttpError::conflict(ErrorMessage::InvalidOrderStatus),
+        err => HttpError::from(err),
+    };
+
+    // the order is delivered once every seller delivered
+    DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .lock_order(&order_id, &[OrderStatus::Paid, OrderStatus::Shipped])
+        .await
+        .map_err(conflict)?
+        .mark_order_shipment_delivered(&order_id, &user.id)
+        .await
+        .map_err(conflict)?
+        .commit()
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/orders/{order_id}/shipments",
+    params(
+        ("order_id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Tracking of the items of the order, one shipment per seller", body = OrderShipmentListResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "Order not found")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:read"])
+    ),
+    tag = "Orders"
+)]
+#[get(
+    "/{order_id}/shipments",
+    wrap = "RequireScope(ApiKeyScope::OrdersRead)",
+    wrap = "RequireAuth"
+)]
+async fn get_shipments(
+    user: Authenticated,
+    order_id: web::Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let order = data
+        .db_client
+        .get_order_if_belong_to_user(&user.id, &order_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNoLongerExist))?;
+
+    let shipments = data
+        .db_client
+        .get_order_shipments(&order.id)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Ok().json(OrderShipmentListResponseDto {
+        status: Status::Success,
+        results: shipments.len(),
+        data: shipments.iter().map(OrderShipmentDto::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/orders",
+    request_body = CreateOrderDto,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first order instead of creating another one")
+    ),
+    responses(
+        (status = 200, description = "Order created successfully", body = OrderResponseDto),
+        (status = 400, description = "Invalid request data"),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "Product not found, or address not found in the user's address book"),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
+    ),
+    tag = "Orders"
+)]
+#[post(
+    "",
+    wrap = "Idempotent",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn create(
+    user: Authenticated,
+    infos: web::Json<CreateOrderDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let order = data
+        .db_client
+        .save_order(
+            &user.id,
+            &infos.product_id,
+            infos.order_details_id.as_ref(),
+            infos.products_number,
+        )
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::ProductNoLongerExist),
+            err => HttpError::from(err),
+        })?;
+
+    Ok(HttpResponse::Ok().json(OrderResponseDto {
+        status: Status::Success,
+        data: OrderDto::from(&order),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/orders/{order_id}",
+    params(
+        ("order_id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 204, description = "Order deleted successfully"),
+        (status = 401, description = "User not logged in"),
+        (status = 404, description = "Order not found"),
+        (status = 409, description = "Order paid, it has to be cancelled or refunded first")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["orders:write"])
+    ),
+    tag = "Orders"
+)]
+#[delete(
+    "/{order_id}",
+    wrap = "RequireScope(ApiKeyScope::OrdersWrite)",
+    wrap = "RequireAuth"
+)]
+async fn delete(
+    user: Authenticated,
+    order_id: web::Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let order = data
+        .db_client
+        .get_order(&order_id)
+        .await
+        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::OrderNoLongerExist))?;
+
+    if order.user_id != user.id {
+        return HttpError::not_found(ErrorMessage::OrderNoLongerExist).into();
+    }
+
+    if matches!(
+        order.status,
+        OrderStatus::Paid | OrderStatus::Shipped | OrderStatus::Delivered
+    ) {
+        // the money would never come back to the buyer
+        return HttpError::conflict(ErrorMessage::InvalidOrderStatus).into();
+    }
+
+    data.db_client
+        .delete_order(&order_id)
+        .await
+        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::{http, test, web, App};
+    use futures_util::future::join_all;
+    use sqlx::{Pool, Postgres};
+
+    use crate::{
+        database::{psql::DBClient, SessionExtractor, UserExtractor},
+        middleware::IDEMPOTENCY_KEY_HEADER,
+        utils::{
+            config::Config,
+            models::{SessionDevice, UserRole},
+            pagination::PageRequest,
+            test_utils::{init_test_orders, init_test_products, test_config},
+            token,
+        },
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_order_with_valid_id(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+
+        let order_response: OrderResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize orders response from JSON");
+
+        assert_eq!(order_response.status, Status::Success);
+
+        let initial_order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        assert_eq!(order_response.data, OrderDto::from(&initial_order));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_order_from_other_user(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", data2.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let body = test::read_body(resp).await;
+
+        let order_response: serde_json::Value =
+            serde_json::from_slice(&body).expect("Failed to deserialize orders response from JSON");
+
+        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+        let actual_message = order_response["message"].clone();
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn post_order(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/orders")
+            .set_json(CreateOrderDto {
+                product_id: data2.product_id,
+                order_details_id: None,
+                products_number: 1,
+            })
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        // panic!("{:#?}", test::read_body(resp).await);
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+        let response =
+            serde_json::from_slice::<OrderResponseDto>(&body).expect("Failed to deserialize Json");
+
+        assert_eq!(response.status, Status::Success);
+        assert_eq!(response.data.user_id, data.user_id);
+        assert_eq!(response.data.product_id, Some(data2.product_id));
+        assert_eq!(response.data.order_details_id, None);
+        assert_eq!(response.data.products_number, Some(1));
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn post_order_with_invalid_product_number(pool: Pool<Postgres>) {
+        let (data, _, data3) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/orders")
+            .set_json(CreateOrderDto {
+                product_id: data3.product_id,
+                order_details_id: None,
+                products_number: 0,
+            })
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let body = test::read_body(resp).await;
+        let response =
+            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize Json");
+
+        let actual_message = response["message"].clone();
+        let expected_message = "products_number: Product number can only be more than 1";
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn validate_order(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        let seller = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
+
+        assert_eq!(order.status, OrderStatus::Paid);
+        assert_eq!(product.number_in_stock, 1);
+        assert_eq!(user.sold_in_cents, 1000 - product.price_in_cents);
+        assert_eq!(seller.sold_in_cents, product.price_in_cents);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn validate_order_with_platform_commission(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = Config {
+            platform_commission_percentage: 10,
+            ..test_config()
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+        let seller = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
+
+        let debited = 1000 - buyer.sold_in_cents;
+        let commission = config.platform_commission(product.price_in_cents);
+
+        assert_eq!(debited, product.price_in_cents);
+        assert_eq!(seller.sold_in_cents, product.price_in_cents * 90 / 100);
+        assert_eq!(debited, seller.sold_in_cents + commission);
+
+        let entries = db_client
+            .get_ledger_entries_by_order(&data.order_id)
+            .await
+            .unwrap();
+
+        assert!(entries.iter().any(|entry| entry.user_id.is_none()
+            && entry.kind == LedgerEntryKind::Commission
+            && entry.amount_in_cents == commission));
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.amount_in_cents)
+                .sum::<i64>(),
+            0
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn validate_same_order_twice(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        // second request (same)
+
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+        let body = test::read_body(resp).await;
+
+        let response = serde_json::from_slice::<serde_json::Value>(&body)
+            .expect("Failed to deserialize response body");
+
+        let actual_message = response["message"].clone();
+        let expected_message = ErrorMessage::InvalidOrderStatus.to_string();
+
+        assert_eq!(actual_message, expected_message);
+
+        // charged only once
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        assert_eq!(product.number_in_stock, 1);
+        assert_eq!(user.sold_in_cents, 1000 - product.price_in_cents);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn post_order_with_idempotency_key(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let post_order = |products_number: i32| {
+            test::TestRequest::post()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .insert_header((IDEMPOTENCY_KEY_HEADER, "order-1"))
+                .uri("/orders")
+                .set_json(CreateOrderDto {
+                    product_id: data2.product_id,
+                    order_details_id: None,
+                    products_number,
+                })
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, post_order(1)).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert!(resp.headers().get("Idempotent-Replayed").is_none());
+
+        let first = test::read_body(resp).await;
+
+        // retried, the first order is sent back
+        let resp = test::call_service(&app, post_order(1)).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(resp.headers().get("Idempotent-Replayed").unwrap(), "true");
+        assert_eq!(test::read_body(resp).await, first);
+
+        // same key for another order
+        let Err(err) = test::try_call_service(&app, post_order(2)).await else {
+            panic!("Service call succeeded, but an error was expected");
+        };
+
+        assert_eq!(
+            err.error_response().status(),
+            http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        let orders = db_client
+            .get_orders_by_user(&data.user_id, &PageRequest::first(10))
+            .await
+            .unwrap();
+        let created = serde_json::from_slice::<OrderResponseDto>(&first).unwrap();
+
+        assert_eq!(
+            orders
+                .items
+                .iter()
+                .filter(|order| order.product_id == Some(data2.product_id))
+                .map(|order| order.id)
+                .collect::<Vec<_>>(),
+            vec![created.data.id]
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn validate_order_retried_with_idempotency_key(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let req = test::TestRequest::post()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .insert_header((IDEMPOTENCY_KEY_HEADER, "validate-1"))
+                .uri(&format!("/orders/{}/validate", data.order_id))
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            // the retry gets the first response, not a conflict
+            assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        }
+
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+
+        assert_eq!(user.sold_in_cents, 1000 - product.price_in_cents);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn validate_orders_concurrently(pool: Pool<Postgres>) {
+        let (_, _, data3) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        // only 2 in stock, for 5 buyers
+        let product = db_client
+            .save_product("lamp", &data3.user_id, None, 10, 2)
+            .await
+            .unwrap();
+
+        let mut requests = vec![];
+
+        for i in 0..5 {
+            let buyer = db_client
+                .save_user("buyer", &format!("buyer{i}@gmail.com"), "password")
+                .await
+                .unwrap();
+
+            DBTransaction::begin(&pool)
+                .await
+                .unwrap()
+                .increase_user_sold(&buyer.id, 100, LedgerEntryKind::TopUp, None)
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let order = db_client
+                .save_order(&buyer.id, &product.id, None, 1)
+                .await
+                .unwrap();
+
+            let token_id = Uuid::new_v4();
+            db_client
+                .save_session(
+                    &buyer.id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
+                .await
+                .unwrap();
+
+            let token = token::create_token(
+                &buyer.id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
+
+            requests.push(
+                test::TestRequest::post()
+                    .insert_header((
+                        http::header::AUTHORIZATION,
+                        http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                    ))
+                    .uri(&format!("/orders/{}/validate", order.id))
+                    .to_request(),
+            );
+        }
+
+        let responses = join_all(
+            requests
+                .into_iter()
+                .map(|req| test::call_service(&app, req)),
+        )
+        .await;
+
+        let validated = responses
+            .iter()
+            .filter(|resp| resp.status() == http::StatusCode::NO_CONTENT)
+            .count();
+        let out_of_stock = responses
+            .iter()
+            .filter(|resp| resp.status() == http::StatusCode::CONFLICT)
+            .count();
+
+        assert_eq!(validated, 2);
+        assert_eq!(out_of_stock, 3);
+
+        let product = db_client.get_product(&product.id).await.unwrap().unwrap();
+        let seller = db_client.get_user(&data3.user_id).await.unwrap().unwrap();
+
+        assert_eq!(product.number_in_stock, 0);
+        assert_eq!(seller.sold_in_cents, 2 * product.price_in_cents);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_valid_order(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::delete()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", &data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let result = db_client
+            .get_order(&data.order_id)
+            .await
+            .expect("Failed to get order by id");
+
+        assert!(result.is_none(), "User found, but no one expected");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_invalid_order(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::delete()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", Uuid::new_v4()))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let body = test::read_body(resp).await;
+
+        let response = serde_json::from_slice::<serde_json::Value>(&body)
+            .expect("Failed to deserialize response body");
+
+        let actual_message = response["message"].clone();
+        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_order_from_other_user(pool: Pool<Postgres>) {
+        let (_, _, data3) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data3.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data3.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::delete()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", Uuid::new_v4()))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let body = test::read_body(resp).await;
+
+        let response = serde_json::from_slice::<serde_json::Value>(&body)
+            .expect("Failed to deserialize response body");
+
+        let actual_message = response["message"].clone();
+        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn delete_same_order_twice(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::delete()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", &data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let result = db_client
+            .get_order(&data.order_id)
+            .await
+            .expect("Failed to get order by id");
+
+        assert!(result.is_none(), "User found, but no one expected");
+
+        // second request (same)
+
+        let req = test::TestRequest::delete()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/orders/{}", &data.order_id))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let body = test::read_body(resp).await;
+
+        let response = serde_json::from_slice::<serde_json::Value>(&body)
+            .expect("Failed to deserialize response body");
+
+        let actual_message = response["message"].clone();
+        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    async fn bearer_token(db_client: &DBClient, config: &Config, user_id: &Uuid) -> String {
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token =
+            token::create_token(user_id, UserRole::Customer, &config.jwt_keys, 60, &token_id)
+                .unwrap();
+
+        format!("Bearer {token}")
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn cancel_paid_order(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let bearer = bearer_token(&db_client, &config, &data.user_id).await;
+
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/cancel", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Ordered the wrong size".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+        let seller = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
+
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert_eq!(product.number_in_stock, 2);
+        assert_eq!(user.sold_in_cents, 1000);
+        assert_eq!(seller.sold_in_cents, 0);
+
+        let req = test::TestRequest::get()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/cancellation", data.order_id))
+            .to_request();
+        let resp: OrderCancellationResponseDto = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(resp.data.status, OrderStatus::Cancelled);
+        assert_eq!(resp.data.cancelled_by, Some(data.user_id));
+        assert_eq!(resp.data.reason, "Ordered the wrong size");
+
+        // refunded only once
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/cancel", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Ordered the wrong size".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+        assert_eq!(user.sold_in_cents, 1000);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn cancel_paid_order_of_restocked_product(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let bearer = bearer_token(&db_client, &config, &data.user_id).await;
+
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        // the seller restocked as much as a product can have
+        db_client
+            .modify_product(&data.product_id, None, None, None, Some(999))
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/cancel", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Ordered the wrong size".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
 
-        assert_eq!(order_response.status, Status::Success);
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+        let seller = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
 
-        let initial_order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
-        assert_eq!(order_response.data, OrderDto::from(&initial_order));
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert_eq!(product.number_in_stock, 999);
+        assert_eq!(user.sold_in_cents, 1000);
+        assert_eq!(seller.sold_in_cents, 0);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_order_from_other_user(pool: Pool<Postgres>) {
+    async fn cancel_paid_order_with_platform_commission(pool: Pool<Postgres>) {
         let (data, data2, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool.clone());
-        let config = test_config();
+        let config = Config {
+            platform_commission_percentage: 10,
+            ..test_config()
+        };
 
         let app = test::init_service(
             App::new()
@@ -314,41 +2256,82 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        // the money held by the buyer, the seller and the platform
+        let total_money = || async {
+            let buyer = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+            let seller = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
+            let platform: i64 = db_client
+                .get_ledger_entries_by_order(&data.order_id)
+                .await
+                .unwrap()
+                .iter()
+                .filter(|entry| entry.user_id.is_none())
+                .map(|entry| entry.amount_in_cents)
+                .sum();
+
+            (
+                buyer.sold_in_cents,
+                seller.sold_in_cents,
+                platform,
+                buyer.sold_in_cents + seller.sold_in_cents + platform,
+            )
+        };
+
+        let bearer = bearer_token(&db_client, &config, &data.user_id).await;
 
-        let req = test::TestRequest::get()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", data2.order_id))
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/validate", data.order_id))
             .to_request();
-
         let resp = test::call_service(&app, req).await;
 
-        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
 
-        let body = test::read_body(resp).await;
+        let (_, _, platform, paid_total) = total_money().await;
 
-        let order_response: serde_json::Value =
-            serde_json::from_slice(&body).expect("Failed to deserialize orders response from JSON");
+        assert!(platform > 0);
+        assert_eq!(paid_total, 1000);
 
-        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
-        let actual_message = order_response["message"].clone();
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/cancel", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Ordered the wrong size".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
 
-        assert_eq!(actual_message, expected_message);
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        assert_eq!(total_money().await, (1000, 0, 0, 1000));
+
+        let entries = db_client
+            .get_ledger_entries_by_order(&data.order_id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.amount_in_cents)
+                .sum::<i64>(),
+            0
+        );
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn post_order(pool: Pool<Postgres>) {
-        let (data, data2, _) = init_test_orders(&pool).await;
+    async fn cancel_shipped_order(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
@@ -362,49 +2345,44 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Paid)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Shipped)
+            .await
+            .unwrap()
+            .commit()
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let bearer = bearer_token(&db_client, &config, &data.user_id).await;
 
         let req = test::TestRequest::post()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri("/orders")
-            .set_json(CreateOrderDto {
-                product_id: data2.product_id,
-                order_details_id: None,
-                products_number: 1,
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/cancel", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Too late".to_string(),
             })
             .to_request();
-
         let resp = test::call_service(&app, req).await;
 
-        // panic!("{:#?}", test::read_body(resp).await);
-        assert_eq!(resp.status(), http::StatusCode::OK);
-
-        let body = test::read_body(resp).await;
-        let response =
-            serde_json::from_slice::<OrderResponseDto>(&body).expect("Failed to deserialize Json");
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
 
-        assert_eq!(response.status, Status::Success);
-        assert_eq!(response.data.user_id, data.user_id);
-        assert_eq!(response.data.product_id, data2.product_id);
-        assert_eq!(response.data.order_details_id, None);
-        assert_eq!(response.data.products_number, 1);
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        assert_eq!(order.status, OrderStatus::Shipped);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn post_order_with_invalid_product_number(pool: Pool<Postgres>) {
-        let (data, _, data3) = init_test_orders(&pool).await;
+    async fn refund_order_as_seller(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool.clone());
-        let config = test_config();
+        let config = Config {
+            platform_commission_percentage: 10,
+            ..test_config()
+        };
 
         let app = test::init_service(
             App::new()
@@ -416,44 +2394,88 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let buyer_bearer = bearer_token(&db_client, &config, &data.user_id).await;
+        let seller_bearer = bearer_token(&db_client, &config, &data2.user_id).await;
 
         let req = test::TestRequest::post()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri("/orders")
-            .set_json(CreateOrderDto {
-                product_id: data3.product_id,
-                order_details_id: None,
-                products_number: 0,
+            .insert_header((http::header::AUTHORIZATION, buyer_bearer.as_str()))
+            .uri(&format!("/orders/{}/validate", data.order_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        // only the seller of the products can refund
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, buyer_bearer.as_str()))
+            .uri(&format!("/orders/{}/refund", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Damaged".to_string(),
             })
             .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
 
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, seller_bearer.as_str()))
+            .uri(&format!("/orders/{}/refund", data.order_id))
+            .set_json(CancelOrderDto {
+                reason: "Damaged".to_string(),
+            })
+            .to_request();
         let resp = test::call_service(&app, req).await;
 
-        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
 
-        let body = test::read_body(resp).await;
-        let response =
-            serde_json::from_slice::<serde_json::Value>(&body).expect("Failed to deserialize Json");
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let product = db_client
+            .get_product(&data.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
+        let seller = db_client.get_user(&data2.user_id).await.unwrap().unwrap();
 
-        let actual_message = response["message"].clone();
-        let expected_message = "products_number: Product number can only be more than 1";
+        assert_eq!(order.status, OrderStatus::Refunded);
+        assert_eq!(product.number_in_stock, 2);
+        // the seller gives back what they were credited, commission included
+        assert_eq!(user.sold_in_cents, 1000);
+        assert_eq!(seller.sold_in_cents, 0);
 
-        assert_eq!(actual_message, expected_message);
+        let entries = db_client
+            .get_ledger_entries_by_order(&data.order_id)
+            .await
+            .unwrap();
+        let refunds = entries
+            .iter()
+            .filter(|entry| entry.kind == LedgerEntryKind::Refund)
+            .count();
+
+        assert_eq!(refunds, 2);
+
+        let cancellation = db_client
+            .get_order_cancellation(&data.order_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(cancellation.status, OrderStatus::Refunded);
+        assert_eq!(cancellation.cancelled_by, Some(data2.user_id));
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_valid_order(pool: Pool<Postgres>) {
+    async fn delete_paid_order(pool: Pool<Postgres>) {
         let (data, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
@@ -468,38 +2490,32 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Paid)
+            .await
+            .unwrap()
+            .commit()
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let bearer = bearer_token(&db_client, &config, &data.user_id).await;
 
         let req = test::TestRequest::delete()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", &data.order_id))
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}", data.order_id))
             .to_request();
-
         let resp = test::call_service(&app, req).await;
 
-        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
-
-        let result = db_client
-            .get_order(&data.order_id)
-            .await
-            .expect("Failed to get order by id");
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
 
-        assert!(result.is_none(), "User found, but no one expected");
+        assert!(db_client.get_order(&data.order_id).await.unwrap().is_some());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_invalid_order(pool: Pool<Postgres>) {
-        let (data, _, _) = init_test_orders(&pool).await;
+    async fn ship_and_deliver_order(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
@@ -513,43 +2529,85 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+        let buyer_bearer = bearer_token(&db_client, &config, &data.user_id).await;
+        let seller_bearer = bearer_token(&db_client, &config, &data2.user_id).await;
+
+        let ship_request = |bearer: &str| {
+            test::TestRequest::post()
+                .insert_header((http::header::AUTHORIZATION, bearer))
+                .uri(&format!("/orders/{}/ship", data.order_id))
+                .set_json(ShipOrderDto {
+                    tracking_number: "1Z999AA10123456784".to_string(),
+                })
+                .to_request()
+        };
+
+        // not paid yet
+        let resp = test::call_service(&app, ship_request(&seller_bearer)).await;
+
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .update_order_status(&data.order_id, OrderStatus::Paid)
+            .await
+            .unwrap()
+            .commit()
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        // only the seller ships
+        let resp = test::call_service(&app, ship_request(&buyer_bearer)).await;
 
-        let req = test::TestRequest::delete()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", Uuid::new_v4()))
-            .to_request();
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
 
-        let resp = test::call_service(&app, req).await;
+        let resp = test::call_service(&app, ship_request(&seller_bearer)).await;
 
-        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
 
-        let body = test::read_body(resp).await;
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        assert_eq!(order.status, OrderStatus::Shipped);
 
-        let response = serde_json::from_slice::<serde_json::Value>(&body)
-            .expect("Failed to deserialize response body");
+        // shipped only once
+        let resp = test::call_service(&app, ship_request(&seller_bearer)).await;
 
-        let actual_message = response["message"].clone();
-        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
 
-        assert_eq!(actual_message, expected_message);
+        let req = test::TestRequest::get()
+            .insert_header((http::header::AUTHORIZATION, buyer_bearer.as_str()))
+            .uri(&format!("/orders/{}/shipments", data.order_id))
+            .to_request();
+        let resp: OrderShipmentListResponseDto = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(resp.results, 1);
+        assert_eq!(resp.data[0].tracking_number, "1Z999AA10123456784");
+        assert_eq!(resp.data[0].shipped_by, Some(data2.user_id));
+        assert!(resp.data[0].delivered_at.is_none());
+
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, seller_bearer.as_str()))
+            .uri(&format!("/orders/{}/deliver", data.order_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let shipments = db_client.get_order_shipments(&data.order_id).await.unwrap();
+
+        assert_eq!(order.status, OrderStatus::Delivered);
+        assert!(shipments[0].delivered_at.is_some());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_order_from_other_user(pool: Pool<Postgres>) {
-        let (_, _, data3) = init_test_orders(&pool).await;
+    async fn fulfil_order_of_several_sellers(pool: Pool<Postgres>) {
+        let (shoes, jacket, hat) = init_test_products(&pool).await;
         let db_client = DBClient::new(pool.clone());
-        let config = test_config();
+        let config = Config {
+            platform_commission_percentage: 10,
+            ..test_config()
+        };
 
         let app = test::init_service(
             App::new()
@@ -561,41 +2619,155 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data3.user_id)
+        // the owner of the hat buys the shoes and the jacket, of two other sellers
+        let buyer_id = hat.user_id;
+        let order_id = Uuid::new_v4();
+        let mut tx = DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .save_order(&order_id, &buyer_id, None, 35 + 50)
             .await
             .unwrap();
 
-        let token =
-            token::create_token(&data3.user_id, config.secret_key.as_bytes(), 60, &token_id)
-                .unwrap();
+        for product_id in [shoes.product_id, jacket.product_id] {
+            let product = db_client.get_product(&product_id).await.unwrap().unwrap();
+            tx = tx.save_order_item(&order_id, &product, 1).await.unwrap();
+        }
 
-        let req = test::TestRequest::delete()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", Uuid::new_v4()))
-            .to_request();
+        tx.increase_user_sold(&buyer_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
 
-        let resp = test::call_service(&app, req).await;
+        let buyer_bearer = bearer_token(&db_client, &config, &buyer_id).await;
+        let shoes_bearer = bearer_token(&db_client, &config, &shoes.user_id).await;
+        let jacket_bearer = bearer_token(&db_client, &config, &jacket.user_id).await;
+
+        let post = |bearer: &str, action: &str| {
+            test::TestRequest::post()
+                .insert_header((http::header::AUTHORIZATION, bearer))
+                .uri(&format!("/orders/{order_id}/{action}"))
+        };
+        let status = || async {
+            db_client
+                .get_order(&order_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .status
+        };
+        let balance = |user_id: Uuid| {
+            let db_client = db_client.clone();
+            async move {
+                db_client
+                    .get_user(&user_id)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .sold_in_cents
+            }
+        };
+
+        let resp = test::call_service(&app, post(&buyer_bearer, "validate").to_request()).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
 
-        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        let shipping = ShipOrderDto {
+            tracking_number: "1Z999AA10123456784".to_string(),
+        };
+        let reason = CancelOrderDto {
+            reason: "Out of this size".to_string(),
+        };
+
+        // paid until the jacket is shipped or refunded
+        let resp = test::call_service(
+            &app,
+            post(&shoes_bearer, "ship").set_json(&shipping).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(status().await, OrderStatus::Paid);
 
-        let body = test::read_body(resp).await;
+        // the shoes left already
+        let resp = test::call_service(
+            &app,
+            post(&buyer_bearer, "cancel").set_json(&reason).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
 
-        let response = serde_json::from_slice::<serde_json::Value>(&body)
-            .expect("Failed to deserialize response body");
+        let resp = test::call_service(
+            &app,
+            post(&jacket_bearer, "refund")
+                .set_json(&reason)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(status().await, OrderStatus::Shipped);
+        assert_eq!(balance(buyer_id).await, 1000 - 35);
+        assert_eq!(balance(jacket.user_id).await, 0);
 
-        let actual_message = response["message"].clone();
-        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+        let jacket_stock = db_client
+            .get_product(&jacket.product_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .number_in_stock;
+        assert_eq!(jacket_stock, 2);
+
+        // nothing left to ship or refund for the jacket
+        for (action, request) in [
+            ("ship", post(&jacket_bearer, "ship").set_json(&shipping)),
+            ("refund", post(&jacket_bearer, "refund").set_json(&reason)),
+        ] {
+            let resp = test::call_service(&app, request.to_request()).await;
+            assert_eq!(resp.status(), http::StatusCode::CONFLICT, "{action}");
+        }
+
+        let resp = test::call_service(&app, post(&shoes_bearer, "deliver").to_request()).await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(status().await, OrderStatus::Delivered);
+
+        let items = db_client.get_order_items(&order_id).await.unwrap();
+        let shipments = db_client.get_order_shipments(&order_id).await.unwrap();
+
+        assert_eq!(shipments.len(), 1);
+        assert_eq!(shipments[0].shipped_by, Some(shoes.user_id));
+        for item in &items {
+            let shipped = item.product_id == shoes.product_id;
+            assert_eq!(item.shipment_id.is_some(), shipped);
+            assert_eq!(item.refunded_at.is_none(), shipped);
+        }
+
+        // the last items of the order
+        let resp = test::call_service(
+            &app,
+            post(&shoes_bearer, "refund").set_json(&reason).to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(status().await, OrderStatus::Refunded);
+        assert_eq!(balance(buyer_id).await, 1000);
+        assert_eq!(balance(shoes.user_id).await, 0);
 
-        assert_eq!(actual_message, expected_message);
+        let entries = db_client
+            .get_ledger_entries_by_order(&order_id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.amount_in_cents)
+                .sum::<i64>(),
+            0
+        );
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn delete_same_order_twice(pool: Pool<Postgres>) {
+    async fn validate_order_after_price_change(pool: Pool<Postgres>) {
         let (data, _, _) = init_test_orders(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
@@ -610,56 +2782,48 @@ mod tests {
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&data.user_id, 1000, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
-
-        let req = test::TestRequest::delete()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", &data.order_id))
-            .to_request();
-
-        let resp = test::call_service(&app, req).await;
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let ordered_price = order.unit_price_in_cents.unwrap();
 
-        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        assert_eq!(order.total_in_cents, ordered_price);
 
-        let result = db_client
-            .get_order(&data.order_id)
+        // raised by the seller before the payment
+        db_client
+            .modify_product(&data.product_id, None, None, Some(ordered_price * 2), None)
             .await
-            .expect("Failed to get order by id");
-
-        assert!(result.is_none(), "User found, but no one expected");
+            .unwrap();
 
-        // second request (same)
+        let bearer = bearer_token(&db_client, &config, &data.user_id).await;
 
-        let req = test::TestRequest::delete()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/orders/{}", &data.order_id))
+        let req = test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/orders/{}/validate", data.order_id))
             .to_request();
-
         let resp = test::call_service(&app, req).await;
 
-        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
 
-        let body = test::read_body(resp).await;
+        let response: serde_json::Value = test::read_body_json(resp).await;
 
-        let response = serde_json::from_slice::<serde_json::Value>(&body)
-            .expect("Failed to deserialize response body");
+        assert_eq!(
+            response["message"],
+            ErrorMessage::ProductChanged.to_string()
+        );
 
-        let actual_message = response["message"].clone();
-        let expected_message = ErrorMessage::OrderNoLongerExist.to_string();
+        let order = db_client.get_order(&data.order_id).await.unwrap().unwrap();
+        let user = db_client.get_user(&data.user_id).await.unwrap().unwrap();
 
-        assert_eq!(actual_message, expected_message);
+        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(user.sold_in_cents, 1000);
     }
 }
@@ -0,0 +1,612 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{
+    get,
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    post,
+    web::{self, Bytes, Json, Path},
+    HttpRequest, HttpResponse,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    database::{
+        transaction::{DBTransaction, ITransaction},
+        PaymentExtractor,
+    },
+    dtos::payments::{
+        ConfirmPaymentIntentDto, CreatePaymentIntentDto, PaymentIntentDto, PaymentIntentResponseDto,
+    },
+    error::{ErrorMessage, HttpError},
+    middleware::{Authenticated, Idempotent, RequireAuth},
+    payment::{
+        mock::{MockGateway, SIGNATURE_HEADER},
+        PaymentGateway, PaymentOutcome,
+    },
+    utils::{
+        models::{LedgerEntryKind, PaymentIntentStatus},
+        status::Status,
+        AppState,
+    },
+};
+
+/// Only with a session, an API key can not top up the balance
+pub fn config(config: &mut web::ServiceConfig) {
+    config.service(
+        web::scope("/payments")
+            .service(create_intent)
+            .service(get_intent)
+            .service(confirm_intent)
+            .service(webhook),
+    );
+}
+
+/* ------------------ */
+/* --- [ ROUTES ] --- */
+/* ------------------ */
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/intents",
+    request_body = CreatePaymentIntentDto,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first intent instead of creating another one")
+    ),
+    responses(
+        (status = 201, description = "Payment intent created, nothing is charged before it is confirmed", body = PaymentIntentResponseDto),
+        (status = 400, description = "Invalid request data"),
+        (status = 401, description = "User not logged in"),
+        (status = 402, description = "Rejected by the payment gateway"),
+        (status = 403, description = "Sent with an API key"),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Payments"
+)]
+#[post("/intents", wrap = "Idempotent", wrap = "RequireAuth")]
+async fn create_intent(
+    user: Authenticated,
+    infos: Json<CreatePaymentIntentDto>,
+    gateway: web::Data<dyn PaymentGateway>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let gateway_intent = gateway
+        .create_intent(infos.amount_in_cents)
+        .await
+        .map_err(|err| {
+            eprintln!("Warning: {err}");
+            HttpError::payment_required(ErrorMessage::PaymentRejected)
+        })?;
+
+    let intent = data
+        .db_client
+        .save_payment_intent(&user.id, &gateway_intent.id, infos.amount_in_cents)
+        .await
+        .map_err(HttpError::from)?;
+
+    Ok(HttpResponse::Created().json(PaymentIntentResponseDto {
+        status: Status::Success,
+        data: PaymentIntentDto::from(&intent),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/intents/{payment_intent_id}",
+    params(
+        ("payment_intent_id" = Uuid, Path, description = "Payment intent ID")
+    ),
+    responses(
+        (status = 200, description = "Payment intent retrieved successfully", body = PaymentIntentResponseDto),
+        (status = 401, description = "User not logged in"),
+        (status = 403, description = "Sent with an API key"),
+        (status = 404, description = "Payment intent not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Payments"
+)]
+#[get("/intents/{payment_intent_id}", wrap = "RequireAuth")]
+async fn get_intent(
+    user: Authenticated,
+    payment_intent_id: Path<Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let intent = data
+        .db_client
+        .get_payment_intent_if_belong_to_user(&user.id, &payment_intent_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::PaymentIntentNotFound))?;
+
+    Ok(HttpResponse::Ok().json(PaymentIntentResponseDto {
+        status: Status::Success,
+        data: PaymentIntentDto::from(&intent),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/intents/{payment_intent_id}/confirm",
+    request_body = ConfirmPaymentIntentDto,
+    params(
+        ("payment_intent_id" = Uuid, Path, description = "Payment intent ID"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response instead of confirming again")
+    ),
+    responses(
+        (status = 202, description = "Payment started, the balance is credited once the gateway confirms it", body = PaymentIntentResponseDto),
+        (status = 400, description = "Invalid request data"),
+        (status = 401, description = "User not logged in"),
+        (status = 402, description = "Rejected by the payment gateway"),
+        (status = 403, description = "Sent with an API key"),
+        (status = 404, description = "Payment intent not found"),
+        (status = 409, description = "Payment intent already confirmed, or a request with the same Idempotency-Key still in progress"),
+        (status = 422, description = "Idempotency-Key already used for another request")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "Payments"
+)]
+#[post(
+    "/intents/{payment_intent_id}/confirm",
+    wrap = "Idempotent",
+    wrap = "RequireAuth"
+)]
+async fn confirm_intent(
+    user: Authenticated,
+    payment_intent_id: Path<Uuid>,
+    infos: Json<ConfirmPaymentIntentDto>,
+    gateway: web::Data<dyn PaymentGateway>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let mut intent = data
+        .db_client
+        .get_payment_intent_if_belong_to_user(&user.id, &payment_intent_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::PaymentIntentNotFound))?;
+
+    // committed first, to fail if it was confirmed in the meantime without holding the row
+    // during the call to the gateway
+    DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .update_payment_intent_status(
+            &intent.id,
+            &[PaymentIntentStatus::Pending],
+            PaymentIntentStatus::Processing,
+        )
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => {
+                HttpError::conflict(ErrorMessage::InvalidPaymentIntentStatus)
+            }
+            err => HttpError::from(err),
+        })?
+        .commit()
+        .await
+        .map_err(HttpError::from)?;
+
+    if let Err(err) = gateway
+        .confirm_intent(&intent.gateway_intent_id, &infos.payment_method)
+        .await
+    {
+        eprintln!("Warning: {err}");
+
+        // pending again, to be confirmed with another payment method
+        match DBTransaction::begin(data.db_client.pool())
+            .await
+            .map_err(HttpError::from)?
+            .update_payment_intent_status(
+                &intent.id,
+                &[PaymentIntentStatus::Processing],
+                PaymentIntentStatus::Pending,
+            )
+            .await
+        {
+            Ok(tx) => tx.commit().await.map_err(HttpError::from)?,
+            // a webhook settled it in the meantime
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return Err(HttpError::from(err)),
+        }
+
+        return Err(HttpError::payment_required(ErrorMessage::PaymentRejected));
+    }
+
+    intent.status = PaymentIntentStatus::Processing;
+
+    Ok(HttpResponse::Accepted().json(PaymentIntentResponseDto {
+        status: Status::Success,
+        data: PaymentIntentDto::from(&intent),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/webhook",
+    request_body(content = String, description = "Event of the payment gateway, as signed by it"),
+    responses(
+        (status = 204, description = "Event handled, or already handled before"),
+        (status = 400, description = "Invalid signature or event"),
+        (status = 404, description = "Payment intent not found")
+    ),
+    tag = "Payments"
+)]
+#[post("/webhook")]
+async fn webhook(
+    req: HttpRequest,
+    body: Bytes,
+    gateway: web::Data<dyn PaymentGateway>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    handle_webhook(&data, gateway.get_ref(), req.headers(), &body).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The balance is only credited here, once the gateway signed that the payment went through.
+/// The gateways send their webhooks again until they get a success, those are no-ops
+async fn handle_webhook(
+    data: &AppState,
+    gateway: &dyn PaymentGateway,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), HttpError> {
+    let event = gateway
+        .verify_webhook(headers, body)
+        .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidWebhook))?;
+
+    let intent = data
+        .db_client
+        .get_payment_intent_by_gateway_id(&event.gateway_intent_id)
+        .await
+        .map_err(HttpError::from)?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::PaymentIntentNotFound))?;
+
+    let new_status = match event.outcome {
+        PaymentOutcome::Succeeded => PaymentIntentStatus::Succeeded,
+        PaymentOutcome::Failed => PaymentIntentStatus::Failed,
+    };
+
+    let tx = match DBTransaction::begin(data.db_client.pool())
+        .await
+        .map_err(HttpError::from)?
+        .update_payment_intent_status(
+            &intent.id,
+            &[
+                PaymentIntentStatus::Pending,
+                PaymentIntentStatus::Processing,
+            ],
+            new_status,
+        )
+        .await
+    {
+        Ok(tx) => tx,
+        Err(sqlx::Error::RowNotFound) => return Ok(()),
+        Err(err) => return Err(HttpError::from(err)),
+    };
+
+    let tx = match event.outcome {
+        PaymentOutcome::Succeeded => tx
+            .increase_user_sold(
+                &intent.user_id,
+                intent.amount_in_cents,
+                LedgerEntryKind::TopUp,
+                None,
+            )
+            .await
+            .map_err(HttpError::from)?,
+        PaymentOutcome::Failed => tx,
+    };
+
+    tx.commit().await.map_err(HttpError::from)
+}
+
+/// The mock gateway has no server to call the webhook route, its webhooks are handled here
+/// every second, the same way
+pub fn handle_mock_webhooks(gateway: Arc<MockGateway>, data: web::Data<AppState>) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(1));
+
+        loop {
+            interval.tick().await;
+
+            for delivery in gateway.take_deliveries() {
+                let (Ok(name), Ok(signature)) = (
+                    HeaderName::from_bytes(SIGNATURE_HEADER.as_bytes()),
+                    HeaderValue::from_str(&delivery.signature),
+                ) else {
+                    continue;
+                };
+
+                let mut headers = HeaderMap::new();
+                headers.insert(name, signature);
+
+                if let Err(err) =
+                    handle_webhook(&data, gateway.as_ref(), &headers, &delivery.body).await
+                {
+                    eprintln!("Warning: failed to handle a mock payment webhook: {err}");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http, test, App};
+    use chrono::Utc;
+    use serde_json::json;
+    use sqlx::{Pool, Postgres};
+
+    use crate::{
+        database::{psql::DBClient, SessionExtractor, UserExtractor},
+        payment::mock::{WebhookDelivery, DECLINED_PAYMENT_METHOD},
+        utils::{
+            models::{SessionDevice, UserRole},
+            test_utils::{init_test_users, test_config, test_payment_gateway},
+            token,
+        },
+    };
+
+    use super::*;
+
+    async fn bearer_token(db_client: &DBClient, user_id: &Uuid) -> String {
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            user_id,
+            UserRole::Customer,
+            &test_config().jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        format!("Bearer {token}")
+    }
+
+    fn create_intent_request(bearer: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer))
+            .uri("/payments/intents")
+            .set_json(json!({ "amountInCents": 500 }))
+    }
+
+    fn confirm_intent_request(bearer: &str, id: &Uuid, payment_method: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .insert_header((http::header::AUTHORIZATION, bearer))
+            .uri(&format!("/payments/intents/{id}/confirm"))
+            .set_json(json!({ "paymentMethod": payment_method }))
+    }
+
+    fn webhook_request(delivery: &WebhookDelivery) -> test::TestRequest {
+        test::TestRequest::post()
+            .insert_header((SIGNATURE_HEADER, delivery.signature.as_str()))
+            .uri("/payments/webhook")
+            .set_payload(delivery.body.clone())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn top_up_credited_by_webhook(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+        let (gateway, gateway_data) = test_payment_gateway();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .app_data(gateway_data)
+                .configure(config),
+        )
+        .await;
+
+        let bearer = bearer_token(&db_client, &user_id).await;
+
+        let resp = test::call_service(&app, create_intent_request(&bearer).to_request()).await;
+
+        assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+        let intent: PaymentIntentResponseDto = test::read_body_json(resp).await;
+
+        assert_eq!(intent.data.status, PaymentIntentStatus::Pending);
+
+        let req = confirm_intent_request(&bearer, &intent.data.id, "mock_card_visa").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+
+        let confirmed: PaymentIntentResponseDto = test::read_body_json(resp).await;
+
+        assert_eq!(confirmed.data.status, PaymentIntentStatus::Processing);
+
+        // nothing is credited before the webhook
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+        assert_eq!(user.sold_in_cents, 0);
+
+        let deliveries = gateway.take_deliveries();
+        assert_eq!(deliveries.len(), 1);
+
+        // the gateway may send the same event twice
+        for _ in 0..2 {
+            let resp = test::call_service(&app, webhook_request(&deliveries[0]).to_request()).await;
+
+            assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        }
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+        assert_eq!(user.sold_in_cents, 500);
+
+        let req = test::TestRequest::get()
+            .insert_header((http::header::AUTHORIZATION, bearer.as_str()))
+            .uri(&format!("/payments/intents/{}", intent.data.id))
+            .to_request();
+        let resp: PaymentIntentResponseDto = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(resp.data.status, PaymentIntentStatus::Succeeded);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn declined_payment_not_credited(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+        let (gateway, gateway_data) = test_payment_gateway();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .app_data(gateway_data)
+                .configure(config),
+        )
+        .await;
+
+        let bearer = bearer_token(&db_client, &user_id).await;
+
+        let intent: PaymentIntentResponseDto =
+            test::call_and_read_body_json(&app, create_intent_request(&bearer).to_request()).await;
+
+        let req =
+            confirm_intent_request(&bearer, &intent.data.id, DECLINED_PAYMENT_METHOD).to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::ACCEPTED);
+
+        let req = confirm_intent_request(&bearer, &intent.data.id, "mock_card_visa").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+        for delivery in gateway.take_deliveries() {
+            let resp = test::call_service(&app, webhook_request(&delivery).to_request()).await;
+
+            assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
+        }
+
+        let intent = db_client
+            .get_payment_intent_if_belong_to_user(&user_id, &intent.data.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+
+        assert_eq!(intent.status, PaymentIntentStatus::Failed);
+        assert_eq!(user.sold_in_cents, 0);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn rejected_payment_back_to_pending(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+        let (gateway, gateway_data) = test_payment_gateway();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .app_data(gateway_data)
+                .configure(config),
+        )
+        .await;
+
+        let bearer = bearer_token(&db_client, &user_id).await;
+
+        let intent: PaymentIntentResponseDto =
+            test::call_and_read_body_json(&app, create_intent_request(&bearer).to_request()).await;
+        let saved = db_client
+            .get_payment_intent_if_belong_to_user(&user_id, &intent.data.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the gateway no longer knows it, it refuses to confirm it
+        gateway
+            .confirm_intent(&saved.gateway_intent_id, "mock_card_visa")
+            .await
+            .unwrap();
+        gateway.take_deliveries();
+
+        let req = confirm_intent_request(&bearer, &intent.data.id, "mock_card_visa").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::PAYMENT_REQUIRED);
+
+        let saved = db_client
+            .get_payment_intent_if_belong_to_user(&user_id, &intent.data.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(saved.status, PaymentIntentStatus::Pending);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn webhook_with_forged_signature(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool);
+        let (gateway, gateway_data) = test_payment_gateway();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: db_client.clone(),
+                }))
+                .app_data(gateway_data)
+                .configure(config),
+        )
+        .await;
+
+        let bearer = bearer_token(&db_client, &user_id).await;
+
+        let intent: PaymentIntentResponseDto =
+            test::call_and_read_body_json(&app, create_intent_request(&bearer).to_request()).await;
+
+        let req = confirm_intent_request(&bearer, &intent.data.id, "mock_card_visa").to_request();
+        test::call_service(&app, req).await;
+
+        let delivery = gateway.take_deliveries().remove(0);
+        let forged = WebhookDelivery {
+            signature: MockGateway::new("guessed-secret")
+                .sign(Utc::now().timestamp(), &delivery.body),
+            body: delivery.body,
+        };
+
+        let resp = test::call_service(&app, webhook_request(&forged).to_request()).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+        assert_eq!(user.sold_in_cents, 0);
+    }
+}
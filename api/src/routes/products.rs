@@ -3,16 +3,17 @@ use crate::{
     dtos::{
         products::{
             CreateProductDto, FilterProductDto, FilterProductListResponseDto,
-            FilterProductResponseDto, ProductDto, ProductResponseDto,
+            FilterProductResponseDto, ModifyProductDto, ProductDto, ProductResponseDto,
+            SearchProductsQueryDto,
         },
         RequestQueryDto,
     },
     error::{ErrorMessage, HttpError},
-    middleware::{Authenticated, RequireAuth},
-    utils::{status::Status, AppState},
+    middleware::{Authenticated, RequireAuth, RequireScope},
+    utils::{models::ApiKeyScope, status::Status, AppState},
 };
 use actix_web::{
-    delete, get, post,
+    delete, get, patch, post,
     web::{self, Json, Path, Query},
     HttpResponse,
 };
@@ -22,9 +23,12 @@ use validator::Validate;
 pub(super) fn config(config: &mut web::ServiceConfig) {
     config.service(
         web::scope("/products")
+            // before `get_by_id`, "search" is not a product id
+            .service(search)
             .service(get_by_id)
             .service(get_all)
             .service(delete)
+            .service(modify)
             .service(create),
     );
 }
@@ -45,11 +49,16 @@ pub(super) fn config(config: &mut web::ServiceConfig) {
         (status = 404, description = "Product not found")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["products:read"])
     ),
     tag = "Products"
 )]
-#[get("/{product_id}", wrap = "RequireAuth")]
+#[get(
+    "/{product_id}",
+    wrap = "RequireScope(ApiKeyScope::ProductsRead)",
+    wrap = "RequireAuth"
+)]
 async fn get_by_id(
     id: web::Path<Uuid>,
     data: web::Data<AppState>,
@@ -82,11 +91,16 @@ async fn get_by_id(
         (status = 404, description = "Product not found")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["products:write"])
     ),
     tag = "Products"
 )]
-#[delete("/{product_id}", wrap = "RequireAuth")]
+#[delete(
+    "/{product_id}",
+    wrap = "RequireScope(ApiKeyScope::ProductsWrite)",
+    wrap = "RequireAuth"
+)]
 async fn delete(
     user: Authenticated,
     product_id: Path<Uuid>,
@@ -115,23 +129,95 @@ async fn delete(
     Ok(HttpResponse::NoContent().finish())
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/products/{product_id}",
+    params(
+        ("product_id" = Uuid, Path, description = "Product ID")
+    ),
+    request_body = ModifyProductDto,
+    responses(
+        (status = 200, description = "Product updated successfully", body = ProductResponseDto),
+        (status = 400, description = "Invalid request data"),
+        (status = 401, description = "User not logged in, or not the product's owner"),
+        (status = 404, description = "Product not found")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["products:write"])
+    ),
+    tag = "Products"
+)]
+#[patch(
+    "/{product_id}",
+    wrap = "RequireScope(ApiKeyScope::ProductsWrite)",
+    wrap = "RequireAuth"
+)]
+async fn modify(
+    user: Authenticated,
+    product_id: Path<Uuid>,
+    infos: Json<ModifyProductDto>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let product = data
+        .db_client
+        .get_product(&product_id.into_inner())
+        .await
+        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
+        .ok_or_else(|| HttpError::not_found(ErrorMessage::ProductNoLongerExist))?;
+
+    if product.user_id != user.id {
+        return HttpError::unauthorized(ErrorMessage::PermissionDenied).into();
+    }
+
+    let product = data
+        .db_client
+        .modify_product(
+            &product.id,
+            infos.name.as_deref(),
+            infos.description.as_deref(),
+            infos.price_in_cents,
+            infos.number_in_stock,
+        )
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::ProductNoLongerExist),
+            err => HttpError::from(err),
+        })?;
+
+    Ok(HttpResponse::Ok().json(ProductResponseDto {
+        status: Status::Success,
+        data: ProductDto::from(&product),
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/api/products",
     params(
-        ("page" = Option<usize>, Query, description = "Page number for pagination"),
-        ("limit" = Option<usize>, Query, description = "Number of items per page")
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Number of items per page"),
+        ("with_total" = Option<bool>, Query, description = "Also count all the items")
     ),
     responses(
         (status = 200, description = "Products retrieved successfully", body = FilterProductListResponseDto),
         (status = 401, description = "User not logged in")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["products:read"])
     ),
     tag = "Products"
 )]
-#[get("", wrap = "RequireAuth")]
+#[get(
+    "",
+    wrap = "RequireScope(ApiKeyScope::ProductsRead)",
+    wrap = "RequireAuth"
+)]
 async fn get_all(
     data: web::Data<AppState>,
     query: Query<RequestQueryDto>,
@@ -140,22 +226,88 @@ async fn get_all(
         .validate()
         .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(10);
+    let page = query.page_request().map_err(HttpError::bad_request)?;
 
-    let products: Vec<FilterProductDto> = data
+    let products = data
         .db_client
-        .get_all_products(page as u32, limit)
+        .get_all_products(&page)
         .await
         .map_err(|_err| HttpError::server_error(ErrorMessage::ServerError.to_string()))?
-        .iter()
-        .map(FilterProductDto::filter)
-        .collect();
+        .map(FilterProductDto::filter);
 
     Ok(HttpResponse::Ok().json(FilterProductListResponseDto {
         status: Status::Success,
-        results: products.len(),
-        data: products,
+        results: products.items.len(),
+        data: products.items,
+        next_cursor: products.next_cursor,
+        has_more: products.has_more,
+        total: products.total,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/products/search",
+    params(
+        ("q" = Option<String>, Query, description = "Full-text query over the name and the description"),
+        ("min_price" = Option<i64>, Query, description = "Minimum price in cents"),
+        ("max_price" = Option<i64>, Query, description = "Maximum price in cents"),
+        ("in_stock" = Option<bool>, Query, description = "Only the products in stock"),
+        ("seller_id" = Option<Uuid>, Query, description = "Only the products of this seller"),
+        ("sort" = Option<ProductSort>, Query, description = "relevance (default), newest, price_asc or price_desc"),
+        ("cursor" = Option<String>, Query, description = "Keyset pagination on the sorted value then the id: `next_cursor` of the previous page, with the same sort"),
+        ("limit" = Option<usize>, Query, description = "Number of items per page"),
+        ("with_total" = Option<bool>, Query, description = "Also count all the items")
+    ),
+    responses(
+        (status = 200, description = "Matching products", body = FilterProductListResponseDto),
+        (status = 400, description = "Invalid query parameters, or cursor of another sort"),
+        (status = 401, description = "User not logged in")
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_key" = ["products:read"])
+    ),
+    tag = "Products"
+)]
+#[get(
+    "/search",
+    wrap = "RequireScope(ApiKeyScope::ProductsRead)",
+    wrap = "RequireAuth"
+)]
+async fn search(
+    data: web::Data<AppState>,
+    query: Query<SearchProductsQueryDto>,
+) -> Result<HttpResponse, HttpError> {
+    query
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    if let (Some(min_price), Some(max_price)) = (query.min_price, query.max_price) {
+        if min_price > max_price {
+            return HttpError::bad_request("min_price can not be more than max_price").into();
+        }
+    }
+
+    let search = query.to_search();
+    let page = query
+        .page_request(&search)
+        .map_err(HttpError::bad_request)?;
+
+    let products = data
+        .db_client
+        .search_products(&search, &page)
+        .await
+        .map_err(HttpError::from)?
+        .map(FilterProductDto::filter);
+
+    Ok(HttpResponse::Ok().json(FilterProductListResponseDto {
+        status: Status::Success,
+        results: products.items.len(),
+        data: products.items,
+        next_cursor: products.next_cursor,
+        has_more: products.has_more,
+        total: products.total,
     }))
 }
 
@@ -169,11 +321,16 @@ async fn get_all(
         (status = 401, description = "User not logged in")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["products:write"])
     ),
     tag = "Products"
 )]
-#[post("", wrap = "RequireAuth")]
+#[post(
+    "",
+    wrap = "RequireScope(ApiKeyScope::ProductsWrite)",
+    wrap = "RequireAuth"
+)]
 async fn create(
     user: Authenticated,
     product: Json<CreateProductDto>,
@@ -221,9 +378,10 @@ mod tests {
     use sqlx::{Pool, Postgres};
 
     use crate::{
-        database::{psql::DBClient, UserModifier},
+        database::{psql::DBClient, SessionExtractor},
         error::ErrorMessage,
         utils::{
+            models::{SessionDevice, UserRole},
             test_utils::{init_test_products, test_config},
             token,
         },
@@ -260,19 +418,30 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::get()
             .insert_header((
                 http::header::AUTHORIZATION,
                 http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
             ))
-            .uri("/products?page=1&limit=10")
+            .uri("/products?limit=10")
             .to_request();
 
         let resp = test::call_service(&app, req).await;
@@ -311,7 +480,7 @@ mod tests {
         )
         .await;
 
-        let _ = db_client
+        let computer = db_client
             .save_product(
                 "computer",
                 &data.user_id,
@@ -325,19 +494,30 @@ mod tests {
         let token_id = Uuid::new_v4();
 
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::get()
             .insert_header((
                 http::header::AUTHORIZATION,
                 http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
             ))
-            .uri("/products?page=1&limit=1") // limit to 1 result
+            .uri("/products?limit=1") // limit to 1 result
             .to_request();
 
         let resp = test::call_service(&app, req).await;
@@ -349,7 +529,153 @@ mod tests {
             .expect("Failed to deserialize products response from JSON");
 
         assert_eq!(product_list_response.results, 1);
-        assert_eq!(product_list_response.data[0].id, data.product_id);
+        assert_eq!(product_list_response.data[0].id, computer.id); // newest first
+        assert!(product_list_response.has_more);
+        assert!(product_list_response.next_cursor.is_some());
+        assert_eq!(product_list_response.total, None);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_all_products_with_cursor(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let mut seen = Vec::new();
+        let mut uri = "/products?limit=2&with_total=true".to_string();
+
+        loop {
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri(&uri)
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), http::StatusCode::OK);
+
+            let body = test::read_body(resp).await;
+
+            let product_list_response: FilterProductListResponseDto = serde_json::from_slice(&body)
+                .expect("Failed to deserialize products response from JSON");
+
+            assert_eq!(product_list_response.total, Some(3));
+            seen.extend(product_list_response.data.iter().map(|product| product.id));
+
+            match product_list_response.next_cursor {
+                Some(cursor) => {
+                    assert!(product_list_response.has_more);
+                    uri = format!("/products?limit=2&with_total=true&cursor={cursor}");
+                }
+                None => {
+                    assert!(!product_list_response.has_more);
+                    break;
+                }
+            }
+        }
+
+        // every product exactly once
+        assert_eq!(seen.len(), 3);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 3);
+
+        // the former offset pagination is refused, not ignored
+        for uri in ["/products?page=2", "/products?withTotal=true"] {
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri(uri)
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_all_products_with_invalid_cursor(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/products?cursor=not-a-cursor")
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
@@ -382,13 +708,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token =
-            token::create_token(&Uuid::new_v4(), config.secret_key.as_bytes(), 60, &token_id)
-                .unwrap();
+        let token = token::create_token(
+            &Uuid::new_v4(),
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::get()
             .insert_header((
@@ -431,12 +767,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::get()
             .insert_header((
@@ -477,12 +824,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::get()
             .insert_header((
@@ -524,12 +882,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::post()
             .insert_header((
@@ -581,12 +950,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::post()
             .insert_header((
@@ -635,12 +1015,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::post()
             .insert_header((
@@ -689,12 +1080,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::delete()
             .insert_header((
@@ -734,12 +1136,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::delete()
             .insert_header((
@@ -782,12 +1195,23 @@ mod tests {
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &data.user_id)
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token = token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-            .unwrap();
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let req = test::TestRequest::delete()
             .insert_header((
@@ -832,4 +1256,347 @@ mod tests {
 
         assert_eq!(actual_message, expected_message);
     }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn modify_own_product(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::patch()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/products/{}", &data.product_id))
+            .set_json(ModifyProductDto {
+                price_in_cents: Some(45),
+                number_in_stock: Some(10),
+                ..Default::default()
+            })
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+
+        let response: ProductResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize product from JSON");
+
+        assert_eq!(response.data.name, "shoes");
+        assert_eq!(response.data.price_in_cents, 45);
+        assert_eq!(response.data.number_in_stock, 10);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn modify_someone_else_product(pool: Pool<Postgres>) {
+        let (data, data2, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::patch()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/products/{}", &data2.product_id))
+            .set_json(ModifyProductDto {
+                price_in_cents: Some(1),
+                ..Default::default()
+            })
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+        let product = db_client
+            .get_product(&data2.product_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(product.price_in_cents, 50);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn modify_product_with_negative_price(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::patch()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/products/{}", &data.product_id))
+            .set_json(ModifyProductDto {
+                price_in_cents: Some(-1),
+                ..Default::default()
+            })
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn search_products(pool: Pool<Postgres>) {
+        let (data, data2, data3) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/products/search?max_price=50&in_stock=true&sort=price_asc")
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+
+        let response: FilterProductListResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize products from JSON");
+
+        let ids: Vec<Uuid> = response.data.iter().map(|product| product.id).collect();
+
+        assert_eq!(
+            ids,
+            vec![data3.product_id, data.product_id, data2.product_id]
+        );
+
+        // the same, one page at a time
+        let mut seen = Vec::new();
+        let mut uri =
+            "/products/search?max_price=50&sort=price_asc&limit=1&with_total=true".to_string();
+
+        loop {
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri(&uri)
+                .to_request();
+
+            let response: FilterProductListResponseDto =
+                test::call_and_read_body_json(&app, req).await;
+
+            assert_eq!(response.total, Some(3));
+            seen.extend(response.data.iter().map(|product| product.id));
+
+            match response.next_cursor {
+                Some(cursor) => {
+                    uri = format!(
+                        "/products/search?max_price=50&sort=price_asc&limit=1&with_total=true&cursor={cursor}"
+                    );
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(seen, ids);
+
+        // a cursor is only valid for its sort
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/products/search?limit=1")
+            .to_request();
+
+        let response: FilterProductListResponseDto = test::call_and_read_body_json(&app, req).await;
+        let cursor = response.next_cursor.expect("Next cursor not found");
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/products/search?sort=price_desc&cursor={cursor}"))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn search_products_with_invalid_price_range(pool: Pool<Postgres>) {
+        let (data, _, _) = init_test_products(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &data.user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &data.user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/products/search?min_price=100&max_price=10")
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
 }
@@ -1,25 +1,32 @@
 use crate::{
     database::{
-        transaction::{DBTransaction, ITransaction},
-        OrderExtractor, ProductExtractor, UserExtractor,
+        AddressExtractor, ApiKeyExtractor, CartExtractor, LedgerExtractor, OrderExtractor,
+        ProductExtractor, SessionExtractor, UserExtractor, UserModifier,
     },
     dtos::{
-        orders::{OrderDto, OrderListResponseDto},
+        addresses::{AddressDto, AddressListResponseDto, AddressResponseDto, SaveAddressDto},
+        cart::{
+            AddCartItemDto, CartItemDto, CartItemListResponseDto, CartItemResponseDto,
+            ModifyCartItemDto,
+        },
+        ledger::{LedgerEntryDto, LedgerEntryListResponseDto},
+        orders::{OrderDto, OrderListResponseDto, SaleDto, SaleListResponseDto, SalesQueryDto},
         products::{
             FilterProductDto, FilterProductListResponseDto, ProductDto, ProductListResponseDto,
         },
         users::{
-            AddSoldDto, FilterForeignUserDto, FilterUserDto, ForeignUserResponseDto,
-            UserListResponseDto, UserResponseDto,
+            ChangePasswordDto, FilterForeignUserDto, FilterUserDto, ForeignUserResponseDto,
+            ModifyUserDto, UserListResponseDto, UserResponseDto,
         },
         RequestQueryDto,
     },
     error::{ErrorMessage, HttpError},
-    middleware::{Authenticated, RequireAuth},
-    utils::{status::Status, AppState},
+    mailer::Mailer,
+    middleware::{Authenticated, RequireAuth, RequireScope},
+    utils::{models::ApiKeyScope, password, status::Status, AppState},
 };
 use actix_web::{
-    delete, get, post,
+    delete, get, patch, post, put,
     web::{self, Data, Path, Query},
     HttpResponse,
 };
@@ -30,12 +37,16 @@ pub(super) fn config(config: &mut web::ServiceConfig) {
     config.service(
         web::scope("/users")
             .service(get_me)
+            .service(modify_me)
+            .service(change_password)
             .service(get_by_id)
             .service(get_all)
             .service(delete)
-            .service(add_sold)
             .configure(orders::config)
-            .configure(products::config),
+            .configure(products::config)
+            .configure(transactions::config)
+            .configure(cart::config)
+            .configure(addresses::config),
     );
 }
 
@@ -55,11 +66,16 @@ pub(super) fn config(config: &mut web::ServiceConfig) {
         (status = 404, description = "User not found")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["users:read"])
     ),
     tag = "Users"
 )]
-#[get("/{user_id}", wrap = "RequireAuth")]
+#[get(
+    "/{user_id}",
+    wrap = "RequireScope(ApiKeyScope::UsersRead)",
+    wrap = "RequireAuth"
+)]
 async fn get_by_id(
     id: web::Path<Uuid>,
     data: web::Data<AppState>,
@@ -87,11 +103,16 @@ async fn get_by_id(
         (status = 401, description = "User not logged in")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["users:read"])
     ),
     tag = "Users"
 )]
-#[get("/me", wrap = "RequireAuth")]
+#[get(
+    "/me",
+    wrap = "RequireScope(ApiKeyScope::UsersRead)",
+    wrap = "RequireAuth"
+)]
 async fn get_me(user: Authenticated) -> Result<HttpResponse, HttpError> {
     let filtered_user = FilterUserDto::filter_user(&user);
 
@@ -104,14 +125,81 @@ async fn get_me(user: Authenticated) -> Result<HttpResponse, HttpError> {
 }
 
 #[utoipa::path(
-    post,
-    path = "/api/users/me/sold",
-    params(
-        ("sold_to_add" = i64, Query, description = "Amount to add to user's balance in cents")
+    patch,
+    path = "/api/users/me",
+    request_body = ModifyUserDto,
+    responses(
+        (status = 200, description = "User updated, a new email has to be verified", body = UserResponseDto),
+        (status = 400, description = "Invalid request data, or missing or wrong current password for a new email"),
+        (status = 401, description = "User not logged in"),
+        (status = 409, description = "Email already exists")
+    ),
+    security(
+        ("bearer_auth" = [])
     ),
+    tag = "Users"
+)]
+#[patch("/me", wrap = "RequireAuth")]
+async fn modify_me(
+    user: Authenticated,
+    infos: web::Json<ModifyUserDto>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    infos
+        .validate()
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+    let email_changed = infos
+        .email
+        .as_ref()
+        .is_some_and(|email| *email != user.email);
+
+    // the email is where the password can be reset, a stolen token is not enough to change it
+    if email_changed {
+        let current_password = infos
+            .current_password
+            .as_deref()
+            .ok_or_else(|| HttpError::bad_request(ErrorMessage::CurrentPasswordRequired))?;
+
+        let is_valid =
+            password::compare(current_password, &user.password).map_err(HttpError::bad_request)?;
+
+        if !is_valid {
+            return HttpError::bad_request(ErrorMessage::WrongPassword).into();
+        }
+    }
+
+    // unique constraint violation -> 409 (EmailExist)
+    let user = data
+        .db_client
+        .modify_user(
+            &user.id,
+            infos.name.as_deref(),
+            infos.email.as_deref(),
+            infos.photo_url.as_deref(),
+        )
+        .await
+        .map_err(HttpError::from)?;
+
+    // the new email is unverified until the link sent to it is followed
+    if email_changed {
+        super::auth::send_email_verification(&data, mailer.get_ref(), &user).await;
+    }
+
+    Ok(HttpResponse::Ok().json(UserResponseDto {
+        status: Status::Success,
+        data: FilterUserDto::filter_user(&user),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/me/password",
+    request_body = ChangePasswordDto,
     responses(
-        (status = 204, description = "Balance updated successfully"),
-        (status = 400, description = "Invalid request data"),
+        (status = 204, description = "Password changed, every session and API key is revoked"),
+        (status = 400, description = "Invalid request data, or wrong current password"),
         (status = 401, description = "User not logged in")
     ),
     security(
@@ -119,23 +207,45 @@ async fn get_me(user: Authenticated) -> Result<HttpResponse, HttpError> {
     ),
     tag = "Users"
 )]
-#[post("/me/sold", wrap = "RequireAuth")]
-async fn add_sold(
+#[post("/me/password", wrap = "RequireAuth")]
+async fn change_password(
     user: Authenticated,
-    infos: Query<AddSoldDto>,
+    infos: web::Json<ChangePasswordDto>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, HttpError> {
     infos
         .validate()
         .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-    DBTransaction::begin(data.db_client.pool())
+    let is_valid =
+        password::compare(&infos.old_password, &user.password).map_err(HttpError::bad_request)?;
+
+    if !is_valid {
+        return HttpError::bad_request(ErrorMessage::WrongPassword).into();
+    }
+
+    data.env
+        .password_policy
+        .check(&infos.new_password)
+        .map_err(HttpError::bad_request)?;
+
+    let hashed_password = password::hash(&infos.new_password, &data.env.password_hash)
+        .map_err(HttpError::server_error)?;
+
+    data.db_client
+        .modify_user_password(&user.id, &hashed_password)
         .await
-        .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
-        .increase_user_sold(&user.id, infos.sold_to_add)
+        .map_err(HttpError::from)?;
+
+    // logs out every session, the user has to log in again with the new password
+    data.db_client
+        .delete_sessions_by_user(&user.id)
         .await
-        .map_err(HttpError::from)?
-        .commit()
+        .map_err(HttpError::from)?;
+
+    // the keys could have been made by whoever knew the old password
+    data.db_client
+        .delete_api_keys_by_user(&user.id)
         .await
         .map_err(HttpError::from)?;
 
@@ -168,19 +278,25 @@ async fn delete(user: Authenticated, data: web::Data<AppState>) -> Result<HttpRe
     get,
     path = "/api/users",
     params(
-        ("page" = Option<usize>, Query, description = "Page number for pagination"),
-        ("limit" = Option<usize>, Query, description = "Number of items per page")
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Number of items per page"),
+        ("with_total" = Option<bool>, Query, description = "Also count all the items")
     ),
     responses(
         (status = 200, description = "Users retrieved successfully", body = UserListResponseDto),
         (status = 401, description = "User not logged in")
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_key" = ["users:read"])
     ),
     tag = "Users"
 )]
-#[get("", wrap = "RequireAuth")]
+#[get(
+    "",
+    wrap = "RequireScope(ApiKeyScope::UsersRead)",
+    wrap = "RequireAuth"
+)]
 async fn get_all(
     data: web::Data<AppState>,
     query: Query<RequestQueryDto>,
@@ -189,22 +305,22 @@ async fn get_all(
         .validate()
         .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(10);
+    let page = query.page_request().map_err(HttpError::bad_request)?;
 
-    let users: Vec<FilterForeignUserDto> = data
+    let users = data
         .db_client
-        .get_all_users(page as u32, limit)
+        .get_all_users(&page)
         .await
         .map_err(|_err| HttpError::server_error(ErrorMessage::ServerError.to_string()))?
-        .iter()
-        .map(FilterForeignUserDto::filter_user)
-        .collect();
+        .map(FilterForeignUserDto::filter_user);
 
     Ok(HttpResponse::Ok().json(UserListResponseDto {
         status: Status::Success,
-        results: users.len(),
-        data: users,
+        results: users.items.len(),
+        data: users.items,
+        next_cursor: users.next_cursor,
+        has_more: users.has_more,
+        total: users.total,
     }))
 }
 
@@ -220,40 +336,46 @@ pub mod products {
         get,
         path = "/api/users/me/products",
         params(
-            ("page" = Option<usize>, Query, description = "Page number for pagination"),
-            ("limit" = Option<usize>, Query, description = "Number of items per page")
+            ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+            ("limit" = Option<usize>, Query, description = "Number of items per page"),
+            ("with_total" = Option<bool>, Query, description = "Also count all the items")
         ),
         responses(
             (status = 200, description = "User's products retrieved successfully", body = ProductListResponseDto),
             (status = 401, description = "User not logged in")
         ),
         security(
-            ("bearer_auth" = [])
+            ("bearer_auth" = []),
+            ("api_key" = ["products:read"])
         ),
         tag = "Users"
     )]
-    #[get("/me/products", wrap = "RequireAuth")]
+    #[get(
+        "/me/products",
+        wrap = "RequireScope(ApiKeyScope::ProductsRead)",
+        wrap = "RequireAuth"
+    )]
     async fn get_my_products(
         user: Authenticated,
         query: Query<RequestQueryDto>,
         data: Data<AppState>,
     ) -> Result<HttpResponse, HttpError> {
-        let page = query.page.unwrap_or(1);
-        let limit = query.limit.unwrap_or(10);
+        let page = query.page_request().map_err(HttpError::bad_request)?;
 
-        let products: Vec<ProductDto> = data
+        let products = data
             .db_client
-            .get_products_by_user(&user.id, page as u32, limit)
+            .get_products_by_user(&user.id, &page)
             .await
             .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
-            .iter()
-            .map(ProductDto::from)
-            .collect();
+            .map(ProductDto::from);
 
         Ok(HttpResponse::Ok().json(ProductListResponseDto {
             status: Status::Success,
-            results: products.len(),
-            data: products,
+            results: products.items.len(),
+            data: products.items,
+            next_cursor: products.next_cursor,
+            has_more: products.has_more,
+            total: products.total,
         }))
     }
 
@@ -262,8 +384,9 @@ pub mod products {
         path = "/api/users/{user_id}/products",
         params(
             ("user_id" = Uuid, Path, description = "User ID"),
-            ("page" = Option<usize>, Query, description = "Page number for pagination"),
-            ("limit" = Option<usize>, Query, description = "Number of items per page")
+            ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+            ("limit" = Option<usize>, Query, description = "Number of items per page"),
+            ("with_total" = Option<bool>, Query, description = "Also count all the items")
         ),
         responses(
             (status = 200, description = "User's products retrieved successfully", body = FilterProductListResponseDto),
@@ -271,18 +394,22 @@ pub mod products {
             (status = 404, description = "User not found")
         ),
         security(
-            ("bearer_auth" = [])
+            ("bearer_auth" = []),
+            ("api_key" = ["products:read"])
         ),
         tag = "Users"
     )]
-    #[get("/{user_id}/products", wrap = "RequireAuth")]
+    #[get(
+        "/{user_id}/products",
+        wrap = "RequireScope(ApiKeyScope::ProductsRead)",
+        wrap = "RequireAuth"
+    )]
     async fn get_user_products(
         user_id: Path<Uuid>,
         query: Query<RequestQueryDto>,
         data: Data<AppState>,
     ) -> Result<HttpResponse, HttpError> {
-        let page = query.page.unwrap_or(1);
-        let limit = query.limit.unwrap_or(10);
+        let page = query.page_request().map_err(HttpError::bad_request)?;
 
         let user = data
             .db_client
@@ -291,19 +418,20 @@ pub mod products {
             .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
             .ok_or_else(|| HttpError::not_found(ErrorMessage::UserNoLongerExist))?; // check if user exists
 
-        let products: Vec<FilterProductDto> = data
+        let products = data
             .db_client
-            .get_products_by_user(&user.id, page as u32, limit)
+            .get_products_by_user(&user.id, &page)
             .await
             .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
-            .iter()
-            .map(FilterProductDto::filter)
-            .collect();
+            .map(FilterProductDto::filter);
 
         Ok(HttpResponse::Ok().json(FilterProductListResponseDto {
             status: Status::Success,
-            results: products.len(),
-            data: products,
+            results: products.items.len(),
+            data: products.items,
+            next_cursor: products.next_cursor,
+            has_more: products.has_more,
+            total: products.total,
         }))
     }
 }
@@ -313,26 +441,32 @@ pub mod orders {
     use super::*;
 
     pub(super) fn config(config: &mut web::ServiceConfig) {
-        config.service(get_my_orders);
+        config.service(get_my_orders).service(get_my_sales);
     }
 
     #[utoipa::path(
         get,
         path = "/api/users/me/orders",
         params(
-            ("page" = Option<usize>, Query, description = "Page number for pagination"),
-            ("limit" = Option<usize>, Query, description = "Number of items per page")
+            ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+            ("limit" = Option<usize>, Query, description = "Number of items per page"),
+            ("with_total" = Option<bool>, Query, description = "Also count all the items")
         ),
         responses(
             (status = 200, description = "User's orders retrieved successfully", body = OrderListResponseDto),
             (status = 401, description = "User not logged in")
         ),
         security(
-            ("bearer_auth" = [])
+            ("bearer_auth" = []),
+            ("api_key" = ["orders:read"])
         ),
         tag = "Users"
     )]
-    #[get("/me/orders", wrap = "RequireAuth")]
+    #[get(
+        "/me/orders",
+        wrap = "RequireScope(ApiKeyScope::OrdersRead)",
+        wrap = "RequireAuth"
+    )]
     async fn get_my_orders(
         user: Authenticated,
         query: Query<RequestQueryDto>,
@@ -342,168 +476,948 @@ pub mod orders {
             .validate()
             .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-        let page = query.page.unwrap_or(1);
-        let limit = query.limit.unwrap_or(10);
+        let page = query.page_request().map_err(HttpError::bad_request)?;
 
-        let orders: Vec<OrderDto> = data
+        let orders = data
             .db_client
-            .get_orders_by_user(&user.id, page as u32, limit)
+            .get_orders_by_user(&user.id, &page)
             .await
             .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
-            .iter()
-            .map(OrderDto::from)
-            .collect();
+            .map(OrderDto::from);
 
         Ok(HttpResponse::Ok().json(OrderListResponseDto {
             status: Status::Success,
-            results: orders.len(),
-            data: orders,
+            results: orders.items.len(),
+            data: orders.items,
+            next_cursor: orders.next_cursor,
+            has_more: orders.has_more,
+            total: orders.total,
         }))
     }
-}
 
-// #[put("/{user_id}/sold", wrap = "RequireAuth")]
-// async fn add_sold(
-//     id: Path<i32>,
-//     infos: Query<AddSoldModel>,
-//     data: web::Data<AppState>
-// ) -> HttpResponse {
+    #[utoipa::path(
+        get,
+        path = "/api/users/me/sales",
+        params(
+            ("status" = Option<OrderStatus>, Query, description = "Only the orders in this status"),
+            ("from" = Option<String>, Query, description = "Orders created at or after this date (RFC 3339)"),
+            ("to" = Option<String>, Query, description = "Orders created before this date (RFC 3339)"),
+            ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+            ("limit" = Option<usize>, Query, description = "Number of items per page"),
+            ("with_total" = Option<bool>, Query, description = "Also count all the items")
+        ),
+        responses(
+            (status = 200, description = "Orders placed on the user's products, with only their items", body = SaleListResponseDto),
+            (status = 400, description = "Invalid query parameters"),
+            (status = 401, description = "User not logged in")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["orders:read"])
+        ),
+        tag = "Users"
+    )]
+    #[get(
+        "/me/sales",
+        wrap = "RequireScope(ApiKeyScope::OrdersRead)",
+        wrap = "RequireAuth"
+    )]
+    async fn get_my_sales(
+        user: Authenticated,
+        query: Query<SalesQueryDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        query
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-//     match db_services::users::add_sold_to_user(id.into_inner(), infos.sold_to_add, &data.db_client).await {
-//         Ok(user) => HttpResponse::Ok().json(user),
-//         Err(err) => err,
-//     }
+        let page = query.page_request().map_err(HttpError::bad_request)?;
 
-// }
+        let orders = data
+            .db_client
+            .get_sales_by_seller(&user.id, &query.to_search(), &page)
+            .await
+            .map_err(HttpError::from)?;
 
-#[cfg(test)]
-mod tests {
-    use actix_web::{
-        http::{self, header::HeaderValue},
-        test, App,
-    };
-    use sqlx::{Pool, Postgres};
+        let mut sales = Vec::with_capacity(orders.items.len());
 
-    use crate::{
-        database::{psql::DBClient, UserModifier},
-        error::{ErrorMessage, ErrorResponse},
-        utils::{
-            password,
-            test_utils::{init_test_orders, init_test_products, init_test_users, test_config},
-            token,
-        },
-    };
+        for order in &orders.items {
+            // the other sellers' items are none of the user's business
+            let items = data
+                .db_client
+                .get_order_items_by_seller(&order.id, &user.id)
+                .await
+                .map_err(HttpError::from)?;
 
-    use super::*;
+            sales.push(SaleDto::from(order, &items));
+        }
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_by_id_with_valid_token_and_id(pool: Pool<Postgres>) {
-        let (user_id, _, _) = init_test_users(&pool).await;
-        let db_client = DBClient::new(pool.clone());
-        let config = test_config();
+        Ok(HttpResponse::Ok().json(SaleListResponseDto {
+            status: Status::Success,
+            results: sales.len(),
+            data: sales,
+            next_cursor: orders.next_cursor,
+            has_more: orders.has_more,
+            total: orders.total,
+        }))
+    }
+}
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &user_id)
-            .await
-            .unwrap();
+#[allow(clippy::wildcard_imports)]
+pub mod transactions {
+    use super::*;
 
-        let token =
-            token::create_token(&user_id, config.secret_key.as_bytes(), 60, &token_id).unwrap();
+    pub(super) fn config(config: &mut web::ServiceConfig) {
+        config.service(get_my_transactions);
+    }
 
-        let initial_user = db_client
-            .get_user(&user_id)
-            .await
-            .expect("Failed to get user by id")
-            .unwrap();
+    #[utoipa::path(
+        get,
+        path = "/api/users/me/transactions",
+        params(
+            ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+            ("limit" = Option<usize>, Query, description = "Number of items per page"),
+            ("with_total" = Option<bool>, Query, description = "Also count all the items")
+        ),
+        responses(
+            (status = 200, description = "User's balance history retrieved successfully", body = LedgerEntryListResponseDto),
+            (status = 400, description = "Invalid query parameters"),
+            (status = 401, description = "User not logged in")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["users:read"])
+        ),
+        tag = "Users"
+    )]
+    #[get(
+        "/me/transactions",
+        wrap = "RequireScope(ApiKeyScope::UsersRead)",
+        wrap = "RequireAuth"
+    )]
+    async fn get_my_transactions(
+        user: Authenticated,
+        query: Query<RequestQueryDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        query
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-        let app = test::init_service(
-            App::new()
-                .app_data(web::Data::new(AppState {
-                    env: config.clone(),
-                    db_client,
-                }))
-                .configure(super::config),
-        )
-        .await;
+        let page = query.page_request().map_err(HttpError::bad_request)?;
 
-        let req = test::TestRequest::get()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/users/{user_id}"))
-            .to_request();
+        let entries = data
+            .db_client
+            .get_ledger_entries_by_user(&user.id, &page)
+            .await
+            .map_err(|_| HttpError::server_error(ErrorMessage::ServerError))?
+            .map(LedgerEntryDto::from);
 
-        let resp = test::call_service(&app, req).await;
+        Ok(HttpResponse::Ok().json(LedgerEntryListResponseDto {
+            status: Status::Success,
+            results: entries.items.len(),
+            data: entries.items,
+            next_cursor: entries.next_cursor,
+            has_more: entries.has_more,
+            total: entries.total,
+        }))
+    }
+}
 
-        assert_eq!(resp.status(), http::StatusCode::OK);
+#[allow(clippy::wildcard_imports)]
+pub mod cart {
+    use super::*;
 
-        let body = test::read_body(resp).await;
+    pub(super) fn config(config: &mut web::ServiceConfig) {
+        config
+            .service(get_my_cart)
+            .service(add_to_my_cart)
+            .service(modify_my_cart_item)
+            .service(delete_my_cart_item);
+    }
 
-        let user_response: ForeignUserResponseDto =
-            serde_json::from_slice(&body).expect("Failed to deserialize user response from JSON");
-        let responded_user = user_response.data;
+    #[utoipa::path(
+        get,
+        path = "/api/users/me/cart",
+        responses(
+            (status = 200, description = "User's cart retrieved successfully", body = CartItemListResponseDto),
+            (status = 401, description = "User not logged in")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["cart:read"])
+        ),
+        tag = "Cart"
+    )]
+    #[get(
+        "/me/cart",
+        wrap = "RequireScope(ApiKeyScope::CartRead)",
+        wrap = "RequireAuth"
+    )]
+    async fn get_my_cart(
+        user: Authenticated,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        let items: Vec<CartItemDto> = data
+            .db_client
+            .get_cart_items(&user.id)
+            .await
+            .map_err(HttpError::from)?
+            .iter()
+            .map(CartItemDto::from)
+            .collect();
 
-        assert_eq!(
-            responded_user,
-            FilterForeignUserDto::filter_user(&initial_user)
-        );
+        Ok(HttpResponse::Ok().json(CartItemListResponseDto {
+            status: Status::Success,
+            results: items.len(),
+            data: items,
+        }))
     }
 
-    #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_by_id_with_invalid_id(pool: Pool<Postgres>) {
-        let (user_id, _, _) = init_test_users(&pool).await;
-        let db_client = DBClient::new(pool.clone());
-        let config = test_config();
+    #[utoipa::path(
+        post,
+        path = "/api/users/me/cart",
+        request_body = AddCartItemDto,
+        responses(
+            (status = 200, description = "Product added to the cart", body = CartItemResponseDto),
+            (status = 400, description = "Invalid request data, or own product"),
+            (status = 401, description = "User not logged in"),
+            (status = 404, description = "Product not found")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["cart:write"])
+        ),
+        tag = "Cart"
+    )]
+    #[post(
+        "/me/cart",
+        wrap = "RequireScope(ApiKeyScope::CartWrite)",
+        wrap = "RequireAuth"
+    )]
+    async fn add_to_my_cart(
+        user: Authenticated,
+        infos: web::Json<AddCartItemDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        infos
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &user_id)
+        data.db_client
+            .get_product(&infos.product_id)
             .await
-            .unwrap();
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::not_found(ErrorMessage::ProductNotFound))?;
 
-        let token =
-            token::create_token(&user_id, config.secret_key.as_bytes(), 60, &token_id).unwrap();
-
-        assert!(matches!(db_client.get_user(&user_id).await, Ok(Some(_))));
+        let item = data
+            .db_client
+            .save_cart_item(&user.id, &infos.product_id, infos.products_number)
+            .await
+            .map_err(HttpError::from)?;
 
-        let app = test::init_service(
-            App::new()
-                .app_data(web::Data::new(AppState {
-                    env: config.clone(),
-                    db_client,
-                }))
-                .configure(super::config),
-        )
-        .await;
+        Ok(HttpResponse::Ok().json(CartItemResponseDto {
+            status: Status::Success,
+            data: CartItemDto::from(&item),
+        }))
+    }
 
-        let req = test::TestRequest::get()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri(&format!("/users/{}", Uuid::new_v4()))
+    #[utoipa::path(
+        patch,
+        path = "/api/users/me/cart/{product_id}",
+        params(
+            ("product_id" = Uuid, Path, description = "Product ID")
+        ),
+        request_body = ModifyCartItemDto,
+        responses(
+            (status = 200, description = "Cart line updated", body = CartItemResponseDto),
+            (status = 400, description = "Invalid request data"),
+            (status = 401, description = "User not logged in"),
+            (status = 404, description = "Product not in the cart")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["cart:write"])
+        ),
+        tag = "Cart"
+    )]
+    #[patch(
+        "/me/cart/{product_id}",
+        wrap = "RequireScope(ApiKeyScope::CartWrite)",
+        wrap = "RequireAuth"
+    )]
+    async fn modify_my_cart_item(
+        user: Authenticated,
+        product_id: Path<Uuid>,
+        infos: web::Json<ModifyCartItemDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        infos
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+        let item = data
+            .db_client
+            .modify_cart_item(&user.id, &product_id, infos.products_number)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::CartItemNotFound),
+                err => HttpError::from(err),
+            })?;
+
+        Ok(HttpResponse::Ok().json(CartItemResponseDto {
+            status: Status::Success,
+            data: CartItemDto::from(&item),
+        }))
+    }
+
+    #[utoipa::path(
+        delete,
+        path = "/api/users/me/cart/{product_id}",
+        params(
+            ("product_id" = Uuid, Path, description = "Product ID")
+        ),
+        responses(
+            (status = 204, description = "Product removed from the cart"),
+            (status = 401, description = "User not logged in"),
+            (status = 404, description = "Product not in the cart")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["cart:write"])
+        ),
+        tag = "Cart"
+    )]
+    #[delete(
+        "/me/cart/{product_id}",
+        wrap = "RequireScope(ApiKeyScope::CartWrite)",
+        wrap = "RequireAuth"
+    )]
+    async fn delete_my_cart_item(
+        user: Authenticated,
+        product_id: Path<Uuid>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        data.db_client
+            .delete_cart_item(&user.id, &product_id)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::CartItemNotFound),
+                err => HttpError::from(err),
+            })?;
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+#[allow(clippy::wildcard_imports)]
+pub mod addresses {
+    use super::*;
+
+    pub(super) fn config(config: &mut web::ServiceConfig) {
+        config
+            .service(get_my_addresses)
+            .service(get_my_address)
+            .service(create_my_address)
+            .service(modify_my_address)
+            .service(delete_my_address);
+    }
+
+    fn address_not_found(err: sqlx::Error) -> HttpError {
+        match err {
+            sqlx::Error::RowNotFound => HttpError::not_found(ErrorMessage::AddressNotFound),
+            err => HttpError::from(err),
+        }
+    }
+
+    #[utoipa::path(
+        get,
+        path = "/api/users/me/addresses",
+        params(
+            ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+            ("limit" = Option<usize>, Query, description = "Number of items per page"),
+            ("with_total" = Option<bool>, Query, description = "Also count all the items")
+        ),
+        responses(
+            (status = 200, description = "User's addresses retrieved successfully", body = AddressListResponseDto),
+            (status = 400, description = "Invalid query parameters"),
+            (status = 401, description = "User not logged in")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["users:read"])
+        ),
+        tag = "Addresses"
+    )]
+    #[get(
+        "/me/addresses",
+        wrap = "RequireScope(ApiKeyScope::UsersRead)",
+        wrap = "RequireAuth"
+    )]
+    async fn get_my_addresses(
+        user: Authenticated,
+        query: Query<RequestQueryDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        query
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+        let page = query.page_request().map_err(HttpError::bad_request)?;
+
+        let addresses = data
+            .db_client
+            .get_addresses_by_user(&user.id, &page)
+            .await
+            .map_err(HttpError::from)?
+            .map(AddressDto::from);
+
+        Ok(HttpResponse::Ok().json(AddressListResponseDto {
+            status: Status::Success,
+            results: addresses.items.len(),
+            data: addresses.items,
+            next_cursor: addresses.next_cursor,
+            has_more: addresses.has_more,
+            total: addresses.total,
+        }))
+    }
+
+    #[utoipa::path(
+        get,
+        path = "/api/users/me/addresses/{address_id}",
+        params(
+            ("address_id" = Uuid, Path, description = "Address ID")
+        ),
+        responses(
+            (status = 200, description = "Address found", body = AddressResponseDto),
+            (status = 401, description = "User not logged in"),
+            (status = 404, description = "Address not found")
+        ),
+        security(
+            ("bearer_auth" = []),
+            ("api_key" = ["users:read"])
+        ),
+        tag = "Addresses"
+    )]
+    #[get(
+        "/me/addresses/{address_id}",
+        wrap = "RequireScope(ApiKeyScope::UsersRead)",
+        wrap = "RequireAuth"
+    )]
+    async fn get_my_address(
+        user: Authenticated,
+        address_id: Path<Uuid>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        let address = data
+            .db_client
+            .get_address_if_belong_to_user(&user.id, &address_id)
+            .await
+            .map_err(HttpError::from)?
+            .ok_or_else(|| HttpError::not_found(ErrorMessage::AddressNotFound))?;
+
+        Ok(HttpResponse::Ok().json(AddressResponseDto {
+            status: Status::Success,
+            data: AddressDto::from(&address),
+        }))
+    }
+
+    #[utoipa::path(
+        post,
+        path = "/api/users/me/addresses",
+        request_body = SaveAddressDto,
+        responses(
+            (status = 201, description = "Address created", body = AddressResponseDto),
+            (status = 400, description = "Invalid request data"),
+            (status = 401, description = "User not logged in")
+        ),
+        security(
+            ("bearer_auth" = [])
+        ),
+        tag = "Addresses"
+    )]
+    #[post("/me/addresses", wrap = "RequireAuth")]
+    async fn create_my_address(
+        user: Authenticated,
+        infos: web::Json<SaveAddressDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        infos
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+        let address = data
+            .db_client
+            .save_address(
+                &user.id,
+                &infos.street,
+                &infos.city,
+                &infos.postal_code,
+                &infos.country,
+            )
+            .await
+            .map_err(HttpError::from)?;
+
+        Ok(HttpResponse::Created().json(AddressResponseDto {
+            status: Status::Success,
+            data: AddressDto::from(&address),
+        }))
+    }
+
+    #[utoipa::path(
+        put,
+        path = "/api/users/me/addresses/{address_id}",
+        params(
+            ("address_id" = Uuid, Path, description = "Address ID")
+        ),
+        request_body = SaveAddressDto,
+        responses(
+            (status = 200, description = "Address updated", body = AddressResponseDto),
+            (status = 400, description = "Invalid request data"),
+            (status = 401, description = "User not logged in"),
+            (status = 404, description = "Address not found")
+        ),
+        security(
+            ("bearer_auth" = [])
+        ),
+        tag = "Addresses"
+    )]
+    #[put("/me/addresses/{address_id}", wrap = "RequireAuth")]
+    async fn modify_my_address(
+        user: Authenticated,
+        address_id: Path<Uuid>,
+        infos: web::Json<SaveAddressDto>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        infos
+            .validate()
+            .map_err(|err| HttpError::bad_request(err.to_string()))?;
+
+        let address = data
+            .db_client
+            .modify_address(
+                &user.id,
+                &address_id,
+                &infos.street,
+                &infos.city,
+                &infos.postal_code,
+                &infos.country,
+            )
+            .await
+            .map_err(address_not_found)?;
+
+        Ok(HttpResponse::Ok().json(AddressResponseDto {
+            status: Status::Success,
+            data: AddressDto::from(&address),
+        }))
+    }
+
+    #[utoipa::path(
+        delete,
+        path = "/api/users/me/addresses/{address_id}",
+        params(
+            ("address_id" = Uuid, Path, description = "Address ID")
+        ),
+        responses(
+            (status = 204, description = "Address deleted, the orders using it keep no address"),
+            (status = 401, description = "User not logged in"),
+            (status = 404, description = "Address not found")
+        ),
+        security(
+            ("bearer_auth" = [])
+        ),
+        tag = "Addresses"
+    )]
+    #[delete("/me/addresses/{address_id}", wrap = "RequireAuth")]
+    async fn delete_my_address(
+        user: Authenticated,
+        address_id: Path<Uuid>,
+        data: web::Data<AppState>,
+    ) -> Result<HttpResponse, HttpError> {
+        data.db_client
+            .delete_address(&user.id, &address_id)
+            .await
+            .map_err(address_not_found)?;
+
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+// #[put("/{user_id}/sold", wrap = "RequireAuth")]
+// async fn add_sold(
+//     id: Path<i32>,
+//     infos: Query<AddSoldModel>,
+//     data: web::Data<AppState>
+// ) -> HttpResponse {
+
+//     match db_services::users::add_sold_to_user(id.into_inner(), infos.sold_to_add, &data.db_client).await {
+//         Ok(user) => HttpResponse::Ok().json(user),
+//         Err(err) => err,
+//     }
+
+// }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{self, header::HeaderValue},
+        test, App,
+    };
+    use sqlx::{Pool, Postgres};
+
+    use crate::{
+        database::{
+            psql::DBClient,
+            transaction::{DBTransaction, ITransaction},
+        },
+        error::{ErrorMessage, ErrorResponse},
+        utils::{
+            models::{LedgerEntryKind, SessionDevice, UserRole},
+            pagination::PageRequest,
+            password,
+            test_utils::{
+                init_test_orders, init_test_products, init_test_users, test_config, test_mailer,
+            },
+            token,
+        },
+    };
+
+    use super::*;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_by_id_with_valid_token_and_id(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let initial_user = db_client
+            .get_user(&user_id)
+            .await
+            .expect("Failed to get user by id")
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/users/{user_id}"))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+
+        let user_response: ForeignUserResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize user response from JSON");
+        let responded_user = user_response.data;
+
+        assert_eq!(
+            responded_user,
+            FilterForeignUserDto::filter_user(&initial_user)
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_by_id_with_invalid_id(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        assert!(matches!(db_client.get_user(&user_id).await, Ok(Some(_))));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri(&format!("/users/{}", Uuid::new_v4()))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let body = test::read_body(resp).await;
+
+        let user_response: ErrorResponse =
+            serde_json::from_slice(&body).expect("Failed to deserialize user response from JSON");
+
+        let expected_message = ErrorMessage::UserNoLongerExist.to_string();
+        let actual_message = user_response.message;
+
+        assert_eq!(actual_message, expected_message);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_by_id_with_invalid_token(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str("Bearer invalid-token").unwrap(),
+            ))
+            .uri(&format!("/users/{user_id}"))
+            .to_request();
+
+        let result = test::try_call_service(&app, req).await.err();
+
+        match result {
+            Some(err) => {
+                let expected_status = http::StatusCode::UNAUTHORIZED;
+                let actual_status = err.as_response_error().status_code();
+
+                assert_eq!(actual_status, expected_status);
+
+                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
+                    .expect("Failed to deserialize JSON string");
+                let expected_message = ErrorMessage::InvalidToken.to_string();
+                assert_eq!(err_response.message, expected_message);
+            }
+            None => {
+                panic!("Service call succeeded, but an error was expected.");
+            }
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_by_id_with_missing_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/users/{user_id}"))
+            .to_request();
+
+        let result = test::try_call_service(&app, req).await.err();
+
+        match result {
+            Some(err) => {
+                let expected_status = http::StatusCode::UNAUTHORIZED;
+                let actual_status = err.as_response_error().status_code();
+
+                assert_eq!(actual_status, expected_status);
+
+                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
+                    .expect("Failed to deserialize JSON string");
+                let expected_message = ErrorMessage::TokenNotProvided.to_string();
+                assert_eq!(err_response.message, expected_message);
+            }
+            None => {
+                panic!("Service call succeeded, but an error was expected.");
+            }
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_by_id_with_expired_token(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client: db_client.clone(),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let expired_token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            -60,
+            &token_id,
+        )
+        .unwrap();
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {expired_token}")).unwrap(),
+            ))
+            .uri(&format!("/users/{user_id}"))
+            .to_request();
+
+        let result = test::try_call_service(&app, req).await.err();
+
+        match result {
+            Some(err) => {
+                let expected_status = http::StatusCode::UNAUTHORIZED;
+                let actual_status = err.as_response_error().status_code();
+
+                assert_eq!(actual_status, expected_status);
+
+                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
+                    .expect("Failed to deserialize JSON string");
+                let expected_message = ErrorMessage::InvalidToken.to_string();
+                assert_eq!(err_response.message, expected_message);
+            }
+            None => {
+                panic!("Service call succeeded, but an error was expected.");
+            }
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn get_me_with_valid_token(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/users/me")
             .to_request();
 
         let resp = test::call_service(&app, req).await;
 
-        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(resp.status(), http::StatusCode::OK);
 
         let body = test::read_body(resp).await;
 
-        let user_response: ErrorResponse =
+        let user_response: UserResponseDto =
             serde_json::from_slice(&body).expect("Failed to deserialize user response from JSON");
+        let user = user_response.data;
 
-        let expected_message = ErrorMessage::UserNoLongerExist.to_string();
-        let actual_message = user_response.message;
-
-        assert_eq!(actual_message, expected_message);
+        assert_eq!(user_id.to_string(), user.id);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_by_id_with_invalid_token(pool: Pool<Postgres>) {
-        let (user_id, _, _) = init_test_users(&pool).await;
+    async fn get_me_with_invalid_token(pool: Pool<Postgres>) {
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
@@ -522,7 +1436,7 @@ mod tests {
                 http::header::AUTHORIZATION,
                 HeaderValue::from_str("Bearer invalid-token").unwrap(),
             ))
-            .uri(&format!("/users/{user_id}"))
+            .uri("/users/me")
             .to_request();
 
         let result = test::try_call_service(&app, req).await.err();
@@ -546,9 +1460,8 @@ mod tests {
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_by_id_with_missing_token(pool: Pool<Postgres>) {
+    async fn get_me_with_missing_token(pool: Pool<Postgres>) {
         let db_client = DBClient::new(pool.clone());
-        let (user_id, _, _) = init_test_users(&pool).await;
         let config = test_config();
 
         let app = test::init_service(
@@ -561,9 +1474,7 @@ mod tests {
         )
         .await;
 
-        let req = test::TestRequest::get()
-            .uri(&format!("/users/{user_id}"))
-            .to_request();
+        let req = test::TestRequest::get().uri("/users/me").to_request();
 
         let result = test::try_call_service(&app, req).await.err();
 
@@ -586,36 +1497,47 @@ mod tests {
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_by_id_with_expired_token(pool: Pool<Postgres>) {
+    async fn get_me_with_expired_token(pool: Pool<Postgres>) {
         let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let expired_token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            -60,
+            &token_id,
+        )
+        .unwrap();
+
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client: db_client.clone(),
+                    db_client,
                 }))
                 .configure(super::config),
         )
         .await;
 
-        let token_id = Uuid::new_v4();
-        db_client
-            .modify_user_last_token_id(Some(&token_id), &user_id)
-            .await
-            .unwrap();
-
-        let expired_token =
-            token::create_token(&user_id, config.secret_key.as_bytes(), -60, &token_id).unwrap();
-
         let req = test::TestRequest::get()
             .insert_header((
                 http::header::AUTHORIZATION,
                 http::header::HeaderValue::from_str(&format!("Bearer {expired_token}")).unwrap(),
             ))
-            .uri(&format!("/users/{user_id}"))
+            .uri("/users/me")
             .to_request();
 
         let result = test::try_call_service(&app, req).await.err();
@@ -639,19 +1561,36 @@ mod tests {
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_me_with_valid_token(pool: Pool<Postgres>) {
-        let (user_id, _, _) = init_test_users(&pool).await;
+    async fn all_users_with_limit_two_query_parameter(pool: Pool<Postgres>) {
+        let (_, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        let hashed_password = password::hash("password123", &test_config().password_hash).unwrap();
+        let user = db_client
+            .save_user("Vivian", "vivian@example.com", &hashed_password)
+            .await
+            .unwrap();
+
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &user_id)
+            .save_session(
+                &user.id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token =
-            token::create_token(&user_id, config.secret_key.as_bytes(), 60, &token_id).unwrap();
+        let token = token::create_token(
+            &user.id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let app = test::init_service(
             App::new()
@@ -668,7 +1607,7 @@ mod tests {
                 http::header::AUTHORIZATION,
                 http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
             ))
-            .uri("/users/me")
+            .uri("/users?limit=2")
             .to_request();
 
         let resp = test::call_service(&app, req).await;
@@ -677,320 +1616,683 @@ mod tests {
 
         let body = test::read_body(resp).await;
 
-        let user_response: UserResponseDto =
-            serde_json::from_slice(&body).expect("Failed to deserialize user response from JSON");
-        let user = user_response.data;
+        let user_list_response: UserListResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize users response from JSON");
 
-        assert_eq!(user_id.to_string(), user.id);
+        assert_eq!(user_list_response.data.len(), 2);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_me_with_invalid_token(pool: Pool<Postgres>) {
+    async fn all_users_with_valid_token(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/users")
+            .to_request();
+
+        let result = test::try_call_service(&app, req).await.unwrap();
+
+        assert_eq!(result.status(), http::StatusCode::OK);
+
+        let body = test::read_body(result).await;
+        let body = serde_json::from_slice::<UserListResponseDto>(&body)
+            .expect("Failed to deserialize json response");
+
+        assert_eq!(body.results, 4);
+        assert_eq!(body.status.to_string(), "success");
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn all_users_with_invalid_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_static("Bearer invalid-token"),
+            ))
+            .uri("/users")
+            .to_request();
+
+        let result = test::try_call_service(&app, req).await.err();
+
+        match result {
+            Some(err) => {
+                let expected_status = http::StatusCode::UNAUTHORIZED;
+                let actual_status = err.as_response_error().status_code();
+
+                assert_eq!(actual_status, expected_status);
+
+                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
+                    .expect("Failed to deserialize JSON string");
+                let expected_message = ErrorMessage::InvalidToken.to_string();
+                assert_eq!(err_response.message, expected_message);
+            }
+            None => {
+                panic!("Service call succeeded, but an error was expected.");
+            }
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn all_users_with_missing_token(pool: Pool<Postgres>) {
+        let db_client = DBClient::new(pool.clone());
+        let config = test_config();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config.clone(),
+                    db_client,
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/users").to_request();
+
+        let result = test::try_call_service(&app, req).await.err();
+
+        match result {
+            Some(err) => {
+                let expected_status = http::StatusCode::UNAUTHORIZED;
+                let actual_status = err.as_response_error().status_code();
+
+                assert_eq!(actual_status, expected_status);
+
+                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
+                    .expect("Failed to deserialize JSON string");
+                let expected_message = ErrorMessage::TokenNotProvided.to_string();
+                assert_eq!(err_response.message, expected_message);
+            }
+            None => {
+                panic!("Service call succeeded, but an error was expected.");
+            }
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn top_up_is_recorded_in_transactions(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
                 .configure(super::config),
         )
         .await;
 
+        DBTransaction::begin(&pool)
+            .await
+            .unwrap()
+            .increase_user_sold(&user_id, 500, LedgerEntryKind::TopUp, None)
+            .await
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
         let req = test::TestRequest::get()
             .insert_header((
                 http::header::AUTHORIZATION,
-                HeaderValue::from_str("Bearer invalid-token").unwrap(),
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
             ))
-            .uri("/users/me")
+            .uri("/users/me/transactions?limit=10")
             .to_request();
 
-        let result = test::try_call_service(&app, req).await.err();
+        let resp = test::call_service(&app, req).await;
 
-        match result {
-            Some(err) => {
-                let expected_status = http::StatusCode::UNAUTHORIZED;
-                let actual_status = err.as_response_error().status_code();
+        assert_eq!(resp.status(), http::StatusCode::OK);
 
-                assert_eq!(actual_status, expected_status);
+        let body = test::read_body(resp).await;
 
-                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
-                    .expect("Failed to deserialize JSON string");
-                let expected_message = ErrorMessage::InvalidToken.to_string();
-                assert_eq!(err_response.message, expected_message);
-            }
-            None => {
-                panic!("Service call succeeded, but an error was expected.");
-            }
-        }
+        let response: LedgerEntryListResponseDto = serde_json::from_slice(&body)
+            .expect("Failed to deserialize transactions response from JSON");
+
+        assert_eq!(response.results, 1);
+        assert_eq!(response.data[0].kind, LedgerEntryKind::TopUp);
+        assert_eq!(response.data[0].amount_in_cents, 500);
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+        assert_eq!(user.sold_in_cents, 500);
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_me_with_missing_token(pool: Pool<Postgres>) {
+    async fn modify_me(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
+        let (mailer_handle, mailer) = test_mailer();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
+                .app_data(mailer)
                 .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::get().uri("/users/me").to_request();
+        let req = test::TestRequest::patch()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/users/me")
+            .set_json(ModifyUserDto {
+                name: Some("Idrissa B.".to_string()),
+                photo_url: Some("https://example.com/me.png".to_string()),
+                ..Default::default()
+            })
+            .to_request();
 
-        let result = test::try_call_service(&app, req).await.err();
+        let resp = test::call_service(&app, req).await;
 
-        match result {
-            Some(err) => {
-                let expected_status = http::StatusCode::UNAUTHORIZED;
-                let actual_status = err.as_response_error().status_code();
+        assert_eq!(resp.status(), http::StatusCode::OK);
 
-                assert_eq!(actual_status, expected_status);
+        let body = test::read_body(resp).await;
 
-                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
-                    .expect("Failed to deserialize JSON string");
-                let expected_message = ErrorMessage::TokenNotProvided.to_string();
-                assert_eq!(err_response.message, expected_message);
-            }
-            None => {
-                panic!("Service call succeeded, but an error was expected.");
-            }
-        }
+        let response: UserResponseDto =
+            serde_json::from_slice(&body).expect("Failed to deserialize user from JSON");
+
+        assert_eq!(response.data.name, "Idrissa B.");
+        assert_eq!(response.data.email, "ibaby@gmail.com");
+        assert_eq!(
+            response.data.photo_url,
+            Some("https://example.com/me.png".to_string())
+        );
+        assert!(mailer_handle.mails().is_empty());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn get_me_with_expired_token(pool: Pool<Postgres>) {
+    async fn modify_me_email_sends_verification_once(pool: Pool<Postgres>) {
         let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
+        let (mailer_handle, mailer) = test_mailer();
+
+        db_client
+            .modify_user_password(
+                &user_id,
+                &password::hash("password1234", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &user_id)
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let expired_token =
-            token::create_token(&user_id, config.secret_key.as_bytes(), -60, &token_id).unwrap();
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
+                .app_data(mailer)
                 .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::get()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {expired_token}")).unwrap(),
-            ))
-            .uri("/users/me")
-            .to_request();
-
-        let result = test::try_call_service(&app, req).await.err();
+        let patch_me = |infos: ModifyUserDto| {
+            test::TestRequest::patch()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me")
+                .set_json(infos)
+                .to_request()
+        };
+
+        let resp = test::call_service(
+            &app,
+            patch_me(ModifyUserDto {
+                email: Some("new@gmail.com".to_string()),
+                current_password: Some("password1234".to_string()),
+                ..Default::default()
+            }),
+        )
+        .await;
 
-        match result {
-            Some(err) => {
-                let expected_status = http::StatusCode::UNAUTHORIZED;
-                let actual_status = err.as_response_error().status_code();
+        assert_eq!(resp.status(), http::StatusCode::OK);
 
-                assert_eq!(actual_status, expected_status);
+        // the email is still unverified, but it did not change
+        for infos in [
+            ModifyUserDto {
+                name: Some("Idrissa B.".to_string()),
+                ..Default::default()
+            },
+            ModifyUserDto {
+                email: Some("new@gmail.com".to_string()),
+                ..Default::default()
+            },
+        ] {
+            let resp = test::call_service(&app, patch_me(infos)).await;
 
-                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
-                    .expect("Failed to deserialize JSON string");
-                let expected_message = ErrorMessage::InvalidToken.to_string();
-                assert_eq!(err_response.message, expected_message);
-            }
-            None => {
-                panic!("Service call succeeded, but an error was expected.");
-            }
+            assert_eq!(resp.status(), http::StatusCode::OK);
         }
+
+        let mails = mailer_handle.mails();
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+
+        assert_eq!(mails.len(), 1);
+        assert_eq!(mails[0].to, "new@gmail.com");
+        assert!(!user.is_email_verified());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn all_users_with_page_one_and_limit_two_query_parameters(pool: Pool<Postgres>) {
-        let (_, _, _) = init_test_users(&pool).await;
+    async fn modify_me_with_existing_email(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
+        let (_, mailer) = test_mailer();
 
-        let hashed_password = password::hash("password123").unwrap();
-        let user = db_client
-            .save_user("Vivian", "vivian@example.com", &hashed_password)
+        db_client
+            .modify_user_password(
+                &user_id,
+                &password::hash("password1234", &test_config().password_hash).unwrap(),
+            )
             .await
             .unwrap();
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &user.id)
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token =
-            token::create_token(&user.id, config.secret_key.as_bytes(), 60, &token_id).unwrap();
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
+                .app_data(mailer)
                 .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::get()
+        let req = test::TestRequest::patch()
             .insert_header((
                 http::header::AUTHORIZATION,
                 http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
             ))
-            .uri("/users?page=1&limit=2")
+            .uri("/users/me")
+            .set_json(ModifyUserDto {
+                email: Some("madamou@gmail.com".to_string()),
+                current_password: Some("password1234".to_string()),
+                ..Default::default()
+            })
             .to_request();
 
         let resp = test::call_service(&app, req).await;
 
-        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
 
         let body = test::read_body(resp).await;
 
-        let user_list_response: UserListResponseDto =
-            serde_json::from_slice(&body).expect("Failed to deserialize users response from JSON");
+        let response: ErrorResponse =
+            serde_json::from_slice(&body).expect("Failed to deserialize error from JSON");
 
-        assert_eq!(user_list_response.data.len(), 2);
+        assert_eq!(response.message, ErrorMessage::EmailExist.to_string());
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn all_users_with_valid_token(pool: Pool<Postgres>) {
+    async fn modify_me_email_without_current_password(pool: Pool<Postgres>) {
         let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
+        let (_, mailer) = test_mailer();
+
+        db_client
+            .modify_user_password(
+                &user_id,
+                &password::hash("password1234", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
 
         let token_id = Uuid::new_v4();
         db_client
-            .modify_user_last_token_id(Some(&token_id), &user_id)
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
             .await
             .unwrap();
 
-        let token =
-            token::create_token(&user_id, config.secret_key.as_bytes(), 60, &token_id).unwrap();
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
 
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
+                .app_data(mailer)
                 .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::get()
-            .insert_header((
-                http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
-            ))
-            .uri("/users")
-            .to_request();
+        for (current_password, message) in [
+            (None, ErrorMessage::CurrentPasswordRequired),
+            (Some("wrongpassword"), ErrorMessage::WrongPassword),
+        ] {
+            let req = test::TestRequest::patch()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me")
+                .set_json(ModifyUserDto {
+                    email: Some("new@gmail.com".to_string()),
+                    current_password: current_password.map(str::to_string),
+                    ..Default::default()
+                })
+                .to_request();
 
-        let result = test::try_call_service(&app, req).await.unwrap();
+            let resp = test::call_service(&app, req).await;
 
-        assert_eq!(result.status(), http::StatusCode::OK);
+            assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
 
-        let body = test::read_body(result).await;
-        let body = serde_json::from_slice::<UserListResponseDto>(&body)
-            .expect("Failed to deserialize json response");
+            let response: ErrorResponse = test::read_body_json(resp).await;
 
-        assert_eq!(body.results, 4);
-        assert_eq!(body.status.to_string(), "success");
+            assert_eq!(response.message, message.to_string());
+        }
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+
+        assert_eq!(user.email, "ibaby@gmail.com");
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn all_users_with_invalid_token(pool: Pool<Postgres>) {
+    async fn change_password(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        db_client
+            .modify_user_password(
+                &user_id,
+                &password::hash("password1234", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let (key, prefix) = token::generate_api_key();
+        db_client
+            .save_api_key(&user_id, "ci", &key, &prefix, &[ApiKeyScope::CartRead])
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
                 .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::get()
+        let req = test::TestRequest::post()
             .insert_header((
                 http::header::AUTHORIZATION,
-                http::header::HeaderValue::from_static("Bearer invalid-token"),
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
             ))
-            .uri("/users")
+            .uri("/users/me/password")
+            .set_json(ChangePasswordDto {
+                old_password: "password1234".to_string(),
+                new_password: "newpassword".to_string(),
+                new_password_confirm: "newpassword".to_string(),
+            })
             .to_request();
 
-        let result = test::try_call_service(&app, req).await.err();
+        let resp = test::call_service(&app, req).await;
 
-        match result {
-            Some(err) => {
-                let expected_status = http::StatusCode::UNAUTHORIZED;
-                let actual_status = err.as_response_error().status_code();
+        assert_eq!(resp.status(), http::StatusCode::NO_CONTENT);
 
-                assert_eq!(actual_status, expected_status);
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
 
-                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
-                    .expect("Failed to deserialize JSON string");
-                let expected_message = ErrorMessage::InvalidToken.to_string();
-                assert_eq!(err_response.message, expected_message);
-            }
-            None => {
-                panic!("Service call succeeded, but an error was expected.");
-            }
-        }
+        assert!(password::compare("newpassword", &user.password).unwrap());
+        assert!(
+            db_client
+                .get_sessions_by_user(&user_id, &PageRequest::first(10))
+                .await
+                .unwrap()
+                .items
+                .is_empty(),
+            "Sessions should be revoked"
+        );
+        assert!(
+            db_client.get_api_key(&key).await.unwrap().is_none(),
+            "API keys should be revoked"
+        );
     }
 
     #[sqlx::test(migrator = "crate::MIGRATOR")]
-    async fn all_users_with_missing_token(pool: Pool<Postgres>) {
+    async fn change_password_with_wrong_old_password(pool: Pool<Postgres>) {
+        let (user_id, _, _) = init_test_users(&pool).await;
         let db_client = DBClient::new(pool.clone());
         let config = test_config();
 
+        db_client
+            .modify_user_password(
+                &user_id,
+                &password::hash("password1234", &test_config().password_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let token_id = Uuid::new_v4();
+        db_client
+            .save_session(
+                &user_id,
+                &token_id,
+                &Uuid::new_v4(),
+                &SessionDevice::default(),
+            )
+            .await
+            .unwrap();
+
+        let token = token::create_token(
+            &user_id,
+            UserRole::Customer,
+            &config.jwt_keys,
+            60,
+            &token_id,
+        )
+        .unwrap();
+
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(AppState {
                     env: config.clone(),
-                    db_client,
+                    db_client: db_client.clone(),
                 }))
                 .configure(super::config),
         )
         .await;
 
-        let req = test::TestRequest::get().uri("/users").to_request();
-
-        let result = test::try_call_service(&app, req).await.err();
-
-        match result {
-            Some(err) => {
-                let expected_status = http::StatusCode::UNAUTHORIZED;
-                let actual_status = err.as_response_error().status_code();
-
-                assert_eq!(actual_status, expected_status);
+        let req = test::TestRequest::post()
+            .insert_header((
+                http::header::AUTHORIZATION,
+                http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+            ))
+            .uri("/users/me/password")
+            .set_json(ChangePasswordDto {
+                old_password: "notmypassword".to_string(),
+                new_password: "newpassword".to_string(),
+                new_password_confirm: "newpassword".to_string(),
+            })
+            .to_request();
 
-                let err_response: ErrorResponse = serde_json::from_str(&err.to_string())
-                    .expect("Failed to deserialize JSON string");
-                let expected_message = ErrorMessage::TokenNotProvided.to_string();
-                assert_eq!(err_response.message, expected_message);
-            }
-            None => {
-                panic!("Service call succeeded, but an error was expected.");
-            }
-        }
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let user = db_client.get_user(&user_id).await.unwrap().unwrap();
+
+        assert!(password::compare("password1234", &user.password).unwrap());
+        assert!(db_client
+            .get_session_by_access_token(&user_id, &token_id.to_string())
+            .await
+            .unwrap()
+            .is_some());
     }
 
     #[cfg(test)]
@@ -1005,13 +2307,23 @@ mod tests {
 
             let token_id = Uuid::new_v4();
             db_client
-                .modify_user_last_token_id(Some(&token_id), &data.user_id)
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
                 .await
                 .unwrap();
 
-            let token =
-                token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-                    .unwrap();
+            let token = token::create_token(
+                &data.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
 
             assert!(matches!(
                 db_client.get_user(&data.user_id).await,
@@ -1058,13 +2370,23 @@ mod tests {
 
             let token_id = Uuid::new_v4();
             db_client
-                .modify_user_last_token_id(Some(&token_id), &data.user_id)
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
                 .await
                 .unwrap();
 
-            let token =
-                token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-                    .unwrap();
+            let token = token::create_token(
+                &data.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
 
             let app = test::init_service(
                 App::new()
@@ -1098,13 +2420,19 @@ mod tests {
 
             let token_id = Uuid::new_v4();
             db_client
-                .modify_user_last_token_id(Some(&token_id), &data.user_id)
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
                 .await
                 .unwrap();
 
             let token = token::create_token(
                 &data.user_id,
-                config.secret_key.as_bytes(),
+                UserRole::Customer,
+                &config.jwt_keys,
                 60,
                 &Uuid::new_v4(),
             )
@@ -1139,13 +2467,23 @@ mod tests {
 
             let token_id = Uuid::new_v4();
             db_client
-                .modify_user_last_token_id(Some(&token_id), &data.user_id)
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
                 .await
                 .unwrap();
 
-            let token =
-                token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-                    .unwrap();
+            let token = token::create_token(
+                &data.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
 
             let app = test::init_service(
                 App::new()
@@ -1192,13 +2530,23 @@ mod tests {
 
             let token_id = Uuid::new_v4();
             db_client
-                .modify_user_last_token_id(Some(&token_id), &data.user_id)
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
                 .await
                 .unwrap();
 
-            let token =
-                token::create_token(&data.user_id, config.secret_key.as_bytes(), 60, &token_id)
-                    .unwrap();
+            let token = token::create_token(
+                &data.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
 
             let app = test::init_service(
                 App::new()
@@ -1237,7 +2585,7 @@ mod tests {
             assert!(orders.iter().all(|order| order.user_id == data.user_id));
             assert!(orders
                 .iter()
-                .any(|order| order.product_id == data2.product_id));
+                .any(|order| order.product_id == Some(data2.product_id)));
             assert_eq!(orders.len(), 2);
         }
 
@@ -1250,7 +2598,8 @@ mod tests {
 
             let token = token::create_token(
                 &Uuid::new_v4(),
-                config.secret_key.as_bytes(),
+                UserRole::Customer,
+                &config.jwt_keys,
                 60,
                 &Uuid::new_v4(),
             )
@@ -1277,5 +2626,347 @@ mod tests {
             // should panic
             let _ = test::call_service(&app, req).await;
         }
+
+        #[sqlx::test(migrator = "crate::MIGRATOR")]
+        async fn get_my_sales(pool: Pool<Postgres>) {
+            let (data, data2, _) = init_test_orders(&pool).await;
+            let db_client = DBClient::new(pool.clone());
+            let config = test_config();
+
+            // the seller of the product bought in the first order
+            let token_id = Uuid::new_v4();
+            db_client
+                .save_session(
+                    &data2.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
+                .await
+                .unwrap();
+
+            let token = token::create_token(
+                &data2.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
+
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState {
+                        env: config.clone(),
+                        db_client: db_client.clone(),
+                    }))
+                    .configure(super::config),
+            )
+            .await;
+
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me/sales?status=pending&from=2020-01-01T00:00:00Z&with_total=true")
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::OK);
+
+            let response: SaleListResponseDto = test::read_body_json(resp).await;
+
+            assert_eq!(response.results, 1);
+            assert_eq!(response.total, Some(1));
+            assert_eq!(response.data[0].order_id, data.order_id);
+            assert_eq!(response.data[0].items.len(), 1);
+            assert_eq!(response.data[0].items[0].product_id, data.product_id);
+            assert_eq!(response.data[0].total_in_cents, 50);
+            assert_eq!(response.data[0].delivery_address, None);
+
+            // none is paid
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me/sales?status=paid")
+                .to_request();
+
+            let response: SaleListResponseDto = test::call_and_read_body_json(&app, req).await;
+
+            assert_eq!(response.results, 0);
+        }
+    }
+
+    #[cfg(test)]
+    mod cart {
+        use super::*;
+
+        #[sqlx::test(migrator = "crate::MIGRATOR")]
+        async fn add_to_my_cart(pool: Pool<Postgres>) {
+            let (data, data2, _) = init_test_products(&pool).await;
+            let db_client = DBClient::new(pool.clone());
+            let config = test_config();
+
+            let token_id = Uuid::new_v4();
+            db_client
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
+                .await
+                .unwrap();
+
+            let token = token::create_token(
+                &data.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
+
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState {
+                        env: config.clone(),
+                        db_client: db_client.clone(),
+                    }))
+                    .configure(super::config),
+            )
+            .await;
+
+            let req = test::TestRequest::post()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me/cart")
+                .set_json(AddCartItemDto {
+                    product_id: data2.product_id,
+                    products_number: 2,
+                })
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::OK);
+
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me/cart")
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::OK);
+
+            let body = test::read_body(resp).await;
+
+            let response: CartItemListResponseDto = serde_json::from_slice(&body)
+                .expect("Failed to deserialize cart response from JSON");
+
+            assert_eq!(response.results, 1);
+            assert_eq!(response.data[0].product_id, data2.product_id);
+            assert_eq!(response.data[0].products_number, 2);
+        }
+
+        #[sqlx::test(migrator = "crate::MIGRATOR")]
+        async fn modify_product_not_in_my_cart(pool: Pool<Postgres>) {
+            let (data, data2, _) = init_test_products(&pool).await;
+            let db_client = DBClient::new(pool.clone());
+            let config = test_config();
+
+            let token_id = Uuid::new_v4();
+            db_client
+                .save_session(
+                    &data.user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
+                .await
+                .unwrap();
+
+            let token = token::create_token(
+                &data.user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
+
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState {
+                        env: config.clone(),
+                        db_client: db_client.clone(),
+                    }))
+                    .configure(super::config),
+            )
+            .await;
+
+            let req = test::TestRequest::patch()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri(&format!("/users/me/cart/{}", data2.product_id))
+                .set_json(ModifyCartItemDto { products_number: 3 })
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[cfg(test)]
+    mod addresses {
+        use super::*;
+
+        #[sqlx::test(migrator = "crate::MIGRATOR")]
+        async fn create_my_address(pool: Pool<Postgres>) {
+            let (user_id, _, _) = init_test_users(&pool).await;
+            let db_client = DBClient::new(pool.clone());
+            let config = test_config();
+
+            let token_id = Uuid::new_v4();
+            db_client
+                .save_session(
+                    &user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
+                .await
+                .unwrap();
+
+            let token = token::create_token(
+                &user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
+
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState {
+                        env: config.clone(),
+                        db_client: db_client.clone(),
+                    }))
+                    .configure(super::config),
+            )
+            .await;
+
+            let req = test::TestRequest::post()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me/addresses")
+                .set_json(SaveAddressDto {
+                    street: "1 main street".to_string(),
+                    city: "Paris".to_string(),
+                    postal_code: "75001".to_string(),
+                    country: "France".to_string(),
+                })
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::CREATED);
+
+            let req = test::TestRequest::get()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri("/users/me/addresses")
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::OK);
+
+            let body = test::read_body(resp).await;
+
+            let response: AddressListResponseDto = serde_json::from_slice(&body)
+                .expect("Failed to deserialize addresses response from JSON");
+
+            assert_eq!(response.results, 1);
+            assert_eq!(response.data[0].city, "Paris");
+        }
+
+        #[sqlx::test(migrator = "crate::MIGRATOR")]
+        async fn delete_someone_else_address(pool: Pool<Postgres>) {
+            let (user_id, user_id2, _) = init_test_users(&pool).await;
+            let db_client = DBClient::new(pool.clone());
+            let config = test_config();
+
+            let address = db_client
+                .save_address(&user_id2, "1 main street", "Paris", "75001", "France")
+                .await
+                .unwrap();
+
+            let token_id = Uuid::new_v4();
+            db_client
+                .save_session(
+                    &user_id,
+                    &token_id,
+                    &Uuid::new_v4(),
+                    &SessionDevice::default(),
+                )
+                .await
+                .unwrap();
+
+            let token = token::create_token(
+                &user_id,
+                UserRole::Customer,
+                &config.jwt_keys,
+                60,
+                &token_id,
+            )
+            .unwrap();
+
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState {
+                        env: config.clone(),
+                        db_client: db_client.clone(),
+                    }))
+                    .configure(super::config),
+            )
+            .await;
+
+            let req = test::TestRequest::delete()
+                .insert_header((
+                    http::header::AUTHORIZATION,
+                    http::header::HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+                ))
+                .uri(&format!("/users/me/addresses/{}", address.id))
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+            assert!(db_client
+                .get_address_if_belong_to_user(&user_id2, &address.id)
+                .await
+                .unwrap()
+                .is_some());
+        }
     }
 }
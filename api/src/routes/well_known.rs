@@ -0,0 +1,98 @@
+use actix_web::{get, http, web, HttpResponse};
+
+use crate::utils::AppState;
+
+pub(super) fn config(config: &mut web::ServiceConfig) {
+    config.service(web::scope("/.well-known").service(jwks));
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "Public keys verifying the tokens, picked by the `kid` of their header. Empty when the tokens are signed with a shared secret"),
+    ),
+    tag = "Authentication"
+)]
+#[get("/jwks.json")]
+async fn jwks(data: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        // short enough for the verifiers to see a new key before it signs anything
+        .insert_header((http::header::CACHE_CONTROL, "public, max-age=300"))
+        .json(data.env.jwt_keys.jwks())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use jsonwebtoken::{jwk::JwkSet, Algorithm};
+    use sqlx::{Pool, Postgres};
+
+    use super::*;
+    use crate::{
+        database::psql::DBClient,
+        utils::{
+            keys::{test_keys::*, JwtKeys},
+            test_utils::test_config,
+        },
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn publish_public_keys(pool: Pool<Postgres>) {
+        let mut config = test_config();
+        config.jwt_keys = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "new",
+            ED25519,
+            &[
+                ("new".to_string(), ED25519_PUBLIC.to_vec()),
+                ("old".to_string(), OLD_ED25519_PUBLIC.to_vec()),
+            ],
+        )
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: config,
+                    db_client: DBClient::new(pool),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/.well-known/jwks.json")
+            .to_request();
+
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let key_set: JwkSet = test::read_body_json(response).await;
+
+        assert_eq!(key_set.keys.len(), 2);
+        assert!(key_set.find("new").is_some());
+        assert!(key_set.find("old").is_some());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn shared_secret_is_not_published(pool: Pool<Postgres>) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    env: test_config(),
+                    db_client: DBClient::new(pool),
+                }))
+                .configure(super::config),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/.well-known/jwks.json")
+            .to_request();
+
+        let key_set: JwkSet = test::call_and_read_body_json(&app, request).await;
+
+        assert!(key_set.keys.is_empty());
+    }
+}
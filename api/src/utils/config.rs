@@ -1,13 +1,57 @@
-use std::env;
+use std::{env, fs, str::FromStr, sync::Arc};
+
+use jsonwebtoken::Algorithm;
+
+use super::{
+    keys::JwtKeys,
+    password::{HashParams, PasswordPolicy},
+};
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub port: u16,
     pub database_url: String,
     // pub redis_url: String,
-    pub secret_key: String,
+    pub jwt_keys: JwtKeys,
     pub access_token_max_seconds: i64,
     pub refresh_token_max_seconds: i64,
+    pub platform_commission_percentage: i64,
+    /// Base of the links sent by email
+    pub app_url: String,
+    pub mail_from: String,
+    pub mailer: MailerConfig,
+    pub email_verification_max_seconds: i64,
+    pub password_reset_max_seconds: i64,
+    /// Time left to give the TOTP code once the password is checked
+    pub mfa_token_max_seconds: i64,
+    /// Name of the account in the authenticator apps
+    pub totp_issuer: String,
+    /// Failed logins in a row before the account is locked
+    pub login_max_failed_attempts: i32,
+    /// First lockout, doubled for each failed login after it
+    pub login_lockout_seconds: i64,
+    pub password_policy: PasswordPolicy,
+    pub password_hash: HashParams,
+    /// Time during which a retried request with the same `Idempotency-Key` is replayed
+    pub idempotency_key_max_seconds: i64,
+    pub payment_gateway: PaymentGatewayConfig,
+}
+
+#[derive(Debug, Clone)]
+pub enum MailerConfig {
+    Smtp {
+        url: String,
+    },
+    /// The mails are written to this directory instead of being sent
+    File {
+        dir: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum PaymentGatewayConfig {
+    /// Accepts every payment method but `mock_card_declined`, for development and tests only
+    Mock { webhook_secret: String },
 }
 
 impl Config {
@@ -15,27 +59,95 @@ impl Config {
         let database_url = database_url();
         // let redis_url = redis_url();
         let port = port();
-        let secret_key = secret_key();
+        let jwt_keys = jwt_keys();
         let access_token_max_seconds = access_token_max_age_in_seconds();
         let refresh_token_max_seconds = refresh_token_max_age_in_seconds();
+        let platform_commission_percentage = platform_commission_percentage();
+        let app_url = app_url();
+        let mail_from = mail_from();
+        let mailer = mailer();
+        let email_verification_max_seconds = email_verification_max_age_in_seconds();
+        let password_reset_max_seconds = password_reset_max_age_in_seconds();
+        let mfa_token_max_seconds = mfa_token_max_age_in_seconds();
+        let totp_issuer = totp_issuer();
+        let login_max_failed_attempts = login_max_failed_attempts();
+        let login_lockout_seconds = login_lockout_seconds();
+        let password_policy = password_policy();
+        let password_hash = password_hash();
+        let idempotency_key_max_seconds = idempotency_key_max_age_in_seconds();
+        let payment_gateway = payment_gateway();
 
         Self {
             port,
             database_url,
             // redis_url,
-            secret_key,
+            jwt_keys,
             access_token_max_seconds,
             refresh_token_max_seconds,
+            platform_commission_percentage,
+            app_url,
+            mail_from,
+            mailer,
+            email_verification_max_seconds,
+            password_reset_max_seconds,
+            mfa_token_max_seconds,
+            totp_issuer,
+            login_max_failed_attempts,
+            login_lockout_seconds,
+            password_policy,
+            password_hash,
+            idempotency_key_max_seconds,
+            payment_gateway,
         }
     }
+
+    /// Part of a payment kept by the platform, the seller is credited of the rest
+    pub fn platform_commission(&self, amount_in_cents: i64) -> i64 {
+        amount_in_cents * self.platform_commission_percentage / 100
+    }
 }
 
 fn secret_key() -> String {
     env::var("SECRET_KEY")
-        .expect("DATABASE_URL need to be set")
+        .expect("SECRET_KEY need to be set")
         .clone()
 }
 
+/// HS256 with `SECRET_KEY` by default, or RS256/EdDSA with PEM files
+fn jwt_keys() -> JwtKeys {
+    let algorithm = env::var("JWT_ALGORITHM").unwrap_or("HS256".to_string());
+
+    if algorithm == "HS256" {
+        return JwtKeys::from_secret(secret_key().as_bytes());
+    }
+
+    let algorithm = Algorithm::from_str(&algorithm).expect("JWT_ALGORITHM: invalid value");
+    let signing_kid = env::var("JWT_SIGNING_KEY_ID").expect("JWT_SIGNING_KEY_ID need to be set");
+    let signing_pem =
+        read_key(&env::var("JWT_SIGNING_KEY").expect("JWT_SIGNING_KEY need to be set"));
+
+    // `<kid>=<path>,...`, the public key of the signing key and the ones still accepted
+    let verification_pems = env::var("JWT_VERIFICATION_KEYS")
+        .expect("JWT_VERIFICATION_KEYS need to be set")
+        .split(',')
+        .map(|entry| {
+            let (kid, path) = entry
+                .trim()
+                .split_once('=')
+                .expect("JWT_VERIFICATION_KEYS: expected <kid>=<path>");
+
+            (kid.to_string(), read_key(path))
+        })
+        .collect::<Vec<_>>();
+
+    JwtKeys::from_pem(algorithm, &signing_kid, &signing_pem, &verification_pems)
+        .unwrap_or_else(|err| panic!("JWT keys: {err}"))
+}
+
+fn read_key(path: &str) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|err| panic!("{path}: {err}"))
+}
+
 fn access_token_max_age_in_seconds() -> i64 {
     let minutes = env::var("ACCESS_TOKEN_MAX_AGE_IN_MINUTES")
         .unwrap_or("15".to_string())
@@ -54,6 +166,178 @@ fn refresh_token_max_age_in_seconds() -> i64 {
     days * 24 * 60 * 60
 }
 
+fn platform_commission_percentage() -> i64 {
+    let percentage = env::var("PLATFORM_COMMISSION_PERCENTAGE")
+        .unwrap_or("0".to_string())
+        .parse::<i64>()
+        .expect("PLATFORM_COMMISSION_PERCENTAGE: invalid value");
+
+    assert!(
+        (0..=100).contains(&percentage),
+        "PLATFORM_COMMISSION_PERCENTAGE: must be between 0 and 100"
+    );
+
+    percentage
+}
+
+fn app_url() -> String {
+    env::var("APP_URL")
+        .unwrap_or("http://localhost:8080".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn mail_from() -> String {
+    env::var("MAIL_FROM").unwrap_or("eapi <no-reply@localhost>".to_string())
+}
+
+/// SMTP when `SMTP_URL` is set, otherwise the mails end up in `MAIL_DIR`
+fn mailer() -> MailerConfig {
+    match env::var("SMTP_URL") {
+        Ok(url) => MailerConfig::Smtp { url },
+        Err(_) => MailerConfig::File {
+            dir: env::var("MAIL_DIR").unwrap_or("mails".to_string()),
+        },
+    }
+}
+
+fn email_verification_max_age_in_seconds() -> i64 {
+    let hours = env::var("EMAIL_VERIFICATION_MAX_AGE_IN_HOURS")
+        .unwrap_or("24".to_string())
+        .parse::<i64>()
+        .expect("EMAIL_VERIFICATION_MAX_AGE_IN_HOURS: invalid value");
+
+    hours * 60 * 60
+}
+
+fn password_reset_max_age_in_seconds() -> i64 {
+    let minutes = env::var("PASSWORD_RESET_MAX_AGE_IN_MINUTES")
+        .unwrap_or("30".to_string())
+        .parse::<i64>()
+        .expect("PASSWORD_RESET_MAX_AGE_IN_MINUTES: invalid value");
+
+    minutes * 60
+}
+
+fn mfa_token_max_age_in_seconds() -> i64 {
+    let minutes = env::var("MFA_TOKEN_MAX_AGE_IN_MINUTES")
+        .unwrap_or("5".to_string())
+        .parse::<i64>()
+        .expect("MFA_TOKEN_MAX_AGE_IN_MINUTES: invalid value");
+
+    minutes * 60
+}
+
+fn totp_issuer() -> String {
+    env::var("TOTP_ISSUER").unwrap_or("eapi".to_string())
+}
+
+fn login_max_failed_attempts() -> i32 {
+    let attempts = env::var("LOGIN_MAX_FAILED_ATTEMPTS")
+        .unwrap_or("5".to_string())
+        .parse::<i32>()
+        .expect("LOGIN_MAX_FAILED_ATTEMPTS: invalid value");
+
+    assert!(attempts > 0, "LOGIN_MAX_FAILED_ATTEMPTS: must be positive");
+
+    attempts
+}
+
+fn login_lockout_seconds() -> i64 {
+    env::var("LOGIN_LOCKOUT_IN_SECONDS")
+        .unwrap_or("60".to_string())
+        .parse::<i64>()
+        .expect("LOGIN_LOCKOUT_IN_SECONDS: invalid value")
+}
+
+fn password_policy() -> PasswordPolicy {
+    let default = PasswordPolicy::default();
+
+    let policy = PasswordPolicy {
+        min_length: parse_var("PASSWORD_MIN_LENGTH", default.min_length),
+        max_length: parse_var("PASSWORD_MAX_LENGTH", default.max_length),
+        require_lowercase: parse_var("PASSWORD_REQUIRE_LOWERCASE", default.require_lowercase),
+        require_uppercase: parse_var("PASSWORD_REQUIRE_UPPERCASE", default.require_uppercase),
+        require_digit: parse_var("PASSWORD_REQUIRE_DIGIT", default.require_digit),
+        require_symbol: parse_var("PASSWORD_REQUIRE_SYMBOL", default.require_symbol),
+        blocklist: match env::var("PASSWORD_BLOCKLIST_FILE") {
+            Ok(path) => {
+                let content =
+                    fs::read_to_string(&path).unwrap_or_else(|err| panic!("{path}: {err}"));
+                Arc::new(PasswordPolicy::parse_blocklist(&content))
+            }
+            Err(_) => default.blocklist,
+        },
+    };
+
+    assert!(
+        0 < policy.min_length && policy.min_length <= policy.max_length,
+        "PASSWORD_MIN_LENGTH: must be positive and at most PASSWORD_MAX_LENGTH"
+    );
+
+    policy
+}
+
+fn password_hash() -> HashParams {
+    let default = HashParams::default();
+
+    HashParams {
+        memory_kib: parse_var("ARGON2_MEMORY_IN_KIB", default.memory_kib),
+        iterations: parse_var("ARGON2_ITERATIONS", default.iterations),
+        parallelism: parse_var("ARGON2_PARALLELISM", default.parallelism),
+    }
+}
+
+fn idempotency_key_max_age_in_seconds() -> i64 {
+    let hours = env::var("IDEMPOTENCY_KEY_MAX_AGE_IN_HOURS")
+        .unwrap_or("24".to_string())
+        .parse::<i64>()
+        .expect("IDEMPOTENCY_KEY_MAX_AGE_IN_HOURS: invalid value");
+
+    hours * 60 * 60
+}
+
+fn payment_gateway() -> PaymentGatewayConfig {
+    match env::var("PAYMENT_GATEWAY")
+        .expect("PAYMENT_GATEWAY need to be set")
+        .as_str()
+    {
+        "mock" => {
+            // accepts any card, must not be picked up by accident
+            assert!(
+                parse_var("ALLOW_MOCK_PAYMENTS", false),
+                "PAYMENT_GATEWAY: the mock gateway needs ALLOW_MOCK_PAYMENTS=true"
+            );
+
+            PaymentGatewayConfig::Mock {
+                webhook_secret: webhook_secret(),
+            }
+        }
+        _ => panic!("PAYMENT_GATEWAY: invalid value"),
+    }
+}
+
+fn webhook_secret() -> String {
+    let secret = env::var("PAYMENT_WEBHOOK_SECRET").expect("PAYMENT_WEBHOOK_SECRET need to be set");
+
+    // the placeholder of the examples is public, webhooks signed with it could be forged
+    assert!(
+        !secret.is_empty() && secret != "change-me",
+        "PAYMENT_WEBHOOK_SECRET: invalid value"
+    );
+
+    secret
+}
+
+fn parse_var<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name}: invalid value")),
+        Err(_) => default,
+    }
+}
+
 fn port() -> u16 {
     env::var("LISTEN")
         .unwrap_or("8080".to_string())
@@ -0,0 +1,383 @@
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use simple_asn1::ASN1Block;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyError {
+    UnsupportedAlgorithm(Algorithm),
+    InvalidKey(String),
+    /// The signing key is not the private key of the verification key with its kid
+    MismatchedSigningKey(String),
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::UnsupportedAlgorithm(algorithm) => {
+                write!(
+                    f,
+                    "{algorithm:?} is not supported, use HS256, RS256 or EdDSA"
+                )
+            }
+            KeyError::InvalidKey(kid) => write!(f, "key {kid:?} is not a valid PEM key"),
+            KeyError::MismatchedSigningKey(kid) => {
+                write!(
+                    f,
+                    "the signing key does not match the verification key {kid:?}"
+                )
+            }
+        }
+    }
+}
+
+struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+    /// Public form of the key, `None` for a shared secret
+    jwk: Option<Jwk>,
+}
+
+/// Signs the tokens with one key and verifies them with any of the active ones,
+/// which are picked by the `kid` of the token's header
+#[derive(Clone)]
+pub struct JwtKeys {
+    signing: Arc<SigningKey>,
+    verification: Arc<Vec<VerificationKey>>,
+}
+
+impl fmt::Debug for JwtKeys {
+    // never print the keys themselves
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKeys")
+            .field("algorithm", &self.signing.algorithm)
+            .field("signing_kid", &self.signing.kid)
+            .field(
+                "verification_kids",
+                &self
+                    .verification
+                    .iter()
+                    .map(|key| &key.kid)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl JwtKeys {
+    /// HS256, without kid: every verifier needs the secret
+    pub fn from_secret(secret: &[u8]) -> Self {
+        JwtKeys {
+            signing: Arc::new(SigningKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(secret),
+            }),
+            verification: Arc::new(vec![VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret),
+                jwk: None,
+            }]),
+        }
+    }
+
+    /// RS256 or EdDSA. `verification_pems` are the `(kid, public key)` still accepted,
+    /// the public key of the signing one included
+    pub fn from_pem(
+        algorithm: Algorithm,
+        signing_kid: &str,
+        signing_pem: &[u8],
+        verification_pems: &[(String, Vec<u8>)],
+    ) -> Result<Self, KeyError> {
+        let signing_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(signing_pem),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(signing_pem),
+            other => return Err(KeyError::UnsupportedAlgorithm(other)),
+        }
+        .map_err(|_| KeyError::InvalidKey(signing_kid.to_string()))?;
+
+        let verification = verification_pems
+            .iter()
+            .map(|(kid, pem)| public_key(algorithm, kid, pem))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let keys = JwtKeys {
+            signing: Arc::new(SigningKey {
+                kid: Some(signing_kid.to_string()),
+                algorithm,
+                key: signing_key,
+            }),
+            verification: Arc::new(verification),
+        };
+
+        keys.check_signing_key()?;
+
+        Ok(keys)
+    }
+
+    /// A token signed now must be verified by the key published with the same kid
+    fn check_signing_key(&self) -> Result<(), KeyError> {
+        let mismatched =
+            || KeyError::MismatchedSigningKey(self.signing.kid.clone().unwrap_or_default());
+
+        let probe = jsonwebtoken::encode(
+            &self.header(),
+            &serde_json::json!({ "probe": true }),
+            self.encoding_key(),
+        )
+        .map_err(|_| mismatched())?;
+
+        let (algorithm, key) = self
+            .verification_key(self.signing.kid.as_deref())
+            .ok_or_else(mismatched)?;
+
+        let mut validation = Validation::new(algorithm);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+
+        jsonwebtoken::decode::<serde_json::Value>(&probe, key, &validation)
+            .map(|_| ())
+            .map_err(|_| mismatched())
+    }
+
+    /// Header of the new tokens, with the kid of the signing key
+    pub fn header(&self) -> Header {
+        Header {
+            kid: self.signing.kid.clone(),
+            ..Header::new(self.signing.algorithm)
+        }
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.signing.key
+    }
+
+    /// The key with this kid and the only algorithm it accepts
+    pub fn verification_key(&self, kid: Option<&str>) -> Option<(Algorithm, &DecodingKey)> {
+        self.verification
+            .iter()
+            .find(|key| key.kid.as_deref() == kid)
+            .map(|key| (key.algorithm, &key.key))
+    }
+
+    /// The public verification keys, empty with a shared secret
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .verification
+                .iter()
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Reads a `PUBLIC KEY` (SubjectPublicKeyInfo) PEM, or a PKCS#1 `RSA PUBLIC KEY` one
+fn public_key(algorithm: Algorithm, kid: &str, pem: &[u8]) -> Result<VerificationKey, KeyError> {
+    let invalid = || KeyError::InvalidKey(kid.to_string());
+
+    let pem = pem::parse(pem).map_err(|_| invalid())?;
+    let blocks = simple_asn1::from_der(pem.contents()).map_err(|_| invalid())?;
+
+    let public_key = match (pem.tag(), blocks.as_slice()) {
+        // SEQUENCE { algorithm, BIT STRING subjectPublicKey }
+        ("PUBLIC KEY", [ASN1Block::Sequence(_, info)]) => match info.as_slice() {
+            [_, ASN1Block::BitString(_, _, key)] => key.clone(),
+            _ => return Err(invalid()),
+        },
+        ("RSA PUBLIC KEY", _) if algorithm == Algorithm::RS256 => pem.contents().to_vec(),
+        _ => return Err(invalid()),
+    };
+
+    let (key, key_algorithm, parameters) = match algorithm {
+        Algorithm::RS256 => {
+            // RSAPublicKey: SEQUENCE { INTEGER modulus, INTEGER exponent }
+            let blocks = simple_asn1::from_der(&public_key).map_err(|_| invalid())?;
+
+            let [ASN1Block::Sequence(_, numbers)] = blocks.as_slice() else {
+                return Err(invalid());
+            };
+            let [ASN1Block::Integer(_, modulus), ASN1Block::Integer(_, exponent)] =
+                numbers.as_slice()
+            else {
+                return Err(invalid());
+            };
+
+            let (_, modulus) = modulus.to_bytes_be();
+            let (_, exponent) = exponent.to_bytes_be();
+
+            (
+                DecodingKey::from_rsa_raw_components(&modulus, &exponent),
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(&modulus),
+                    e: URL_SAFE_NO_PAD.encode(&exponent),
+                }),
+            )
+        }
+        Algorithm::EdDSA => {
+            if public_key.len() != 32 {
+                return Err(invalid());
+            }
+
+            (
+                DecodingKey::from_ed_der(&public_key),
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(&public_key),
+                }),
+            )
+        }
+        other => return Err(KeyError::UnsupportedAlgorithm(other)),
+    };
+
+    Ok(VerificationKey {
+        kid: Some(kid.to_string()),
+        algorithm,
+        key,
+        jwk: Some(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        }),
+    })
+}
+
+#[cfg(test)]
+pub mod test_keys {
+    pub const RSA: &[u8] = include_bytes!("test_keys/rsa.pem");
+    pub const RSA_PUBLIC: &[u8] = include_bytes!("test_keys/rsa.pub.pem");
+    pub const ED25519: &[u8] = include_bytes!("test_keys/ed25519.pem");
+    pub const ED25519_PUBLIC: &[u8] = include_bytes!("test_keys/ed25519.pub.pem");
+    pub const OLD_ED25519: &[u8] = include_bytes!("test_keys/ed25519-old.pem");
+    pub const OLD_ED25519_PUBLIC: &[u8] = include_bytes!("test_keys/ed25519-old.pub.pem");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_keys::*, *};
+
+    #[test]
+    fn load_rsa_keys() {
+        let keys = JwtKeys::from_pem(
+            Algorithm::RS256,
+            "2025-03",
+            RSA,
+            &[("2025-03".to_string(), RSA_PUBLIC.to_vec())],
+        )
+        .unwrap();
+
+        assert_eq!(keys.header().kid.as_deref(), Some("2025-03"));
+        assert_eq!(keys.header().alg, Algorithm::RS256);
+
+        let jwks = keys.jwks();
+        let jwk = jwks.find("2025-03").expect("Key not published");
+
+        match &jwk.algorithm {
+            AlgorithmParameters::RSA(parameters) => assert_eq!(parameters.e, "AQAB"),
+            other => panic!("Expected a RSA key, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn load_eddsa_keys_with_a_retired_one() {
+        let keys = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "new",
+            ED25519,
+            &[
+                ("new".to_string(), ED25519_PUBLIC.to_vec()),
+                ("old".to_string(), OLD_ED25519_PUBLIC.to_vec()),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(keys.jwks().keys.len(), 2);
+        assert!(keys.verification_key(Some("old")).is_some());
+        assert!(keys.verification_key(None).is_none());
+    }
+
+    #[test]
+    fn signing_key_must_match_its_verification_key() {
+        let result = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "new",
+            ED25519,
+            &[("new".to_string(), OLD_ED25519_PUBLIC.to_vec())],
+        );
+
+        assert_eq!(
+            result.err(),
+            Some(KeyError::MismatchedSigningKey("new".to_string()))
+        );
+
+        // not published at all
+        let result = JwtKeys::from_pem(
+            Algorithm::EdDSA,
+            "new",
+            ED25519,
+            &[("old".to_string(), OLD_ED25519_PUBLIC.to_vec())],
+        );
+
+        assert_eq!(
+            result.err(),
+            Some(KeyError::MismatchedSigningKey("new".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_keys() {
+        // an EdDSA public key for RS256
+        let result = JwtKeys::from_pem(
+            Algorithm::RS256,
+            "2025-03",
+            RSA,
+            &[("2025-03".to_string(), ED25519_PUBLIC.to_vec())],
+        );
+
+        assert_eq!(
+            result.err(),
+            Some(KeyError::InvalidKey("2025-03".to_string()))
+        );
+
+        let result = JwtKeys::from_pem(Algorithm::ES256, "2025-03", RSA, &[]);
+
+        assert_eq!(
+            result.err(),
+            Some(KeyError::UnsupportedAlgorithm(Algorithm::ES256))
+        );
+    }
+
+    #[test]
+    fn shared_secret_is_not_published() {
+        let keys = JwtKeys::from_secret(b"my-super-secret-key");
+
+        assert!(keys.jwks().keys.is_empty());
+        assert!(keys.header().kid.is_none());
+    }
+}
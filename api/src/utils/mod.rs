@@ -1,10 +1,13 @@
 pub mod config;
 pub mod constants;
+pub mod keys;
 pub mod models;
+pub mod pagination;
 pub mod password;
 pub mod status;
 pub mod test_utils;
 pub mod token;
+pub mod totp;
 
 use config::Config;
 
@@ -1,5 +1,7 @@
 use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 #[derive(PartialEq, Eq, Debug, Clone, FromRow)]
@@ -8,13 +10,66 @@ pub struct User {
     pub name: String,
     pub email: String,
     pub password: String,
-    pub last_token_id: Option<String>,
+    pub photo_url: Option<String>,
     pub sold_in_cents: i64,
+    pub role: UserRole,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub email_verified_at: Option<DateTime<Utc>>,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl User {
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+}
+
+/// Failed logins in a row on an email, registered or not
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct LoginAttempts {
+    pub email: String,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+
+    pub updated_at: DateTime<Utc>,
+}
+
+impl LoginAttempts {
+    /// Seconds left before the email can be used to log in again
+    pub fn lockout_seconds_left(&self) -> Option<u64> {
+        let millis = (self.locked_until? - Utc::now()).num_milliseconds();
+
+        (millis > 0).then(|| (millis as u64).div_ceil(1000))
+    }
+}
+
+/// Customers become sellers with their first product, admins are only set in the database
+#[derive(
+    PartialEq, Eq, Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub enum UserRole {
+    #[default]
+    Customer,
+    Seller,
+    Admin,
+}
+
+/// What a token sent by email allows to do, once
+#[derive(PartialEq, Eq, Debug, Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "user_token_kind", rename_all = "snake_case")]
+pub enum UserTokenKind {
+    EmailVerification,
+    PasswordReset,
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, FromRow)]
 pub struct Product {
     pub id: Uuid,
@@ -28,15 +83,338 @@ pub struct Product {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Filters of a product search, `None` fields are not applied
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct ProductSearch {
+    /// full-text query over the name and the description
+    pub text: Option<String>,
+    pub min_price_in_cents: Option<i64>,
+    pub max_price_in_cents: Option<i64>,
+    pub in_stock: bool,
+    pub seller_id: Option<Uuid>,
+    pub sort: ProductSort,
+}
+
+impl ProductSearch {
+    /// Relevance needs a full-text query, newest first without
+    pub fn applied_sort(&self) -> ProductSort {
+        match (self.sort, &self.text) {
+            (ProductSort::Relevance, None) => ProductSort::Newest,
+            (sort, _) => sort,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductSort {
+    /// best matches first, or newest without a full-text query
+    #[default]
+    Relevance,
+    Newest,
+    PriceAsc,
+    PriceDesc,
+}
+
 #[allow(clippy::struct_field_names)]
 #[derive(PartialEq, Eq, Debug, Clone, FromRow)]
 pub struct Order {
     pub id: Uuid,
     pub user_id: Uuid,
-    pub product_id: Uuid,
+    /// only set for single product orders, the lines are in `order_items`
+    pub product_id: Option<Uuid>,
     pub order_details_id: Option<Uuid>,
-    pub products_number: i32,
+    /// copy of the `order_details` address, kept when it is edited or deleted
+    pub delivery_street: Option<String>,
+    pub delivery_city: Option<String>,
+    pub delivery_postal_code: Option<String>,
+    pub delivery_country: Option<String>,
+    pub products_number: Option<i32>,
+    /// snapshot of the product at order time, only set for single product orders
+    pub unit_price_in_cents: Option<i64>,
+    pub product_name: Option<String>,
+    /// what validating the order charges
+    pub total_in_cents: i64,
+    pub status: OrderStatus,
     // others fields ?
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+/// Why an order was cancelled or refunded, stored in `order_cancellations`
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct OrderCancellation {
+    pub order_id: Uuid,
+    /// `None` once the user is deleted
+    pub cancelled_by: Option<Uuid>,
+    pub status: OrderStatus,
+    pub reason: String,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// Tracking of the items of an order shipped by one of its sellers, stored in `order_shipments`
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct OrderShipment {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    /// `None` once the user is deleted
+    pub shipped_by: Option<Uuid>,
+    pub tracking_number: String,
+    pub shipped_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Filters of the orders placed on a seller's products, `None` fields are not applied
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct SaleSearch {
+    pub status: Option<OrderStatus>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+/// A delivery address of the user's address book, stored in `order_details`
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct Address {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct OrderItem {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub product_id: Uuid,
+    pub products_number: i32,
+    /// snapshot of the product at order time
+    pub unit_price_in_cents: i64,
+    pub product_name: String,
+    /// set once the seller shipped it
+    pub shipment_id: Option<Uuid>,
+    /// set once the seller refunded it
+    pub refunded_at: Option<DateTime<Utc>>,
+    pub refund_reason: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct CartItem {
+    pub user_id: Uuid,
+    pub product_id: Uuid,
+    pub products_number: i32,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One logged-in device. The tokens' jti are only stored hashed
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub access_token_hash: String,
+    pub refresh_token_hash: String,
+    pub device_label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+}
+
+/// What an API key gives access to, the sessions give access to everything
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "api_key_scope")]
+pub enum ApiKeyScope {
+    #[serde(rename = "users:read")]
+    #[sqlx(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "products:read")]
+    #[sqlx(rename = "products:read")]
+    ProductsRead,
+    #[serde(rename = "products:write")]
+    #[sqlx(rename = "products:write")]
+    ProductsWrite,
+    #[serde(rename = "orders:read")]
+    #[sqlx(rename = "orders:read")]
+    OrdersRead,
+    #[serde(rename = "orders:write")]
+    #[sqlx(rename = "orders:write")]
+    OrdersWrite,
+    #[serde(rename = "cart:read")]
+    #[sqlx(rename = "cart:read")]
+    CartRead,
+    #[serde(rename = "cart:write")]
+    #[sqlx(rename = "cart:write")]
+    CartWrite,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiKeyScope::UsersRead => "users:read",
+            ApiKeyScope::ProductsRead => "products:read",
+            ApiKeyScope::ProductsWrite => "products:write",
+            ApiKeyScope::OrdersRead => "orders:read",
+            ApiKeyScope::OrdersWrite => "orders:write",
+            ApiKeyScope::CartRead => "cart:read",
+            ApiKeyScope::CartWrite => "cart:write",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub last_used_at: Option<DateTime<Utc>>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// `Idempotency-Key` of a user, with the response once the first request is done
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct IdempotencyKey {
+    pub user_id: Uuid,
+    pub key: String,
+    pub fingerprint: String,
+    pub response_status: Option<i16>,
+    pub response_content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+    pub expires_at: DateTime<Utc>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// Pending until `enabled_at` is set by a first valid code
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct UserTotp {
+    pub user_id: Uuid,
+    pub secret: Vec<u8>,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub last_step: Option<i64>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserTotp {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_hash: String,
+    pub used_at: Option<DateTime<Utc>>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// What is known about a device when it logs in
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct SessionDevice {
+    pub label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+/// Lifecycle of an order, the allowed transitions are checked by the
+/// `check_order_status` trigger:
+///
+/// `pending` -> `paid` | `cancelled`
+/// `paid` -> `shipped` | `cancelled` | `refunded`
+/// `shipped` -> `delivered` | `refunded`
+/// `delivered` -> `refunded`
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "order_status", rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Refunded,
+}
+
+/// One balance movement, `users.sold_in_cents` must always be the sum of them
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct LedgerEntry {
+    pub id: Uuid,
+    /// `None` for the platform, see `LedgerEntryKind::Commission`
+    pub user_id: Option<Uuid>,
+    pub order_id: Option<Uuid>,
+    pub kind: LedgerEntryKind,
+    /// positive for a credit, negative for a debit
+    pub amount_in_cents: i64,
+
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "ledger_entry_kind", rename_all = "snake_case")]
+#[serde(rename_all = "camelCase")]
+pub enum LedgerEntryKind {
+    OpeningBalance,
+    TopUp,
+    OrderDebit,
+    SaleCredit,
+    Refund,
+    /// taken by the platform on a sale, negative when given back
+    Commission,
+}
+
+/// `pending` -> `processing` -> `succeeded` | `failed`, a webhook can also end a `pending` one
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "payment_intent_status", rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentIntentStatus {
+    Pending,
+    Processing,
+    Succeeded,
+    Failed,
+}
+
+/// A top-up of the balance, credited once the gateway confirms it
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct PaymentIntent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub gateway_intent_id: String,
+    pub amount_in_cents: i64,
+    pub status: PaymentIntentStatus,
+
+    pub updated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A user whose balance is not the sum of its ledger entries
+#[derive(PartialEq, Eq, Debug, Clone, FromRow)]
+pub struct BalanceDrift {
+    pub user_id: Uuid,
+    pub sold_in_cents: i64,
+    pub ledger_sum_in_cents: i64,
+}
@@ -0,0 +1,237 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::models::{Address, ApiKey, LedgerEntry, Order, Product, ProductSort, Session, User};
+
+/// Position of a row in a list sorted by `created_at DESC, id DESC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    /// Opaque for the client: url-safe base64 of `<created_at in µs>:<id>`
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.created_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let (micros, id) = raw.split_once(':')?;
+
+        Some(Cursor {
+            created_at: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            id: Uuid::parse_str(id).ok()?,
+        })
+    }
+}
+
+/// Position of a row in a product search, on the sorted value then `id`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchCursor {
+    Rank(f32, Uuid),
+    CreatedAt(DateTime<Utc>, Uuid),
+    Price(i64, Uuid),
+}
+
+impl SearchCursor {
+    pub fn of(sort: ProductSort, product: &Product, rank: f32) -> Self {
+        match sort {
+            ProductSort::Relevance => SearchCursor::Rank(rank, product.id),
+            ProductSort::Newest => SearchCursor::CreatedAt(product.created_at, product.id),
+            ProductSort::PriceAsc | ProductSort::PriceDesc => {
+                SearchCursor::Price(product.price_in_cents, product.id)
+            }
+        }
+    }
+
+    /// Opaque for the client: url-safe base64 of `<sort>:<value>:<id>`
+    pub fn encode(&self) -> String {
+        let raw = match self {
+            SearchCursor::Rank(rank, id) => format!("r:{rank}:{id}"),
+            SearchCursor::CreatedAt(created_at, id) => {
+                format!("c:{}:{id}", created_at.timestamp_micros())
+            }
+            SearchCursor::Price(price, id) => format!("p:{price}:{id}"),
+        };
+
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    /// `None` if the cursor was made for another sort
+    pub fn decode(cursor: &str, sort: ProductSort) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let mut parts = raw.splitn(3, ':');
+        let (kind, value, id) = (parts.next()?, parts.next()?, parts.next()?);
+        let id = Uuid::parse_str(id).ok()?;
+
+        match (kind, sort) {
+            ("r", ProductSort::Relevance) => Some(SearchCursor::Rank(value.parse().ok()?, id)),
+            ("c", ProductSort::Newest) => Some(SearchCursor::CreatedAt(
+                DateTime::from_timestamp_micros(value.parse().ok()?)?,
+                id,
+            )),
+            ("p", ProductSort::PriceAsc | ProductSort::PriceDesc) => {
+                Some(SearchCursor::Price(value.parse().ok()?, id))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        match self {
+            SearchCursor::Rank(_, id)
+            | SearchCursor::CreatedAt(_, id)
+            | SearchCursor::Price(_, id) => *id,
+        }
+    }
+}
+
+/// Rows that can be paginated on `(created_at, id)`
+pub trait Keyset {
+    fn cursor(&self) -> Cursor;
+}
+
+macro_rules! impl_keyset {
+    ($($model:ty),*) => {
+        $(impl Keyset for $model {
+            fn cursor(&self) -> Cursor {
+                Cursor {
+                    created_at: self.created_at,
+                    id: self.id,
+                }
+            }
+        })*
+    };
+}
+
+impl_keyset!(User, Product, Order, LedgerEntry, Address, Session, ApiKey);
+
+/// `C` is the position in the sort, `(created_at, id)` but for the product search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRequest<C = Cursor> {
+    /// Rows strictly after this one, from the start if `None`
+    pub after: Option<C>,
+    pub limit: usize,
+    /// Also count every row matching the filters, which costs a second query
+    pub with_total: bool,
+}
+
+impl<C> PageRequest<C> {
+    #[cfg(test)]
+    pub fn first(limit: usize) -> Self {
+        PageRequest {
+            after: None,
+            limit,
+            with_total: false,
+        }
+    }
+
+    /// One more row than asked, to know if there is a next page
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit as i64 + 1
+    }
+}
+
+impl PageRequest {
+    pub fn after_created_at(&self) -> Option<DateTime<Utc>> {
+        self.after.map(|cursor| cursor.created_at)
+    }
+
+    pub fn after_id(&self) -> Option<Uuid> {
+        self.after.map(|cursor| cursor.id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+    pub total: Option<i64>,
+}
+
+impl<T: Keyset> Page<T> {
+    /// `rows` must have been fetched with `request.fetch_limit()`
+    pub fn new(mut rows: Vec<T>, request: &PageRequest, total: Option<i64>) -> Self {
+        let has_more = rows.len() > request.limit;
+        rows.truncate(request.limit);
+
+        let next_cursor = if has_more {
+            rows.last().map(|row| row.cursor().encode())
+        } else {
+            None
+        };
+
+        Page {
+            items: rows,
+            next_cursor,
+            has_more,
+            total,
+        }
+    }
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(&T) -> U) -> Page<U> {
+        Page {
+            items: self.items.iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            has_more: self.has_more,
+            total: self.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor {
+            created_at: DateTime::from_timestamp_micros(1_738_411_200_123_456).unwrap(),
+            id: Uuid::new_v4(),
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    }
+
+    #[test]
+    fn search_cursor_round_trip() {
+        let id = Uuid::new_v4();
+        let cursor = SearchCursor::Rank(0.060_792_7, id);
+
+        assert_eq!(
+            SearchCursor::decode(&cursor.encode(), ProductSort::Relevance),
+            Some(cursor)
+        );
+
+        let cursor = SearchCursor::Price(4_999, id);
+
+        assert_eq!(
+            SearchCursor::decode(&cursor.encode(), ProductSort::PriceDesc),
+            Some(cursor)
+        );
+        // not for another sort
+        assert_eq!(
+            SearchCursor::decode(&cursor.encode(), ProductSort::Newest),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_cursor() {
+        assert_eq!(Cursor::decode("not a cursor"), None);
+        assert_eq!(
+            Cursor::decode(&URL_SAFE_NO_PAD.encode("12:not-an-uuid")),
+            None
+        );
+    }
+}
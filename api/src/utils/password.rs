@@ -1,44 +1,279 @@
-use bcrypt::DEFAULT_COST;
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock},
+};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use ring::rand::{SecureRandom, SystemRandom};
 
 use crate::error::ErrorMessage;
 
-const MAX_PASSWORD_LENGTH: usize = 30;
-const MIN_PASSWORD_LENGTH: usize = 6;
+/// Longest password compared at login whatever the policy, hashing megabytes would be a free DoS
+const MAX_COMPARED_PASSWORD_BYTES: usize = 1024;
+const SALT_LENGTH: usize = 16;
 
-pub fn hash(password: impl Into<String>) -> Result<String, ErrorMessage> {
-    let password = password.into();
+/// Hash of no account's password, made with the parameters of the first call
+static DUMMY_HASH: OnceLock<String> = OnceLock::new();
 
-    if password.is_empty() {
-        return Err(ErrorMessage::EmptyPassword);
+/// Cost of the Argon2id hashes, the old ones are rehashed at login when they change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for HashParams {
+    /// The minimum recommended by OWASP for Argon2id
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl HashParams {
+    fn argon2(&self) -> Result<Argon2<'static>, ErrorMessage> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|_| ErrorMessage::HashingError)?;
+
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
     }
+}
+
+/// Rules for the new passwords, the ones already set can still log in
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Lowercased common or breached passwords, refused whatever their characters
+    pub blocklist: Arc<HashSet<String>>,
+}
 
-    if password.len() < MIN_PASSWORD_LENGTH {
-        return Err(ErrorMessage::PasswordTooShort(MIN_PASSWORD_LENGTH));
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 6,
+            max_length: 30,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            blocklist: Arc::default(),
+        }
     }
+}
 
-    if password.len() > MAX_PASSWORD_LENGTH {
-        return Err(ErrorMessage::PasswordTooLong(MAX_PASSWORD_LENGTH));
+impl PasswordPolicy {
+    /// One password per line, blank lines and `#` comments are skipped
+    pub fn parse_blocklist(content: &str) -> HashSet<String> {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect()
     }
 
-    let hashed = bcrypt::hash(password, DEFAULT_COST)?;
+    /// Lengths are counted in characters, not bytes
+    pub fn check(&self, password: &str) -> Result<(), ErrorMessage> {
+        if password.is_empty() {
+            return Err(ErrorMessage::EmptyPassword);
+        }
+
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            return Err(ErrorMessage::PasswordTooShort(self.min_length));
+        }
+
+        if length > self.max_length {
+            return Err(ErrorMessage::PasswordTooLong(self.max_length));
+        }
+
+        let has = |is_of_class: fn(char) -> bool| password.chars().any(is_of_class);
+
+        for (required, present, class) in [
+            (
+                self.require_lowercase,
+                has(char::is_lowercase),
+                "lowercase letter",
+            ),
+            (
+                self.require_uppercase,
+                has(char::is_uppercase),
+                "uppercase letter",
+            ),
+            (self.require_digit, has(|c| c.is_ascii_digit()), "digit"),
+            (self.require_symbol, has(|c| !c.is_alphanumeric()), "symbol"),
+        ] {
+            if required && !present {
+                return Err(ErrorMessage::PasswordMissingCharacter(class));
+            }
+        }
 
-    Ok(hashed)
+        if self.blocklist.contains(&password.to_lowercase()) {
+            return Err(ErrorMessage::PasswordTooCommon);
+        }
+
+        Ok(())
+    }
 }
 
+/// Argon2id with a random salt, in the PHC string format which keeps the parameters
+pub fn hash(password: &str, params: &HashParams) -> Result<String, ErrorMessage> {
+    let mut salt = [0u8; SALT_LENGTH];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| ErrorMessage::HashingError)?;
+
+    let salt = SaltString::encode_b64(&salt).map_err(|_| ErrorMessage::HashingError)?;
+
+    let hashed = params
+        .argon2()?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| ErrorMessage::HashingError)?;
+
+    Ok(hashed.to_string())
+}
+
+/// Checks against Argon2id hashes, and the bcrypt ones made before
 pub fn compare(password: &str, hashed_password: &str) -> Result<bool, ErrorMessage> {
     if password.is_empty() {
         return Err(ErrorMessage::EmptyPassword);
     }
 
-    if password.len() < MIN_PASSWORD_LENGTH {
-        return Err(ErrorMessage::PasswordTooShort(MIN_PASSWORD_LENGTH));
+    if password.len() > MAX_COMPARED_PASSWORD_BYTES {
+        return Err(ErrorMessage::PasswordTooLong(MAX_COMPARED_PASSWORD_BYTES));
+    }
+
+    if !hashed_password.starts_with("$argon2") {
+        return Ok(bcrypt::verify(password, hashed_password)?);
     }
 
-    if password.len() > MAX_PASSWORD_LENGTH {
-        return Err(ErrorMessage::PasswordTooShort(MAX_PASSWORD_LENGTH));
+    let parsed = PasswordHash::new(hashed_password).map_err(|_| ErrorMessage::InvalidHashFormat)?;
+
+    // the algorithm and the parameters are the ones of the hash
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok())
+}
+
+/// A bcrypt hash, or an Argon2 one with other parameters, to replace once the password is known
+pub fn needs_rehash(hashed_password: &str, params: &HashParams) -> bool {
+    let Ok(parsed) = PasswordHash::new(hashed_password) else {
+        return true;
+    };
+
+    if parsed.algorithm != argon2::ARGON2ID_IDENT {
+        return true;
     }
 
-    let is_valid = bcrypt::verify(password, hashed_password)?;
+    Params::try_from(&parsed).map_or(true, |current| {
+        current.m_cost() != params.memory_kib
+            || current.t_cost() != params.iterations
+            || current.p_cost() != params.parallelism
+    })
+}
+
+/// To compare with when there is no user, as long to check as the hash of a real password
+pub fn dummy_hash(params: &HashParams) -> &'static str {
+    DUMMY_HASH
+        .get_or_init(|| hash("dummy-password", params).expect("Failed to hash the dummy password"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: HashParams = HashParams {
+        memory_kib: 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
 
-    Ok(is_valid)
+    #[test]
+    fn hash_and_compare() {
+        let hashed = hash("password", &PARAMS).unwrap();
+
+        assert!(hashed.starts_with("$argon2id$"));
+        assert!(compare("password", &hashed).unwrap());
+        assert!(!compare("wrong-password", &hashed).unwrap());
+        assert!(!needs_rehash(&hashed, &PARAMS));
+        assert!(needs_rehash(
+            &hashed,
+            &HashParams {
+                iterations: 2,
+                ..PARAMS
+            }
+        ));
+    }
+
+    #[test]
+    fn compare_bcrypt_hashes() {
+        let hashed = bcrypt::hash("password", 4).unwrap();
+
+        assert!(compare("password", &hashed).unwrap());
+        assert!(!compare("wrong-password", &hashed).unwrap());
+        assert!(needs_rehash(&hashed, &PARAMS));
+    }
+
+    #[test]
+    fn policy() {
+        let policy = PasswordPolicy {
+            require_uppercase: true,
+            require_digit: true,
+            blocklist: Arc::new(PasswordPolicy::parse_blocklist(
+                "# most common\nPassword1\n\n  qwerty123 \n",
+            )),
+            ..PasswordPolicy::default()
+        };
+
+        assert!(policy.check("Correct-horse-7").is_ok());
+        assert!(matches!(
+            policy.check("Shor1"),
+            Err(ErrorMessage::PasswordTooShort(6))
+        ));
+        assert!(matches!(
+            policy.check(&"A1".repeat(16)),
+            Err(ErrorMessage::PasswordTooLong(30))
+        ));
+        assert!(matches!(
+            policy.check("no-uppercase-7"),
+            Err(ErrorMessage::PasswordMissingCharacter("uppercase letter"))
+        ));
+        assert!(matches!(
+            policy.check("No-digit-here"),
+            Err(ErrorMessage::PasswordMissingCharacter("digit"))
+        ));
+        assert!(matches!(
+            policy.check("PASSWORD1"),
+            Err(ErrorMessage::PasswordTooCommon)
+        ));
+    }
+
+    #[test]
+    fn default_policy_lengths() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.check(&"a".repeat(6)).is_ok());
+        assert!(policy.check(&"a".repeat(30)).is_ok());
+        assert!(matches!(
+            policy.check(&"a".repeat(5)),
+            Err(ErrorMessage::PasswordTooShort(6))
+        ));
+        assert!(matches!(
+            policy.check(&"a".repeat(31)),
+            Err(ErrorMessage::PasswordTooLong(30))
+        ));
+    }
 }
@@ -24,6 +24,7 @@ impl std::fmt::Display for Status {
     }
 }
 
+/// Only the presence, the handlers check new passwords against the policy of the config
 pub fn validate_password(password: &str) -> Result<(), ValidationError> {
     if password.is_empty() {
         return Err(
@@ -31,15 +32,5 @@ pub fn validate_password(password: &str) -> Result<(), ValidationError> {
         );
     }
 
-    if password.len() < 6 {
-        return Err(ValidationError::new("failed")
-            .with_message(Cow::Borrowed("Password must be at least 6 characters")));
-    }
-
-    if password.len() > 30 {
-        return Err(ValidationError::new("failed")
-            .with_message(Cow::Borrowed("Password must be at most 25 characters")));
-    }
-
     Ok(())
 }
@@ -1,12 +1,22 @@
 #![cfg(test)]
 #![allow(dead_code)]
 
+use actix_web::web;
 use sqlx::{Pool, Postgres};
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 use uuid::Uuid;
 
-use super::config::Config;
-use crate::database::{psql::DBClient, OrderExtractor, ProductExtractor, UserExtractor};
+use super::{
+    config::{Config, MailerConfig, PaymentGatewayConfig},
+    keys::JwtKeys,
+    password::{HashParams, PasswordPolicy},
+};
+use crate::{
+    database::{psql::DBClient, OrderExtractor, ProductExtractor, UserExtractor},
+    mailer::{memory::MemoryMailer, Mailer},
+    payment::{mock::MockGateway, PaymentGateway},
+    rate_limit::{memory::MemoryStore, RateLimitStore},
+};
 
 pub struct TestUser {
     name: &'static str,
@@ -19,12 +29,56 @@ pub fn test_config() -> Config {
         port: 8000,
         database_url: String::new(),
         // redis_url: "".to_string(),
-        secret_key: "my-test-secret".to_string(),
+        jwt_keys: JwtKeys::from_secret(b"my-test-secret"),
         access_token_max_seconds: 60,
         refresh_token_max_seconds: 5 * 60,
+        platform_commission_percentage: 0,
+        app_url: "http://localhost:8080".to_string(),
+        mail_from: "eapi <no-reply@localhost>".to_string(),
+        mailer: MailerConfig::File { dir: String::new() },
+        email_verification_max_seconds: 24 * 60 * 60,
+        password_reset_max_seconds: 30 * 60,
+        mfa_token_max_seconds: 5 * 60,
+        totp_issuer: "eapi".to_string(),
+        login_max_failed_attempts: 3,
+        login_lockout_seconds: 60,
+        password_policy: PasswordPolicy::default(),
+        // cheap, the tests hash a lot of passwords
+        password_hash: HashParams {
+            memory_kib: 4 * 1024,
+            iterations: 1,
+            parallelism: 1,
+        },
+        idempotency_key_max_seconds: 60 * 60,
+        payment_gateway: PaymentGatewayConfig::Mock {
+            webhook_secret: "test-webhook-secret".to_string(),
+        },
     }
 }
 
+/// The mailer to give to `App::app_data`, and a handle on the mails it sent
+pub fn test_mailer() -> (Arc<MemoryMailer>, web::Data<dyn Mailer>) {
+    let mailer = Arc::new(MemoryMailer::default());
+
+    (mailer.clone(), web::Data::from(mailer as Arc<dyn Mailer>))
+}
+
+/// The gateway to give to `App::app_data`, and a handle on the webhooks it would send
+pub fn test_payment_gateway() -> (Arc<MockGateway>, web::Data<dyn PaymentGateway>) {
+    let PaymentGatewayConfig::Mock { webhook_secret } = test_config().payment_gateway;
+    let gateway = Arc::new(MockGateway::new(&webhook_secret));
+
+    (
+        gateway.clone(),
+        web::Data::from(gateway as Arc<dyn PaymentGateway>),
+    )
+}
+
+/// A store to give to `App::app_data`, the routes are not rate limited without one
+pub fn test_rate_limit_store() -> web::Data<dyn RateLimitStore> {
+    web::Data::from(Arc::new(MemoryStore::default()) as Arc<dyn RateLimitStore>)
+}
+
 pub async fn init_test_users(pool: &Pool<Postgres>) -> (Uuid, Uuid, Uuid) {
     let db_client = DBClient::new(pool.clone());
 
@@ -61,6 +115,12 @@ pub async fn init_test_users(pool: &Pool<Postgres>) -> (Uuid, Uuid, Uuid) {
         users_id.push(user.id);
     }
 
+    // they stand for existing accounts, which have all verified their email
+    sqlx::query("UPDATE users SET email_verified_at = NOW()")
+        .execute(pool)
+        .await
+        .unwrap();
+
     (users_id[0], users_id[1], users_id[2])
 }
 
@@ -208,10 +268,28 @@ pub async fn init_test_orders(
         orders_data.push(TestOrderData {
             order_id: order.id,
             user_id: order.user_id,
-            product_id: order.product_id,
+            product_id: order.product_id.unwrap(),
             order_details_id: order.order_details_id,
         });
     }
 
     (orders_data[0], orders_data[1], orders_data[2])
 }
+
+/// Admins are only set in the database
+/// Users saved with `save_user` have to verify their email before logging in
+pub async fn verify_email(pool: &Pool<Postgres>, user_id: &Uuid) {
+    sqlx::query("UPDATE users SET email_verified_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+pub async fn make_admin(pool: &Pool<Postgres>, user_id: &Uuid) {
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
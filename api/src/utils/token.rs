@@ -1,12 +1,17 @@
 use std::fmt::Display;
 
 use actix_web::{http, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::Validation;
+use ring::{
+    digest,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{error::{ErrorMessage, ErrorResponse, HttpError}, utils::status::Status};
+use crate::{error::{ErrorMessage, ErrorResponse, HttpError}, utils::{keys::JwtKeys, models::UserRole, status::Status}};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
@@ -21,11 +26,28 @@ pub struct TokenClaims {
 
     // JWT id
     pub jti: String,
+
+    // role when the token was created, the database stays the reference for authorization
+    pub role: UserRole,
+}
+
+/// Audience of the MFA challenge tokens, so that they are refused as access tokens
+const MFA_AUDIENCE: &str = "mfa";
+
+/// Proves that the password was checked, the login ends with a TOTP or recovery code
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaClaims {
+    pub sub: Uuid,
+    pub iat: usize,
+    pub exp: usize,
+    pub jti: String,
+    pub aud: String,
 }
 
 pub fn create_token(
     user_id: &Uuid,
-    secret: &[u8],
+    role: UserRole,
+    keys: &JwtKeys,
     expires_in_seconds: i64,
     token_id: &Uuid,
 ) -> Result<String, jsonwebtoken::errors::Error> {
@@ -39,21 +61,60 @@ pub fn create_token(
         iat,
         exp,
         jti,
+        role,
+    };
+
+    jsonwebtoken::encode(&keys.header(), &claims, keys.encoding_key())
+}
+
+pub fn decode_token(token: impl Into<String>, keys: &JwtKeys) -> Result<TokenClaims, HttpError> {
+    // tokens with an audience, like the MFA ones, are refused
+    decode(&token.into(), keys, |_| {})
+}
+
+pub fn create_mfa_token(
+    user_id: &Uuid,
+    keys: &JwtKeys,
+    expires_in_seconds: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+
+    let claims = MfaClaims {
+        sub: *user_id,
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::seconds(expires_in_seconds)).timestamp() as usize,
+        jti: Uuid::new_v4().to_string(),
+        aud: MFA_AUDIENCE.to_string(),
     };
 
-    jsonwebtoken::encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret),
-    )
+    jsonwebtoken::encode(&keys.header(), &claims, keys.encoding_key())
 }
 
-pub fn decode_token(token: impl Into<String>, secret: &[u8]) -> Result<TokenClaims, HttpError> {
-    let decoded = jsonwebtoken::decode::<TokenClaims>(
-        &token.into(),
-        &DecodingKey::from_secret(secret),
-        &Validation::new(jsonwebtoken::Algorithm::HS256),
-    );
+pub fn decode_mfa_token(token: &str, keys: &JwtKeys) -> Result<MfaClaims, HttpError> {
+    decode(token, keys, |validation| {
+        validation.set_audience(&[MFA_AUDIENCE]);
+        validation.set_required_spec_claims(&["exp", "aud"]);
+    })
+}
+
+fn decode<T: DeserializeOwned>(
+    token: &str,
+    keys: &JwtKeys,
+    configure: impl FnOnce(&mut Validation),
+) -> Result<T, HttpError> {
+    // the kid picks the key, which only accepts its own algorithm
+    let key = jsonwebtoken::decode_header(token)
+        .ok()
+        .and_then(|header| keys.verification_key(header.kid.as_deref()));
+
+    let Some((algorithm, key)) = key else {
+        return HttpError::new(ErrorMessage::InvalidToken, 401).into();
+    };
+
+    let mut validation = Validation::new(algorithm);
+    configure(&mut validation);
+
+    let decoded = jsonwebtoken::decode::<T>(token, key, &validation);
 
     match decoded {
         Ok(token) => {
@@ -64,6 +125,40 @@ pub fn decode_token(token: impl Into<String>, secret: &[u8]) -> Result<TokenClai
     }
 }
 
+/// Sessions are looked up by this hash, a fast one is enough since the jti is random
+pub fn hash_token_id(token_id: &str) -> String {
+    digest::digest(&digest::SHA256, token_id.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// 256 random bits, url safe, for the links sent by email
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("Failed to generate a random token");
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Marks the API keys apart from the other secrets, in logs or in leaked code
+const API_KEY_PREFIX: &str = "eapi_";
+/// Characters of the key kept in clear, to recognize it in the list of keys
+const API_KEY_VISIBLE_LENGTH: usize = 12;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// The key, only shown once, and its visible start
+pub fn generate_api_key() -> (String, String) {
+    let key = format!("{API_KEY_PREFIX}{}", random_token());
+    let prefix = key[..API_KEY_VISIBLE_LENGTH].to_string();
+
+    (key, prefix)
+}
+
 fn jwt_failed(message: impl Display) -> ErrorResponse {
     ErrorResponse {
         status: Status::Failure.to_string(),
@@ -92,27 +187,56 @@ pub fn extract_token_from(request: &HttpRequest) -> Result<String, ErrorResponse
     Ok(token_value.to_string())
 }
 
+/// The key of `X-Api-Key` or of an `Authorization: ApiKey <key>` header, `None` for the other
+/// requests which can still carry an access token
+pub fn extract_api_key_from(request: &HttpRequest) -> Option<String> {
+    if let Some(value) = request.headers().get(API_KEY_HEADER) {
+        return value.to_str().ok().map(str::to_string);
+    }
+
+    let value = request.headers().get(http::header::AUTHORIZATION)?;
+    let (key_type, key) = value.to_str().ok()?.split_once(' ')?;
+
+    (key_type == "ApiKey").then(|| key.to_string())
+}
+
 #[cfg(test)]
 mod tests {
+    use jsonwebtoken::Algorithm;
+
     use super::*;
+    use crate::utils::keys::test_keys::*;
+
+    fn eddsa_keys(signing_kid: &str, signing_pem: &[u8], kids: &[&str]) -> JwtKeys {
+        let verification_pems = kids
+            .iter()
+            .map(|kid| {
+                let pem = if *kid == "old" { OLD_ED25519_PUBLIC } else { ED25519_PUBLIC };
+                (kid.to_string(), pem.to_vec())
+            })
+            .collect::<Vec<_>>();
+
+        JwtKeys::from_pem(Algorithm::EdDSA, signing_kid, signing_pem, &verification_pems).unwrap()
+    }
 
     #[test]
     fn create_and_decode_token() {
         let user_id = Uuid::new_v4();
-        let secret = b"my-super-secret-key";
+        let keys = JwtKeys::from_secret(b"my-super-secret-key");
 
-        let token = create_token(&user_id, secret, 60, &Uuid::new_v4()).unwrap();
-        let decoded_id = decode_token(&token, secret).unwrap().sub;
+        let token = create_token(&user_id, UserRole::Seller, &keys, 60, &Uuid::new_v4()).unwrap();
+        let claims = decode_token(&token, &keys).unwrap();
 
-        assert_eq!(decoded_id, user_id);
+        assert_eq!(claims.sub, user_id);
+        assert_eq!(claims.role, UserRole::Seller);
     }
 
     #[test]
     fn decode_invalid_token() {
-        let secret = b"my-super-secret-key";
+        let keys = JwtKeys::from_secret(b"my-super-secret-key");
         let invalid_token = "invalid-token";
 
-        let result = decode_token(invalid_token, secret);
+        let result = decode_token(invalid_token, &keys);
 
         assert!(result.is_err());
 
@@ -123,10 +247,10 @@ mod tests {
 
     #[test]
     fn decode_expired_token() {
-        let secret = b"my-super-secret-key";
-        let expired_token = create_token(&Uuid::new_v4(), secret, -60, &Uuid::new_v4()).unwrap();
+        let keys = JwtKeys::from_secret(b"my-super-secret-key");
+        let expired_token = create_token(&Uuid::new_v4(), UserRole::Customer, &keys, -60, &Uuid::new_v4()).unwrap();
 
-        let result = decode_token(expired_token, secret);
+        let result = decode_token(expired_token, &keys);
 
         assert!(result.is_err());
 
@@ -134,4 +258,58 @@ mod tests {
         assert_eq!(error.status, 401);
         assert_eq!(error.message, ErrorMessage::InvalidToken.to_string());
     }
+
+    #[test]
+    fn rotate_eddsa_keys() {
+        let user_id = Uuid::new_v4();
+
+        // signed before the rotation
+        let old_keys = eddsa_keys("old", OLD_ED25519, &["old"]);
+        let token = create_token(&user_id, UserRole::Customer, &old_keys, 60, &Uuid::new_v4()).unwrap();
+
+        // the old key is still accepted during the rotation
+        let keys = eddsa_keys("new", ED25519, &["new", "old"]);
+        assert_eq!(decode_token(&token, &keys).unwrap().sub, user_id);
+
+        let new_token = create_token(&user_id, UserRole::Customer, &keys, 60, &Uuid::new_v4()).unwrap();
+        assert_eq!(jsonwebtoken::decode_header(&new_token).unwrap().kid.as_deref(), Some("new"));
+
+        // then retired
+        let keys = eddsa_keys("new", ED25519, &["new"]);
+        assert!(decode_token(&token, &keys).is_err());
+        assert!(decode_token(&new_token, &keys).is_ok());
+    }
+
+    #[test]
+    fn decode_token_of_another_algorithm() {
+        let hs256_token = create_token(
+            &Uuid::new_v4(),
+            UserRole::Customer,
+            &JwtKeys::from_secret(b"my-super-secret-key"),
+            60,
+            &Uuid::new_v4(),
+        )
+        .unwrap();
+
+        let keys = eddsa_keys("new", ED25519, &["new"]);
+        let error = decode_token(hs256_token, &keys).unwrap_err();
+
+        assert_eq!(error.status, 401);
+        assert_eq!(error.message, ErrorMessage::InvalidToken.to_string());
+    }
+
+    #[test]
+    fn mfa_and_access_tokens_are_not_interchangeable() {
+        let keys = JwtKeys::from_secret(b"my-test-secret");
+        let user_id = Uuid::new_v4();
+
+        let mfa_token = create_mfa_token(&user_id, &keys, 60).unwrap();
+        let access_token =
+            create_token(&user_id, UserRole::Customer, &keys, 60, &Uuid::new_v4()).unwrap();
+
+        assert_eq!(decode_mfa_token(&mfa_token, &keys).unwrap().sub, user_id);
+
+        assert!(decode_token(mfa_token, &keys).is_err());
+        assert!(decode_mfa_token(&access_token, &keys).is_err());
+    }
 }
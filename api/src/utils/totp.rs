@@ -0,0 +1,195 @@
+use std::fmt::Write;
+
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+/// RFC 4648 alphabet, the one authenticator apps expect for the secret
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+const SECRET_LENGTH: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+
+pub const RECOVERY_CODES_NUMBER: usize = 8;
+
+pub fn generate_secret() -> Vec<u8> {
+    random_bytes(SECRET_LENGTH)
+}
+
+pub fn encode_secret(secret: &[u8]) -> String {
+    let mut encoded = String::new();
+
+    for chunk in secret.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer
+            .iter()
+            .fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+
+        // no padding, only the characters which carry bits of the chunk
+        for index in 0..(chunk.len() * 8).div_ceil(5) {
+            let value = (bits >> (35 - index * 5)) & 0x1f;
+            encoded.push(BASE32_ALPHABET[value as usize] as char);
+        }
+    }
+
+    encoded
+}
+
+/// Key URI read by the authenticator apps, usually shown as a QR code
+pub fn otpauth_uri(secret: &[u8], issuer: &str, account: &str) -> String {
+    let issuer = percent_encode(issuer);
+
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        percent_encode(account),
+        encode_secret(secret),
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+        encoded
+    })
+}
+
+pub fn step_at(unix_seconds: i64) -> i64 {
+    unix_seconds.div_euclid(STEP_SECONDS)
+}
+
+/// RFC 6238 with HMAC-SHA1, as RFC 4226 over the time step
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// The time step that `code` was generated for, one step of clock drift is accepted either way
+pub fn verify(secret: &[u8], code: &str, unix_seconds: i64) -> Option<i64> {
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    let step = step_at(unix_seconds);
+
+    (step - 1..=step + 1).find(|step| {
+        let expected = code_at(secret, *step);
+
+        // constant time, not to tell how many digits match
+        expected
+            .bytes()
+            .zip(code.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    })
+}
+
+/// Single-use codes for when the authenticator is lost, like `abcde-fghij`
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES_NUMBER)
+        .map(|_| {
+            let code = random_bytes(10)
+                .iter()
+                .map(|byte| BASE32_ALPHABET[(byte & 0x1f) as usize].to_ascii_lowercase() as char)
+                .collect::<String>();
+
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("Failed to generate random bytes");
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA1 secret of the RFC 6238 test vectors
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn rfc_6238_test_vectors() {
+        // the last 6 of the 8 digits of the RFC
+        for (time, code) in [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            assert_eq!(code_at(RFC_SECRET, step_at(time)), code);
+        }
+    }
+
+    #[test]
+    fn verify_with_clock_drift() {
+        let now = 1_234_567_890;
+        let step = step_at(now);
+
+        assert_eq!(verify(RFC_SECRET, "005924", now), Some(step));
+        assert_eq!(verify(RFC_SECRET, "005924", now + 30), Some(step));
+        assert_eq!(verify(RFC_SECRET, "005924", now - 30), Some(step));
+        assert_eq!(verify(RFC_SECRET, "005924", now + 60), None);
+
+        assert_eq!(verify(RFC_SECRET, "5924", now), None);
+        assert_eq!(verify(RFC_SECRET, "00592a", now), None);
+    }
+
+    #[test]
+    fn encode_secret_in_base32() {
+        assert_eq!(encode_secret(b""), "");
+        assert_eq!(encode_secret(b"f"), "MY");
+        assert_eq!(encode_secret(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(
+            encode_secret(RFC_SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+    }
+
+    #[test]
+    fn otpauth_uri_of_an_email() {
+        assert_eq!(
+            otpauth_uri(b"foobar", "My eapi", "user@example.com"),
+            "otpauth://totp/My%20eapi:user%40example.com?secret=MZXW6YTBOI&issuer=My%20eapi&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_codes_are_distinct() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODES_NUMBER);
+        assert!(codes.iter().all(|code| code.len() == 11));
+        assert!(codes
+            .iter()
+            .enumerate()
+            .all(|(index, code)| !codes[index + 1..].contains(code)));
+    }
+}